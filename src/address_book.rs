@@ -3,5 +3,31 @@ use ethers::prelude::*;
 pub(crate) const SPOOKY_SWAP_ROUTER: &str = "0xF491e7B69E4244ad4002BC14e878a34207E38c29";
 pub(crate) const SPOOKY_SWAP_FACTORY: &str = "0x152eE697f2E276fA89E96742e9bB9aB1F2E61bE3";
 
+pub(crate) const UNISWAP_V2_FACTORY: &str = "0x5C69bEe701ef814a2B6a3EDD4B1652CB9cc5aA6f";
+pub(crate) const UNISWAP_V2_ROUTER: &str = "0x7a250d5630B4cF539739dF2C5dAcb4c659F2488D";
+pub(crate) const SUSHISWAP_ROUTER: &str = "0xd9e1cE17f2641f24aE83637ab66a2cca9C378B9F";
+pub(crate) const UNISWAP_V3_ROUTER: &str = "0xE592427A0AEce92De3Edee1F18E0157C05861564";
+pub(crate) const UNISWAP_V3_ROUTER_02: &str = "0x68b3465833fb72A70ecDF485E0e4C7bD8665Fc45";
+pub(crate) const UNIVERSAL_ROUTER: &str = "0x3fC91A3afd70395Cd496C647d5a6CC9D4B2b7FAD";
+pub(crate) const UNISWAP_V3_FACTORY: &str = "0x1F98431c8aD98523631AE4a59f267346ea31F984";
+pub(crate) const QUOTER_V2: &str = "0x61fFE014bA17989E743c5F6cB21bF9697530B21e";
+pub(crate) const BALANCER_VAULT: &str = "0xBA12222222228d8Ba445958a75a0704d566BF2C8";
+pub(crate) const WETH: &str = "0xC02aaA39b223FE8D0A0e5C4F27eAD9083C756Cc2";
+
+/// Routers whose pending txs are worth running through the strategies.
+pub(crate) const KNOWN_ROUTERS: [&str; 5] =
+    [UNISWAP_V2_ROUTER, SUSHISWAP_ROUTER, UNISWAP_V3_ROUTER, UNISWAP_V3_ROUTER_02, UNIVERSAL_ROUTER];
+
 abigen!(UniV2Router, "src/abi/UniV2Router.json");
 abigen!(UniV2Factory, "src/abi/UniV2Factory.json");
+abigen!(LpPair, "src/abi/LpPair.json");
+abigen!(ERC20, "src/abi/ERC20.json");
+abigen!(UniV3Router, "src/abi/UniV3Router.json");
+abigen!(UniV3Factory, "src/abi/UniV3Factory.json");
+abigen!(UniV3Pool, "src/abi/UniV3Pool.json");
+abigen!(QuoterV2, "src/abi/QuoterV2.json");
+abigen!(BalancerVault, "src/abi/BalancerVault.json");
+abigen!(WETH9, "src/abi/WETH9.json");
+abigen!(UniversalRouter, "src/abi/UniversalRouter.json");
+abigen!(L1Bridge, "src/abi/L1Bridge.json");
+abigen!(AggregatorProxy, "src/abi/AggregatorProxy.json");
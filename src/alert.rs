@@ -1,26 +1,492 @@
+use std::collections::hash_map::DefaultHasher;
 use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
 
-/// Alerts discord channel, via webhook, we found an opportunity.
+use async_trait::async_trait;
+use futures_util::future::join_all;
+use tracing::{info, warn};
+
+use crate::settings::{AlertChannels, Settings};
+
+static NOTIFIER: OnceLock<MultiNotifier> = OnceLock::new();
+static LIMITER: OnceLock<AlertLimiter> = OnceLock::new();
+
+/// Discord rejects messages over 2000 characters.
+const DISCORD_MAX_LENGTH: usize = 1900;
+
+/// A channel alerts can be delivered to.
+#[async_trait]
+pub trait Notifier: Send + Sync + std::fmt::Debug {
+    fn name(&self) -> &'static str;
+
+    async fn notify(&self, message: &str) -> Result<(), Box<dyn std::error::Error + Send + Sync>>;
+}
+
+/// Discord channel, via webhook.
+#[derive(Debug)]
+pub struct Discord {
+    webhook: String,
+    client: reqwest::Client,
+}
+
+impl Discord {
+    pub fn new(webhook: String) -> Self {
+        Self { webhook, client: reqwest::Client::new() }
+    }
+}
+
+#[async_trait]
+impl Notifier for Discord {
+    fn name(&self) -> &'static str {
+        "discord"
+    }
+
+    async fn notify(&self, message: &str) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let mut map = HashMap::new();
+        map.insert("content", truncate(message, DISCORD_MAX_LENGTH));
+        self.client.post(&self.webhook).json(&map).send().await?.error_for_status()?;
+        Ok(())
+    }
+}
+
+/// Telegram chat, via the Bot API.
+#[derive(Debug)]
+pub struct Telegram {
+    api_url: String,
+    chat_id: String,
+    client: reqwest::Client,
+}
+
+impl Telegram {
+    pub fn new(bot_token: &str, chat_id: String) -> Self {
+        Self::with_api_base("https://api.telegram.org", bot_token, chat_id)
+    }
+
+    /// Against a different Bot API server, e.g. a self-hosted one.
+    pub fn with_api_base(api_base: &str, bot_token: &str, chat_id: String) -> Self {
+        Self {
+            api_url: format!("{}/bot{}/sendMessage", api_base, bot_token),
+            chat_id,
+            client: reqwest::Client::new(),
+        }
+    }
+}
+
+#[async_trait]
+impl Notifier for Telegram {
+    fn name(&self) -> &'static str {
+        "telegram"
+    }
+
+    async fn notify(&self, message: &str) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let mut map = HashMap::new();
+        map.insert("chat_id", self.chat_id.as_str());
+        map.insert("text", message);
+        self.client.post(&self.api_url).json(&map).send().await?.error_for_status()?;
+        Ok(())
+    }
+}
+
+/// Slack channel, via incoming webhook.
+#[derive(Debug)]
+pub struct Slack {
+    webhook: String,
+    client: reqwest::Client,
+}
+
+impl Slack {
+    pub fn new(webhook: String) -> Self {
+        Self { webhook, client: reqwest::Client::new() }
+    }
+}
+
+#[async_trait]
+impl Notifier for Slack {
+    fn name(&self) -> &'static str {
+        "slack"
+    }
+
+    async fn notify(&self, message: &str) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let mut map = HashMap::new();
+        map.insert("text", message);
+        self.client.post(&self.webhook).json(&map).send().await?.error_for_status()?;
+        Ok(())
+    }
+}
+
+/// Fans an alert out to every configured channel. One channel failing
+/// is logged and doesn't stop delivery to the others.
+#[derive(Debug, Default)]
+pub struct MultiNotifier {
+    notifiers: Vec<Box<dyn Notifier>>,
+}
+
+impl MultiNotifier {
+    pub fn new(notifiers: Vec<Box<dyn Notifier>>) -> Self {
+        Self { notifiers }
+    }
+
+    /// Every channel with credentials configured; missing channels are skipped.
+    pub fn from_channels(channels: &AlertChannels) -> Self {
+        let mut notifiers: Vec<Box<dyn Notifier>> = Vec::new();
+        if let Some(webhook) = &channels.discord_webhook {
+            notifiers.push(Box::new(Discord::new(webhook.clone())));
+        }
+        if let (Some(bot_token), Some(chat_id)) = (&channels.telegram_bot_token, &channels.telegram_chat_id) {
+            notifiers.push(Box::new(Telegram::new(bot_token, chat_id.clone())));
+        }
+        if let Some(webhook) = &channels.slack_webhook {
+            notifiers.push(Box::new(Slack::new(webhook.clone())));
+        }
+        Self::new(notifiers)
+    }
+
+    pub fn from_env() -> Self {
+        Self::from_channels(&AlertChannels::from_env_over(AlertChannels::default()))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.notifiers.is_empty()
+    }
+
+    /// Sends to all channels concurrently and returns how many accepted the message.
+    pub async fn notify_all(&self, message: &str) -> usize {
+        let results = join_all(self.notifiers.iter().map(|notifier| notifier.notify(message))).await;
+
+        let mut delivered = 0;
+        for (notifier, result) in self.notifiers.iter().zip(results) {
+            match result {
+                Ok(()) => delivered += 1,
+                Err(err) => warn!("Could not send alert to {}, err: {}", notifier.name(), err),
+            }
+        }
+        delivered
+    }
+}
+
+/// Token bucket for alert sends.
+#[derive(Debug)]
+struct TokenBucket {
+    capacity: f64,
+    tokens: f64,
+    refill_per_sec: f64,
+    last_refill: Instant,
+}
+
+impl TokenBucket {
+    fn new(per_minute: u32, burst: u32) -> Self {
+        Self {
+            capacity: burst as f64,
+            tokens: burst as f64,
+            refill_per_sec: per_minute as f64 / 60.0,
+            last_refill: Instant::now(),
+        }
+    }
+
+    fn try_take(&mut self, now: Instant) -> bool {
+        let elapsed = now.saturating_duration_since(self.last_refill).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.refill_per_sec).min(self.capacity);
+        self.last_refill = now;
+
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+/// Identical alerts seen inside one dedup window.
+#[derive(Debug)]
+struct Occurrences {
+    first_seen: Instant,
+    repeats: u32,
+}
+
+/// How an alert was classified by the limiter.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Admission {
+    /// New message within the rate limit: send it.
+    Send,
+    /// New message, but the rate limit is exhausted.
+    RateLimited,
+    /// Duplicate of a message sent in the current window; `first_repeat`
+    /// is set once per window so the caller can schedule the summary.
+    Repeat { first_repeat: bool },
+}
+
+/// Rate limits alerts and coalesces identical ones within a short window.
+#[derive(Debug)]
+pub struct AlertLimiter {
+    bucket: Mutex<TokenBucket>,
+    dedup_window: Duration,
+    seen: Mutex<HashMap<u64, Occurrences>>,
+}
+
+impl AlertLimiter {
+    pub fn new(per_minute: u32, burst: u32, dedup_window: Duration) -> Self {
+        Self {
+            bucket: Mutex::new(TokenBucket::new(per_minute, burst)),
+            dedup_window,
+            seen: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn from_settings(settings: &Settings) -> Self {
+        Self::new(
+            settings.alert_rate_per_minute,
+            settings.alert_burst,
+            Duration::from_secs(settings.alert_dedup_window_secs),
+        )
+    }
+
+    pub fn admit(&self, msg: &str, now: Instant) -> Admission {
+        let key = message_key(msg);
+        {
+            let mut seen = self.seen.lock().unwrap();
+            // Windows with repeats are closed by `take_repeats`; quiet ones just expire
+            seen.retain(|_, occ| occ.repeats > 0 || now.saturating_duration_since(occ.first_seen) < self.dedup_window);
+
+            if let Some(occ) = seen.get_mut(&key) {
+                occ.repeats += 1;
+                return Admission::Repeat { first_repeat: occ.repeats == 1 };
+            }
+            seen.insert(key, Occurrences { first_seen: now, repeats: 0 });
+        }
+
+        if self.try_send(now) {
+            Admission::Send
+        } else {
+            Admission::RateLimited
+        }
+    }
+
+    /// Closes `msg`'s dedup window, returning how many times it was repeated.
+    pub fn take_repeats(&self, msg: &str) -> u32 {
+        self.seen.lock().unwrap().remove(&message_key(msg)).map_or(0, |occ| occ.repeats)
+    }
+
+    pub fn try_send(&self, now: Instant) -> bool {
+        self.bucket.lock().unwrap().try_take(now)
+    }
+
+    pub fn dedup_window(&self) -> Duration {
+        self.dedup_window
+    }
+}
+
+fn message_key(msg: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    msg.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Configures alert channels, rate limiting and dedup from settings. Later calls are no-ops.
+pub fn init(settings: &Settings) {
+    let _ = NOTIFIER.set(MultiNotifier::from_channels(&settings.alert_channels));
+    let _ = LIMITER.set(AlertLimiter::from_settings(settings));
+}
+
+fn limiter() -> &'static AlertLimiter {
+    LIMITER.get_or_init(|| AlertLimiter::from_settings(&Settings::default()))
+}
+
+/// Alerts every configured channel that we found an opportunity.
+///
+/// Identical messages within the dedup window are sent once, then summarized
+/// as a single "xN occurrences" alert when the window closes.
 pub async fn alert(msg: &str, block: &u64) {
-    let msg = format!(
+    let limiter = limiter();
+    match limiter.admit(msg, Instant::now()) {
+        Admission::Send => send(msg, *block).await,
+        Admission::RateLimited => warn!("Alert rate limited: {}", msg),
+        Admission::Repeat { first_repeat: false } => {}
+        Admission::Repeat { first_repeat: true } => {
+            let msg = msg.to_string();
+            let block = *block;
+            tokio::spawn(async move {
+                tokio::time::sleep(limiter.dedup_window()).await;
+                let occurrences = limiter.take_repeats(&msg) + 1;
+                if !limiter.try_send(Instant::now()) {
+                    warn!("Alert rate limited: {} (x{} occurrences)", msg, occurrences);
+                    return;
+                }
+                let summary = format!(
+                    "{}\n🔁 x{} occurrences in the last {}s",
+                    msg,
+                    occurrences,
+                    limiter.dedup_window().as_secs()
+                );
+                send(&summary, block).await;
+            });
+        }
+    }
+}
+
+async fn send(msg: &str, block: u64) {
+    let message = format!(
         "-----------------------------\n🔍 Block: {:?}\n-----------------------------\n{}",
         block, msg
     );
 
-    let max_length = 1900.min(msg.len());
-    let message = msg[..max_length].to_string();
-    let mut map = HashMap::new();
-    map.insert("content", message.to_string());
+    let notifier = NOTIFIER.get_or_init(MultiNotifier::from_env);
+    if notifier.notify_all(&message).await == 0 {
+        info!("Message: {}", message);
+    }
+}
+
+/// First `max_length` bytes of `message`, cut on a char boundary.
+fn truncate(message: &str, max_length: usize) -> &str {
+    if message.len() <= max_length {
+        return message;
+    }
+    let mut end = max_length;
+    while !message.is_char_boundary(end) {
+        end -= 1;
+    }
+    &message[..end]
+}
 
-    let webhook = std::env::var("DISCORD_WEBHOOK").expect("missing DISCORD_WEBHOOK");
-    let client = reqwest::Client::new();
-    let res = client.post(webhook.to_string()).json(&map).send().await;
+#[cfg(test)]
+mod tests {
+    use mockito::Matcher;
+    use serde_json::json;
 
-    match res {
-        Ok(_) => {}
-        Err(err) => {
-            println!("Could not send alert to discord, err: {}", err);
-            println!("Message: {}", message);
+    use super::*;
+
+    #[derive(Debug)]
+    struct Stub {
+        fails: bool,
+    }
+
+    #[async_trait]
+    impl Notifier for Stub {
+        fn name(&self) -> &'static str {
+            "stub"
         }
+
+        async fn notify(&self, _message: &str) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+            if self.fails {
+                Err("channel down".into())
+            } else {
+                Ok(())
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn one_channel_failing_doesnt_stop_the_others() {
+        let notifier = MultiNotifier::new(vec![
+            Box::new(Stub { fails: true }),
+            Box::new(Stub { fails: false }),
+            Box::new(Stub { fails: false }),
+        ]);
+        assert_eq!(notifier.notify_all("hello").await, 2);
+    }
+
+    #[tokio::test]
+    async fn discord_posts_the_truncated_message_as_content() {
+        let mut server = mockito::Server::new_async().await;
+        let long = "x".repeat(DISCORD_MAX_LENGTH + 10);
+        let hook = server
+            .mock("POST", "/webhook")
+            .match_body(Matcher::Json(json!({ "content": "x".repeat(DISCORD_MAX_LENGTH) })))
+            .with_status(204)
+            .create_async()
+            .await;
+
+        Discord::new(format!("{}/webhook", server.url())).notify(&long).await.unwrap();
+        hook.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn telegram_posts_chat_id_and_text_to_the_bots_send_message() {
+        let mut server = mockito::Server::new_async().await;
+        let api = server
+            .mock("POST", "/bot123:abc/sendMessage")
+            .match_body(Matcher::Json(json!({ "chat_id": "42", "text": "sandwich landed" })))
+            .with_status(200)
+            .create_async()
+            .await;
+
+        Telegram::with_api_base(&server.url(), "123:abc", "42".to_string()).notify("sandwich landed").await.unwrap();
+        api.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn slack_posts_the_message_as_text() {
+        let mut server = mockito::Server::new_async().await;
+        let hook = server
+            .mock("POST", "/services/x")
+            .match_body(Matcher::Json(json!({ "text": "sandwich landed" })))
+            .with_status(200)
+            .create_async()
+            .await;
+
+        Slack::new(format!("{}/services/x", server.url())).notify("sandwich landed").await.unwrap();
+        hook.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn a_rejected_webhook_is_an_error() {
+        let mut server = mockito::Server::new_async().await;
+        let _hook = server.mock("POST", "/services/x").with_status(403).create_async().await;
+        assert!(Slack::new(format!("{}/services/x", server.url())).notify("hello").await.is_err());
+    }
+
+    #[test]
+    fn channels_without_full_credentials_are_skipped() {
+        let channels = AlertChannels {
+            slack_webhook: Some("https://hooks.slack.com/services/x".to_string()),
+            // A chat id alone isn't enough to reach Telegram
+            telegram_chat_id: Some("42".to_string()),
+            ..Default::default()
+        };
+        let notifier = MultiNotifier::from_channels(&channels);
+        assert_eq!(notifier.notifiers.iter().map(|n| n.name()).collect::<Vec<_>>(), vec!["slack"]);
+        assert!(MultiNotifier::from_channels(&AlertChannels::default()).is_empty());
+    }
+
+    #[test]
+    fn truncate_cuts_on_a_char_boundary() {
+        assert_eq!(truncate("short", 10), "short");
+        // '🔍' is four bytes; cutting inside it drops it whole
+        assert_eq!(truncate("ab🔍cd", 4), "ab");
+        assert_eq!(truncate("ab🔍cd", 6), "ab🔍");
+    }
+
+    #[test]
+    fn limiter_sends_a_burst_then_refills_per_minute() {
+        let limiter = AlertLimiter::new(60, 2, Duration::ZERO);
+        let now = Instant::now();
+        assert!(limiter.try_send(now) && limiter.try_send(now));
+        assert!(!limiter.try_send(now));
+        // 60 per minute is one a second
+        assert!(limiter.try_send(now + Duration::from_secs(1)));
+        assert_eq!(limiter.admit("new", now + Duration::from_secs(1)), Admission::RateLimited);
+    }
+
+    #[test]
+    fn identical_alerts_are_coalesced_within_the_window() {
+        let limiter = AlertLimiter::new(60, 10, Duration::from_secs(30));
+        let now = Instant::now();
+        assert_eq!(limiter.admit("reverted", now), Admission::Send);
+        assert_eq!(limiter.admit("reverted", now), Admission::Repeat { first_repeat: true });
+        assert_eq!(limiter.admit("reverted", now), Admission::Repeat { first_repeat: false });
+        assert_eq!(limiter.admit("landed", now), Admission::Send);
+
+        assert_eq!(limiter.take_repeats("reverted"), 2);
+        assert_eq!(limiter.admit("reverted", now), Admission::Send);
+    }
+
+    #[test]
+    fn quiet_windows_expire_on_their_own() {
+        let limiter = AlertLimiter::new(60, 10, Duration::from_secs(30));
+        let now = Instant::now();
+        assert_eq!(limiter.admit("reverted", now), Admission::Send);
+        assert_eq!(limiter.admit("reverted", now + Duration::from_secs(31)), Admission::Send);
     }
 }
@@ -0,0 +1,213 @@
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use ethers::{abi::AbiDecode, prelude::*};
+
+use crate::address_book::{ERC20Calls, UniV2RouterCalls, KNOWN_ROUTERS};
+use crate::helpers::address;
+use crate::strategy::MEVOpportunity;
+use crate::uni_v3;
+
+/// A pending ERC20 approval to a known router.
+#[derive(Debug, Clone)]
+pub struct PendingApproval {
+    pub tx_hash: H256,
+    pub owner: Address,
+    pub token: Address,
+    pub router: Address,
+    pub amount: U256,
+    pub seen_at: Instant,
+}
+
+/// Correlates pending approvals with the swap that usually follows them: an approval
+/// to a router is an early signal a swap of that token is imminent.
+#[derive(Debug)]
+pub struct ApprovalTracker {
+    window: Duration,
+    pending: HashMap<(Address, Address), PendingApproval>,
+}
+
+impl ApprovalTracker {
+    pub fn new(window: Duration) -> Self {
+        Self {
+            window,
+            pending: HashMap::new(),
+        }
+    }
+
+    /// Records `tx`, seen at `now`, if it approves a known router. Returns the approval so the caller can pre-position.
+    pub fn observe_approval(&mut self, tx: &Transaction, now: Instant) -> Option<PendingApproval> {
+        let token = tx.to?;
+        let Ok(ERC20Calls::Approve(call)) = ERC20Calls::decode(&tx.input) else {
+            return None;
+        };
+
+        if call.value.is_zero() || !KNOWN_ROUTERS.iter().any(|&router| call.spender == address(router)) {
+            return None;
+        }
+
+        self.prune(now);
+        let approval = PendingApproval {
+            tx_hash: tx.hash,
+            owner: tx.from,
+            token,
+            router: call.spender,
+            amount: call.value,
+            seen_at: now,
+        };
+        self.pending.insert((tx.from, token), approval.clone());
+        Some(approval)
+    }
+
+    /// Matches a swap seen at `now` against an earlier approval of its input token by the same sender.
+    pub fn correlate_swap(&mut self, tx: &Transaction, now: Instant) -> Option<PendingApproval> {
+        let router = tx.to?;
+        let token_in = swap_input_token(tx)?;
+
+        let key = (tx.from, token_in);
+        let approval = self.pending.get(&key)?;
+        if approval.router != router || now.saturating_duration_since(approval.seen_at) > self.window {
+            return None;
+        }
+
+        self.pending.remove(&key)
+    }
+
+    pub fn len(&self) -> usize {
+        self.pending.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pending.is_empty()
+    }
+
+    fn prune(&mut self, now: Instant) {
+        let window = self.window;
+        self.pending.retain(|_, approval| now.saturating_duration_since(approval.seen_at) <= window);
+    }
+}
+
+/// Moves a correlated swap's opportunities ahead: the approval already told us it's coming.
+pub fn prioritize_correlated(opportunities: &mut [MEVOpportunity]) {
+    for opp in opportunities {
+        opp.priority = opp.priority.saturating_add(2).min(10);
+    }
+}
+
+/// Input token of a token-input router swap (ETH-input swaps need no approval).
+pub fn swap_input_token(tx: &Transaction) -> Option<Address> {
+    if let Ok(call) = UniV2RouterCalls::decode(&tx.input) {
+        let path = match call {
+            UniV2RouterCalls::SwapExactTokensForETH(c) => c.path,
+            UniV2RouterCalls::SwapExactTokensForETHSupportingFeeOnTransferTokens(c) => c.path,
+            UniV2RouterCalls::SwapExactTokensForTokens(c) => c.path,
+            UniV2RouterCalls::SwapExactTokensForTokensSupportingFeeOnTransferTokens(c) => c.path,
+            UniV2RouterCalls::SwapTokensForExactETH(c) => c.path,
+            UniV2RouterCalls::SwapTokensForExactTokens(c) => c.path,
+            _ => return None,
+        };
+        return path.first().copied();
+    }
+
+    uni_v3::decode_router_swaps(&tx.input).first().map(|swap| swap.token_in())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::address_book::erc20::ApproveCall;
+    use crate::address_book::{SwapExactTokensForTokensCall, SUSHISWAP_ROUTER, UNISWAP_V2_ROUTER};
+    use crate::strategy::types::{BackrunDetails, StrategyType};
+    use ethers::abi::AbiEncode;
+
+    fn account(n: u64) -> Address {
+        Address::from_low_u64_be(n)
+    }
+
+    fn approve(token: Address, spender: Address) -> Transaction {
+        let call = ApproveCall { spender, value: U256::MAX };
+        Transaction { from: account(1), to: Some(token), input: call.encode().into(), ..Default::default() }
+    }
+
+    fn opportunity(priority: u8) -> MEVOpportunity {
+        MEVOpportunity {
+            id: format!("backrun_{}", priority),
+            target_tx: Transaction::default(),
+            strategy_type: StrategyType::Backrun(BackrunDetails {
+                victim_tx: Transaction::default(),
+                backrun_tx: Default::default(),
+            }),
+            estimated_profit: U256::from(1_000),
+            gas_cost: U256::from(200),
+            priority,
+            reserve_block: U64::zero(),
+            expiry_block: U64::one(),
+            observed_at: Instant::now(),
+            landing_probability: 1.0,
+        }
+    }
+
+    fn swap(router: Address, token_in: Address) -> Transaction {
+        let call = SwapExactTokensForTokensCall {
+            amount_in: 1.into(),
+            amount_out_min: 0.into(),
+            path: vec![token_in, account(0x70c)],
+            to: account(1),
+            deadline: 0.into(),
+        };
+        Transaction { from: account(1), to: Some(router), input: call.encode().into(), ..Default::default() }
+    }
+
+    #[test]
+    fn only_approvals_to_known_routers_are_tracked() {
+        let now = Instant::now();
+        let mut tracker = ApprovalTracker::new(Duration::from_secs(60));
+        assert!(tracker.observe_approval(&approve(account(0x70c), account(0xbad)), now).is_none());
+        let approval = tracker.observe_approval(&approve(account(0x70c), address(UNISWAP_V2_ROUTER)), now).unwrap();
+        assert_eq!((approval.owner, approval.token, approval.amount), (account(1), account(0x70c), U256::MAX));
+        assert_eq!(tracker.len(), 1);
+    }
+
+    #[test]
+    fn a_swap_through_the_approved_router_consumes_the_approval() {
+        let (token, router) = (account(0x70c), address(UNISWAP_V2_ROUTER));
+        let now = Instant::now();
+        let mut tracker = ApprovalTracker::new(Duration::from_secs(60));
+        tracker.observe_approval(&approve(token, router), now);
+
+        assert!(tracker.correlate_swap(&swap(address(SUSHISWAP_ROUTER), token), now).is_none());
+        assert!(tracker.correlate_swap(&swap(router, token), now).is_some());
+        assert!(tracker.correlate_swap(&swap(router, token), now).is_none());
+        assert!(tracker.is_empty());
+    }
+
+    #[test]
+    fn approvals_expire_after_the_window() {
+        let (token, router) = (account(0x70c), address(UNISWAP_V2_ROUTER));
+        let window = Duration::from_secs(60);
+        let approved_at = Instant::now();
+        let mut tracker = ApprovalTracker::new(window);
+
+        tracker.observe_approval(&approve(token, router), approved_at);
+        assert!(tracker.correlate_swap(&swap(router, token), approved_at + window + Duration::from_secs(1)).is_none());
+
+        tracker.observe_approval(&approve(token, router), approved_at);
+        assert!(tracker.correlate_swap(&swap(router, token), approved_at + window).is_some());
+    }
+
+    #[test]
+    fn the_swap_an_approval_announced_is_prioritized() {
+        let (token, router) = (account(0x70c), address(UNISWAP_V2_ROUTER));
+        let now = Instant::now();
+        let mut tracker = ApprovalTracker::new(Duration::from_secs(60));
+        tracker.observe_approval(&approve(token, router), now);
+
+        let correlated = tracker.correlate_swap(&swap(router, token), now + Duration::from_secs(12)).unwrap();
+        assert_eq!((correlated.owner, correlated.token), (account(1), token));
+
+        let mut opportunities = vec![opportunity(5), opportunity(9)];
+        prioritize_correlated(&mut opportunities);
+        let priorities: Vec<u8> = opportunities.iter().map(|opp| opp.priority).collect();
+        assert_eq!(priorities, [7, 10]);
+    }
+}
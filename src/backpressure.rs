@@ -0,0 +1,194 @@
+use std::collections::VecDeque;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+use futures_util::{stream, Stream};
+use tokio::sync::Notify;
+use tracing::warn;
+
+use crate::metrics::metrics;
+
+/// What to do with a new pending tx when the queue in front of the strategies is full.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BackpressurePolicy {
+    /// Evict the oldest queued item; stale pending txs are the least valuable.
+    DropOldest,
+    /// Stop reading the subscription until there is room again.
+    Block,
+}
+
+impl FromStr for BackpressurePolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "drop_oldest" => Ok(Self::DropOldest),
+            "block" => Ok(Self::Block),
+            other => Err(format!("unknown backpressure policy: {}", other)),
+        }
+    }
+}
+
+/// Bounded queue between a mempool subscription and the fetch/analysis pipeline.
+///
+/// One task pushes from the subscription while the pipeline pops, so a slow
+/// node shows up as queue depth, lag and drops instead of silent stalls.
+#[derive(Debug)]
+pub struct PendingQueue<T> {
+    items: Mutex<VecDeque<(Instant, T)>>,
+    capacity: usize,
+    policy: BackpressurePolicy,
+    saturated: AtomicBool,
+    closed: AtomicBool,
+    item_ready: Notify,
+    space_ready: Notify,
+}
+
+impl<T> PendingQueue<T> {
+    pub fn new(capacity: usize, policy: BackpressurePolicy) -> Self {
+        Self {
+            items: Mutex::new(VecDeque::with_capacity(capacity)),
+            capacity: capacity.max(1),
+            policy,
+            saturated: AtomicBool::new(false),
+            closed: AtomicBool::new(false),
+            item_ready: Notify::new(),
+            space_ready: Notify::new(),
+        }
+    }
+
+    pub async fn push(&self, item: T) {
+        let mut item = Some(item);
+        let mut blocked_since: Option<Instant> = None;
+
+        loop {
+            {
+                let mut items = self.items.lock().unwrap();
+                if items.len() >= self.capacity {
+                    if !self.saturated.swap(true, Ordering::Relaxed) {
+                        warn!("⚠️ Pending tx queue saturated ({} items), applying {:?}", self.capacity, self.policy);
+                    }
+                    if self.policy == BackpressurePolicy::DropOldest {
+                        items.pop_front();
+                        metrics().pending_tx_dropped.inc();
+                    }
+                }
+
+                if items.len() < self.capacity {
+                    items.push_back((Instant::now(), item.take().unwrap()));
+                    metrics().pending_tx_queue_depth.set(items.len() as i64);
+                    if let Some(since) = blocked_since {
+                        metrics().pending_tx_blocked_seconds.inc_by(since.elapsed().as_secs_f64());
+                    }
+                    self.item_ready.notify_one();
+                    return;
+                }
+            }
+
+            blocked_since.get_or_insert_with(Instant::now);
+            self.space_ready.notified().await;
+        }
+    }
+
+    /// Next item in arrival order; `None` once closed and drained.
+    pub async fn pop(&self) -> Option<T> {
+        loop {
+            {
+                let mut items = self.items.lock().unwrap();
+                if let Some((queued_at, item)) = items.pop_front() {
+                    metrics().pending_tx_queue_depth.set(items.len() as i64);
+                    metrics().pending_tx_queue_lag_seconds.observe(queued_at.elapsed().as_secs_f64());
+                    if items.is_empty() {
+                        self.saturated.store(false, Ordering::Relaxed);
+                    }
+                    self.space_ready.notify_one();
+                    return Some(item);
+                }
+                if self.closed.load(Ordering::Relaxed) {
+                    return None;
+                }
+            }
+
+            self.item_ready.notified().await;
+        }
+    }
+
+    /// Marks the producer as finished; `pop` drains what is left and then ends.
+    pub fn close(&self) {
+        self.closed.store(true, Ordering::Relaxed);
+        self.item_ready.notify_one();
+    }
+
+    pub fn len(&self) -> usize {
+        self.items.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn into_stream(self: Arc<Self>) -> impl Stream<Item = T> {
+        stream::unfold(self, |queue| async move { queue.pop().await.map(|item| (item, queue)) })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use futures_util::StreamExt;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn drop_oldest_evicts_the_stalest_item() {
+        let queue = PendingQueue::new(2, BackpressurePolicy::DropOldest);
+        for i in 1..=3 {
+            queue.push(i).await;
+        }
+        queue.close();
+        assert_eq!(queue.pop().await, Some(2));
+        assert_eq!(queue.pop().await, Some(3));
+        assert_eq!(queue.pop().await, None);
+    }
+
+    #[tokio::test]
+    async fn block_waits_for_room_instead_of_dropping() {
+        let queue = Arc::new(PendingQueue::new(1, BackpressurePolicy::Block));
+        queue.push(1).await;
+
+        let producer = tokio::spawn({
+            let queue = queue.clone();
+            async move { queue.push(2).await }
+        });
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert!(!producer.is_finished());
+        assert_eq!(queue.len(), 1);
+
+        assert_eq!(queue.pop().await, Some(1));
+        tokio::time::timeout(Duration::from_secs(1), producer).await.unwrap().unwrap();
+        assert_eq!(queue.pop().await, Some(2));
+    }
+
+    #[tokio::test]
+    async fn pop_waits_for_an_item_and_ends_once_closed() {
+        let queue = Arc::new(PendingQueue::new(4, BackpressurePolicy::Block));
+        let consumer = tokio::spawn({
+            let queue = queue.clone();
+            async move { queue.into_stream().collect::<Vec<_>>().await }
+        });
+        queue.push("a").await;
+        queue.push("b").await;
+        queue.close();
+        assert_eq!(tokio::time::timeout(Duration::from_secs(1), consumer).await.unwrap().unwrap(), vec!["a", "b"]);
+    }
+
+    #[test]
+    fn policy_parses_from_its_env_names() {
+        assert_eq!("drop_oldest".parse(), Ok(BackpressurePolicy::DropOldest));
+        assert_eq!("block".parse(), Ok(BackpressurePolicy::Block));
+        assert!("drop_newest".parse::<BackpressurePolicy>().is_err());
+    }
+}
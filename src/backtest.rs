@@ -0,0 +1,207 @@
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use ethers::prelude::*;
+use tracing::warn;
+
+use crate::mempool::is_router_transaction;
+use crate::strategy::{DecisionTrace, MEVOpportunity, StrategyManager};
+
+/// What one strategy would have found over a backtest.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct StrategyTally {
+    pub opportunities: usize,
+    /// Sum of the opportunities' simulated profit net of gas.
+    pub net_profit: U256,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct BacktestReport {
+    pub blocks: u64,
+    /// Router txs replayed through the strategies.
+    pub txs: usize,
+    pub by_strategy: BTreeMap<&'static str, StrategyTally>,
+}
+
+impl BacktestReport {
+    pub fn record(&mut self, opportunity: &MEVOpportunity) {
+        let tally = self.by_strategy.entry(opportunity.strategy_type.name()).or_default();
+        tally.opportunities += 1;
+        tally.net_profit = tally.net_profit.saturating_add(opportunity.net_profit());
+    }
+
+    pub fn print(&self) {
+        println!("📈 Backtest over {} blocks, {} router txs:", self.blocks, self.txs);
+        for (strategy, tally) in &self.by_strategy {
+            println!(
+                "   - {}: {} opportunities, {} ETH net",
+                strategy,
+                tally.opportunities,
+                ethers::utils::format_ether(tally.net_profit)
+            );
+        }
+    }
+}
+
+/// Replays the router txs of past blocks through the strategies.
+///
+/// The manager's config must point at an anvil node: before each block it is
+/// re-forked from `archive_rpc` at the parent block, so every tx of the block
+/// is analyzed and simulated against the state it was sent into.
+#[derive(Debug)]
+pub struct Backtester {
+    manager: Arc<StrategyManager>,
+    archive: Provider<Http>,
+    archive_rpc: String,
+}
+
+impl Backtester {
+    pub fn new(
+        manager: Arc<StrategyManager>,
+        archive_rpc: &str,
+    ) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        Ok(Self {
+            manager,
+            archive: Provider::<Http>::try_from(archive_rpc)?,
+            archive_rpc: archive_rpc.to_string(),
+        })
+    }
+
+    /// Replays blocks `from..=to`.
+    pub async fn run(&self, from: u64, to: u64) -> Result<BacktestReport, Box<dyn std::error::Error + Send + Sync>> {
+        let mut report = BacktestReport::default();
+        for number in from..=to {
+            let Some(block) = self.archive.get_block_with_txs(number).await? else {
+                warn!("⚠️ Block {} not found on the archive node, skipping", number);
+                continue;
+            };
+            let txs: Vec<Transaction> = block.transactions.into_iter().filter(is_router_transaction).collect();
+            self.fork_at(number.saturating_sub(1)).await?;
+
+            for tx in &txs {
+                for opportunity in self.manager.analyze_transaction(tx, &DecisionTrace::disabled()).await {
+                    report.record(&opportunity);
+                }
+            }
+            report.blocks += 1;
+            report.txs += txs.len();
+        }
+        Ok(report)
+    }
+
+    /// Resets the anvil node to the archive's state at `number`.
+    async fn fork_at(&self, number: u64) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let config = self.manager.config();
+        let forking = serde_json::json!({ "forking": { "jsonRpcUrl": self.archive_rpc, "blockNumber": number } });
+        let _: () = config.http.provider().request("anvil_reset", [forking]).await?;
+
+        config.block_height.set(U64::from(number));
+        config.pool_cache.clear();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::address_book::{SwapExactETHForTokensCall, UniV2RouterCalls};
+    use crate::settings::{Settings, StrategiesEnabled};
+    use crate::strategy::{BackrunDetails, DexType, FlashloanDetails, StrategyType};
+    use crate::test_support::{self, MockNode};
+    use ethers::abi::AbiEncode;
+    use mockito::Matcher;
+    use serde_json::json;
+    use std::time::Instant;
+
+    fn opportunity(strategy_type: StrategyType, estimated_profit: u64) -> MEVOpportunity {
+        MEVOpportunity {
+            id: "backtest".to_string(),
+            target_tx: Transaction::default(),
+            strategy_type,
+            estimated_profit: U256::from(estimated_profit),
+            gas_cost: U256::from(100),
+            priority: 0,
+            reserve_block: U64::zero(),
+            expiry_block: U64::one(),
+            observed_at: Instant::now(),
+            landing_probability: 1.0,
+        }
+    }
+
+    #[test]
+    fn report_tallies_net_profit_per_strategy() {
+        let backrun = || StrategyType::Backrun(BackrunDetails { victim_tx: Transaction::default(), backrun_tx: Default::default() });
+        let flashloan = StrategyType::Flashloan(FlashloanDetails {
+            victim_tx: Transaction::default(),
+            flashloan_tx: Default::default(),
+            loan_token: Address::zero(),
+            loan_amount: U256::zero(),
+            steps: Vec::new(),
+        });
+
+        let mut report = BacktestReport::default();
+        report.record(&opportunity(backrun(), 1_000));
+        report.record(&opportunity(backrun(), 500));
+        // Gas above the estimate nets to nothing rather than going negative
+        report.record(&opportunity(flashloan, 50));
+
+        let backruns = &report.by_strategy[backrun().name()];
+        assert_eq!((backruns.opportunities, backruns.net_profit), (2, U256::from(1_300)));
+        assert_eq!(report.by_strategy.values().map(|tally| tally.opportunities).sum::<usize>(), 3);
+        assert!(report.by_strategy.values().any(|tally| tally.net_profit.is_zero()));
+    }
+
+    #[tokio::test]
+    async fn replays_a_fixture_block_set_against_the_forked_parent_state() {
+        // The anvil node the strategies simulate on; it answers the flash loan backrun's calls
+        let mut anvil = MockNode::new().await;
+        let loan_amount = U256::exp10(18);
+        anvil.rpc("eth_call", json!(Bytes::from(vec![loan_amount, U256::exp10(21)].encode()))).await;
+        anvil.rpc("eth_estimateGas", json!("0x927c0")).await;
+        let reset = anvil
+            .server
+            .mock("POST", "/")
+            .match_body(Matcher::PartialJson(json!({ "method": "anvil_reset", "params": [{ "forking": { "blockNumber": 100 } }] })))
+            .with_body(json!({ "jsonrpc": "2.0", "id": 1, "result": null }).to_string())
+            .expect(1)
+            .create_async()
+            .await;
+        let settings = Settings {
+            strategies_enabled: StrategiesEnabled::from_list("flashloan"),
+            executor_address: Some(Address::from_low_u64_be(0xe8ec)),
+            ..test_support::settings()
+        };
+        let config = Arc::new(test_support::config(&anvil.url(), settings).await);
+        let chain = &config.chain;
+
+        // Block 101 holds an ETH -> token swap through Uniswap and a plain transfer; 102 was never mined
+        let call = UniV2RouterCalls::SwapExactETHForTokens(SwapExactETHForTokensCall {
+            amount_out_min: U256::zero(),
+            path: vec![chain.weth, Address::from_low_u64_be(0x70c)],
+            to: Address::from_low_u64_be(0xbeef),
+            deadline: U256::MAX,
+        });
+        let swap = Transaction {
+            hash: H256::from_low_u64_be(1),
+            to: chain.dexes.router(DexType::UniswapV2),
+            value: loan_amount,
+            input: call.encode().into(),
+            ..Default::default()
+        };
+        let transfer = Transaction { hash: H256::from_low_u64_be(2), to: Some(Address::from_low_u64_be(0xbeef)), ..Default::default() };
+        let block = Block { number: Some(U64::from(101)), transactions: vec![swap, transfer], ..Default::default() };
+        let mut archive = MockNode::new().await;
+        archive.rpc_matching(json!({ "method": "eth_getBlockByNumber", "params": ["0x65", true] }), json!(block)).await;
+        archive.rpc_matching(json!({ "method": "eth_getBlockByNumber", "params": ["0x66", true] }), json!(null)).await;
+
+        let manager = Arc::new(StrategyManager::new(config.clone()).await);
+        let report = Backtester::new(manager, &archive.url()).unwrap().run(101, 102).await.unwrap();
+
+        assert_eq!((report.blocks, report.txs), (1, 1));
+        let flashloans = &report.by_strategy["flashloan"];
+        assert_eq!(flashloans.opportunities, 1);
+        assert!(!flashloans.net_profit.is_zero());
+        reset.assert_async().await;
+        assert_eq!(config.block_height.get(), U64::from(100));
+    }
+}
@@ -0,0 +1,88 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use ethers::types::U64;
+
+/// The one block height every component agrees on.
+///
+/// Fed by the block scanner; strategies and the executor read it instead of
+/// each asking the node, which around a block boundary can answer N to one
+/// caller and N+1 to the next.
+#[derive(Debug, Default)]
+pub struct BlockHeight {
+    number: AtomicU64,
+    timestamp: AtomicU64,
+}
+
+impl BlockHeight {
+    pub fn new(number: U64) -> Self {
+        Self { number: AtomicU64::new(number.as_u64()), timestamp: AtomicU64::new(0) }
+    }
+
+    pub fn get(&self) -> U64 {
+        U64::from(self.number.load(Ordering::Acquire))
+    }
+
+    /// Overwrites the height, e.g. when a reorg lands on a shorter chain.
+    pub fn set(&self, number: U64) {
+        self.number.store(number.as_u64(), Ordering::Release);
+    }
+
+    /// Moves the height forward to `number`. Stale or repeated updates are ignored.
+    /// Returns true if the height changed.
+    pub fn advance(&self, number: U64) -> bool {
+        let number = number.as_u64();
+        self.number.fetch_max(number, Ordering::AcqRel) < number
+    }
+
+    /// Timestamp of the newest head seen, or 0 before the first.
+    pub fn timestamp(&self) -> u64 {
+        self.timestamp.load(Ordering::Acquire)
+    }
+
+    /// Records a head's timestamp. Older timestamps are ignored.
+    pub fn advance_timestamp(&self, timestamp: u64) {
+        self.timestamp.fetch_max(timestamp, Ordering::AcqRel);
+    }
+
+    /// `secs` past the head's timestamp, or past the clock's before the first head is seen.
+    pub fn deadline(&self, secs: u64) -> u64 {
+        let now = match self.timestamp() {
+            0 => SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs()),
+            timestamp => timestamp,
+        };
+        now.saturating_add(secs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn deadline_is_bounded_by_the_head_timestamp() {
+        let height = BlockHeight::new(U64::from(100));
+        height.advance_timestamp(1_700_000_000);
+        // A stale head doesn't pull the deadline back
+        height.advance_timestamp(1_699_999_988);
+        assert_eq!(height.deadline(60), 1_700_000_060);
+        assert_eq!(height.deadline(u64::MAX), u64::MAX);
+    }
+
+    #[test]
+    fn deadline_falls_back_to_the_clock_before_the_first_head() {
+        let before = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
+        let deadline = BlockHeight::default().deadline(60);
+        let after = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
+        assert!((before + 60..=after + 60).contains(&deadline));
+    }
+
+    #[test]
+    fn advance_ignores_stale_heights() {
+        let height = BlockHeight::new(U64::from(100));
+        assert!(height.advance(U64::from(101)));
+        assert!(!height.advance(U64::from(99)));
+        assert!(!height.advance(U64::from(101)));
+        assert_eq!(height.get(), U64::from(101));
+    }
+}
@@ -1,17 +1,258 @@
+use std::collections::VecDeque;
 use std::{sync::Arc, time::Duration};
 
-use ethers::prelude::{k256::ecdsa::SigningKey, SignerMiddleware, *};
+use ethers::prelude::*;
+use futures_util::{Stream, StreamExt};
 use tokio::time::sleep;
+use tracing::{info, warn};
 
-pub async fn loop_blocks(http_provider: Arc<SignerMiddleware<Provider<Http>, Wallet<SigningKey>>>) {
-    let mut last_block: U64 = U64::zero();
+use crate::gas_oracle::{GasOracle, REWARD_PERCENTILES};
+use crate::shutdown::Shutdown;
+use crate::signer::SignerClient;
+use crate::Config;
+
+/// The canonical chain replaced blocks we had already seen.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Reorg {
+    /// How many of our blocks were orphaned.
+    pub depth: u64,
+    /// Lowest block whose hash changed.
+    pub fork_block: U64,
+    pub new_tip: U64,
+}
+
+/// Remembers the hashes of the last `history` blocks and spots when a new
+/// block doesn't build on them.
+#[derive(Debug)]
+pub struct ReorgDetector {
+    history: usize,
+    /// (number, hash), oldest first.
+    chain: VecDeque<(U64, H256)>,
+}
+
+impl ReorgDetector {
+    pub fn new(history: usize) -> Self {
+        Self {
+            history: history.max(1),
+            chain: VecDeque::new(),
+        }
+    }
+
+    pub fn tip(&self) -> Option<(U64, H256)> {
+        self.chain.back().copied()
+    }
+
+    pub fn hash_at(&self, number: U64) -> Option<H256> {
+        self.chain.iter().find(|(n, _)| *n == number).map(|(_, hash)| *hash)
+    }
+
+    /// Whether a block at `number` with `parent_hash` builds on what we've seen.
+    /// Parents older than our history can't be checked and are assumed to.
+    pub fn extends(&self, number: U64, parent_hash: H256) -> bool {
+        if number.is_zero() {
+            return true;
+        }
+        self.hash_at(number - 1).is_none_or(|hash| hash == parent_hash)
+    }
+
+    /// Records a canonical block. Anything we held at or above `number` under a
+    /// different hash is dropped as orphaned and reported.
+    ///
+    /// When walking a reorg, insert the new branch oldest first so the whole
+    /// depth is reported once, from the fork point.
+    pub fn insert(&mut self, number: U64, hash: H256) -> Option<Reorg> {
+        if self.hash_at(number) == Some(hash) {
+            return None;
+        }
+
+        let before = self.chain.len();
+        self.chain.retain(|(n, _)| *n < number);
+        let depth = (before - self.chain.len()) as u64;
+
+        self.chain.push_back((number, hash));
+        while self.chain.len() > self.history {
+            self.chain.pop_front();
+        }
+
+        (depth > 0).then_some(Reorg { depth, fork_block: number, new_tip: number })
+    }
+}
+
+/// Follows the chain head over one `newHeads` subscription: tracks it into the
+/// config's shared `block_height`, feeds each block's fees to its `gas_oracle`,
+/// expires its `pool_cache`, broadcasts a `Reorg` whenever blocks we'd already
+/// seen are replaced, and then the head itself on `heads`. Resubscribes if the
+/// subscription drops.
+pub async fn loop_blocks(config: Arc<Config>, shutdown: Shutdown) {
+    let gas_oracle = &config.gas_oracle;
+    let mut detector = ReorgDetector::new(config.settings.reorg_history_blocks);
+    record_fees(&config.http, gas_oracle, gas_oracle.window() as u64, BlockNumber::Latest).await;
+    while !shutdown.is_triggered() {
+        match config.wss.subscribe_blocks().await {
+            Ok(heads) => follow_heads(&config, &mut detector, heads, &shutdown).await,
+            Err(e) => warn!("⚠️ newHeads subscription failed: {}", e),
+        }
+        if !shutdown.is_triggered() {
+            warn!("⚠️ newHeads subscription ended, resubscribing");
+            sleep(Duration::from_secs(1)).await;
+        }
+    }
+}
+
+/// Applies each head from `heads` until it ends or shutdown is triggered. A head
+/// already applied (e.g. re-sent by the node) is skipped, so every block reaches
+/// `heads` subscribers once.
+async fn follow_heads<S>(config: &Config, detector: &mut ReorgDetector, mut heads: S, shutdown: &Shutdown)
+where
+    S: Stream<Item = Block<H256>> + Unpin,
+{
     loop {
-        if let Ok(block) = http_provider.get_block_number().await {
-            if block > last_block {
-                last_block = block;
-                println!("\n---------- BLOCK: {:?} ----------", block);
-            }
+        let head = tokio::select! {
+            _ = shutdown.wait() => return,
+            head = heads.next() => match head {
+                Some(head) => head,
+                None => return,
+            },
+        };
+        let (Some(number), Some(hash)) = (head.number, head.hash) else {
+            continue;
+        };
+        if detector.hash_at(number) == Some(hash) {
+            continue;
+        }
+        apply_new_head(config, detector, head, number).await;
+    }
+}
+
+async fn apply_new_head(config: &Config, detector: &mut ReorgDetector, head: Block<H256>, number: U64) {
+    let (http_provider, block_height) = (&config.http, &config.block_height);
+    let timestamp = head.timestamp.as_u64();
+    let reorg = apply_head(http_provider, detector, head.clone(), config.settings.reorg_history_blocks).await;
+    // Re-read replaced blocks' fees along with the new head's
+    let refreshed = reorg.map_or(1, |reorg| reorg.depth + 1);
+    record_fees(http_provider, &config.gas_oracle, refreshed, number.into()).await;
+    if let Some(reorg) = reorg {
+        // A shorter winning chain moves the height back
+        block_height.set(number);
+        config.pool_cache.clear();
+        let _ = config.reorgs.send(reorg);
+    }
+    block_height.advance_timestamp(timestamp);
+    if block_height.advance(number) {
+        config.pool_cache.advance(number);
+        info!("---------- BLOCK: {:?} ----------", number);
+    }
+    let _ = config.heads.send(head);
+}
+
+/// Feeds the `blocks` blocks up to `newest` from `eth_feeHistory` into the oracle.
+async fn record_fees(
+    http_provider: &SignerClient,
+    gas_oracle: &GasOracle,
+    blocks: u64,
+    newest: BlockNumber,
+) {
+    match http_provider.fee_history(blocks, newest, &REWARD_PERCENTILES).await {
+        Ok(history) => gas_oracle.record(&history),
+        Err(e) => warn!("⚠️ eth_feeHistory failed: {}", e),
+    }
+}
+
+/// Walks back from `head` until it joins the chain we've seen (or history runs
+/// out), then records the new branch oldest first.
+async fn apply_head(
+    http_provider: &SignerClient,
+    detector: &mut ReorgDetector,
+    head: Block<H256>,
+    history: usize,
+) -> Option<Reorg> {
+    let mut branch = vec![head];
+    while branch.len() < history {
+        let oldest = branch.last()?;
+        if detector.extends(oldest.number?, oldest.parent_hash) {
+            break;
+        }
+        match http_provider.get_block(oldest.parent_hash).await {
+            Ok(Some(parent)) => branch.push(parent),
+            _ => break,
+        }
+    }
+
+    let new_tip = branch.first()?.number?;
+    let mut reorg: Option<Reorg> = None;
+    for block in branch.iter().rev() {
+        let (Some(number), Some(hash)) = (block.number, block.hash) else {
+            continue;
+        };
+        if let Some(found) = detector.insert(number, hash) {
+            reorg = Some(match reorg {
+                Some(earlier) => Reorg { depth: earlier.depth + found.depth, ..earlier },
+                None => found,
+            });
+        }
+    }
+    reorg.map(|reorg| Reorg { new_tip, ..reorg })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{self, MockNode};
+
+    fn hash(n: u64) -> H256 {
+        H256::from_low_u64_be(n)
+    }
+
+    #[test]
+    fn extends_checks_the_parent_only_within_history() {
+        let mut detector = ReorgDetector::new(2);
+        for n in 1..=3 {
+            detector.insert(U64::from(n), hash(n));
+        }
+        assert!(detector.extends(U64::from(4), hash(3)));
+        assert!(!detector.extends(U64::from(4), hash(99)));
+        // Block 1 fell out of the two we remember
+        assert!(detector.extends(U64::from(2), hash(99)));
+        assert_eq!(detector.hash_at(U64::from(1)), None);
+    }
+
+    #[test]
+    fn a_replaced_branch_is_reported_once_from_the_fork() {
+        let mut detector = ReorgDetector::new(10);
+        for n in 1..=5 {
+            assert_eq!(detector.insert(U64::from(n), hash(n)), None);
+        }
+        // Re-seeing a block is not a reorg
+        assert_eq!(detector.insert(U64::from(5), hash(5)), None);
+
+        let reorg = detector.insert(U64::from(4), hash(40));
+        assert_eq!(reorg, Some(Reorg { depth: 2, fork_block: U64::from(4), new_tip: U64::from(4) }));
+        assert_eq!(detector.insert(U64::from(5), hash(50)), None);
+        assert_eq!(detector.tip(), Some((U64::from(5), hash(50))));
+    }
+
+    #[tokio::test]
+    async fn each_head_is_applied_and_broadcast_once_even_when_resent() {
+        let node = MockNode::new().await;
+        let config = test_support::config(&node.url(), test_support::settings()).await;
+        let mut received = config.heads.subscribe();
+
+        let header = |n: u64| Block::<H256> {
+            number: Some(U64::from(n)),
+            hash: Some(hash(n)),
+            parent_hash: hash(n - 1),
+            timestamp: U256::from(1_700_000_000 + n * 12),
+            ..Default::default()
+        };
+        // The node re-sends the tip, and later an older block it already sent
+        let heads = futures_util::stream::iter([101, 101, 102, 102, 101, 103].map(header));
+        follow_heads(&config, &mut ReorgDetector::new(10), heads, &Shutdown::new()).await;
+
+        let mut broadcast = Vec::new();
+        while let Ok(head) = received.try_recv() {
+            broadcast.push(head.number.unwrap().as_u64());
         }
-        sleep(Duration::from_millis(1)).await;
+        assert_eq!(broadcast, [101, 102, 103]);
+        assert_eq!(config.block_height.get(), U64::from(103));
     }
 }
@@ -0,0 +1,168 @@
+use ethers::prelude::*;
+
+use crate::address_book::{
+    BALANCER_VAULT, QUOTER_V2, UNISWAP_V2_FACTORY, UNISWAP_V2_ROUTER, UNISWAP_V3_FACTORY, UNISWAP_V3_ROUTER, WETH,
+};
+use crate::helpers::address;
+use crate::strategy::DexRegistry;
+
+/// Chain id anvil and hardhat nodes report unless told otherwise.
+pub const LOCAL_FORK_CHAIN_ID: u64 = 31337;
+
+/// Token and DEX addresses of one chain, picked from the RPC's chain id at
+/// startup so strategies never trade mainnet addresses on another chain.
+#[derive(Debug, Clone)]
+pub struct ChainConfig {
+    pub chain_id: u64,
+    pub name: &'static str,
+    /// Wrapped native token every route starts or ends in (WBNB on BSC).
+    pub weth: Address,
+    pub usdc: Address,
+    pub dai: Address,
+    /// V2 factory pairs are derived from and priced on.
+    pub v2_factory: Address,
+    /// Router of that factory.
+    pub v2_router: Address,
+    pub v3_factory: Option<Address>,
+    /// V3 `SwapRouter` (the original, whose swaps take a deadline) sandwich legs
+    /// go through; V3 swaps aren't sandwiched without one.
+    pub v3_router: Option<Address>,
+    pub quoter_v2: Option<Address>,
+    /// Balancer V2 vault, where flash loans are borrowed.
+    pub balancer_vault: Option<Address>,
+    /// DEXes the arbitrage strategy scans and pair indexes are built for.
+    pub dexes: DexRegistry,
+    /// Mainnet contracts that bridge ERC20s into this chain.
+    pub l1_bridges: Vec<Address>,
+    /// Typical time for a deposit from mainnet to arrive.
+    pub deposit_time_secs: u64,
+}
+
+impl ChainConfig {
+    pub fn mainnet() -> Self {
+        Self {
+            chain_id: 1,
+            name: "mainnet",
+            weth: address(WETH),
+            usdc: address("0xA0b86991c6218b36c1d19D4a2e9Eb0cE3606eB48"),
+            dai: address("0x6B175474E89094C44Da98b954EedeAC495271d0F"),
+            v2_factory: address(UNISWAP_V2_FACTORY),
+            v2_router: address(UNISWAP_V2_ROUTER),
+            v3_factory: Some(address(UNISWAP_V3_FACTORY)),
+            v3_router: Some(address(UNISWAP_V3_ROUTER)),
+            quoter_v2: Some(address(QUOTER_V2)),
+            balancer_vault: Some(address(BALANCER_VAULT)),
+            dexes: DexRegistry::mainnet(),
+            l1_bridges: Vec::new(),
+            deposit_time_secs: 0,
+        }
+    }
+
+    pub fn arbitrum() -> Self {
+        Self {
+            chain_id: 42161,
+            name: "arbitrum",
+            weth: address("0x82aF49447D8a07e3bd95BD0d56f35241523fBab1"),
+            usdc: address("0xaf88d065e77c8cC2239327C5EDb3A432268e5831"),
+            dai: address("0xDA10009cBd5D07dd0CeCc66161FC93D7c9000da1"),
+            v2_factory: address("0xf1D7CC64Fb4452F05c498126312eBE29f30Fbcf9"),
+            v2_router: address("0x4752ba5DBc23f44D87826276BF6Fd6b1C372aD24"),
+            v3_factory: Some(address(UNISWAP_V3_FACTORY)),
+            v3_router: Some(address(UNISWAP_V3_ROUTER)),
+            quoter_v2: Some(address(QUOTER_V2)),
+            balancer_vault: Some(address(BALANCER_VAULT)),
+            dexes: DexRegistry::arbitrum(),
+            l1_bridges: vec![
+                address("0xa3A7B6F88361F48403514059F1F16C8E78d60EeC"), // L1ERC20Gateway
+                address("0xcEe284F754E854890e311e3280b767F80797180d"), // L1CustomGateway
+            ],
+            deposit_time_secs: 600,
+        }
+    }
+
+    pub fn base() -> Self {
+        Self {
+            chain_id: 8453,
+            name: "base",
+            weth: address("0x4200000000000000000000000000000000000006"),
+            usdc: address("0x833589fCD6eDb6E08f4c7C32D4f71b54bdA02913"),
+            dai: address("0x50c5725949A6F0c72E6C4a641F24049A917DB0Cb"),
+            v2_factory: address("0x8909Dc15e40173Ff4699343b6eB8132c65e18eC6"),
+            v2_router: address("0x4752ba5DBc23f44D87826276BF6Fd6b1C372aD24"),
+            v3_factory: Some(address("0x33128a8fC17869897dcE68Ed026d694621f6FDfD")),
+            v3_router: None,
+            quoter_v2: Some(address("0x3d4e44Eb1374240CE5F1B871ab261CD16335B76a")),
+            balancer_vault: Some(address(BALANCER_VAULT)),
+            dexes: DexRegistry::base(),
+            l1_bridges: vec![address("0x3154Cf16ccdb4C6d922629664174b904d80F2C35")], // L1StandardBridge
+            deposit_time_secs: 180,
+        }
+    }
+
+    pub fn bsc() -> Self {
+        Self {
+            chain_id: 56,
+            name: "bsc",
+            weth: address("0xbb4CdB9CBd36B01bD1cBaEBF2De08d9173bc095c"),
+            usdc: address("0x8AC76a51cc950d9822D68b83fE1Ad97B32Cd580d"),
+            dai: address("0x1AF3F329e8BE154074D8769D1FFa4eE058B1DBc3"),
+            v2_factory: address("0xcA143Ce32Fe78f1f7019d7d551a6402fC5350c73"),
+            v2_router: address("0x10ED43C718714eb63d5aA57B78B54704E256024E"),
+            v3_factory: Some(address("0xdB1d10011AD0Ff90774D0C6Bb92e5C5c8b4461F7")),
+            v3_router: None,
+            quoter_v2: Some(address("0x78D78E420Da98ad378D7799bE8f4AF69033EB077")),
+            balancer_vault: None,
+            dexes: DexRegistry::bsc(),
+            l1_bridges: Vec::new(),
+            deposit_time_secs: 0,
+        }
+    }
+
+    /// The preset for `chain_id`. Local forks are assumed to fork mainnet.
+    pub fn for_chain_id(chain_id: u64) -> Option<Self> {
+        match chain_id {
+            1 | LOCAL_FORK_CHAIN_ID => Some(Self::mainnet()),
+            42161 => Some(Self::arbitrum()),
+            8453 => Some(Self::base()),
+            56 => Some(Self::bsc()),
+            _ => None,
+        }
+    }
+
+    /// DEXes to scan: the named `DEX_PRESET` if one is set, else the chain's own.
+    pub fn dex_registry(&self, preset: Option<&str>) -> DexRegistry {
+        preset.and_then(DexRegistry::preset).unwrap_or_else(|| self.dexes.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::strategy::DexType;
+
+    #[test]
+    fn local_forks_are_mainnet_and_unknown_chains_have_no_preset() {
+        assert_eq!(ChainConfig::for_chain_id(LOCAL_FORK_CHAIN_ID).unwrap().name, "mainnet");
+        assert_eq!(ChainConfig::for_chain_id(8453).unwrap().weth, address("0x4200000000000000000000000000000000000006"));
+        assert!(ChainConfig::for_chain_id(10).is_none());
+    }
+
+    #[test]
+    fn each_chain_registers_its_own_v2_factory_and_router() {
+        for chain_id in [1, 42161, 8453, 56] {
+            let chain = ChainConfig::for_chain_id(chain_id).unwrap();
+            assert_eq!(chain.chain_id, chain_id);
+            let dex = chain.dexes.dex_of_factory(chain.v2_factory);
+            assert!(dex.is_some(), "{} doesn't register its V2 factory", chain.name);
+            assert_eq!(chain.dexes.router(dex.unwrap()), Some(chain.v2_router), "{}", chain.name);
+        }
+    }
+
+    #[test]
+    fn a_dex_preset_overrides_the_chain_registry() {
+        let bsc = ChainConfig::bsc();
+        assert!(bsc.dex_registry(None).router(DexType::UniswapV3).is_none());
+        assert!(bsc.dex_registry(Some("mainnet")).router(DexType::UniswapV3).is_some());
+        assert!(bsc.dex_registry(Some("nowhere")).router(DexType::UniswapV3).is_none());
+    }
+}
@@ -0,0 +1,330 @@
+use ethers::abi::{self, AbiDecode, ParamType, Token};
+use ethers::prelude::*;
+use ethers::utils::id;
+
+use crate::address_book::UniV2RouterCalls;
+use crate::uni_v3::{self, RouterSwap};
+use crate::universal_router::{self, Execute};
+
+// ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+//  Pending tx classification
+//  - One place for the selectors the strategies act on
+// ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+
+/// 4-byte selectors of the swap functions the strategies know how to analyze.
+const SWAP_SELECTORS: [[u8; 4]; 22] = [
+    [0x38, 0xed, 0x17, 0x39], // swapExactTokensForTokens
+    [0x88, 0x03, 0xdb, 0xee], // swapTokensForExactTokens
+    [0x7f, 0xf3, 0x6a, 0xb5], // swapExactETHForTokens
+    [0x4a, 0x25, 0xd9, 0x4a], // swapTokensForExactETH
+    [0x18, 0xcb, 0xaf, 0xe5], // swapExactTokensForETH
+    [0xfb, 0x3b, 0xdb, 0x41], // swapETHForExactTokens
+    [0x5c, 0x11, 0xd7, 0x95], // swapExactTokensForTokensSupportingFeeOnTransferTokens
+    [0xb6, 0xf9, 0xde, 0x95], // swapExactETHForTokensSupportingFeeOnTransferTokens
+    [0x79, 0x1a, 0xc9, 0x47], // swapExactTokensForETHSupportingFeeOnTransferTokens
+    [0x41, 0x4b, 0xf3, 0x89], // exactInputSingle (V3)
+    [0xc0, 0x4b, 0x8d, 0x59], // exactInput (V3)
+    [0xdb, 0x3e, 0x21, 0x98], // exactOutputSingle (V3)
+    [0xf2, 0x8c, 0x04, 0x98], // exactOutput (V3)
+    [0xac, 0x96, 0x50, 0xd8], // multicall(bytes[]) (V3)
+    [0x04, 0xe4, 0x5a, 0xaf], // exactInputSingle (SwapRouter02)
+    [0xb8, 0x58, 0x18, 0x3f], // exactInput (SwapRouter02)
+    [0x50, 0x23, 0xb4, 0xdf], // exactOutputSingle (SwapRouter02)
+    [0x09, 0xb8, 0x13, 0x46], // exactOutput (SwapRouter02)
+    [0x5a, 0xe4, 0x01, 0xdc], // multicall(uint256,bytes[]) (SwapRouter02)
+    [0x1f, 0x04, 0x64, 0xd1], // multicall(bytes32,bytes[]) (SwapRouter02)
+    [0x35, 0x93, 0x56, 0x4c], // execute(bytes,bytes[],uint256) (Universal Router)
+    [0x24, 0x85, 0x6b, 0xc3], // execute(bytes,bytes[]) (Universal Router)
+];
+
+const AAVE_LIQUIDATION_CALL: &str = "liquidationCall(address,address,address,uint256,bool)";
+const COMPOUND_LIQUIDATE_BORROW: &str = "liquidateBorrow(address,uint256,address)";
+const COMPOUND_LIQUIDATE_BORROW_ETH: &str = "liquidateBorrow(address,address)";
+
+const OCR2_TRANSMIT: &str = "transmit(bytes32[3],bytes,bytes32[],bytes32[],bytes32)";
+const OCR1_TRANSMIT: &str = "transmit(bytes,bytes32[],bytes32[],bytes32)";
+const FLUX_SUBMIT: &str = "submit(uint256,int256)";
+
+/// Cheap pre-filter: whether `input` starts with a swap selector we decode.
+pub fn has_swap_selector(input: &[u8]) -> bool {
+    input.len() >= 4 && SWAP_SELECTORS.iter().any(|selector| input[..4] == selector[..])
+}
+
+/// Lending market a liquidation goes to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LendingProtocol {
+    /// Aave V2/V3 `liquidationCall` on the pool.
+    Aave,
+    /// Compound V2 `liquidateBorrow` on the borrowed cToken.
+    Compound,
+}
+
+/// A decoded liquidation call.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Liquidation {
+    pub protocol: LendingProtocol,
+    pub borrower: Address,
+    /// Asset repaid; for Compound it is the cToken the call goes to, so `None`.
+    pub debt_asset: Option<Address>,
+    /// Asset (Aave) or cToken (Compound) seized from the borrower.
+    pub collateral: Address,
+    /// Debt repaid; for cETH, the ETH sent.
+    pub repay_amount: U256,
+}
+
+/// What a pending tx does, with the parameters strategies act on.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TxClass {
+    /// A Uniswap V2-style router swap.
+    SwapV2(UniV2RouterCalls),
+    /// The swaps of a V3 SwapRouter/SwapRouter02 call, multicalls unpacked.
+    SwapV3(Vec<RouterSwap>),
+    /// A Universal Router `execute`, which may or may not swap.
+    UniversalRouter(Execute),
+    Liquidation(Liquidation),
+    /// A Chainlink aggregator report, with the answer it sets. Whether the
+    /// aggregator is one we follow depends on where the tx goes.
+    OracleUpdate { answer: U256 },
+    /// A V2 router `addLiquidity`/`addLiquidityETH`.
+    AddLiquidity(UniV2RouterCalls),
+    Unknown,
+}
+
+impl TxClass {
+    pub fn is_swap(&self) -> bool {
+        match self {
+            TxClass::SwapV2(_) | TxClass::SwapV3(_) => true,
+            TxClass::UniversalRouter(execute) => execute.swaps().next().is_some(),
+            _ => false,
+        }
+    }
+}
+
+/// Classifies `tx` by its calldata.
+pub fn classify(tx: &Transaction) -> TxClass {
+    let input = &tx.input;
+    if let Ok(call) = UniV2RouterCalls::decode(input) {
+        return match call {
+            UniV2RouterCalls::AddLiquidity(_) | UniV2RouterCalls::AddLiquidityETH(_) => TxClass::AddLiquidity(call),
+            call if is_v2_swap(&call) => TxClass::SwapV2(call),
+            _ => TxClass::Unknown,
+        };
+    }
+
+    let v3_swaps = uni_v3::decode_router_swaps(input);
+    if !v3_swaps.is_empty() {
+        return TxClass::SwapV3(v3_swaps);
+    }
+    if let Some(execute) = universal_router::decode_execute(input) {
+        return TxClass::UniversalRouter(execute);
+    }
+    if let Some(liquidation) = decode_liquidation(input, tx.value) {
+        return TxClass::Liquidation(liquidation);
+    }
+    if let Some(answer) = decode_oracle_answer(input) {
+        return TxClass::OracleUpdate { answer };
+    }
+    TxClass::Unknown
+}
+
+fn is_v2_swap(call: &UniV2RouterCalls) -> bool {
+    matches!(
+        call,
+        UniV2RouterCalls::SwapExactTokensForTokens(_)
+            | UniV2RouterCalls::SwapTokensForExactTokens(_)
+            | UniV2RouterCalls::SwapExactETHForTokens(_)
+            | UniV2RouterCalls::SwapTokensForExactETH(_)
+            | UniV2RouterCalls::SwapExactTokensForETH(_)
+            | UniV2RouterCalls::SwapETHForExactTokens(_)
+            | UniV2RouterCalls::SwapExactTokensForTokensSupportingFeeOnTransferTokens(_)
+            | UniV2RouterCalls::SwapExactETHForTokensSupportingFeeOnTransferTokens(_)
+            | UniV2RouterCalls::SwapExactTokensForETHSupportingFeeOnTransferTokens(_)
+    )
+}
+
+/// An Aave `liquidationCall` or a Compound `liquidateBorrow` (cERC20 or cETH).
+pub fn decode_liquidation(input: &[u8], value: U256) -> Option<Liquidation> {
+    if input.len() < 4 {
+        return None;
+    }
+    let (selector, args) = input.split_at(4);
+    if selector == id(AAVE_LIQUIDATION_CALL) {
+        let params = [ParamType::Address, ParamType::Address, ParamType::Address, ParamType::Uint(256), ParamType::Bool];
+        let mut tokens = abi::decode(&params, args).ok()?.into_iter();
+        let collateral = tokens.next()?.into_address()?;
+        let debt_asset = tokens.next()?.into_address()?;
+        let borrower = tokens.next()?.into_address()?;
+        let repay_amount = tokens.next()?.into_uint()?;
+        Some(Liquidation { protocol: LendingProtocol::Aave, borrower, debt_asset: Some(debt_asset), collateral, repay_amount })
+    } else if selector == id(COMPOUND_LIQUIDATE_BORROW) {
+        let params = [ParamType::Address, ParamType::Uint(256), ParamType::Address];
+        let mut tokens = abi::decode(&params, args).ok()?.into_iter();
+        let borrower = tokens.next()?.into_address()?;
+        let repay_amount = tokens.next()?.into_uint()?;
+        let collateral = tokens.next()?.into_address()?;
+        Some(Liquidation { protocol: LendingProtocol::Compound, borrower, debt_asset: None, collateral, repay_amount })
+    } else if selector == id(COMPOUND_LIQUIDATE_BORROW_ETH) {
+        let mut tokens = abi::decode(&[ParamType::Address, ParamType::Address], args).ok()?.into_iter();
+        let borrower = tokens.next()?.into_address()?;
+        let collateral = tokens.next()?.into_address()?;
+        Some(Liquidation { protocol: LendingProtocol::Compound, borrower, debt_asset: None, collateral, repay_amount: value })
+    } else {
+        None
+    }
+}
+
+/// The answer an aggregator call reports, decoded from OCR2 or OCR1 `transmit`
+/// or a FluxAggregator `submit`. The OCR median is the middle observation.
+pub fn decode_oracle_answer(input: &[u8]) -> Option<U256> {
+    if input.len() < 4 {
+        return None;
+    }
+    let (selector, args) = input.split_at(4);
+    let answer = if selector == id(OCR2_TRANSMIT) {
+        let params = [
+            ParamType::FixedArray(Box::new(ParamType::FixedBytes(32)), 3),
+            ParamType::Bytes,
+            ParamType::Array(Box::new(ParamType::FixedBytes(32))),
+            ParamType::Array(Box::new(ParamType::FixedBytes(32))),
+            ParamType::FixedBytes(32),
+        ];
+        let report = abi::decode(&params, args).ok()?.swap_remove(1).into_bytes()?;
+        let report_params = [
+            ParamType::Uint(32),
+            ParamType::FixedBytes(32),
+            ParamType::Array(Box::new(ParamType::Int(192))),
+            ParamType::Int(192),
+        ];
+        median(abi::decode(&report_params, &report).ok()?.swap_remove(2))?
+    } else if selector == id(OCR1_TRANSMIT) {
+        let params = [
+            ParamType::Bytes,
+            ParamType::Array(Box::new(ParamType::FixedBytes(32))),
+            ParamType::Array(Box::new(ParamType::FixedBytes(32))),
+            ParamType::FixedBytes(32),
+        ];
+        let report = abi::decode(&params, args).ok()?.swap_remove(0).into_bytes()?;
+        let report_params = [
+            ParamType::FixedBytes(32),
+            ParamType::FixedBytes(32),
+            ParamType::Array(Box::new(ParamType::Int(192))),
+        ];
+        median(abi::decode(&report_params, &report).ok()?.swap_remove(2))?
+    } else if selector == id(FLUX_SUBMIT) {
+        let mut submission = abi::decode(&[ParamType::Uint(256), ParamType::Int(256)], args).ok()?;
+        I256::from_raw(submission.swap_remove(1).into_int()?)
+    } else {
+        return None;
+    };
+
+    // Prices are never negative; anything else isn't a feed we can trade on
+    (answer > I256::zero()).then(|| answer.into_raw())
+}
+
+/// Middle entry of an `int192[]` observation list, which OCR reports sorted.
+fn median(observations: Token) -> Option<I256> {
+    let observations = observations.into_array()?;
+    let middle = observations.get(observations.len() / 2)?.clone();
+    Some(I256::from_raw(middle.into_int()?))
+}
+
+#[cfg(test)]
+mod tests {
+    use ethers::abi::AbiEncode;
+
+    use super::*;
+    use crate::address_book::{AddLiquidityETHCall, SwapExactETHForTokensCall};
+
+    fn tx(input: Vec<u8>, value: U256) -> Transaction {
+        Transaction { input: input.into(), value, ..Default::default() }
+    }
+
+    #[test]
+    fn swap_selectors_match_their_signatures() {
+        for signature in [
+            "swapExactTokensForTokens(uint256,uint256,address[],address,uint256)",
+            "swapExactETHForTokens(uint256,address[],address,uint256)",
+            "swapExactTokensForETHSupportingFeeOnTransferTokens(uint256,uint256,address[],address,uint256)",
+            "exactInputSingle((address,address,uint24,address,uint256,uint256,uint256,uint160))",
+            "exactInputSingle((address,address,uint24,address,uint256,uint256,uint160))",
+            "multicall(uint256,bytes[])",
+            "execute(bytes,bytes[],uint256)",
+        ] {
+            assert!(has_swap_selector(&id(signature)), "{} isn't recognized", signature);
+        }
+        assert!(!has_swap_selector(&id("transfer(address,uint256)")));
+        assert!(!has_swap_selector(&[0x38, 0xed]));
+    }
+
+    #[test]
+    fn classifies_v2_swaps_and_liquidity_adds() {
+        let path = vec![Address::from_low_u64_be(1), Address::from_low_u64_be(2)];
+        let swap = SwapExactETHForTokensCall { amount_out_min: U256::one(), path, to: Address::zero(), deadline: U256::MAX };
+        let class = classify(&tx(swap.encode(), U256::exp10(18)));
+        assert!(matches!(class, TxClass::SwapV2(UniV2RouterCalls::SwapExactETHForTokens(_))));
+        assert!(class.is_swap());
+
+        let add = AddLiquidityETHCall {
+            token: Address::from_low_u64_be(1),
+            amount_token_desired: U256::one(),
+            amount_token_min: U256::one(),
+            amount_eth_min: U256::one(),
+            to: Address::zero(),
+            deadline: U256::MAX,
+        };
+        let class = classify(&tx(add.encode(), U256::exp10(18)));
+        assert!(matches!(class, TxClass::AddLiquidity(_)));
+        assert!(!class.is_swap());
+        assert_eq!(classify(&tx(vec![0xde, 0xad, 0xbe, 0xef], U256::zero())), TxClass::Unknown);
+    }
+
+    #[test]
+    fn decodes_aave_and_compound_liquidations() {
+        let (collateral, debt, borrower) = (Address::from_low_u64_be(1), Address::from_low_u64_be(2), Address::from_low_u64_be(3));
+        let mut aave = id(AAVE_LIQUIDATION_CALL).to_vec();
+        aave.extend(abi::encode(&[
+            Token::Address(collateral),
+            Token::Address(debt),
+            Token::Address(borrower),
+            Token::Uint(U256::from(500)),
+            Token::Bool(false),
+        ]));
+        assert_eq!(
+            classify(&tx(aave, U256::zero())),
+            TxClass::Liquidation(Liquidation {
+                protocol: LendingProtocol::Aave,
+                borrower,
+                debt_asset: Some(debt),
+                collateral,
+                repay_amount: U256::from(500),
+            })
+        );
+
+        // cETH repays with the ETH sent
+        let mut compound_eth = id(COMPOUND_LIQUIDATE_BORROW_ETH).to_vec();
+        compound_eth.extend(abi::encode(&[Token::Address(borrower), Token::Address(collateral)]));
+        let liquidation = decode_liquidation(&compound_eth, U256::from(7)).unwrap();
+        assert_eq!((liquidation.protocol, liquidation.repay_amount, liquidation.debt_asset), (LendingProtocol::Compound, U256::from(7), None));
+    }
+
+    #[test]
+    fn decodes_oracle_answers_and_rejects_negative_ones() {
+        let submit = |answer: I256| {
+            let mut input = id(FLUX_SUBMIT).to_vec();
+            input.extend(abi::encode(&[Token::Uint(U256::one()), Token::Int(answer.into_raw())]));
+            input
+        };
+        assert_eq!(classify(&tx(submit(I256::from(2_000)), U256::zero())), TxClass::OracleUpdate { answer: U256::from(2_000) });
+        assert_eq!(decode_oracle_answer(&submit(I256::from(-5))), None);
+
+        // OCR reports carry their observations sorted; the answer is the middle one
+        let report = abi::encode(&[
+            Token::FixedBytes(vec![0; 32]),
+            Token::FixedBytes(vec![0; 32]),
+            Token::Array([10, 20, 30].into_iter().map(|n| Token::Int(U256::from(n))).collect()),
+        ]);
+        let mut transmit = id(OCR1_TRANSMIT).to_vec();
+        transmit.extend(abi::encode(&[Token::Bytes(report), Token::Array(vec![]), Token::Array(vec![]), Token::FixedBytes(vec![0; 32])]));
+        assert_eq!(decode_oracle_answer(&transmit), Some(U256::from(20)));
+    }
+}
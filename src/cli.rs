@@ -0,0 +1,220 @@
+use std::sync::Arc;
+
+use clap::{Parser, Subcommand};
+use ethers::prelude::*;
+
+use crate::backtest::Backtester;
+use crate::strategy::{DecisionTrace, MEVOpportunity, SimulationResult, StrategyManager};
+use crate::Config;
+
+#[derive(Debug, Parser)]
+#[command(name = "mev-template", about = "Multi-strategy MEV bot")]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Watch the mempool and submit opportunities (the default).
+    Run,
+    /// Run every strategy on one tx and print what it would do, without submitting.
+    Simulate {
+        /// Hash of the tx to analyze; it must still be pending or just mined.
+        #[arg(long)]
+        tx: H256,
+    },
+    /// Replay past blocks through the strategies, with `NETWORK_RPC` pointing at an anvil node.
+    Backtest {
+        from: u64,
+        to: u64,
+        /// Archive node the anvil node is re-forked from before each block.
+        #[arg(long, env = "BACKTEST_ARCHIVE_RPC")]
+        archive_rpc: String,
+    },
+}
+
+impl Cli {
+    pub async fn execute(self) {
+        match self.command.unwrap_or(Command::Run) {
+            Command::Run => crate::run().await,
+            Command::Simulate { tx } => simulate(tx).await,
+            Command::Backtest { from, to, archive_rpc } => backtest(from, to, &archive_rpc).await,
+        }
+    }
+}
+
+async fn manager() -> Option<Arc<StrategyManager>> {
+    match Config::try_new().await {
+        Ok(config) => Some(Arc::new(StrategyManager::new(Arc::new(config)).await)),
+        Err(e) => {
+            eprintln!("❌ Configuration error: {}", e);
+            None
+        }
+    }
+}
+
+async fn simulate(tx_hash: H256) {
+    let Some(manager) = manager().await else {
+        return;
+    };
+    let tx = match manager.config().http.get_transaction(tx_hash).await {
+        Ok(Some(tx)) => tx,
+        Ok(None) => {
+            eprintln!("❌ Transaction {:?} not found", tx_hash);
+            return;
+        }
+        Err(e) => {
+            eprintln!("❌ Could not fetch {:?}: {}", tx_hash, e);
+            return;
+        }
+    };
+
+    let simulated = simulate_tx(&manager, &tx).await;
+    if simulated.is_empty() {
+        println!("🤷 No opportunities in {:?}", tx_hash);
+    }
+    for (opportunity, result) in simulated {
+        match result {
+            Ok(sim) => println!(
+                "💎 {} ({}): {} ETH estimated, {} ETH simulated net, {} gas",
+                opportunity.id,
+                opportunity.strategy_type.name(),
+                ethers::utils::format_ether(opportunity.net_profit()),
+                ethers::utils::format_ether(sim.profit),
+                sim.gas_used
+            ),
+            Err(e) => eprintln!("⚠️ {} ({}): simulation failed: {}", opportunity.id, opportunity.strategy_type.name(), e),
+        }
+    }
+}
+
+/// Every opportunity the strategies find in `tx`, each re-simulated. The
+/// decision trace is printed so rejected strategies show why.
+pub async fn simulate_tx(
+    manager: &StrategyManager,
+    tx: &Transaction,
+) -> Vec<(MEVOpportunity, Result<SimulationResult, String>)> {
+    let trace = DecisionTrace::enabled(tx.hash);
+    let opportunities = manager.analyze_transaction(tx, &trace).await;
+    trace.emit();
+
+    let mut simulated = Vec::new();
+    for opportunity in opportunities {
+        let result = manager.simulate(&opportunity).await;
+        simulated.push((opportunity, result));
+    }
+    simulated
+}
+
+async fn backtest(from: u64, to: u64, archive_rpc: &str) {
+    let Some(manager) = manager().await else {
+        return;
+    };
+    let backtester = match Backtester::new(manager, archive_rpc) {
+        Ok(backtester) => backtester,
+        Err(e) => {
+            eprintln!("❌ Invalid archive RPC {}: {}", archive_rpc, e);
+            return;
+        }
+    };
+
+    match backtester.run(from, to).await {
+        Ok(report) => report.print(),
+        Err(e) => eprintln!("❌ Backtest failed: {}", e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::address_book::SwapExactETHForTokensCall;
+    use crate::pool_cache::PoolReserves;
+    use crate::settings::StrategiesEnabled;
+    use crate::strategy::pairs::{derive_pair_address, UNISWAP_V2_INIT_CODE_HASH};
+    use crate::test_support::{self, MockNode};
+    use crate::uni;
+    use clap::CommandFactory;
+    use ethers::abi::AbiEncode;
+    use serde_json::json;
+
+    #[test]
+    fn cli_definition_is_valid() {
+        Cli::command().debug_assert();
+    }
+
+    #[test]
+    fn simulate_parses_the_tx_hash() {
+        let hash = format!("{:?}", H256::from_low_u64_be(0xabc));
+        let cli = Cli::try_parse_from(["mev-template", "simulate", "--tx", &hash]).unwrap();
+        assert!(matches!(cli.command, Some(Command::Simulate { tx }) if tx == H256::from_low_u64_be(0xabc)));
+
+        assert!(Cli::try_parse_from(["mev-template", "simulate", "--tx", "0x1234"]).is_err());
+        assert!(Cli::try_parse_from(["mev-template"]).unwrap().command.is_none());
+    }
+
+    #[test]
+    fn backtest_takes_a_block_range_and_archive() {
+        let cli = Cli::try_parse_from(["mev-template", "backtest", "100", "200", "--archive-rpc", "http://archive:8545"]).unwrap();
+        assert!(matches!(
+            cli.command,
+            Some(Command::Backtest { from: 100, to: 200, archive_rpc }) if archive_rpc == "http://archive:8545"
+        ));
+    }
+
+    #[tokio::test]
+    async fn simulate_finds_and_prices_the_sandwich_in_a_fixture_swap() {
+        let mut node = MockNode::new().await;
+        node.rpc("eth_getBalance", json!(U256::exp10(21))).await;
+        node.rpc("eth_blockNumber", json!("0x64")).await;
+        node.rpc("eth_getTransactionCount", json!("0x0")).await;
+        node.rpc("eth_call", json!("0x")).await;
+        node.rpc("eth_estimateGas", json!("0x186a0")).await;
+        let settings = crate::settings::Settings {
+            strategies_enabled: StrategiesEnabled {
+                sandwich: true,
+                arbitrage: false,
+                flashloan: false,
+                enhanced_sandwich: false,
+                advanced: false,
+            },
+            simulate_victim_after_frontrun: false,
+            ..test_support::settings()
+        };
+        let config = test_support::config(&node.url(), settings).await;
+
+        // A 20 ETH buy accepting 3% slippage on a 100 ETH / 200k token pair
+        let (weth, token) = (config.chain.weth, Address::from_low_u64_be(2));
+        let (reserve_weth, reserve_token) = (U256::exp10(20), U256::exp10(18) * 200_000);
+        let pair = derive_pair_address(config.chain.v2_factory, UNISWAP_V2_INIT_CODE_HASH.parse().unwrap(), weth, token);
+        config.pool_cache.insert(pair, config.block_height.get(), PoolReserves { token0: token, reserve0: reserve_token, reserve1: reserve_weth });
+        let victim_in = U256::exp10(18) * 20;
+        let (expected_out, _, _) = uni::get_amount_out(victim_in, reserve_weth, reserve_token);
+        let call = SwapExactETHForTokensCall {
+            amount_out_min: expected_out * 97 / 100,
+            path: vec![weth, token],
+            to: Address::from_low_u64_be(0xb0b),
+            deadline: U256::MAX,
+        };
+        let victim = Transaction {
+            hash: H256::from_low_u64_be(0x5a5),
+            from: Address::from_low_u64_be(0xb0b),
+            to: Some(config.chain.v2_router),
+            value: victim_in,
+            input: call.encode().into(),
+            gas_price: Some(U256::exp10(10)),
+            ..Default::default()
+        };
+        let manager = StrategyManager::new(Arc::new(config)).await;
+
+        let simulated = simulate_tx(&manager, &victim).await;
+        let [(opportunity, Ok(result))] = simulated.as_slice() else {
+            panic!("expected one simulated opportunity, got {:?}", simulated);
+        };
+        assert_eq!(opportunity.strategy_type.name(), "sandwich");
+        assert_eq!(opportunity.target_tx.hash, victim.hash);
+        assert!(result.success && !result.profit.is_zero());
+        // Frontrun and backrun, at the node's 100k each
+        assert_eq!(result.gas_used, U256::from(200_000));
+    }
+}
@@ -0,0 +1,43 @@
+use std::fmt;
+
+/// Why the bot could not be configured at startup.
+#[derive(Debug)]
+pub enum ConfigError {
+    /// A required env var (or config file key) is not set.
+    MissingEnv(&'static str),
+    /// A URL that does not parse.
+    InvalidUrl { key: &'static str, url: String, reason: String },
+    /// The node could not be reached or rejected the connection.
+    ProviderConnect { url: String, reason: String },
+    /// `PRIVATE_KEY` is not a valid secp256k1 key.
+    InvalidPrivateKey,
+    /// The configured remote signer could not be reached or is unavailable in this build.
+    Signer { kind: &'static str, reason: String },
+    /// The config file is unreadable or not valid TOML for our schema.
+    ConfigFile { path: String, reason: String },
+    /// The RPC serves a different chain than the config expects.
+    ChainMismatch { expected: u64, actual: u64 },
+    /// No address preset exists for the chain the RPC serves.
+    UnsupportedChain(u64),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::MissingEnv(key) => write!(f, "missing {}", key),
+            ConfigError::InvalidUrl { key, url, reason } => write!(f, "invalid {} {:?}: {}", key, url, reason),
+            ConfigError::ProviderConnect { url, reason } => write!(f, "could not connect to {}: {}", url, reason),
+            ConfigError::InvalidPrivateKey => write!(f, "PRIVATE_KEY is not a valid private key"),
+            ConfigError::Signer { kind, reason } => write!(f, "could not set up {} signer: {}", kind, reason),
+            ConfigError::ConfigFile { path, reason } => write!(f, "invalid config file {}: {}", path, reason),
+            ConfigError::ChainMismatch { expected, actual } => {
+                write!(f, "RPC serves chain {}, config expects {}", actual, expected)
+            }
+            ConfigError::UnsupportedChain(chain_id) => {
+                write!(f, "no token/DEX addresses for chain {} (supported: mainnet, arbitrum, base, bsc)", chain_id)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
@@ -0,0 +1,147 @@
+use serde::Deserialize;
+
+use crate::settings::{AlertChannels, Settings};
+
+/// Default location of the config file, overridable via `BOT_CONFIG`.
+pub const DEFAULT_CONFIG_PATH: &str = "bot.toml";
+
+/// Contents of `bot.toml`. Every key is optional; anything left out keeps its
+/// built-in default, and env vars override whatever the file sets.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BotToml {
+    pub network: NetworkSection,
+    pub strategy: StrategySection,
+    pub relays: Option<Vec<String>>,
+    pub alerts: AlertsSection,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct NetworkSection {
+    pub rpc: Option<String>,
+    pub wss: Option<String>,
+    pub chain_id: Option<u64>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct StrategySection {
+    pub sandwich_min_profit_eth: Option<f64>,
+    pub enhanced_sandwich_min_profit_eth: Option<f64>,
+    pub arbitrage_min_profit_eth: Option<f64>,
+    pub flashloan_min_profit_eth: Option<f64>,
+    pub victim_safety_margin_bps: Option<u64>,
+    pub max_victims_per_sandwich: Option<usize>,
+    pub max_reserve_age_blocks: Option<u64>,
+    pub dex_preset: Option<String>,
+    pub enabled: Option<Vec<String>>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AlertsSection {
+    pub discord_webhook: Option<String>,
+    pub telegram_bot_token: Option<String>,
+    pub telegram_chat_id: Option<String>,
+    pub slack_webhook: Option<String>,
+}
+
+impl BotToml {
+    pub fn load(path: &str) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        Self::parse(&std::fs::read_to_string(path)?)
+    }
+
+    pub fn parse(contents: &str) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        Ok(toml::from_str(contents)?)
+    }
+
+    /// `base` with every value the file sets applied on top.
+    pub fn apply(&self, mut base: Settings) -> Settings {
+        let strategy = &self.strategy;
+        if let Some(v) = strategy.sandwich_min_profit_eth {
+            base.sandwich_min_profit_eth = v;
+        }
+        if let Some(v) = strategy.enhanced_sandwich_min_profit_eth {
+            base.enhanced_sandwich_min_profit_eth = v;
+        }
+        if let Some(v) = strategy.arbitrage_min_profit_eth {
+            base.arbitrage_min_profit_eth = v;
+        }
+        if let Some(v) = strategy.flashloan_min_profit_eth {
+            base.flashloan_min_profit_eth = v;
+        }
+        if let Some(v) = strategy.victim_safety_margin_bps {
+            base.victim_safety_margin_bps = v;
+        }
+        if let Some(v) = strategy.max_victims_per_sandwich {
+            base.max_victims_per_sandwich = v;
+        }
+        if let Some(v) = strategy.max_reserve_age_blocks {
+            base.risk.max_reserve_age_blocks = v;
+        }
+        if let Some(v) = &strategy.dex_preset {
+            base.dex_preset = Some(v.clone());
+        }
+        if let Some(names) = &strategy.enabled {
+            base.strategies_enabled = crate::settings::StrategiesEnabled::from_list(&names.join(","));
+        }
+
+        if self.network.chain_id.is_some() {
+            base.chain_id = self.network.chain_id;
+        }
+        if let Some(relays) = self.relays.as_ref().filter(|relays| !relays.is_empty()) {
+            base.relay.urls = relays.clone();
+        }
+
+        let alerts = &self.alerts;
+        base.alert_channels = AlertChannels {
+            discord_webhook: alerts.discord_webhook.clone().or(base.alert_channels.discord_webhook),
+            telegram_bot_token: alerts.telegram_bot_token.clone().or(base.alert_channels.telegram_bot_token),
+            telegram_chat_id: alerts.telegram_chat_id.clone().or(base.alert_channels.telegram_chat_id),
+            slack_webhook: alerts.slack_webhook.clone().or(base.alert_channels.slack_webhook),
+        };
+
+        base
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_example_config_parses_and_applies() {
+        let file = BotToml::parse(include_str!("../bot.toml.example")).unwrap();
+        assert_eq!(file.network.chain_id, Some(1));
+
+        let settings = file.apply(Settings::default());
+        assert_eq!(settings.chain_id, Some(1));
+        assert_eq!(settings.relay.urls, vec!["https://relay.flashbots.net".to_string()]);
+        assert_eq!(settings.victim_safety_margin_bps, 50);
+        assert_eq!(settings.dex_preset.as_deref(), Some("mainnet"));
+        assert!(settings.strategies_enabled.advanced);
+        assert!(settings.alert_channels.discord_webhook.is_some());
+        assert!(settings.alert_channels.telegram_bot_token.is_none());
+    }
+
+    #[test]
+    fn keys_left_out_keep_the_base_settings() {
+        let file = BotToml::parse("relays = []\n[strategy]\nenabled = [\"arbitrage\"]\n").unwrap();
+        let mut base = Settings::default();
+        base.alert_channels.slack_webhook = Some("https://hooks.slack.com/services/x".to_string());
+
+        let settings = file.apply(base);
+        // An empty relay list can't replace the default one
+        assert_eq!(settings.relay.urls, Settings::default().relay.urls);
+        assert_eq!(settings.sandwich_min_profit_eth, Settings::default().sandwich_min_profit_eth);
+        assert!(settings.strategies_enabled.arbitrage && !settings.strategies_enabled.sandwich);
+        assert!(settings.alert_channels.slack_webhook.is_some());
+    }
+
+    #[test]
+    fn unknown_keys_are_rejected() {
+        assert!(BotToml::parse("[strategy]\nsandwich_min_profit = 0.1\n").is_err());
+        assert!(BotToml::parse("relay = [\"https://relay.flashbots.net\"]\n").is_err());
+    }
+}
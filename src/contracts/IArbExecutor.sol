@@ -0,0 +1,23 @@
+// SPDX-License-Identifier: MIT
+pragma solidity ^0.8.13;
+
+/// Runs every hop of an arbitrage route in one call, trading from the
+/// executor's own token balance, and reverts unless the route closes with at
+/// least `minAmountOut` of the token it started from, or if mined after `deadline`.
+interface IArbExecutor {
+    struct Hop {
+        address pool;
+        address tokenIn;
+        address tokenOut;
+        /// 0 = Uniswap V2-style pair, 1 = Uniswap V3 pool.
+        uint8 kind;
+        /// Swap fee in bps, for V2-style pairs; V3 pools know their own.
+        uint24 fee;
+    }
+
+    function execute(Hop[] calldata route, uint256 amountIn, uint256 minAmountOut, uint256 deadline) external returns (uint256 amountOut);
+
+    /// Forwards `msg.value` to `block.coinbase`, so a bundle can pay whichever
+    /// builder includes it.
+    function payCoinbase() external payable;
+}
@@ -1,69 +1,291 @@
-use std::sync::Arc;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, RwLock};
 
-use ethers::prelude::{abi::AbiDecode, k256::ecdsa::SigningKey, *};
+use ethers::prelude::{abi::AbiDecode, *};
+use futures_util::future::join_all;
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
 
-use crate::address_book::{UniV2Factory, UniV2Router, UniV2RouterCalls};
+use crate::address_book::{LpPair, PairCreatedFilter, UniV2Factory, UniV2Router, UniV2RouterCalls};
+use crate::strategy::pairs::sort_tokens;
+use crate::signer::SignerClient;
+
+/// `allPairs` entries read concurrently per page while indexing.
+pub const PAIR_INDEX_BATCH: usize = 100;
+
+/// Pages between writes of the index file while backfilling.
+const SAVE_EVERY_PAGES: usize = 50;
+
+/// On-disk form of a `PairIndex`.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct PairIndexFile {
+    scanned: usize,
+    pairs: Vec<(Address, Address, Address)>,
+}
+
+/// Every pair of one V2 factory, keyed by its sorted tokens.
+///
+/// Filled from `allPairs` and kept current from `PairCreated`; optionally
+/// persisted to `path` so a restart only reads pairs created since.
+#[derive(Debug, Default)]
+pub struct PairIndex {
+    pairs: RwLock<HashMap<(Address, Address), Address>>,
+    /// `allPairs` entries already read.
+    scanned: AtomicUsize,
+    path: Option<String>,
+}
+
+impl PairIndex {
+    /// An index persisted at `path`, picking up whatever was saved there.
+    pub fn new(path: Option<String>) -> Self {
+        let index = Self { path, ..Self::default() };
+        if let Some(saved) = index.path.as_ref().and_then(|path| std::fs::read_to_string(path).ok()) {
+            match serde_json::from_str::<PairIndexFile>(&saved) {
+                Ok(file) => {
+                    for (token0, token1, pair) in file.pairs {
+                        index.insert(token0, token1, pair);
+                    }
+                    index.scanned.store(file.scanned, Ordering::Relaxed);
+                }
+                Err(e) => warn!("⚠️ Ignoring unreadable pair index {:?}: {}", index.path, e),
+            }
+        }
+        index
+    }
+
+    pub fn insert(&self, token_a: Address, token_b: Address, pair: Address) {
+        self.pairs.write().unwrap().insert(sort_tokens(token_a, token_b), pair);
+    }
+
+    /// The pair of `token_a` and `token_b`, in either order.
+    pub fn get(&self, token_a: Address, token_b: Address) -> Option<Address> {
+        self.pairs.read().unwrap().get(&sort_tokens(token_a, token_b)).copied()
+    }
+
+    pub fn len(&self) -> usize {
+        self.pairs.read().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// How many `allPairs` entries have been read.
+    pub fn scanned(&self) -> usize {
+        self.scanned.load(Ordering::Relaxed)
+    }
+
+    pub fn set_scanned(&self, scanned: usize) {
+        self.scanned.store(scanned, Ordering::Relaxed);
+    }
+
+    /// Writes the index to its file, if it has one.
+    pub fn save(&self) -> std::io::Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        let file = PairIndexFile {
+            scanned: self.scanned(),
+            pairs: self
+                .pairs
+                .read()
+                .unwrap()
+                .iter()
+                .map(|((token0, token1), pair)| (*token0, *token1, *pair))
+                .collect(),
+        };
+        std::fs::write(path, serde_json::to_string(&file)?)
+    }
+}
 
 #[allow(dead_code)]
+#[derive(Debug)]
 pub struct Dex {
+    middleware: Arc<SignerClient>,
     factory_address: Address,
     router_address: Address,
-    factory: UniV2Factory<SignerMiddleware<Provider<Http>, Wallet<SigningKey>>>,
-    router: UniV2Router<SignerMiddleware<Provider<Http>, Wallet<SigningKey>>>,
+    factory: UniV2Factory<SignerClient>,
+    router: UniV2Router<SignerClient>,
+    index: PairIndex,
 }
 
 impl Dex {
     pub fn new(
-        middleware: Arc<SignerMiddleware<Provider<Http>, Wallet<SigningKey>>>,
+        middleware: Arc<SignerClient>,
+        factory_address: Address,
+        router_address: Address,
+    ) -> Self {
+        Self::with_index(middleware, factory_address, router_address, PairIndex::default())
+    }
+
+    /// A DEX whose pairs are tracked in `index` (see `index_pairs`).
+    pub fn with_index(
+        middleware: Arc<SignerClient>,
         factory_address: Address,
         router_address: Address,
+        index: PairIndex,
     ) -> Self {
         let factory = UniV2Factory::new(factory_address, Arc::clone(&middleware));
         let router = UniV2Router::new(router_address, Arc::clone(&middleware));
         Self {
+            middleware,
             factory_address,
             router_address,
             factory,
             router,
+            index,
         }
     }
 
+    pub fn factory_address(&self) -> Address {
+        self.factory_address
+    }
+
+    /// The indexed pair of `token0` and `token1` (in either order) on this DEX.
+    pub fn pair_for(&self, token0: Address, token1: Address) -> Option<Address> {
+        self.index.get(token0, token1)
+    }
+
+    pub fn index(&self) -> &PairIndex {
+        &self.index
+    }
+
     /// A quick way to decode tx hex data.
     pub async fn decode_router_tx_data(&self, tx_data: String) {
         let calldata: Bytes = tx_data.parse().unwrap();
         let decoded = UniV2RouterCalls::decode(&calldata).unwrap();
-        println!("Decoded dex tx: {:?}", decoded);
+        info!("Decoded dex tx: {:?}", decoded);
     }
 
     /// Attempts to retrieve the total pairs created from the dex's factory.
     pub async fn get_pairs(&self) {
-        println!("Calling allPairsLength from {}", self.factory_address);
+        info!("Calling allPairsLength from {}", self.factory_address);
         match self.factory.all_pairs_length().call().await {
             Ok(result) => {
-                println!("   ~ [PASS] Total pairs: {:?}", result)
+                info!("   ~ [PASS] Total pairs: {:?}", result)
             }
             Err(e) => {
-                println!("   ~ [FAIL] Total pairs: {:?}", e)
+                warn!("   ~ [FAIL] Total pairs: {:?}", e)
+            }
+        }
+    }
+
+    /// Reads every `allPairs` entry not yet indexed, `batch` at a time. Stops at the
+    /// first page that fails so the next call resumes from there.
+    pub async fn index_pairs(&self, batch: usize) {
+        let total = match self.factory.all_pairs_length().call().await {
+            Ok(total) => total.as_usize(),
+            Err(e) => {
+                warn!("⚠️ allPairsLength failed on {:?}: {}", self.factory_address, e);
+                return;
+            }
+        };
+
+        let mut pages = 0;
+        while self.index.scanned() < total {
+            let start = self.index.scanned();
+            let end = (start + batch.max(1)).min(total);
+            let page: Option<Vec<_>> = join_all((start..end).map(|i| self.pair_at(i))).await.into_iter().collect();
+            let Some(page) = page else {
+                warn!("⚠️ Pair indexing of {:?} stopped at {}/{}", self.factory_address, start, total);
+                break;
+            };
+
+            for (token0, token1, pair) in page {
+                self.index.insert(token0, token1, pair);
             }
+            self.index.set_scanned(end);
+
+            pages += 1;
+            if pages % SAVE_EVERY_PAGES == 0 {
+                self.save_index();
+            }
+        }
+        self.save_index();
+        info!("📇 Indexed {} pairs from {:?}", self.index.len(), self.factory_address);
+    }
+
+    /// `(token0, token1, pair)` of the factory's `i`th pair.
+    async fn pair_at(&self, i: usize) -> Option<(Address, Address, Address)> {
+        let pair = self.factory.all_pairs(U256::from(i)).call().await.ok()?;
+        let lp = LpPair::new(pair, self.middleware.clone());
+        let token0 = lp.token_0().call().await.ok()?;
+        let token1 = lp.token_1().call().await.ok()?;
+        Some((token0, token1, pair))
+    }
+
+    fn save_index(&self) {
+        if let Err(e) = self.index.save() {
+            warn!("⚠️ Failed to save pair index of {:?}: {}", self.factory_address, e);
         }
     }
 
-    /// Streams the "PairCreated" event from the `factory_address`.
-    pub async fn stream_pairs_created(&self, ws: &Provider<Ws>) {
+    /// Streams the "PairCreated" event from the `factory_address` into the index.
+    ///
+    /// Subscribes before backfilling with `index_pairs`, so pools created while
+    /// the backfill runs aren't missed.
+    pub async fn stream_pairs_created(&self, ws: &Provider<Ws>, batch: usize) {
         let filter = Filter::new()
             .address(self.factory_address)
-            .event("PairCreated");
+            .event("PairCreated(address,address,address,uint256)");
 
-        let mut stream: SubscriptionStream<Ws, Log> = ws.subscribe_logs(&filter).await.unwrap();
+        let mut stream: SubscriptionStream<Ws, Log> = match ws.subscribe_logs(&filter).await {
+            Ok(stream) => stream,
+            Err(e) => {
+                warn!("⚠️ PairCreated subscription failed on {:?}: {}", self.factory_address, e);
+                return;
+            }
+        };
+        self.index_pairs(batch).await;
 
-        println!("Listening for PairCreated events, from {}", self.factory_address);
+        info!("Listening for PairCreated events, from {}", self.factory_address);
         while let Some(log) = stream.next().await {
-            println!(
-                "   ~ [FOUND] Hash {:?}\nLog: {:?}",
-                log.transaction_hash,
-                log.data,
-                // PsNewSale::decode(log.data)
-            );
+            match parse_log::<PairCreatedFilter>(log) {
+                Ok(created) => {
+                    info!("   ~ [FOUND] Pair {:?} ({:?}/{:?})", created.pair, created.token_0, created.token_1);
+                    self.index.insert(created.token_0, created.token_1, created.pair);
+                    self.save_index();
+                }
+                Err(e) => warn!("⚠️ Undecodable PairCreated log: {}", e),
+            }
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn token(n: u64) -> Address {
+        Address::from_low_u64_be(n)
+    }
+
+    #[test]
+    fn pairs_are_found_in_either_token_order() {
+        let index = PairIndex::default();
+        index.insert(token(2), token(1), token(0x9001));
+        assert_eq!(index.get(token(1), token(2)), Some(token(0x9001)));
+        assert_eq!(index.get(token(2), token(1)), Some(token(0x9001)));
+        assert_eq!(index.get(token(1), token(3)), None);
+    }
+
+    #[test]
+    fn a_saved_index_resumes_where_it_stopped() {
+        let path = std::env::temp_dir().join(format!("mev-bot-pairs-{}.json", std::process::id()));
+        let path = path.to_str().unwrap().to_string();
+        let index = PairIndex::new(Some(path.clone()));
+        index.insert(token(1), token(2), token(0x9001));
+        index.set_scanned(1);
+        index.save().unwrap();
+
+        let reloaded = PairIndex::new(Some(path.clone()));
+        assert_eq!((reloaded.len(), reloaded.scanned()), (1, 1));
+        assert_eq!(reloaded.get(token(2), token(1)), Some(token(0x9001)));
+
+        // An unreadable file starts the index from scratch
+        std::fs::write(&path, "not json").unwrap();
+        assert!(PairIndex::new(Some(path.clone())).is_empty());
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -1,83 +1,261 @@
 use std::sync::Arc;
 use std::collections::HashMap;
+use std::time::{Duration, Instant};
+use tokio::sync::broadcast::{self, error::RecvError};
 use tokio::sync::Mutex;
+use futures_util::Stream;
 
 use ethers::{
-    providers::{Middleware, Provider, StreamExt, TransactionStream, Ws},
-    types::Transaction,
+    providers::StreamExt,
+    types::{Transaction, U256},
 };
+use tracing::{error, info, warn, Instrument};
 
-use crate::strategy::{StrategyManager, AdvancedMEVFeatures};
+use crate::strategy::{
+    BidPolicy, DecisionTrace, StrategyManager, AdvancedMEVFeatures, OpportunityBus, OpportunityScheduler, PublishedOpportunity, TraceStep,
+};
+use crate::strategy::bundle::{coinbase_bribe_cost, unwrap_cost};
 use crate::alert::alert;
+use crate::classifier::{classify, has_swap_selector};
+use crate::approvals::{prioritize_correlated, ApprovalTracker};
+use crate::health::Health;
+use crate::metrics::{metrics, wei_to_eth};
+use crate::mempool::{is_router_transaction, tx_span};
+use crate::mempool_source::MempoolSource;
+use crate::replacement::ReplacementTracker;
+use crate::shutdown::Shutdown;
 
+/// Runs until `shutdown` is triggered or the mempool stream ends. Analysis
+/// tasks still running on return are tracked by `shutdown` for draining.
 pub async fn enhanced_mempool_monitor(
-    ws_provider: Arc<Provider<Ws>>,
+    source: &dyn MempoolSource,
     strategy_manager: Arc<StrategyManager>,
+    health: Arc<Health>,
+    shutdown: Shutdown,
 ) {
     // Initialize advanced features
     let advanced_features = Arc::new(AdvancedMEVFeatures::new(strategy_manager.config().clone()));
     
+    info!("🚀 Enhanced MEV Bot Active - Multi-Strategy Mode");
+    info!("📊 Strategies: Sandwich, Arbitrage, JIT, Backrun, Statistical Arb");
+    info!("----------------------------------------------");
+
+    let tx_stream = match source.subscribe().await {
+        Ok(tx_stream) => tx_stream,
+        Err(e) => {
+            error!("❌ Could not subscribe to the {} mempool: {}", source.name(), e);
+            return;
+        }
+    };
+    health.set_subscribed(true);
+
+    // Opportunities from every pending tx compete for the same blocks
+    let scheduler = Arc::new(OpportunityScheduler::from_settings(&strategy_manager.config().settings));
+    let bus = OpportunityBus::new();
+    let executor = execute_published(bus.subscribe(), strategy_manager.clone(), scheduler.clone(), shutdown.clone());
+    let in_flight = shutdown.enter();
+    tokio::spawn(async move {
+        let _in_flight = in_flight;
+        executor.await;
+    }.in_current_span());
+
+    process_pending_txs(tx_stream, &strategy_manager, &advanced_features, bus, &scheduler, &health, &shutdown).await;
+    health.set_subscribed(false);
+}
+
+/// Queues what the analyzers publish and submits whatever fits the current
+/// block, until every publisher is gone. Each new head retries what an earlier
+/// block's budget or conflicts deferred, and drops what has expired.
+async fn execute_published(
+    mut published: broadcast::Receiver<PublishedOpportunity>,
+    strategy_manager: Arc<StrategyManager>,
+    scheduler: Arc<OpportunityScheduler>,
+    shutdown: Shutdown,
+) {
+    let mut heads = strategy_manager.config().heads.subscribe();
+    loop {
+        tokio::select! {
+            published = published.recv() => match published {
+                Ok(PublishedOpportunity { opportunity, expected_profit }) => scheduler.schedule(opportunity, expected_profit),
+                Err(RecvError::Lagged(missed)) => {
+                    warn!("⚠️ Executor fell behind the analyzers, {} opportunities dropped", missed);
+                    continue;
+                }
+                Err(RecvError::Closed) => break,
+            },
+            head = heads.recv() => match head {
+                Ok(_) | Err(RecvError::Lagged(_)) if !scheduler.is_empty() => {}
+                _ => continue,
+            },
+        }
+
+        for opportunity in scheduler.dispatch(strategy_manager.config().block_height.get()) {
+            let strategy_manager = strategy_manager.clone();
+            let in_flight = shutdown.enter();
+            let span = tx_span(&opportunity.target_tx);
+            tokio::spawn(async move {
+                let _in_flight = in_flight;
+                execute_opportunity(&opportunity, &strategy_manager).await;
+            }.instrument(span));
+        }
+    }
+}
+
+async fn process_pending_txs<S>(
+    mut tx_stream: S,
+    strategy_manager: &Arc<StrategyManager>,
+    advanced_features: &Arc<AdvancedMEVFeatures>,
+    bus: OpportunityBus,
+    scheduler: &OpportunityScheduler,
+    health: &Health,
+    shutdown: &Shutdown,
+) where
+    S: Stream<Item = Transaction> + Unpin,
+{
     // Track processed transactions
     let processed_txs = Arc::new(Mutex::new(HashMap::new()));
-    
-    // Subscribe to pending transactions
-    let tx_hash_stream = ws_provider.subscribe_pending_txs().await.unwrap();
-    let mut tx_stream = TransactionStream::new(&ws_provider, tx_hash_stream, 512); // Increased buffer
-    
-    println!("🚀 Enhanced MEV Bot Active - Multi-Strategy Mode");
-    println!("📊 Strategies: Sandwich, Arbitrage, JIT, Backrun, Statistical Arb");
-    println!("----------------------------------------------");
-    
-    while let Some(maybe_tx) = tx_stream.next().await {
-        if let Ok(tx) = maybe_tx {
-            // Skip if already processed
-            let mut processed = processed_txs.lock().await;
-            if processed.contains_key(&tx.hash) {
+
+    // Approvals to routers announce the swap that follows
+    let settings = strategy_manager.config().settings.clone();
+    let mut approvals = settings
+        .track_approvals
+        .then(|| ApprovalTracker::new(Duration::from_secs(settings.approval_window_secs)));
+    let mut replacements = ReplacementTracker::new(Duration::from_secs(settings.replacement_window_secs));
+
+    loop {
+        let tx = tokio::select! {
+            _ = shutdown.wait() => {
+                info!("🛑 Shutdown requested, no longer accepting pending txs");
+                break;
+            }
+            next = tx_stream.next() => match next {
+                Some(tx) => tx,
+                None => break,
+            },
+        };
+        let observed_at = Instant::now();
+        health.tx_seen();
+
+        // Skip if already processed
+        let mut processed = processed_txs.lock().await;
+        if processed.contains_key(&tx.hash) {
+            continue;
+        }
+        processed.insert(tx.hash, true);
+        metrics().pending_txs_seen.inc();
+
+        // A replaced victim reverts whatever we built around it; the replacement itself is analyzed fresh
+        if let Some(replacement) = replacements.observe(&tx) {
+            let cancelled = scheduler.cancel_victim(replacement.replaced);
+            if cancelled > 0 {
+                info!(
+                    "♻️ Victim {:?} replaced by {:?} ({:?}), dropped {} opportunities",
+                    replacement.replaced, replacement.replacement, replacement.kind, cancelled
+                );
+            }
+        }
+
+        let mut prioritized = false;
+        if let Some(tracker) = approvals.as_mut() {
+            if let Some(approval) = tracker.observe_approval(&tx, observed_at) {
+                info!("🔓 Router approval from {:?} for token {:?}, pre-positioning", approval.owner, approval.token);
+                let strategy_manager_clone = strategy_manager.clone();
+                tokio::spawn(async move {
+                    strategy_manager_clone.prewarm_token(approval.token).await;
+                });
                 continue;
             }
-            processed.insert(tx.hash, true);
-            
-            // Process transaction with multiple strategies
-            let strategy_manager_clone = strategy_manager.clone();
-            let advanced_features_clone = advanced_features.clone();
-            let ws_provider_clone = ws_provider.clone();
-            
-            tokio::spawn(async move {
-                analyze_with_all_strategies(
-                    tx,
-                    strategy_manager_clone,
-                    advanced_features_clone,
-                    ws_provider_clone
-                ).await;
-            });
+
+            if let Some(approval) = tracker.correlate_swap(&tx, observed_at) {
+                info!("⚡ Swap {:?} follows approval {:?}, prioritizing", tx.hash, approval.tx_hash);
+                prioritized = true;
+            }
         }
+        
+        // Process transaction with multiple strategies
+        let strategy_manager_clone = strategy_manager.clone();
+        let advanced_features_clone = advanced_features.clone();
+        let bus_clone = bus.clone();
+        let in_flight = shutdown.enter();
+        
+        let span = tx_span(&tx);
+        tokio::spawn(async move {
+            let _in_flight = in_flight;
+            analyze_with_all_strategies(
+                tx,
+                observed_at,
+                prioritized,
+                strategy_manager_clone,
+                advanced_features_clone,
+                bus_clone
+            ).await;
+        }.instrument(span));
     }
 }
 
 async fn analyze_with_all_strategies(
     tx: Transaction,
+    observed_at: Instant,
+    prioritized: bool,
     strategy_manager: Arc<StrategyManager>,
     advanced_features: Arc<AdvancedMEVFeatures>,
-    ws_provider: Arc<Provider<Ws>>,
+    bus: OpportunityBus,
+) {
+    let trace = strategy_manager.decision_trace(&tx);
+    analyze_and_publish(&tx, &trace, observed_at, prioritized, &strategy_manager, &advanced_features, &bus).await;
+    trace.emit();
+}
+
+/// Runs every strategy on `tx` and publishes whatever still pays once settled,
+/// recording each decision on `trace`.
+async fn analyze_and_publish(
+    tx: &Transaction,
+    trace: &DecisionTrace,
+    observed_at: Instant,
+    prioritized: bool,
+    strategy_manager: &StrategyManager,
+    advanced_features: &AdvancedMEVFeatures,
+    bus: &OpportunityBus,
 ) {
+    // Cheap pre-filter: plain transfers, NFT mints etc. can never be sandwiched
+    if !trace.gate("swap_candidate", is_swap_candidate(tx)) {
+        return;
+    }
+
+    // Searchers escalating against each other on one pool; strategies read it off the tracker
+    if let Some(war) = strategy_manager.observe_pending_swap(tx).await {
+        metrics().gas_wars.inc();
+        info!(
+            "⚔️ Gas war on pool {:?}: {} bidders, {} escalations, {} -> {} gwei",
+            war.pool,
+            war.bidders,
+            war.escalations,
+            war.opening_bid / U256::exp10(9),
+            war.top_bid / U256::exp10(9)
+        );
+    }
+
     let mut all_opportunities = Vec::new();
     
     // 1. Traditional sandwich & arbitrage
-    let basic_opps = strategy_manager.analyze_transaction(&tx).await;
+    let mut basic_opps = strategy_manager.analyze_observed(tx, trace, observed_at).await;
+    if trace.gate("approval_correlated", prioritized) {
+        prioritize_correlated(&mut basic_opps);
+    }
     all_opportunities.extend(basic_opps);
     
     // 2. JIT liquidity opportunities
-    if let Some(jit_opp) = advanced_features.find_jit_opportunities(&tx).await {
-        println!("💧 JIT Opportunity: {} ETH liquidity, {} ETH fees",
+    if let Some(jit_opp) = advanced_features.find_jit_opportunities(tx).await {
+        info!("💧 JIT Opportunity: {} ETH liquidity, {} ETH fees",
             ethers::utils::format_ether(jit_opp.liquidity_amount),
             ethers::utils::format_ether(jit_opp.expected_fees)
         );
     }
     
     // 3. Backrun opportunities
-    let backrun_opps = advanced_features.find_backrun_opportunities(&tx).await;
+    let backrun_opps = advanced_features.find_backrun_opportunities(tx, &classify(tx)).await;
     for backrun in backrun_opps {
-        println!("🎯 Backrun Opportunity: {:?} - {} ETH profit",
+        info!("🎯 Backrun Opportunity: {:?} - {} ETH profit",
             backrun.strategy,
             ethers::utils::format_ether(backrun.expected_profit)
         );
@@ -86,10 +264,10 @@ async fn analyze_with_all_strategies(
     // 4. Multi-DEX arbitrage (check periodically, not on every tx)
     static mut LAST_ARB_CHECK: u64 = 0;
     unsafe {
-        if LAST_ARB_CHECK % 100 == 0 {
+        if LAST_ARB_CHECK.is_multiple_of(100) {
             let arb_paths = advanced_features.find_multi_dex_arbitrage(tx.from).await;
             for path in arb_paths.iter().take(3) {
-                println!("🔄 Arbitrage Path: {} hops, {} ETH profit",
+                info!("🔄 Arbitrage Path: {} hops, {} ETH profit",
                     path.path.len() - 1,
                     ethers::utils::format_ether(path.expected_profit)
                 );
@@ -98,37 +276,65 @@ async fn analyze_with_all_strategies(
         LAST_ARB_CHECK += 1;
     }
     
-    // Execute best opportunity
-    if !all_opportunities.is_empty() {
-        all_opportunities.sort_by(|a, b| {
-            b.estimated_profit.saturating_sub(b.gas_cost)
-                .cmp(&a.estimated_profit.saturating_sub(a.gas_cost))
-        });
-        
-        if let Some(best_opp) = all_opportunities.first() {
-            execute_opportunity(best_opp, &strategy_manager, &ws_provider).await;
-        }
+    // WETH proceeds only pay for gas once unwrapped: rank on what reaches our ETH balance,
+    // after whatever the bundle pays the builder directly
+    let config = strategy_manager.config();
+    let gas_price = config.gas_oracle.gas_price(50.0);
+    let unwrap_cost = unwrap_cost(gas_price);
+    let weth = config.chain.weth;
+    let bribe_bps = BidPolicy::from_settings(&config.settings).coinbase_bribe_bps;
+    let expected_profit = |opp: &crate::strategy::MEVOpportunity| {
+        opp.settled_profit(weth, unwrap_cost).saturating_sub(coinbase_bribe_cost(opp.net_profit(), bribe_bps, gas_price))
+    };
+    for opp in &all_opportunities {
+        metrics().opportunities_found.with_label_values(&[opp.strategy_type.name()]).inc();
+        metrics().estimated_profit_eth.observe(wei_to_eth(expected_profit(opp)));
+    }
+    all_opportunities.retain(|opp| trace.gate("settled_profit", !expected_profit(opp).is_zero()));
+
+    if all_opportunities.is_empty() {
+        trace.record(TraceStep::Action("skip: no profitable opportunity".to_string()));
+        return;
+    }
+
+    // The executor queues them alongside other txs' opportunities and submits whatever fits
+    for opp in all_opportunities {
+        trace.record(TraceStep::Action(format!("publish {}", opp.id)));
+        let profit = expected_profit(&opp);
+        bus.publish(opp, profit);
+    }
+}
+
+/// Returns true if the tx targets a known router or calls a recognized swap selector.
+fn is_swap_candidate(tx: &Transaction) -> bool {
+    if is_router_transaction(tx) {
+        return true;
     }
+
+    has_swap_selector(&tx.input)
 }
 
 async fn execute_opportunity(
     opportunity: &crate::strategy::MEVOpportunity,
     strategy_manager: &Arc<StrategyManager>,
-    ws_provider: &Arc<Provider<Ws>>,
 ) {
     let net_profit = opportunity.estimated_profit.saturating_sub(opportunity.gas_cost);
     
-    println!("\n💎 Executing MEV Opportunity:");
-    println!("   Type: {:?}", opportunity.strategy_type);
-    println!("   Gross Profit: {} ETH", ethers::utils::format_ether(opportunity.estimated_profit));
-    println!("   Gas Cost: {} ETH", ethers::utils::format_ether(opportunity.gas_cost));
-    println!("   Net Profit: {} ETH", ethers::utils::format_ether(net_profit));
+    info!(
+        opportunity = %opportunity.id,
+        strategy = ?opportunity.strategy_type,
+        gross_eth = %ethers::utils::format_ether(opportunity.estimated_profit),
+        gas_eth = %ethers::utils::format_ether(opportunity.gas_cost),
+        net_eth = %ethers::utils::format_ether(net_profit),
+        p_land = %format!("{:.2}", opportunity.landing_probability),
+        "💎 Executing MEV opportunity"
+    );
     
     match strategy_manager.execute_opportunity(opportunity).await {
         Ok(tx_hash) => {
-            println!("✅ Success! Bundle: {}", tx_hash);
+            info!("✅ Success! Bundle: {}", tx_hash);
             
-            let current_block = ws_provider.get_block_number().await.unwrap_or_default();
+            let current_block = strategy_manager.config().block_height.get();
             let msg = format!(
                 "💰 MEV Executed!\nType: {:?}\nNet Profit: {} ETH\nTx: {}",
                 opportunity.strategy_type,
@@ -138,7 +344,41 @@ async fn execute_opportunity(
             alert(&msg, &current_block.as_u64()).await;
         },
         Err(e) => {
-            println!("❌ Execution failed: {}", e);
+            error!("❌ Execution failed: {}", e);
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use ethers::types::Address;
+    use mockito::Matcher;
+    use tokio::sync::broadcast::error::TryRecvError;
+
+    use super::*;
+    use crate::test_support::{self, MockNode};
+
+    #[tokio::test]
+    async fn plain_transfers_are_dropped_before_any_strategy_runs() {
+        let mut node = MockNode::new().await;
+        let config = Arc::new(test_support::config(&node.url(), test_support::settings()).await);
+        let strategy_manager = StrategyManager::new(config.clone()).await;
+        let advanced_features = AdvancedMEVFeatures::new(config);
+        let bus = OpportunityBus::new();
+        let mut published = bus.subscribe();
+        // Every strategy reads pools, balances or gas off the node; none may get that far
+        let untouched = node.server.mock("POST", Matcher::Any).expect(0).create_async().await;
+
+        let transfer = Transaction {
+            to: Some(Address::from_low_u64_be(0xbeef)),
+            value: U256::exp10(18),
+            ..Default::default()
+        };
+        let trace = DecisionTrace::enabled(transfer.hash);
+        analyze_and_publish(&transfer, &trace, Instant::now(), false, &strategy_manager, &advanced_features, &bus).await;
+
+        assert!(matches!(trace.steps().as_slice(), [TraceStep::Gate { name: "swap_candidate", passed: false }]));
+        assert!(matches!(published.try_recv(), Err(TryRecvError::Empty)));
+        untouched.assert_async().await;
+    }
+}
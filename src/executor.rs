@@ -0,0 +1,107 @@
+use ethers::abi::AbiEncode;
+use ethers::prelude::*;
+
+use crate::strategy::{ArbitrageDetails, DexType, PoolInfo};
+
+abigen!(ArbExecutor, "src/abi/ArbExecutor.json");
+
+/// `Hop.kind` of a Uniswap V2-style pair.
+pub const HOP_V2: u8 = 0;
+/// `Hop.kind` of a Uniswap V3 pool.
+pub const HOP_V3: u8 = 1;
+
+/// One executor hop per pool, trading `path[i]` for `path[i + 1]` on `pools[i]`.
+pub fn route_hops(path: &[Address], pools: &[PoolInfo]) -> Result<Vec<Hop>, String> {
+    if path.len() != pools.len() + 1 {
+        return Err(format!("{} tokens can't route through {} pools", path.len(), pools.len()));
+    }
+
+    Ok(pools
+        .iter()
+        .zip(path.windows(2))
+        .map(|(pool, tokens)| Hop {
+            pool: pool.address,
+            token_in: tokens[0],
+            token_out: tokens[1],
+            kind: if pool.dex_type == DexType::UniswapV3 { HOP_V3 } else { HOP_V2 },
+            fee: pool.fee.into(),
+        })
+        .collect())
+}
+
+/// `payCoinbase()` calldata; the tx's value goes to the block's builder.
+pub fn encode_pay_coinbase() -> Bytes {
+    PayCoinbaseCall.encode().into()
+}
+
+/// `execute(route, amountIn, minAmountOut, deadline)` calldata for the arbitrage in `details`.
+///
+/// The route must close in the token it starts from; `minAmountOut` is the input
+/// plus the expected profit, so the executor reverts rather than trade at a loss.
+pub fn encode_execute(details: &ArbitrageDetails, deadline: U256) -> Result<Bytes, String> {
+    if details.path.first() != details.path.last() {
+        return Err("arbitrage route must end in the token it starts from".to_string());
+    }
+    let route = route_hops(&details.path, &details.pools)?;
+
+    Ok(ExecuteCall {
+        route,
+        amount_in: details.amount_in,
+        min_amount_out: details.amount_in.saturating_add(details.expected_profit),
+        deadline,
+    }
+    .encode()
+    .into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethers::abi::AbiDecode;
+
+    fn token(n: u64) -> Address {
+        Address::from_low_u64_be(n)
+    }
+
+    fn pool(n: u64, dex_type: DexType, fee: u16) -> PoolInfo {
+        PoolInfo {
+            address: token(0x9000 + n),
+            token0: Address::zero(),
+            token1: Address::zero(),
+            reserve0: U256::zero(),
+            reserve1: U256::zero(),
+            fee,
+            dex_type,
+            v3: None,
+        }
+    }
+
+    fn arbitrage(path: Vec<Address>) -> ArbitrageDetails {
+        ArbitrageDetails {
+            path,
+            pools: vec![pool(1, DexType::UniswapV2, 3000), pool(2, DexType::UniswapV3, 500)],
+            amount_in: U256::from(1_000),
+            expected_profit: U256::from(30),
+            gas_estimate: U256::zero(),
+            router: None,
+        }
+    }
+
+    #[test]
+    fn execute_routes_each_hop_and_requires_the_profit_back() {
+        let calldata = encode_execute(&arbitrage(vec![token(1), token(2), token(1)]), U256::from(99)).unwrap();
+        let call = ExecuteCall::decode(&calldata).unwrap();
+        assert_eq!((call.amount_in, call.min_amount_out, call.deadline), (U256::from(1_000), U256::from(1_030), U256::from(99)));
+        assert_eq!(call.route.iter().map(|hop| (hop.token_in, hop.token_out, hop.kind)).collect::<Vec<_>>(), vec![
+            (token(1), token(2), HOP_V2),
+            (token(2), token(1), HOP_V3),
+        ]);
+        assert_eq!(call.route[1].fee, 500);
+    }
+
+    #[test]
+    fn execute_rejects_open_or_mismatched_routes() {
+        assert!(encode_execute(&arbitrage(vec![token(1), token(2), token(3)]), U256::zero()).is_err());
+        assert!(encode_execute(&arbitrage(vec![token(1), token(1)]), U256::zero()).is_err());
+    }
+}
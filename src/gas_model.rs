@@ -0,0 +1,220 @@
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+
+use ethers::types::{Address, U256};
+
+use crate::gas_oracle::GasOracle;
+use crate::helpers::env_or;
+use crate::settings::Settings;
+
+/// Gas units each kind of tx is estimated to burn.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GasEstimates {
+    pub sandwich_frontrun: u64,
+    pub sandwich_backrun: u64,
+    /// Fixed part of an arbitrage tx, before its hops.
+    pub arb_base: u64,
+    pub arb_hop: u64,
+    /// What borrowing and repaying a flash loan adds to a tx.
+    pub flashloan_overhead: u64,
+    /// A flash-loan-funded backrun, loan included.
+    pub flashloan_backrun: u64,
+    pub backrun: u64,
+    pub jit_liquidity: u64,
+}
+
+impl Default for GasEstimates {
+    fn default() -> Self {
+        Self {
+            sandwich_frontrun: 250_000,
+            sandwich_backrun: 250_000,
+            arb_base: 250_000,
+            arb_hop: 50_000,
+            flashloan_overhead: 100_000,
+            flashloan_backrun: 600_000,
+            backrun: 300_000,
+            jit_liquidity: 600_000,
+        }
+    }
+}
+
+/// One of the estimates in `GasEstimates`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GasKind {
+    SandwichFrontrun,
+    SandwichBackrun,
+    ArbBase,
+    ArbHop,
+    FlashloanOverhead,
+    FlashloanBackrun,
+    Backrun,
+    JitLiquidity,
+}
+
+impl GasEstimates {
+    pub fn from_env_over(defaults: Self) -> Self {
+        Self {
+            sandwich_frontrun: env_or("GAS_SANDWICH_FRONTRUN", defaults.sandwich_frontrun),
+            sandwich_backrun: env_or("GAS_SANDWICH_BACKRUN", defaults.sandwich_backrun),
+            arb_base: env_or("GAS_ARB_BASE", defaults.arb_base),
+            arb_hop: env_or("GAS_ARB_HOP", defaults.arb_hop),
+            flashloan_overhead: env_or("GAS_FLASHLOAN_OVERHEAD", defaults.flashloan_overhead),
+            flashloan_backrun: env_or("GAS_FLASHLOAN_BACKRUN", defaults.flashloan_backrun),
+            backrun: env_or("GAS_BACKRUN", defaults.backrun),
+            jit_liquidity: env_or("GAS_JIT_LIQUIDITY", defaults.jit_liquidity),
+        }
+    }
+
+    fn slot(&mut self, kind: GasKind) -> &mut u64 {
+        match kind {
+            GasKind::SandwichFrontrun => &mut self.sandwich_frontrun,
+            GasKind::SandwichBackrun => &mut self.sandwich_backrun,
+            GasKind::ArbBase => &mut self.arb_base,
+            GasKind::ArbHop => &mut self.arb_hop,
+            GasKind::FlashloanOverhead => &mut self.flashloan_overhead,
+            GasKind::FlashloanBackrun => &mut self.flashloan_backrun,
+            GasKind::Backrun => &mut self.backrun,
+            GasKind::JitLiquidity => &mut self.jit_liquidity,
+        }
+    }
+}
+
+/// What a sandwich's gas depends on beyond the amounts traded: the pools each
+/// leg swaps through (their tokens' transfer hooks included) and whether a
+/// flash loan funds it.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct RouteShape {
+    pub pools: Vec<Address>,
+    pub flashloan: bool,
+}
+
+impl RouteShape {
+    pub fn new(pools: Vec<Address>, flashloan: bool) -> Self {
+        Self { pools, flashloan }
+    }
+
+    pub fn hops(&self) -> usize {
+        self.pools.len()
+    }
+}
+
+/// Per-strategy gas estimates, priced off the live gas oracle. Strategies
+/// read their gas costs here rather than hardcoding them, so the estimates
+/// can be configured and calibrated against what txs actually burn.
+#[derive(Debug)]
+pub struct GasModel {
+    estimates: RwLock<GasEstimates>,
+    /// Frontrun plus backrun gas measured by simulating each route shape.
+    routes: RwLock<HashMap<RouteShape, u64>>,
+    gas_oracle: Arc<GasOracle>,
+}
+
+impl GasModel {
+    pub fn new(estimates: GasEstimates, gas_oracle: Arc<GasOracle>) -> Self {
+        Self {
+            estimates: RwLock::new(estimates),
+            routes: RwLock::new(HashMap::new()),
+            gas_oracle,
+        }
+    }
+
+    pub fn from_settings(settings: &Settings, gas_oracle: Arc<GasOracle>) -> Self {
+        Self::new(settings.gas.estimates, gas_oracle)
+    }
+
+    pub fn estimates(&self) -> GasEstimates {
+        *self.estimates.read().unwrap()
+    }
+
+    pub fn set(&self, kind: GasKind, gas: u64) {
+        *self.estimates.write().unwrap().slot(kind) = gas;
+    }
+
+    /// Moves the `kind` estimate an eighth of the way toward `gas_used` observed
+    /// on a receipt, so one outlier doesn't swing it.
+    pub fn calibrate(&self, kind: GasKind, gas_used: u64) {
+        let mut estimates = self.estimates.write().unwrap();
+        let estimate = estimates.slot(kind);
+        *estimate = (*estimate * 7 + gas_used) / 8;
+    }
+
+    /// Frontrun plus backrun, with the flash loan's overhead if one funds it.
+    pub fn sandwich_gas(&self, flashloan: bool) -> u64 {
+        let estimates = self.estimates();
+        let overhead = if flashloan { estimates.flashloan_overhead } else { 0 };
+        estimates.sandwich_frontrun + estimates.sandwich_backrun + overhead
+    }
+
+    /// Frontrun plus backrun along `route`: what simulating it measured, or until
+    /// then the configured estimate with another swap per leg for each extra hop.
+    pub fn sandwich_route_gas(&self, route: &RouteShape) -> u64 {
+        if let Some(measured) = self.routes.read().unwrap().get(route) {
+            return *measured;
+        }
+        let extra_hops = route.hops().saturating_sub(1) as u64;
+        self.sandwich_gas(route.flashloan) + 2 * extra_hops * self.estimates().arb_hop
+    }
+
+    /// Records the frontrun and backrun gas a simulation of `route` measured,
+    /// moving an earlier measurement an eighth of the way toward it.
+    pub fn record_route(&self, route: RouteShape, gas_used: u64) {
+        let mut routes = self.routes.write().unwrap();
+        let measured = routes.entry(route).or_insert(gas_used);
+        *measured = (*measured * 7 + gas_used) / 8;
+    }
+
+    /// An arbitrage swapping through `hops` pools.
+    pub fn arbitrage_gas(&self, hops: usize) -> u64 {
+        let estimates = self.estimates();
+        estimates.arb_base + estimates.arb_hop * hops as u64
+    }
+
+    /// What burning `gas` costs at the oracle's `percentile` gas price.
+    pub fn cost(&self, gas: u64, percentile: f64) -> U256 {
+        U256::from(gas).saturating_mul(self.gas_oracle.gas_price(percentile))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn model() -> GasModel {
+        GasModel::new(GasEstimates::default(), Arc::new(GasOracle::new(3)))
+    }
+
+    #[test]
+    fn sandwich_gas_adds_the_flash_loan_overhead() {
+        let model = model();
+        assert_eq!(model.sandwich_gas(false), 500_000);
+        assert_eq!(model.sandwich_gas(true), 600_000);
+        assert_eq!(model.arbitrage_gas(3), 400_000);
+    }
+
+    #[test]
+    fn calibrate_moves_an_eighth_of_the_way() {
+        let model = model();
+        model.calibrate(GasKind::Backrun, 380_000);
+        assert_eq!(model.estimates().backrun, 310_000);
+
+        model.set(GasKind::ArbHop, 80_000);
+        assert_eq!(model.arbitrage_gas(1), 330_000);
+    }
+
+    #[test]
+    fn route_gas_estimates_extra_hops_until_measured() {
+        let model = model();
+        let pools = |n: u64| (1..=n).map(Address::from_low_u64_be).collect::<Vec<_>>();
+        let route = RouteShape::new(pools(3), false);
+        // Two extra hops, each swapped through by both legs
+        assert_eq!(model.sandwich_route_gas(&route), 500_000 + 4 * 50_000);
+
+        model.record_route(route.clone(), 400_000);
+        assert_eq!(model.sandwich_route_gas(&route), 400_000);
+        model.record_route(route.clone(), 480_000);
+        assert_eq!(model.sandwich_route_gas(&route), 410_000);
+
+        // Same pools funded by a flash loan is a different shape
+        assert_eq!(model.sandwich_route_gas(&RouteShape::new(pools(3), true)), 800_000);
+    }
+}
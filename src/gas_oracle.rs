@@ -0,0 +1,263 @@
+use std::collections::VecDeque;
+use std::sync::RwLock;
+
+use ethers::types::{FeeHistory, Transaction, U256, U64};
+
+/// Priority-fee percentiles requested from `eth_feeHistory` for every block.
+pub const REWARD_PERCENTILES: [f64; 5] = [10.0, 25.0, 50.0, 75.0, 90.0];
+
+/// Gas price assumed before the first fee history arrives.
+const FALLBACK_GAS_PRICE: u64 = 50_000_000_000; // 50 gwei
+const FALLBACK_PRIORITY_FEE: u64 = 2_000_000_000; // 2 gwei
+
+/// One block's fee data.
+#[derive(Debug, Clone)]
+struct BlockFees {
+    number: U64,
+    base_fee: U256,
+    /// Priority fees paid at each of `REWARD_PERCENTILES`.
+    rewards: Vec<U256>,
+}
+
+#[derive(Debug, Default)]
+struct FeeWindow {
+    /// Oldest first.
+    blocks: VecDeque<BlockFees>,
+    /// Base fee of the block after the newest one in the window.
+    next_base_fee: Option<U256>,
+}
+
+/// Rolling base fee and priority fee statistics over the last few blocks,
+/// fed from `eth_feeHistory` by the block scanner. Read this instead of
+/// hardcoding gas prices.
+#[derive(Debug)]
+pub struct GasOracle {
+    window: usize,
+    fees: RwLock<FeeWindow>,
+    /// Effective gas prices of our most recently landed bundle txs, oldest first.
+    included_bids: RwLock<VecDeque<U256>>,
+}
+
+impl GasOracle {
+    pub fn new(window: usize) -> Self {
+        Self {
+            window: window.max(1),
+            fees: RwLock::new(FeeWindow::default()),
+            included_bids: RwLock::new(VecDeque::new()),
+        }
+    }
+
+    /// Blocks the statistics are taken over.
+    pub fn window(&self) -> usize {
+        self.window
+    }
+
+    /// Adds the blocks in `history` (requested with `REWARD_PERCENTILES`),
+    /// replacing any we already hold at the same height.
+    pub fn record(&self, history: &FeeHistory) {
+        let oldest = history.oldest_block.as_u64();
+        let mut fees = self.fees.write().unwrap();
+
+        for (i, base_fee) in history.base_fee_per_gas.iter().take(history.gas_used_ratio.len()).enumerate() {
+            let number = U64::from(oldest + i as u64);
+            fees.blocks.retain(|block| block.number != number);
+            fees.blocks.push_back(BlockFees {
+                number,
+                base_fee: *base_fee,
+                rewards: history.reward.get(i).cloned().unwrap_or_default(),
+            });
+        }
+        fees.blocks.make_contiguous().sort_by_key(|block| block.number);
+        while fees.blocks.len() > self.window {
+            fees.blocks.pop_front();
+        }
+
+        // feeHistory returns one extra base fee: the projection for the next block
+        if history.base_fee_per_gas.len() > history.gas_used_ratio.len() {
+            fees.next_base_fee = history.base_fee_per_gas.last().copied();
+        }
+    }
+
+    /// Base fee of the newest block seen.
+    pub fn base_fee(&self) -> Option<U256> {
+        self.fees.read().unwrap().blocks.back().map(|block| block.base_fee)
+    }
+
+    /// Base fee the next block will charge, falling back to the latest one.
+    pub fn next_base_fee(&self) -> Option<U256> {
+        let fees = self.fees.read().unwrap();
+        fees.next_base_fee.or_else(|| fees.blocks.back().map(|block| block.base_fee))
+    }
+
+    /// `percentile` of the base fees across the window.
+    pub fn base_fee_percentile(&self, percentile: f64) -> Option<U256> {
+        let fees = self.fees.read().unwrap();
+        percentile_of(fees.blocks.iter().map(|block| block.base_fee).collect(), percentile)
+    }
+
+    /// Typical priority fee paid at `percentile` of each block (snapped to the
+    /// nearest of `REWARD_PERCENTILES`), as the median across the window so a
+    /// single spiky block doesn't move it.
+    pub fn priority_fee(&self, percentile: f64) -> Option<U256> {
+        let column = REWARD_PERCENTILES
+            .iter()
+            .enumerate()
+            .min_by(|(_, a), (_, b)| (*a - percentile).abs().total_cmp(&(*b - percentile).abs()))
+            .map(|(i, _)| i)?;
+
+        let fees = self.fees.read().unwrap();
+        let rewards = fees.blocks.iter().filter_map(|block| block.rewards.get(column).copied()).collect();
+        percentile_of(rewards, 50.0)
+    }
+
+    /// How far to price a tx above or below one it has to land next to: the
+    /// median priority fee, so the step scales with what blocks are paying.
+    pub fn bid_step(&self) -> U256 {
+        self.priority_fee(50.0).unwrap_or(U256::from(FALLBACK_PRIORITY_FEE))
+    }
+
+    /// Records the effective gas price a landed bundle tx paid.
+    pub fn record_included_bid(&self, effective_gas_price: U256) {
+        let mut bids = self.included_bids.write().unwrap();
+        bids.push_back(effective_gas_price);
+        while bids.len() > self.window {
+            bids.pop_front();
+        }
+    }
+
+    /// Lowest bid expected to get a bundle included: the higher of what blocks
+    /// paid at `percentile` priority and `percentile` of our own landed bids.
+    /// Fee history tracks competition rising even while our bids stop landing.
+    pub fn bid_floor(&self, percentile: f64) -> U256 {
+        let market = self.gas_price(percentile);
+        let landed = percentile_of(self.included_bids.read().unwrap().iter().copied().collect(), percentile);
+        landed.map_or(market, |landed| landed.max(market))
+    }
+
+    /// What `tx` would pay per gas in the next block; `None` if it carries no fee fields.
+    pub fn effective_gas_price(&self, tx: &Transaction) -> Option<U256> {
+        match self.next_base_fee() {
+            Some(base_fee) => effective_gas_price(tx, base_fee),
+            None => tx.gas_price.or(tx.max_fee_per_gas),
+        }
+    }
+
+    /// Where paying `gas_price` in the next block ranks among recent priority
+    /// fees: the highest of `REWARD_PERCENTILES` it meets, or 0 below all of
+    /// them. `None` before the first fee history.
+    pub fn bid_percentile(&self, gas_price: U256) -> Option<f64> {
+        let priority_fee = gas_price.saturating_sub(self.next_base_fee()?);
+        let percentile = REWARD_PERCENTILES
+            .iter()
+            .copied()
+            .filter(|percentile| self.priority_fee(*percentile).is_some_and(|fee| priority_fee >= fee))
+            .fold(0.0, f64::max);
+        Some(percentile)
+    }
+
+    /// What a tx should pay per gas to land at `percentile` priority in the next block.
+    pub fn gas_price(&self, percentile: f64) -> U256 {
+        match self.next_base_fee() {
+            Some(base_fee) => base_fee + self.priority_fee(percentile).unwrap_or(U256::from(FALLBACK_PRIORITY_FEE)),
+            None => U256::from(FALLBACK_GAS_PRICE),
+        }
+    }
+}
+
+/// What `tx` pays per gas in a block with `base_fee`. For an EIP-1559 tx that's the
+/// base fee plus its priority fee, capped at its max fee; the `gas_price` nodes
+/// report for one is only that cap. Legacy txs pay their `gas_price`.
+pub fn effective_gas_price(tx: &Transaction, base_fee: U256) -> Option<U256> {
+    match (tx.max_fee_per_gas, tx.max_priority_fee_per_gas) {
+        (Some(max_fee), Some(priority_fee)) => Some(max_fee.min(base_fee.saturating_add(priority_fee))),
+        (Some(max_fee), None) => Some(max_fee),
+        _ => tx.gas_price,
+    }
+}
+
+/// Nearest-rank `percentile` of `values`.
+fn percentile_of(mut values: Vec<U256>, percentile: f64) -> Option<U256> {
+    if values.is_empty() {
+        return None;
+    }
+    values.sort();
+    let rank = ((percentile.clamp(0.0, 100.0) / 100.0) * values.len() as f64).ceil() as usize;
+    Some(values[rank.saturating_sub(1).min(values.len() - 1)])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn gwei(n: u64) -> U256 {
+        U256::from(n) * U256::exp10(9)
+    }
+
+    /// Fee history of blocks from `oldest`, each paying `rewards` at `REWARD_PERCENTILES`.
+    fn history(oldest: u64, base_fees: &[u64], rewards: &[[u64; 5]]) -> FeeHistory {
+        FeeHistory {
+            base_fee_per_gas: base_fees.iter().map(|fee| gwei(*fee)).collect(),
+            gas_used_ratio: vec![0.5; rewards.len()],
+            oldest_block: U256::from(oldest),
+            reward: rewards.iter().map(|block| block.iter().map(|fee| gwei(*fee)).collect()).collect(),
+        }
+    }
+
+    #[test]
+    fn percentile_of_takes_the_nearest_rank() {
+        let values: Vec<U256> = (1..=10).rev().map(U256::from).collect();
+        assert_eq!(percentile_of(values.clone(), 50.0), Some(U256::from(5)));
+        assert_eq!(percentile_of(values.clone(), 75.0), Some(U256::from(8)));
+        assert_eq!(percentile_of(values.clone(), 0.0), Some(U256::from(1)));
+        assert_eq!(percentile_of(values.clone(), 100.0), Some(U256::from(10)));
+        assert_eq!(percentile_of(values, 250.0), Some(U256::from(10)));
+        assert_eq!(percentile_of(Vec::new(), 50.0), None);
+    }
+
+    #[test]
+    fn priority_fee_is_the_median_across_the_window_of_the_nearest_column() {
+        let oracle = GasOracle::new(3);
+        oracle.record(&history(
+            100,
+            &[10, 11, 12, 13],
+            &[[1, 2, 3, 4, 5], [1, 2, 3, 4, 50], [1, 2, 3, 4, 6]],
+        ));
+        assert_eq!(oracle.next_base_fee(), Some(gwei(13)));
+        // 80 snaps to the 75th column; a single spiky block doesn't move the 90th
+        assert_eq!(oracle.priority_fee(80.0), Some(gwei(4)));
+        assert_eq!(oracle.priority_fee(90.0), Some(gwei(6)));
+        assert_eq!(oracle.gas_price(50.0), gwei(16));
+        assert_eq!(oracle.bid_percentile(gwei(17)), Some(75.0));
+        assert_eq!(oracle.bid_percentile(gwei(13)), Some(0.0));
+    }
+
+    #[test]
+    fn record_keeps_only_the_newest_window() {
+        let oracle = GasOracle::new(2);
+        oracle.record(&history(100, &[10, 20], &[[1; 5]]));
+        oracle.record(&history(101, &[20, 30, 40], &[[1; 5], [1; 5]]));
+        assert_eq!(oracle.base_fee_percentile(0.0), Some(gwei(20)));
+        assert_eq!(oracle.base_fee(), Some(gwei(30)));
+    }
+
+    #[test]
+    fn bid_floor_follows_the_market_even_when_our_bids_landed_lower() {
+        let oracle = GasOracle::new(3);
+        assert_eq!(oracle.bid_floor(50.0), U256::from(FALLBACK_GAS_PRICE));
+        oracle.record(&history(100, &[10, 10], &[[1, 2, 3, 4, 5]]));
+        oracle.record_included_bid(gwei(11));
+        assert_eq!(oracle.bid_floor(50.0), gwei(13));
+        oracle.record_included_bid(gwei(30));
+        oracle.record_included_bid(gwei(30));
+        assert_eq!(oracle.bid_floor(50.0), gwei(30));
+    }
+
+    #[test]
+    fn effective_gas_price_caps_eip1559_tips_at_the_max_fee() {
+        let tx = Transaction { max_fee_per_gas: Some(gwei(20)), max_priority_fee_per_gas: Some(gwei(3)), ..Default::default() };
+        assert_eq!(effective_gas_price(&tx, gwei(10)), Some(gwei(13)));
+        assert_eq!(effective_gas_price(&tx, gwei(19)), Some(gwei(20)));
+        let legacy = Transaction { gas_price: Some(gwei(7)), ..Default::default() };
+        assert_eq!(effective_gas_price(&legacy, gwei(10)), Some(gwei(7)));
+    }
+}
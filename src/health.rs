@@ -0,0 +1,135 @@
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use ethers::providers::Middleware;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+use tracing::{info, warn};
+
+use crate::settings::Settings;
+
+/// How long `/readyz` waits on the RPC before calling it unreachable.
+const RPC_PROBE_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Liveness and readiness state, written by the mempool monitor and read by
+/// the `/healthz` and `/readyz` endpoints.
+#[derive(Debug)]
+pub struct Health {
+    /// Unix seconds the last pending tx arrived; 0 until the first one.
+    pub last_tx_seen: Arc<AtomicU64>,
+    subscribed: AtomicBool,
+    max_tx_age: Duration,
+}
+
+impl Health {
+    pub fn new(max_tx_age: Duration) -> Self {
+        Self {
+            last_tx_seen: Arc::new(AtomicU64::new(0)),
+            subscribed: AtomicBool::new(false),
+            max_tx_age,
+        }
+    }
+
+    pub fn from_settings(settings: &Settings) -> Self {
+        Self::new(Duration::from_secs(settings.readiness_max_tx_age_secs))
+    }
+
+    pub fn set_subscribed(&self, subscribed: bool) {
+        self.subscribed.store(subscribed, Ordering::Relaxed);
+    }
+
+    pub fn tx_seen(&self) {
+        self.last_tx_seen.store(unix_now(), Ordering::Relaxed);
+    }
+
+    /// Why the bot isn't ready to trade at `now` (unix seconds), if it isn't.
+    pub fn not_ready(&self, now: u64, rpc_reachable: bool) -> Option<String> {
+        if !self.subscribed.load(Ordering::Relaxed) {
+            return Some("mempool not subscribed".to_string());
+        }
+        let last_seen = self.last_tx_seen.load(Ordering::Relaxed);
+        if last_seen == 0 {
+            return Some("no pending tx seen yet".to_string());
+        }
+        let age = now.saturating_sub(last_seen);
+        if age > self.max_tx_age.as_secs() {
+            return Some(format!("last pending tx seen {}s ago", age));
+        }
+        if !rpc_reachable {
+            return Some("RPC unreachable".to_string());
+        }
+        None
+    }
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|elapsed| elapsed.as_secs()).unwrap_or_default()
+}
+
+/// Serves `/healthz` (the process is up) and `/readyz` (subscribed, seeing
+/// pending txs and able to reach `rpc`) on `port`. Not ready is a 503.
+pub async fn serve<M: Middleware + 'static>(health: Arc<Health>, port: u16, rpc: Arc<M>) {
+    let listener = match TcpListener::bind(("0.0.0.0", port)).await {
+        Ok(listener) => listener,
+        Err(e) => {
+            warn!("Could not start health endpoint on port {}, err: {}", port, e);
+            return;
+        }
+    };
+
+    info!("🩺 Health endpoint listening on :{}/healthz and /readyz", port);
+    loop {
+        let Ok((mut socket, _)) = listener.accept().await else {
+            continue;
+        };
+        let (health, rpc) = (health.clone(), rpc.clone());
+
+        tokio::spawn(async move {
+            let mut request = [0u8; 1024];
+            let read = socket.read(&mut request).await.unwrap_or(0);
+            let path = std::str::from_utf8(&request[..read])
+                .ok()
+                .and_then(|request| request.split_whitespace().nth(1))
+                .unwrap_or("/");
+
+            let (status, body) = match path {
+                "/healthz" => ("200 OK", "ok".to_string()),
+                "/readyz" => {
+                    let rpc_reachable = matches!(tokio::time::timeout(RPC_PROBE_TIMEOUT, rpc.get_block_number()).await, Ok(Ok(_)));
+                    match health.not_ready(unix_now(), rpc_reachable) {
+                        None => ("200 OK", "ready".to_string()),
+                        Some(reason) => ("503 Service Unavailable", reason),
+                    }
+                }
+                _ => ("404 Not Found", "not found".to_string()),
+            };
+            let response = format!(
+                "HTTP/1.1 {}\r\nContent-Type: text/plain\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                status,
+                body.len(),
+                body
+            );
+            let _ = socket.write_all(response.as_bytes()).await;
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn readiness_reports_the_first_thing_missing() {
+        let health = Health::new(Duration::from_secs(60));
+        assert_eq!(health.not_ready(1_000, true).as_deref(), Some("mempool not subscribed"));
+
+        health.set_subscribed(true);
+        assert_eq!(health.not_ready(1_000, true).as_deref(), Some("no pending tx seen yet"));
+
+        health.last_tx_seen.store(900, Ordering::Relaxed);
+        assert_eq!(health.not_ready(1_000, true).as_deref(), Some("last pending tx seen 100s ago"));
+        assert_eq!(health.not_ready(950, false).as_deref(), Some("RPC unreachable"));
+        assert_eq!(health.not_ready(950, true), None);
+    }
+}
@@ -1,4 +1,9 @@
-use ethers::prelude::{k256::ecdsa::SigningKey, *};
+use ethers::prelude::*;
+
+use crate::config_error::ConfigError;
+use crate::rpc::RetryHttp;
+use crate::settings::Settings;
+use crate::signer::{BotSigner, SignerClient};
 
 /// Converts &str to Address.
 pub fn address(address: &str) -> Address {
@@ -12,23 +17,25 @@ pub fn to_1e18(input: u64) -> U256 {
     parsed * ether
 }
 
-/// Sets up middleware w/ our private key env var.
-pub async fn setup_signer(
-    provider: Provider<Http>,
-) -> SignerMiddleware<Provider<Http>, Wallet<SigningKey>> {
-    let chain_id = provider
-        .get_chainid()
-        .await
-        .expect("Failed to get chain id.");
+/// Converts a (config) ETH amount to wei; unparseable amounts become zero.
+pub fn eth_to_wei(eth: f64) -> U256 {
+    ethers::utils::parse_ether(eth).unwrap_or_default()
+}
 
-    let priv_key = std::env::var("PRIVATE_KEY").expect("missing PRIVATE_KEY");
+/// Sets up middleware w/ the signer the env selects.
+pub async fn setup_signer(provider: Provider<RetryHttp>) -> SignerClient {
+    try_setup_signer(provider, &Settings::from_env()).await.unwrap_or_else(|e| panic!("{}", e))
+}
 
-    let wallet = priv_key
-        .parse::<LocalWallet>()
-        .expect("Failed to parse wallet")
-        .with_chain_id(chain_id.as_u64());
+/// Sets up middleware w/ the signer `settings` selects, reporting what went wrong.
+pub async fn try_setup_signer(provider: Provider<RetryHttp>, settings: &Settings) -> Result<SignerClient, ConfigError> {
+    let chain_id = provider.get_chainid().await.map_err(|e| ConfigError::ProviderConnect {
+        url: provider.as_ref().url().to_string(),
+        reason: e.to_string(),
+    })?;
 
-    SignerMiddleware::new(provider, wallet)
+    let signer = BotSigner::from_settings(settings, chain_id.as_u64()).await?;
+    Ok(SignerMiddleware::new(provider, signer))
 }
 
 /// Creates a binding for an ABI.
@@ -37,9 +44,67 @@ pub fn bind(name: &str, abi: &str) {
     let name: String = format!("b_{}", name);
     let bindings = Abigen::new(&name, abi).unwrap().generate().unwrap();
     let path: String = format!("src/bindings/{}.rs", name);
-    match std::fs::File::create(path.clone()) {
-        Ok(_) => {}
-        Err(_) => {}
-    }
+    let _ = std::fs::File::create(&path);
     bindings.write_to_file(&path).unwrap();
 }
+
+/// Reads an env var and parses it, falling back to `default` when unset or invalid.
+pub fn env_or<T: std::str::FromStr>(key: &str, default: T) -> T {
+    std::env::var(key)
+        .ok()
+        .and_then(|value| value.trim().parse().ok())
+        .unwrap_or(default)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::config_file::{BotToml, NetworkSection};
+    use crate::test_support::{self, MockNode};
+    use crate::Config;
+
+    #[tokio::test]
+    async fn an_unreachable_rpc_is_a_config_error() {
+        let settings = Settings { rpc_max_retries: 0, ..Settings::default() };
+        let http: Http = "http://127.0.0.1:1".parse().unwrap();
+        let provider = Provider::new(RetryHttp::from_settings(http, &settings));
+
+        let error = try_setup_signer(provider, &settings).await.unwrap_err();
+        assert!(matches!(&error, ConfigError::ProviderConnect { url, .. } if url == "http://127.0.0.1:1/"), "{}", error);
+        assert!(error.to_string().starts_with("could not connect to http://127.0.0.1:1/"));
+    }
+
+    // These read the unset `PRIVATE_KEY` and `NETWORK_RPC`, as a fresh checkout's tests do
+
+    #[tokio::test]
+    async fn a_local_signer_without_a_private_key_is_missing_env() {
+        let mut node = MockNode::new().await;
+        node.rpc("eth_chainId", json!("0x1")).await;
+        let http: Http = node.url().parse().unwrap();
+        let provider = Provider::new(RetryHttp::from_settings(http, &test_support::settings()));
+
+        let error = try_setup_signer(provider, &test_support::settings()).await.unwrap_err();
+        assert!(matches!(error, ConfigError::MissingEnv("PRIVATE_KEY")), "{}", error);
+        assert_eq!(error.to_string(), "missing PRIVATE_KEY");
+    }
+
+    #[tokio::test]
+    async fn a_config_without_an_rpc_url_is_missing_env() {
+        let error = Config::connect(BotToml::default(), test_support::settings()).await.unwrap_err();
+        assert!(matches!(error, ConfigError::MissingEnv("NETWORK_RPC")), "{}", error);
+    }
+
+    #[tokio::test]
+    async fn an_unparseable_rpc_url_is_invalid() {
+        let file = BotToml {
+            network: NetworkSection { rpc: Some("not a url".to_string()), ..NetworkSection::default() },
+            ..BotToml::default()
+        };
+
+        let error = Config::connect(file, test_support::settings()).await.unwrap_err();
+        assert!(matches!(&error, ConfigError::InvalidUrl { key: "NETWORK_RPC", url, .. } if url == "not a url"), "{}", error);
+        assert!(error.to_string().starts_with("invalid NETWORK_RPC \"not a url\": "));
+    }
+}
@@ -0,0 +1,201 @@
+use ethers::prelude::*;
+use rusqlite::{params, Connection, OptionalExtension};
+use std::path::Path;
+use std::sync::Mutex;
+
+use crate::strategy::MEVOpportunity;
+
+/// Persistent PnL record of every opportunity we submitted.
+///
+/// Amounts are stored as decimal wei strings: they overflow SQLite's 64-bit
+/// integers, and net profit can be negative.
+#[derive(Debug)]
+pub struct Ledger {
+    conn: Mutex<Connection>,
+}
+
+/// One row of the ledger.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LedgerEntry {
+    pub id: String,
+    pub strategy: String,
+    pub target_tx: H256,
+    pub submitted_block: u64,
+    /// `None` until inclusion has been checked.
+    pub landed: Option<bool>,
+    pub gross_profit: U256,
+    pub gas_spent: U256,
+    pub net_profit: I256,
+    pub relay: String,
+}
+
+impl Ledger {
+    pub fn open<P: AsRef<Path>>(path: P) -> rusqlite::Result<Self> {
+        Self::with_connection(Connection::open(path)?)
+    }
+
+    pub fn in_memory() -> rusqlite::Result<Self> {
+        Self::with_connection(Connection::open_in_memory()?)
+    }
+
+    fn with_connection(conn: Connection) -> rusqlite::Result<Self> {
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS opportunities (
+                id              TEXT PRIMARY KEY,
+                strategy        TEXT NOT NULL,
+                target_tx       TEXT NOT NULL,
+                submitted_block INTEGER NOT NULL,
+                landed          INTEGER,
+                gross_profit    TEXT NOT NULL,
+                gas_spent       TEXT NOT NULL,
+                net_profit      TEXT NOT NULL,
+                relay           TEXT NOT NULL
+            );",
+        )?;
+        Ok(Self { conn: Mutex::new(conn) })
+    }
+
+    /// Writes any dirty pages to disk, e.g. before shutting down.
+    pub fn flush(&self) -> rusqlite::Result<()> {
+        self.conn.lock().unwrap().cache_flush()
+    }
+
+    /// Records a submitted opportunity at its estimated profit, pending inclusion.
+    pub fn record_submission(
+        &self,
+        opportunity: &MEVOpportunity,
+        submitted_block: U64,
+        relay: &str,
+    ) -> rusqlite::Result<()> {
+        let net_profit = signed_difference(opportunity.estimated_profit, opportunity.gas_cost);
+        self.conn.lock().unwrap().execute(
+            "INSERT OR REPLACE INTO opportunities
+                (id, strategy, target_tx, submitted_block, landed, gross_profit, gas_spent, net_profit, relay)
+             VALUES (?1, ?2, ?3, ?4, NULL, ?5, ?6, ?7, ?8)",
+            params![
+                opportunity.id,
+                opportunity.strategy_type.name(),
+                format!("{:?}", opportunity.target_tx.hash),
+                submitted_block.as_u64(),
+                opportunity.estimated_profit.to_string(),
+                opportunity.gas_cost.to_string(),
+                net_profit.to_string(),
+                relay,
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// Settles an opportunity once inclusion is known. Opportunities that didn't
+    /// land keep only the gas they burned, if any.
+    pub fn record_inclusion(
+        &self,
+        id: &str,
+        landed: bool,
+        gross_profit: U256,
+        gas_spent: U256,
+    ) -> rusqlite::Result<()> {
+        let gross_profit = if landed { gross_profit } else { U256::zero() };
+        let net_profit = signed_difference(gross_profit, gas_spent);
+        self.conn.lock().unwrap().execute(
+            "UPDATE opportunities SET landed = ?2, gross_profit = ?3, gas_spent = ?4, net_profit = ?5 WHERE id = ?1",
+            params![id, landed, gross_profit.to_string(), gas_spent.to_string(), net_profit.to_string()],
+        )?;
+        Ok(())
+    }
+
+    pub fn entry(&self, id: &str) -> rusqlite::Result<Option<LedgerEntry>> {
+        self.conn
+            .lock()
+            .unwrap()
+            .query_row(
+                "SELECT id, strategy, target_tx, submitted_block, landed, gross_profit, gas_spent, net_profit, relay
+                 FROM opportunities WHERE id = ?1",
+                params![id],
+                |row| {
+                    Ok(LedgerEntry {
+                        id: row.get(0)?,
+                        strategy: row.get(1)?,
+                        target_tx: row.get::<_, String>(2)?.parse().unwrap_or_default(),
+                        submitted_block: row.get(3)?,
+                        landed: row.get(4)?,
+                        gross_profit: U256::from_dec_str(&row.get::<_, String>(5)?).unwrap_or_default(),
+                        gas_spent: U256::from_dec_str(&row.get::<_, String>(6)?).unwrap_or_default(),
+                        net_profit: I256::from_dec_str(&row.get::<_, String>(7)?).unwrap_or_default(),
+                        relay: row.get(8)?,
+                    })
+                },
+            )
+            .optional()
+    }
+
+    /// Net profit over everything confirmed as landed or failed. Pending rows are excluded.
+    pub fn total_net_profit(&self) -> rusqlite::Result<I256> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare("SELECT net_profit FROM opportunities WHERE landed IS NOT NULL")?;
+        let rows = stmt.query_map([], |row| row.get::<_, String>(0))?;
+
+        let mut total = I256::zero();
+        for net_profit in rows {
+            total += I256::from_dec_str(&net_profit?).unwrap_or_default();
+        }
+        Ok(total)
+    }
+}
+
+fn signed_difference(a: U256, b: U256) -> I256 {
+    I256::from_raw(a).saturating_sub(I256::from_raw(b))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::strategy::{BackrunDetails, StrategyType};
+    use std::time::Instant;
+
+    fn opportunity(id: &str, estimated_profit: u64, gas_cost: u64) -> MEVOpportunity {
+        MEVOpportunity {
+            id: id.to_string(),
+            target_tx: Transaction { hash: H256::from_low_u64_be(7), ..Default::default() },
+            strategy_type: StrategyType::Backrun(BackrunDetails {
+                victim_tx: Transaction::default(),
+                backrun_tx: Default::default(),
+            }),
+            estimated_profit: U256::from(estimated_profit),
+            gas_cost: U256::from(gas_cost),
+            priority: 0,
+            reserve_block: U64::zero(),
+            expiry_block: U64::one(),
+            observed_at: Instant::now(),
+            landing_probability: 1.0,
+        }
+    }
+
+    #[test]
+    fn submissions_are_pending_until_settled() {
+        let ledger = Ledger::in_memory().unwrap();
+        ledger.record_submission(&opportunity("a", 1_000, 300), U64::from(42), "flashbots").unwrap();
+
+        let entry = ledger.entry("a").unwrap().unwrap();
+        assert_eq!(entry.landed, None);
+        assert_eq!((entry.submitted_block, entry.net_profit), (42, I256::from(700)));
+        assert_eq!(entry.target_tx, H256::from_low_u64_be(7));
+        // Pending rows don't count yet
+        assert_eq!(ledger.total_net_profit().unwrap(), I256::zero());
+        assert_eq!(ledger.entry("missing").unwrap(), None);
+    }
+
+    #[test]
+    fn bundles_that_didnt_land_keep_only_their_gas() {
+        let ledger = Ledger::in_memory().unwrap();
+        ledger.record_submission(&opportunity("landed", 1_000, 300), U64::from(42), "flashbots").unwrap();
+        ledger.record_submission(&opportunity("missed", 1_000, 300), U64::from(42), "flashbots").unwrap();
+
+        ledger.record_inclusion("landed", true, U256::from(900), U256::from(250)).unwrap();
+        ledger.record_inclusion("missed", false, U256::from(900), U256::from(100)).unwrap();
+
+        let missed = ledger.entry("missed").unwrap().unwrap();
+        assert_eq!((missed.landed, missed.gross_profit, missed.net_profit), (Some(false), U256::zero(), I256::from(-100)));
+        assert_eq!(ledger.total_net_profit().unwrap(), I256::from(550));
+    }
+}
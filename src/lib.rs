@@ -1,63 +1,262 @@
 pub mod address_book;
+pub mod approvals;
+pub mod backpressure;
+pub mod backtest;
 pub mod alert;
+pub mod block_height;
 pub mod block_scanner;
+pub mod chain;
+pub mod classifier;
+pub mod cli;
+pub mod config_error;
+pub mod config_file;
 pub mod dex;
+pub mod executor;
+pub mod gas_model;
+pub mod gas_oracle;
+pub mod health;
 pub mod helpers;
+pub mod ledger;
+pub mod metrics;
 pub mod mempool;
+pub mod mempool_replay;
+pub mod mempool_source;
+pub mod pool_cache;
+pub mod replacement;
+pub mod rpc;
 pub mod uni;
+pub mod uni_v3;
+pub mod universal_router;
+pub mod settings;
+pub mod shutdown;
+pub mod signer;
 pub mod strategy;
+pub mod token_info;
+#[cfg(test)]
+mod test_support;
 
 use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::sync::broadcast;
 
 use address_book::*;
-use ethers::prelude::k256::ecdsa::SigningKey;
 use ethers::prelude::*;
 use helpers::address;
-use strategy::StrategyManager;
+use strategy::{BridgeMonitor, DexType, StrategyManager};
+use tracing::{error, info, warn};
 
-use crate::dex::Dex;
-use crate::helpers::setup_signer;
+use crate::block_height::BlockHeight;
+use crate::block_scanner::Reorg;
+use crate::chain::ChainConfig;
+use crate::config_error::ConfigError;
+use crate::config_file::{BotToml, DEFAULT_CONFIG_PATH};
+use crate::dex::{Dex, PairIndex, PAIR_INDEX_BATCH};
+use crate::gas_model::GasModel;
+use crate::gas_oracle::GasOracle;
+use crate::health::Health;
+use crate::pool_cache::PoolCache;
+use crate::helpers::try_setup_signer;
+use crate::rpc::RetryHttp;
+use crate::settings::Settings;
+use crate::shutdown::Shutdown;
+use crate::signer::SignerClient;
+use crate::token_info::TokenInfoResolver;
 
 #[derive(Debug)]
 pub struct Config {
-    pub http: Arc<SignerMiddleware<Provider<Http>, Wallet<SigningKey>>>,
+    pub http: Arc<SignerClient>,
     pub wss: Arc<Provider<Ws>>,
+    pub settings: Settings,
+    /// Token and DEX addresses of the chain the RPC serves.
+    pub chain: ChainConfig,
+    /// Current block as seen by the block scanner; read this rather than the node.
+    pub block_height: Arc<BlockHeight>,
+    /// V2 pair reserves read this block, expired by the block scanner.
+    pub pool_cache: Arc<PoolCache>,
+    /// Decimals and symbols of tokens seen so far.
+    pub token_info: Arc<TokenInfoResolver>,
+    /// Recent base and priority fees, kept current by the block scanner.
+    pub gas_oracle: Arc<GasOracle>,
+    /// Per-strategy gas estimates, priced off `gas_oracle`.
+    pub gas_model: Arc<GasModel>,
+    /// Reorgs detected by the block scanner; subscribe to re-evaluate pending work.
+    pub reorgs: broadcast::Sender<Reorg>,
+    /// Each new head the block scanner follows, once `block_height`, `gas_oracle`
+    /// and `pool_cache` have moved to it; subscribe to do per-block work.
+    pub heads: broadcast::Sender<Block<H256>>,
+    /// The preset's V2 DEXes with pair indexes, when `index_pairs` is on.
+    pub dexes: Vec<Arc<Dex>>,
 }
 
 impl Config {
+    /// Like `try_new`, panicking on misconfiguration.
     pub async fn new() -> Self {
-        let network = std::env::var("NETWORK_RPC").expect("missing NETWORK_RPC");
-        let provider: Provider<Http> = Provider::<Http>::try_from(network).unwrap();
-        let middleware = Arc::new(setup_signer(provider.clone()).await);
+        Self::try_new().await.unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Loads `bot.toml` (or the file at `BOT_CONFIG`) if present, otherwise env vars only.
+    pub async fn try_new() -> Result<Self, ConfigError> {
+        let path = std::env::var("BOT_CONFIG").unwrap_or_else(|_| DEFAULT_CONFIG_PATH.to_string());
+        if std::path::Path::new(&path).exists() {
+            return Self::try_from_file(&path).await;
+        }
+        Self::connect(BotToml::default(), Settings::from_env()).await
+    }
+
+    /// Like `try_from_file`, panicking on misconfiguration.
+    pub async fn from_file(path: &str) -> Self {
+        Self::try_from_file(path).await.unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Typed config from a TOML file, with env vars overriding anything it sets.
+    pub async fn try_from_file(path: &str) -> Result<Self, ConfigError> {
+        let file = BotToml::load(path).map_err(|e| ConfigError::ConfigFile {
+            path: path.to_string(),
+            reason: e.to_string(),
+        })?;
+        let settings = Settings::from_env_over(file.apply(Settings::default()));
+        Self::connect(file, settings).await
+    }
+
+    async fn connect(file: BotToml, settings: Settings) -> Result<Self, ConfigError> {
+        let network = std::env::var("NETWORK_RPC")
+            .ok()
+            .or(file.network.rpc)
+            .ok_or(ConfigError::MissingEnv("NETWORK_RPC"))?;
+        let primary = network.parse::<Http>().map_err(|e| ConfigError::InvalidUrl {
+            key: "NETWORK_RPC",
+            url: network.clone(),
+            reason: e.to_string(),
+        })?;
+        let provider = Provider::new(RetryHttp::from_settings(primary, &settings));
+        let middleware = Arc::new(try_setup_signer(provider, &settings).await?);
+        let chain_id = middleware.signer().chain_id();
+        if let Some(expected) = settings.chain_id {
+            if chain_id != expected {
+                return Err(ConfigError::ChainMismatch { expected, actual: chain_id });
+            }
+        }
+        let chain = ChainConfig::for_chain_id(chain_id).ok_or(ConfigError::UnsupportedChain(chain_id))?;
 
-        let ws_network = std::env::var("NETWORK_WSS").expect("missing NETWORK_WSS");
-        let ws_provider: Provider<Ws> = Provider::<Ws>::connect(ws_network).await.unwrap();
-        Self {
+        let ws_network = std::env::var("NETWORK_WSS")
+            .ok()
+            .or(file.network.wss)
+            .ok_or(ConfigError::MissingEnv("NETWORK_WSS"))?;
+        if let Err(e) = reqwest::Url::parse(&ws_network) {
+            return Err(ConfigError::InvalidUrl { key: "NETWORK_WSS", url: ws_network, reason: e.to_string() });
+        }
+        let ws_provider = Provider::<Ws>::connect(ws_network.as_str()).await.map_err(|e| {
+            ConfigError::ProviderConnect { url: ws_network.clone(), reason: e.to_string() }
+        })?;
+
+        // Seeded here so nothing reads block 0 before the scanner's first update
+        let block_height = Arc::new(BlockHeight::new(middleware.get_block_number().await.unwrap_or_default()));
+
+        let dexes = if settings.index_pairs { Self::indexed_dexes(&middleware, &settings, &chain) } else { Vec::new() };
+        let gas_oracle = Arc::new(GasOracle::new(settings.gas.oracle_window_blocks));
+
+        Ok(Self {
+            dexes,
+            pool_cache: Arc::new(PoolCache::new(middleware.clone(), block_height.clone())),
+            token_info: Arc::new(TokenInfoResolver::new(middleware.clone(), settings.token_info_cache_size)),
             http: middleware,
             wss: Arc::new(ws_provider),
-        }
+            block_height,
+            gas_model: Arc::new(GasModel::from_settings(&settings, gas_oracle.clone())),
+            gas_oracle,
+            reorgs: broadcast::channel(16).0,
+            heads: broadcast::channel(16).0,
+            settings,
+            chain,
+        })
+    }
+
+    /// Deadline for swap calldata we build: `swap_deadline_secs` past the head
+    /// block's timestamp, or past the clock's before the first head is seen.
+    pub fn swap_deadline(&self) -> U256 {
+        U256::from(self.block_height.deadline(self.settings.swap_deadline_secs))
     }
 
     pub async fn create_dex(&self, factory: Address, router: Address) -> Dex {
         Dex::new(self.http.clone(), factory, router)
     }
+
+    /// One `Dex` per V2 factory of the chain's DEXes, each with its own (possibly persisted) pair index.
+    fn indexed_dexes(
+        middleware: &Arc<SignerClient>,
+        settings: &Settings,
+        chain: &ChainConfig,
+    ) -> Vec<Arc<Dex>> {
+        let registry = chain.dex_registry(settings.dex_preset.as_deref());
+        let mut dexes = Vec::new();
+        for (dex, factories) in &registry.factories {
+            // V3 pools aren't enumerable through `allPairs`
+            if *dex == DexType::UniswapV3 {
+                continue;
+            }
+            let Some(router) = registry.router(*dex) else {
+                continue;
+            };
+            for factory in factories {
+                let path = settings
+                    .pair_index_dir
+                    .as_ref()
+                    .map(|dir| format!("{}/pairs-{:?}.json", dir.trim_end_matches('/'), factory));
+                dexes.push(Arc::new(Dex::with_index(middleware.clone(), *factory, router, PairIndex::new(path))));
+            }
+        }
+        dexes
+    }
+
+    /// The pair of `token_a` and `token_b` on the first indexed DEX that has one.
+    pub fn pair_for(&self, token_a: Address, token_b: Address) -> Option<Address> {
+        self.dexes.iter().find_map(|dex| dex.pair_for(token_a, token_b))
+    }
 }
 
 /// Run the MEV bot with advanced strategies
 pub async fn run() {
-    println!("🚀 Starting MEV Bot - Jaredfromsubway Style");
+    info!("🚀 Starting MEV Bot - Jaredfromsubway Style");
     
-    let config = Arc::new(Config::new().await);
+    let config = match Config::try_new().await {
+        Ok(config) => Arc::new(config),
+        Err(e) => {
+            error!("❌ Configuration error: {}", e);
+            return;
+        }
+    };
     
     // Initialize strategy manager
     let strategy_manager = Arc::new(StrategyManager::new(config.clone()).await);
     
     // Display configuration
-    println!("📊 Configuration:");
-    println!("   - Network RPC: {}", std::env::var("NETWORK_RPC").unwrap_or_default());
-    println!("   - Min Profit: 0.1 ETH");
-    println!("   - Strategies: Sandwich Attack, Cross-DEX Arbitrage");
-    println!("   - Bundle Submission: Flashbots");
+    info!(
+        network_rpc = %std::env::var("NETWORK_RPC").unwrap_or_default(),
+        min_profit = "0.1 ETH",
+        strategies = "Sandwich Attack, Cross-DEX Arbitrage",
+        submission = "Flashbots",
+        "📊 Configuration"
+    );
+    
+    alert::init(&config.settings);
+
+    // Kill-switch: SIGUSR1 or the halt file stops execution without stopping the process
+    tokio::spawn(strategy_manager.emergency_stop().watch());
+    tokio::spawn(strategy_manager.clone().watch_reorgs());
+    // Token/pool lists are re-read whenever their file changes
+    tokio::spawn(strategy_manager.access_lists().watch());
+
+    // Prometheus metrics endpoint
+    metrics::init(&config.settings);
+    let metrics_port = config.settings.metrics_port;
+    tokio::spawn(async move {
+        metrics::serve(metrics_port).await;
+    });
+
+    // Liveness/readiness probes for systemd or k8s
+    let health = Arc::new(Health::from_settings(&config.settings));
+    tokio::spawn(health::serve(health.clone(), config.settings.health_port, config.http.clone()));
     
     // Example of how to interact with a DEX (optional)
     let spooky_factory = address(SPOOKY_SWAP_FACTORY);
@@ -65,13 +264,51 @@ pub async fn run() {
     let dex = config.create_dex(spooky_factory, spooky_router).await;
     dex.get_pairs().await;
 
+    // Deposits/withdrawals on the L1 bridges of BRIDGE_DEST_RPC's chain, priced on both sides
+    if let Some(bridge_monitor) = BridgeMonitor::from_config(config.clone()).await {
+        tokio::spawn(bridge_monitor.watch());
+    }
+
+    // Pair indexes: backfill from `allPairs`, then follow `PairCreated`
+    for dex in &config.dexes {
+        let (dex, wss) = (dex.clone(), config.wss.clone());
+        tokio::spawn(async move {
+            dex.stream_pairs_created(&wss, PAIR_INDEX_BATCH).await;
+        });
+    }
+
+    // Ctrl-C stops intake, drains in-flight work, then returns from `run`
+    let shutdown = Shutdown::new();
+    let ctrl_c_shutdown = shutdown.clone();
+    tokio::spawn(async move {
+        if tokio::signal::ctrl_c().await.is_ok() {
+            info!("🛑 Ctrl-C received, shutting down...");
+            ctrl_c_shutdown.trigger();
+        }
+    });
+
     // Thread for checking what block we're on
     let config_clone = config.clone();
+    let scanner_shutdown = shutdown.clone();
     tokio::spawn(async move {
-        block_scanner::loop_blocks(Arc::clone(&config_clone.http)).await;
+        block_scanner::loop_blocks(config_clone, scanner_shutdown).await;
     });
 
     // Main MEV monitoring loop with strategy execution
-    enhanced_mempool::enhanced_mempool_monitor(Arc::clone(&config.wss), strategy_manager).await;
+    match mempool_source::connect(&config.settings, Arc::clone(&config.wss)).await {
+        Ok(source) => {
+            enhanced_mempool::enhanced_mempool_monitor(source.as_ref(), strategy_manager.clone(), health, shutdown.clone())
+                .await
+        }
+        Err(e) => error!("❌ Mempool source unavailable: {}", e),
+    }
+    shutdown.trigger();
+
+    let timeout = Duration::from_secs(config.settings.shutdown_timeout_secs);
+    info!("⏳ Waiting up to {:?} for {} in-flight task(s)", timeout, shutdown.in_flight());
+    if !shutdown.drain(timeout).await {
+        warn!("⚠️ {} task(s) still in flight, exiting anyway", shutdown.in_flight());
+    }
+    strategy_manager.flush_ledger();
 }
 pub mod enhanced_mempool;
@@ -1,15 +1,22 @@
-use mev_template::run;
+use clap::Parser;
+use mev_template::cli::Cli;
+use tracing::info;
+use tracing_subscriber::EnvFilter;
 
 #[tokio::main]
 async fn main() {
     dotenv::dotenv().ok();
 
-    // Initialize logger
-    env_logger::init();
+    // Initialize logger, filtered by RUST_LOG (e.g. `RUST_LOG=mev_template=debug`)
+    tracing_subscriber::fmt()
+        .with_env_filter(EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info")))
+        .init();
 
-    println!("Starting the MEV bot...");
+    let cli = Cli::parse();
 
-    run().await;
+    info!("Starting the MEV bot...");
 
-    println!("Bot stopped.");
+    cli.execute().await;
+
+    info!("Bot stopped.");
 }
\ No newline at end of file
@@ -2,6 +2,7 @@ use ethers::types::U256;
 
 use std::sync::Arc;
 use std::collections::HashMap;
+use std::time::Instant;
 use tokio::sync::Mutex;
 
 use ethers::{
@@ -9,8 +10,10 @@ use ethers::{
     providers::{Middleware, Provider, StreamExt, TransactionStream, Ws},
     types::Transaction,
 };
+use tracing::{error, info, info_span, Instrument, Span};
 
-use crate::address_book::UniV2RouterCalls;
+use crate::address_book::{UniV2RouterCalls, KNOWN_ROUTERS};
+use crate::helpers::address;
 use crate::strategy::StrategyManager;
 use crate::alert::alert;
 
@@ -19,11 +22,11 @@ pub async fn loop_mempool(ws_provider: Arc<Provider<Ws>>) {
     let tx_hash_stream = ws_provider.subscribe_pending_txs().await.unwrap();
     let mut tx_stream = TransactionStream::new(&ws_provider, tx_hash_stream, 256);
 
-    println!("---------- MONITORING MEMPOOL ----------");
+    info!("---------- MONITORING MEMPOOL ----------");
     while let Some(maybe_tx) = tx_stream.next().await {
         if let Ok(tx) = maybe_tx {
             if let Ok(decoded) = UniV2RouterCalls::decode(&tx.input) {
-                println!("Transaction: {:#?}\nRouter Call: {:#?}\n", tx, decoded);
+                info!("Transaction: {:#?}\nRouter Call: {:#?}\n", tx, decoded);
             }
         }
     }
@@ -40,11 +43,12 @@ pub async fn loop_mempool_with_strategies(
     let tx_hash_stream = ws_provider.subscribe_pending_txs().await.unwrap();
     let mut tx_stream = TransactionStream::new(&ws_provider, tx_hash_stream, 256);
 
-    println!("🔍 MEV Bot Active - Monitoring Mempool");
-    println!("---------- SCANNING FOR OPPORTUNITIES ----------");
+    info!("🔍 MEV Bot Active - Monitoring Mempool");
+    info!("---------- SCANNING FOR OPPORTUNITIES ----------");
     
     while let Some(maybe_tx) = tx_stream.next().await {
         if let Ok(tx) = maybe_tx {
+            let observed_at = Instant::now();
             // Skip if we've already processed this transaction
             let mut processed = processed_txs.lock().await;
             if processed.contains_key(&tx.hash) {
@@ -56,11 +60,9 @@ pub async fn loop_mempool_with_strategies(
             if is_router_transaction(&tx) {
                 // Spawn a task to analyze this transaction
                 let strategy_manager_clone = strategy_manager.clone();
-                let ws_provider_clone = ws_provider.clone();
                 
-                tokio::spawn(async move {
-                    analyze_and_execute(tx, strategy_manager_clone, ws_provider_clone).await;
-                });
+                let span = tx_span(&tx);
+                tokio::spawn(analyze_and_execute(tx, observed_at, strategy_manager_clone).instrument(span));
             }
         }
     }
@@ -68,8 +70,8 @@ pub async fn loop_mempool_with_strategies(
 
 async fn analyze_and_execute(
     tx: Transaction,
+    observed_at: Instant,
     strategy_manager: Arc<StrategyManager>,
-    ws_provider: Arc<Provider<Ws>>,
 ) {
     // Decode router call for logging
     if let Ok(decoded) = UniV2RouterCalls::decode(&tx.input) {
@@ -78,19 +80,19 @@ async fn analyze_and_execute(
             .map(|gp| gp / 1_000_000_000u64)
             .unwrap_or(U256::zero());
             
-        println!("\n🎯 Analyzing Transaction: {}", tx.hash);
-        println!("   Router Call: {:?}", get_call_type(&decoded));
-        println!("   Gas Price: {} gwei", gas_price_gwei);
+        info!(call = ?get_call_type(&decoded), gas_price_gwei = %gas_price_gwei, "🎯 Analyzing transaction");
     }
     
     // Analyze transaction for MEV opportunities
-    let opportunities = strategy_manager.analyze_transaction(&tx).await;
+    let trace = strategy_manager.decision_trace(&tx);
+    let opportunities = strategy_manager.analyze_observed(&tx, &trace, observed_at).await;
+    trace.emit();
     
     if opportunities.is_empty() {
         return;
     }
     
-    println!("💰 Found {} MEV opportunities!", opportunities.len());
+    info!("💰 Found {} MEV opportunities!", opportunities.len());
     
     // Sort opportunities by priority and profit
     let mut sorted_opportunities = opportunities;
@@ -101,19 +103,19 @@ async fn analyze_and_execute(
     
     // Execute the best opportunity
     for opportunity in sorted_opportunities.iter() {
-        println!("\n🚀 Executing MEV Strategy:");
-        println!("   Type: {:?}", opportunity.strategy_type);
-        println!("   Estimated Profit: {} ETH", 
-                 ethers::utils::format_ether(opportunity.estimated_profit));
-        println!("   Gas Cost: {} ETH", 
-                 ethers::utils::format_ether(opportunity.gas_cost));
+        info!(
+            strategy = ?opportunity.strategy_type,
+            estimated_eth = %ethers::utils::format_ether(opportunity.estimated_profit),
+            gas_eth = %ethers::utils::format_ether(opportunity.gas_cost),
+            "🚀 Executing MEV strategy"
+        );
         
         match strategy_manager.execute_opportunity(opportunity).await {
             Ok(tx_hash) => {
-                println!("✅ MEV Bundle Submitted: {}", tx_hash);
+                info!("✅ MEV Bundle Submitted: {}", tx_hash);
                 
                 // Send alert about successful opportunity
-                let current_block = ws_provider.get_block_number().await.unwrap_or_default();
+                let current_block = strategy_manager.config().block_height.get();
                 let msg = format!(
                     "MEV Opportunity Executed!\nType: {:?}\nProfit: {} ETH\nTx: {}",
                     opportunity.strategy_type,
@@ -125,24 +127,22 @@ async fn analyze_and_execute(
                 break; // Only execute one opportunity per transaction
             },
             Err(e) => {
-                println!("❌ Failed to execute MEV strategy: {}", e);
+                error!("❌ Failed to execute MEV strategy: {}", e);
             }
         }
     }
 }
 
-fn is_router_transaction(tx: &Transaction) -> bool {
+/// Span every log emitted while analyzing, simulating and submitting around
+/// `tx` is recorded in, so they can be correlated by its hash.
+pub fn tx_span(tx: &Transaction) -> Span {
+    info_span!("tx", hash = ?tx.hash)
+}
+
+pub(crate) fn is_router_transaction(tx: &Transaction) -> bool {
     // Check if transaction is to a known router
-    let routers = vec![
-        "0x7a250d5630B4cF539739dF2C5dAcb4c659F2488D", // Uniswap V2
-        "0xd9e1cE17f2641f24aE83637ab66a2cca9C378B9F", // Sushiswap
-        "0xE592427A0AEce92De3Edee1F18E0157C05861564", // Uniswap V3
-    ];
-    
     if let Some(to) = tx.to {
-        routers.iter().any(|&router| {
-            to == router.parse::<ethers::types::Address>().unwrap()
-        })
+        KNOWN_ROUTERS.iter().any(|&router| to == address(router))
     } else {
         false
     }
@@ -162,3 +162,94 @@ fn get_call_type(call: &UniV2RouterCalls) -> &'static str {
         _ => "Other",
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::address_book::UNIVERSAL_ROUTER;
+    use ethers::types::{Address, H256};
+    use tracing::field::{Field, Visit};
+    use tracing::span::{Attributes, Id};
+    use tracing::{Event, Subscriber};
+    use tracing_subscriber::layer::{Context, SubscriberExt};
+    use tracing_subscriber::registry::LookupSpan;
+    use tracing_subscriber::Layer;
+
+    /// A span's or event's fields, as `Debug` renders them.
+    #[derive(Debug, Clone, Default)]
+    struct Fields(Vec<(String, String)>);
+
+    impl Visit for Fields {
+        fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+            self.0.push((field.name().to_string(), format!("{:?}", value)));
+        }
+    }
+
+    /// An event, with the name and fields of every span it was emitted in.
+    #[derive(Debug)]
+    struct CapturedEvent {
+        fields: Fields,
+        spans: Vec<(&'static str, Fields)>,
+    }
+
+    #[derive(Clone, Default)]
+    struct CaptureLayer(Arc<std::sync::Mutex<Vec<CapturedEvent>>>);
+
+    impl<S: Subscriber + for<'a> LookupSpan<'a>> Layer<S> for CaptureLayer {
+        fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+            let mut fields = Fields::default();
+            attrs.record(&mut fields);
+            ctx.span(id).unwrap().extensions_mut().insert(fields);
+        }
+
+        fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+            let mut fields = Fields::default();
+            event.record(&mut fields);
+            let spans = ctx
+                .event_scope(event)
+                .into_iter()
+                .flatten()
+                .map(|span| (span.name(), span.extensions().get::<Fields>().cloned().unwrap_or_default()))
+                .collect();
+            self.0.lock().unwrap().push(CapturedEvent { fields, spans });
+        }
+    }
+
+    #[test]
+    fn only_txs_sent_to_known_routers_are_router_txs() {
+        let sent_to = |to: Option<Address>| Transaction { to, ..Default::default() };
+        assert!(is_router_transaction(&sent_to(Some(address(UNIVERSAL_ROUTER)))));
+        assert!(!is_router_transaction(&sent_to(Some(Address::from_low_u64_be(1)))));
+        // Contract deployments go nowhere
+        assert!(!is_router_transaction(&sent_to(None)));
+    }
+
+    #[tokio::test]
+    async fn logs_from_a_txs_analysis_carry_its_hash() {
+        let capture = CaptureLayer::default();
+        let _default = tracing::subscriber::set_default(tracing_subscriber::registry().with(capture.clone()));
+
+        let tx = Transaction { hash: H256::from_low_u64_be(0x71c), ..Default::default() };
+        async {
+            info!("analyzing");
+            tokio::task::yield_now().await;
+            info!("submitted");
+        }
+        .instrument(tx_span(&tx))
+        .await;
+        info!("between txs");
+
+        let events = capture.0.lock().unwrap();
+        let messages: Vec<&str> = events.iter().map(|event| event.fields.0[0].1.as_str()).collect();
+        assert_eq!(messages, ["analyzing", "submitted", "between txs"]);
+        let hash = format!("{:?}", tx.hash);
+        for event in &events[..2] {
+            let [(name, fields)] = event.spans.as_slice() else {
+                panic!("{:?}", event);
+            };
+            assert_eq!(*name, "tx");
+            assert_eq!(fields.0, [("hash".to_string(), hash.clone())]);
+        }
+        assert!(events[2].spans.is_empty(), "{:?}", events[2]);
+    }
+}
@@ -0,0 +1,169 @@
+use std::fs::{File, OpenOptions};
+use std::io::{BufWriter, Write};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use async_trait::async_trait;
+use ethers::prelude::*;
+use futures_util::stream;
+use serde::{Deserialize, Serialize};
+use tokio::time::Instant;
+use tracing::{info, warn};
+
+use crate::mempool_source::{MempoolSource, TxStream};
+
+/// One line of a mempool recording: a pending tx and when it reached us.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecordedTx {
+    /// Unix time the tx arrived, in milliseconds.
+    pub received_at_ms: u64,
+    pub tx: Transaction,
+}
+
+/// Parses a recording, one `RecordedTx` per line. Blank lines are skipped;
+/// malformed ones are skipped with a warning, so a file cut off mid-write
+/// still replays.
+pub fn parse_recording(contents: &str) -> Vec<RecordedTx> {
+    contents
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .filter_map(|(i, line)| match serde_json::from_str(line) {
+            Ok(recorded) => Some(recorded),
+            Err(e) => {
+                warn!("⚠️ Skipping malformed mempool recording line {}: {}", i + 1, e);
+                None
+            }
+        })
+        .collect()
+}
+
+/// Passes `inner`'s pending txs through unchanged, appending each to a
+/// newline-delimited JSON file as it arrives, so an incident's mempool can
+/// be replayed later with `ReplayMempool`.
+pub struct RecordingMempool {
+    inner: Box<dyn MempoolSource>,
+    path: String,
+}
+
+impl RecordingMempool {
+    pub fn new(inner: Box<dyn MempoolSource>, path: String) -> Self {
+        Self { inner, path }
+    }
+}
+
+#[async_trait]
+impl MempoolSource for RecordingMempool {
+    fn name(&self) -> &'static str {
+        self.inner.name()
+    }
+
+    async fn subscribe(&self) -> Result<TxStream, String> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .map_err(|e| format!("could not open mempool recording {}: {}", self.path, e))?;
+        let writer = Arc::new(Mutex::new(BufWriter::new(file)));
+        let tx_stream = self.inner.subscribe().await?;
+        info!("📼 Recording pending txs to {}", self.path);
+
+        let path = self.path.clone();
+        Ok(Box::pin(tx_stream.inspect(move |tx| {
+            if let Err(e) = record(&writer, tx) {
+                warn!("⚠️ Could not record pending tx {:?} to {}: {}", tx.hash, path, e);
+            }
+        })))
+    }
+}
+
+fn record(writer: &Mutex<BufWriter<File>>, tx: &Transaction) -> Result<(), Box<dyn std::error::Error>> {
+    let received_at_ms = SystemTime::now().duration_since(UNIX_EPOCH)?.as_millis() as u64;
+    let line = serde_json::to_string(&RecordedTx { received_at_ms, tx: tx.clone() })?;
+    let mut writer = writer.lock().unwrap();
+    writeln!(writer, "{}", line)?;
+    // Flushed per tx: the recording matters most when the process dies
+    writer.flush()?;
+    Ok(())
+}
+
+/// Streams a recording made by `RecordingMempool` back as pending txs, keeping
+/// the recorded gaps between them divided by `speed`; a `speed` of 0 or less
+/// sends them back to back. The stream ends with the recording.
+pub struct ReplayMempool {
+    path: String,
+    speed: f64,
+}
+
+impl ReplayMempool {
+    pub fn new(path: String, speed: f64) -> Self {
+        Self { path, speed }
+    }
+}
+
+#[async_trait]
+impl MempoolSource for ReplayMempool {
+    fn name(&self) -> &'static str {
+        "replay"
+    }
+
+    async fn subscribe(&self) -> Result<TxStream, String> {
+        let contents = std::fs::read_to_string(&self.path)
+            .map_err(|e| format!("could not read mempool recording {}: {}", self.path, e))?;
+        let recording = parse_recording(&contents);
+        info!("📼 Replaying {} pending txs from {} at {}x", recording.len(), self.path, self.speed);
+        Ok(replay(recording, self.speed))
+    }
+}
+
+/// `recording` as a stream, paced by its arrival times divided by `speed`.
+pub fn replay(recording: Vec<RecordedTx>, speed: f64) -> TxStream {
+    let first = recording.first().map_or(0, |recorded| recorded.received_at_ms);
+    let start = Instant::now();
+    Box::pin(stream::iter(recording).then(move |recorded| async move {
+        if speed > 0.0 {
+            let offset_ms = recorded.received_at_ms.saturating_sub(first) as f64 / speed;
+            tokio::time::sleep_until(start + Duration::from_secs_f64(offset_ms / 1000.0)).await;
+        }
+        recorded.tx
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn recorded(received_at_ms: u64, hash: u64) -> RecordedTx {
+        RecordedTx { received_at_ms, tx: Transaction { hash: H256::from_low_u64_be(hash), ..Default::default() } }
+    }
+
+    #[test]
+    fn parse_recording_skips_blank_and_truncated_lines() {
+        let line = |r: &RecordedTx| serde_json::to_string(r).unwrap();
+        let contents = format!("{}\n\n{}\n{}", line(&recorded(1, 1)), line(&recorded(2, 2)), &line(&recorded(3, 3))[..20]);
+        let recording = parse_recording(&contents);
+        assert_eq!(recording.iter().map(|r| r.received_at_ms).collect::<Vec<_>>(), vec![1, 2]);
+        assert_eq!(recording[1].tx.hash, H256::from_low_u64_be(2));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn replay_keeps_the_recorded_gaps_divided_by_speed() {
+        let start = Instant::now();
+        let recording = vec![recorded(1_000, 1), recorded(1_400, 2), recorded(3_000, 3)];
+        let mut stream = replay(recording, 2.0);
+
+        let mut arrivals = Vec::new();
+        while let Some(tx) = stream.next().await {
+            arrivals.push((tx.hash.to_low_u64_be(), start.elapsed().as_millis()));
+        }
+        assert_eq!(arrivals, vec![(1, 0), (2, 200), (3, 1_000)]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn replay_at_zero_speed_sends_back_to_back() {
+        let start = Instant::now();
+        let hashes: Vec<_> = replay(vec![recorded(0, 1), recorded(60_000, 2)], 0.0).map(|tx| tx.hash).collect().await;
+        assert_eq!(hashes.len(), 2);
+        assert_eq!(start.elapsed(), Duration::ZERO);
+    }
+}
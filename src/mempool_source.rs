@@ -0,0 +1,398 @@
+use std::pin::Pin;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use ethers::prelude::*;
+use ethers::providers::{Authorization, ConnectionDetails};
+use futures_util::{future, SinkExt, Stream};
+use serde_json::{json, Value};
+use tokio::sync::oneshot;
+use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+use tokio_tungstenite::tungstenite::Message;
+use tracing::{info, warn};
+
+use crate::backpressure::{BackpressurePolicy, PendingQueue};
+use crate::mempool_replay::{RecordingMempool, ReplayMempool};
+use crate::metrics::metrics;
+use crate::settings::Settings;
+
+/// bloXroute's cloud API; a local gateway serves the same feed at `ws://127.0.0.1:28333/ws`.
+pub const BLOXROUTE_CLOUD_WS: &str = "wss://api.blxrbdn.com/ws";
+
+pub type TxStream = Pin<Box<dyn Stream<Item = Transaction> + Send>>;
+
+/// A feed of pending txs: our own node's mempool or a private stream.
+#[async_trait]
+pub trait MempoolSource: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    /// Subscribes and streams pending txs until the feed ends.
+    async fn subscribe(&self) -> Result<TxStream, String>;
+}
+
+/// Which feed `run` reads pending txs from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MempoolSourceKind {
+    /// `eth_subscribe` on the `NETWORK_WSS` node.
+    Node,
+    /// bloXroute's `newTxs` stream, from a gateway or the cloud API.
+    Bloxroute,
+    /// Eden's stream, which speaks plain `eth_subscribe` behind an auth header.
+    Eden,
+    /// A file recorded with `MEMPOOL_RECORD_PATH`, played back from `MEMPOOL_REPLAY_PATH`.
+    Replay,
+}
+
+impl FromStr for MempoolSourceKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "node" => Ok(Self::Node),
+            "bloxroute" => Ok(Self::Bloxroute),
+            "eden" => Ok(Self::Eden),
+            "replay" => Ok(Self::Replay),
+            other => Err(format!("unknown mempool source: {}", other)),
+        }
+    }
+}
+
+/// Connects the source `settings` selects; `wss` is the node the `node` source reads.
+/// With `MEMPOOL_RECORD_PATH` set, a live source also records what it streams.
+pub async fn connect(settings: &Settings, wss: Arc<Provider<Ws>>) -> Result<Box<dyn MempoolSource>, String> {
+    let source = connect_live(settings, wss).await?;
+    match &settings.mempool_record_path {
+        Some(path) if settings.mempool_source != MempoolSourceKind::Replay => {
+            Ok(Box::new(RecordingMempool::new(source, path.clone())))
+        }
+        _ => Ok(source),
+    }
+}
+
+async fn connect_live(settings: &Settings, wss: Arc<Provider<Ws>>) -> Result<Box<dyn MempoolSource>, String> {
+    let auth = settings.mempool_source_auth.clone();
+    match settings.mempool_source {
+        MempoolSourceKind::Node => Ok(Box::new(NodeMempool::from_settings(wss, settings))),
+        MempoolSourceKind::Bloxroute => {
+            let url = settings.mempool_source_url.clone().unwrap_or_else(|| BLOXROUTE_CLOUD_WS.to_string());
+            Ok(Box::new(BloxrouteMempool::from_settings(url, auth, settings)))
+        }
+        MempoolSourceKind::Eden => {
+            let url = settings.mempool_source_url.as_deref().ok_or("the eden source needs MEMPOOL_SOURCE_URL")?;
+            let provider = Provider::new(
+                Ws::connect(ConnectionDetails::new(url, auth.map(Authorization::raw)))
+                    .await
+                    .map_err(|e| format!("could not connect to {}: {}", url, e))?,
+            );
+            Ok(Box::new(NodeMempool {
+                name: "eden",
+                ..NodeMempool::from_settings(Arc::new(provider), settings)
+            }))
+        }
+        MempoolSourceKind::Replay => {
+            let path = settings.mempool_replay_path.clone().ok_or("the replay source needs MEMPOOL_REPLAY_PATH")?;
+            Ok(Box::new(ReplayMempool::new(path, settings.mempool_replay_speed)))
+        }
+    }
+}
+
+/// Pending txs from a node's `eth_subscribe`. Full bodies are tried first,
+/// falling back to the hash stream with each body fetched.
+pub struct NodeMempool {
+    name: &'static str,
+    provider: Arc<Provider<Ws>>,
+    full_bodies: bool,
+    queue_capacity: usize,
+    policy: BackpressurePolicy,
+    fetch_concurrency: usize,
+}
+
+impl NodeMempool {
+    pub fn from_settings(provider: Arc<Provider<Ws>>, settings: &Settings) -> Self {
+        Self {
+            name: "node",
+            provider,
+            full_bodies: settings.full_pending_txs,
+            queue_capacity: settings.tx_queue_capacity,
+            policy: settings.backpressure_policy,
+            fetch_concurrency: settings.tx_fetch_concurrency,
+        }
+    }
+
+    /// Feeds the full-body pending subscription into `queue` from a background task.
+    /// Returns once the node has accepted or rejected the subscription.
+    async fn subscribe_full_bodies(&self, queue: Arc<PendingQueue<Transaction>>) -> Result<(), String> {
+        let (subscribed_tx, subscribed_rx) = oneshot::channel();
+        let provider = self.provider.clone();
+
+        tokio::spawn(async move {
+            match provider.subscribe::<_, Transaction>(("newPendingTransactions", true)).await {
+                Ok(mut tx_stream) => {
+                    let _ = subscribed_tx.send(Ok(()));
+                    while let Some(tx) = tx_stream.next().await {
+                        queue.push(tx).await;
+                    }
+                    queue.close();
+                }
+                Err(e) => {
+                    let _ = subscribed_tx.send(Err(e.to_string()));
+                }
+            }
+        });
+
+        subscribed_rx.await.unwrap_or_else(|_| Err("subscription task ended".to_string()))
+    }
+}
+
+#[async_trait]
+impl MempoolSource for NodeMempool {
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    async fn subscribe(&self) -> Result<TxStream, String> {
+        // Prefer full tx bodies in the notification, skipping the hash -> body round-trip per tx
+        if self.full_bodies {
+            let queue = Arc::new(PendingQueue::new(self.queue_capacity, self.policy));
+            match self.subscribe_full_bodies(queue.clone()).await {
+                Ok(()) => {
+                    info!("📡 Subscribed to full-body pending transactions");
+                    return Ok(Box::pin(queue.into_stream()));
+                }
+                Err(e) => {
+                    warn!("⚠️ Full-body pending subscription rejected ({}), falling back to hash stream", e);
+                }
+            }
+        }
+
+        // Subscribe to pending transaction hashes and fetch each body
+        let hash_queue = Arc::new(PendingQueue::new(self.queue_capacity, self.policy));
+        let (subscribed_tx, subscribed_rx) = oneshot::channel();
+        let feeder_provider = self.provider.clone();
+        let feeder_queue = hash_queue.clone();
+        tokio::spawn(async move {
+            let mut tx_hash_stream = match feeder_provider.subscribe_pending_txs().await {
+                Ok(tx_hash_stream) => tx_hash_stream,
+                Err(e) => {
+                    let _ = subscribed_tx.send(Err(e.to_string()));
+                    return;
+                }
+            };
+            let _ = subscribed_tx.send(Ok(()));
+            while let Some(tx_hash) = tx_hash_stream.next().await {
+                feeder_queue.push(tx_hash).await;
+            }
+            feeder_queue.close();
+        });
+        subscribed_rx.await.unwrap_or_else(|_| Err("subscription task ended".to_string()))?;
+
+        let fetch_provider = self.provider.clone();
+        let tx_stream = hash_queue
+            .into_stream()
+            .map(move |tx_hash| {
+                let provider = fetch_provider.clone();
+                async move {
+                    match provider.get_transaction(tx_hash).await {
+                        Ok(tx) => tx,
+                        Err(_) => {
+                            metrics().rpc_errors.with_label_values(&["get_transaction"]).inc();
+                            None
+                        }
+                    }
+                }
+            })
+            .buffer_unordered(self.fetch_concurrency)
+            .filter_map(future::ready);
+        Ok(Box::pin(tx_stream))
+    }
+}
+
+/// bloXroute's `newTxs` feed, which sees txs from its relay network before
+/// they reach the public mempool.
+pub struct BloxrouteMempool {
+    url: String,
+    auth_header: Option<String>,
+    queue_capacity: usize,
+    policy: BackpressurePolicy,
+}
+
+impl BloxrouteMempool {
+    pub fn new(url: String, auth_header: Option<String>, queue_capacity: usize, policy: BackpressurePolicy) -> Self {
+        Self {
+            url,
+            auth_header,
+            queue_capacity,
+            policy,
+        }
+    }
+
+    pub fn from_settings(url: String, auth_header: Option<String>, settings: &Settings) -> Self {
+        Self::new(url, auth_header, settings.tx_queue_capacity, settings.backpressure_policy)
+    }
+}
+
+#[async_trait]
+impl MempoolSource for BloxrouteMempool {
+    fn name(&self) -> &'static str {
+        "bloxroute"
+    }
+
+    async fn subscribe(&self) -> Result<TxStream, String> {
+        let mut request = self.url.as_str().into_client_request().map_err(|e| e.to_string())?;
+        if let Some(auth) = &self.auth_header {
+            request.headers_mut().insert("Authorization", auth.parse().map_err(|_| "invalid auth header".to_string())?);
+        }
+        let (mut ws, _) = tokio_tungstenite::connect_async(request)
+            .await
+            .map_err(|e| format!("could not connect to {}: {}", self.url, e))?;
+
+        let subscribe = json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "subscribe",
+            "params": ["newTxs", { "include": ["tx_hash", "tx_contents"] }],
+        });
+        ws.send(Message::Text(subscribe.to_string())).await.map_err(|e| e.to_string())?;
+
+        // The gateway answers the subscribe request before any notification
+        loop {
+            let reply = match ws.next().await {
+                Some(Ok(Message::Text(text))) => text,
+                Some(Ok(_)) => continue,
+                Some(Err(e)) => return Err(e.to_string()),
+                None => return Err("stream closed before subscribing".to_string()),
+            };
+            let reply: Value = serde_json::from_str(&reply).map_err(|e| e.to_string())?;
+            if let Some(error) = reply.get("error") {
+                return Err(format!("subscribe rejected: {}", error));
+            }
+            if reply.get("id") == Some(&json!(1)) {
+                break;
+            }
+        }
+        info!("📡 Subscribed to bloXroute newTxs at {}", self.url);
+
+        let queue = Arc::new(PendingQueue::new(self.queue_capacity, self.policy));
+        let feeder_queue = queue.clone();
+        tokio::spawn(async move {
+            while let Some(message) = ws.next().await {
+                match message {
+                    Ok(Message::Text(text)) => {
+                        if let Some(tx) = parse_bloxroute_notification(&text) {
+                            feeder_queue.push(tx).await;
+                        }
+                    }
+                    Ok(Message::Close(_)) | Err(_) => break,
+                    Ok(_) => {}
+                }
+            }
+            feeder_queue.close();
+        });
+        Ok(Box::pin(queue.into_stream()))
+    }
+}
+
+/// The pending tx in a `newTxs` notification. bloXroute sends the hash beside
+/// the contents rather than in them, so it's moved back in.
+pub fn parse_bloxroute_notification(message: &str) -> Option<Transaction> {
+    let message: Value = serde_json::from_str(message).ok()?;
+    let result = message.get("params")?.get("result")?;
+    let mut contents = result.get("txContents")?.clone();
+    if contents.get("hash").is_none() {
+        contents.as_object_mut()?.insert("hash".to_string(), result.get("txHash")?.clone());
+    }
+    serde_json::from_value(contents).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sources_parse_by_name() {
+        assert_eq!("bloxroute".parse::<MempoolSourceKind>(), Ok(MempoolSourceKind::Bloxroute));
+        assert_eq!("replay".parse::<MempoolSourceKind>(), Ok(MempoolSourceKind::Replay));
+        assert!("flashbots".parse::<MempoolSourceKind>().is_err());
+    }
+
+    #[test]
+    fn bloxroute_notifications_get_their_hash_back() {
+        let tx = Transaction { hash: H256::from_low_u64_be(7), nonce: 3.into(), ..Default::default() };
+        let mut contents = serde_json::to_value(&tx).unwrap();
+        contents.as_object_mut().unwrap().remove("hash");
+        let message = serde_json::json!({
+            "jsonrpc": "2.0",
+            "method": "subscribe",
+            "params": { "subscription": "0x1", "result": { "txHash": tx.hash, "txContents": contents } },
+        });
+
+        let parsed = parse_bloxroute_notification(&message.to_string()).unwrap();
+        assert_eq!((parsed.hash, parsed.nonce), (tx.hash, tx.nonce));
+        assert_eq!(parse_bloxroute_notification(r#"{"id":1,"result":"0x1"}"#), None);
+    }
+
+    /// A node that accepts only the full-body pending subscription, then
+    /// notifies `tx` on it.
+    async fn full_body_node(tx: Value) -> String {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("ws://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut ws = tokio_tungstenite::accept_async(stream).await.unwrap();
+            while let Some(Ok(Message::Text(text))) = ws.next().await {
+                let request: Value = serde_json::from_str(&text).unwrap();
+                if request["method"] != "eth_subscribe" || request["params"] != json!(["newPendingTransactions", true]) {
+                    continue;
+                }
+                let subscribed = json!({ "jsonrpc": "2.0", "id": request["id"], "result": "0xabc" });
+                ws.send(Message::Text(subscribed.to_string())).await.unwrap();
+                let notification = json!({
+                    "jsonrpc": "2.0",
+                    "method": "eth_subscription",
+                    "params": { "subscription": "0xabc", "result": tx },
+                });
+                ws.send(Message::Text(notification.to_string())).await.unwrap();
+            }
+        });
+        url
+    }
+
+    #[tokio::test]
+    async fn full_body_notifications_arrive_as_transactions() {
+        // A pending swapExactTokensForTokens as Geth sends it, with no block fields yet
+        let hash: H256 = "0x5c504ed432cb51138bcf09aa5e8a410dd4a1e204ef84bfed1be16dfba1b22060".parse().unwrap();
+        let tx = json!({
+            "blockHash": null,
+            "blockNumber": null,
+            "transactionIndex": null,
+            "from": "0x2c7536e3605d9c16a7a3d7b1898e529396a65c23",
+            "to": "0x7a250d5630b4cf539739df2c5dacb4c659f2488d",
+            "gas": "0x30d40",
+            "gasPrice": "0x4a817c800",
+            "maxFeePerGas": "0x4a817c800",
+            "maxPriorityFeePerGas": "0x3b9aca00",
+            "hash": hash,
+            "input": "0x38ed1739",
+            "nonce": "0x2a",
+            "value": "0x0",
+            "type": "0x2",
+            "accessList": [],
+            "chainId": "0x1",
+            "v": "0x1",
+            "r": "0x3b08715b4403c792b8c7567edea634088bedcd7f60d9352b1f16c69830f3afd5",
+            "s": "0x10b9afb67d2ec8b956f0e1dbc07eb79152904f3a7bf789fc869db56320adfe09",
+        });
+        let provider = Arc::new(Provider::<Ws>::connect(full_body_node(tx).await).await.unwrap());
+        let source = NodeMempool::from_settings(provider, &Settings { full_pending_txs: true, ..Settings::default() });
+
+        let mut stream = source.subscribe().await.unwrap();
+        let received = stream.next().await.unwrap();
+        assert_eq!(received.hash, hash);
+        assert_eq!(received.to, Some("0x7a250d5630b4cf539739df2c5dacb4c659f2488d".parse().unwrap()));
+        assert_eq!(received.nonce, U256::from(42));
+        assert_eq!(received.max_fee_per_gas, Some(U256::from(20_000_000_000u64)));
+        assert_eq!(received.input.to_vec(), vec![0x38, 0xed, 0x17, 0x39]);
+        assert_eq!(received.block_number, None);
+    }
+}
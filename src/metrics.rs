@@ -0,0 +1,222 @@
+use std::sync::OnceLock;
+
+use prometheus::{
+    Counter, Encoder, Histogram, HistogramOpts, IntCounter, IntCounterVec, IntGauge, IntGaugeVec, Opts, Registry,
+    TextEncoder,
+};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+use tracing::{info, warn};
+
+use crate::settings::Settings;
+
+static METRICS: OnceLock<Metrics> = OnceLock::new();
+
+/// Default profit histogram buckets, in ETH.
+pub const DEFAULT_PROFIT_BUCKETS: [f64; 8] = [0.001, 0.01, 0.05, 0.1, 0.25, 0.5, 1.0, 5.0];
+
+/// Prometheus metrics exported by the bot.
+#[derive(Debug)]
+pub struct Metrics {
+    registry: Registry,
+    pub estimated_profit_eth: Histogram,
+    pub realized_profit_eth: Histogram,
+    pub pending_tx_queue_depth: IntGauge,
+    pub pending_tx_dropped: IntCounter,
+    pub pending_tx_queue_lag_seconds: Histogram,
+    pub pending_tx_blocked_seconds: Counter,
+    pub pending_txs_seen: IntCounter,
+    /// Time from first seeing an opportunity's victim to submitting it.
+    pub submission_latency_seconds: Histogram,
+    /// Labeled by `StrategyType::name`.
+    pub opportunities_found: IntCounterVec,
+    pub bundles_submitted: IntCounter,
+    pub bundles_landed: IntCounter,
+    pub gas_wars: IntCounter,
+    /// Labeled by the failing call, e.g. `get_transaction` or `eth_sendBundle`.
+    pub rpc_errors: IntCounterVec,
+    /// RPC calls waiting for the request budget, labeled by `RequestPriority::name`.
+    pub rpc_budget_queued: IntGaugeVec,
+}
+
+impl Metrics {
+    pub fn new(profit_buckets: Vec<f64>) -> Self {
+        let registry = Registry::new();
+
+        let estimated_profit_eth = Histogram::with_opts(
+            HistogramOpts::new("mev_estimated_profit_eth", "Estimated net profit per opportunity, in ETH")
+                .buckets(profit_buckets.clone()),
+        )
+        .unwrap();
+        let realized_profit_eth = Histogram::with_opts(
+            HistogramOpts::new("mev_realized_profit_eth", "Realized profit per landed opportunity, in ETH")
+                .buckets(profit_buckets),
+        )
+        .unwrap();
+
+        let pending_tx_queue_depth =
+            IntGauge::new("mev_pending_tx_queue_depth", "Pending txs waiting to be fetched or analyzed").unwrap();
+        let pending_tx_dropped =
+            IntCounter::new("mev_pending_tx_dropped_total", "Pending txs evicted from a saturated queue").unwrap();
+        let pending_tx_queue_lag_seconds = Histogram::with_opts(
+            HistogramOpts::new("mev_pending_tx_queue_lag_seconds", "Time a pending tx spent queued")
+                .buckets(vec![0.001, 0.005, 0.01, 0.05, 0.1, 0.5, 1.0, 5.0]),
+        )
+        .unwrap();
+        let pending_tx_blocked_seconds = Counter::new(
+            "mev_pending_tx_blocked_seconds_total",
+            "Time the subscription reader spent blocked on a full queue",
+        )
+        .unwrap();
+
+        let pending_txs_seen = IntCounter::new("mev_pending_txs_seen_total", "Unique pending txs received").unwrap();
+        let submission_latency_seconds = Histogram::with_opts(
+            HistogramOpts::new("mev_submission_latency_seconds", "Time from seeing a victim to submitting the opportunity")
+                .buckets(vec![0.01, 0.05, 0.1, 0.25, 0.5, 1.0, 2.0, 5.0, 12.0]),
+        )
+        .unwrap();
+        let opportunities_found = IntCounterVec::new(
+            Opts::new("mev_opportunities_found_total", "Opportunities that passed simulation, per strategy"),
+            &["strategy"],
+        )
+        .unwrap();
+        let bundles_submitted =
+            IntCounter::new("mev_bundles_submitted_total", "Bundles accepted by the relay").unwrap();
+        let bundles_landed = IntCounter::new("mev_bundles_landed_total", "Bundles included on-chain").unwrap();
+        let gas_wars = IntCounter::new("mev_gas_wars_total", "Pools whose pending bids escalated into a gas war").unwrap();
+        let rpc_errors =
+            IntCounterVec::new(Opts::new("mev_rpc_errors_total", "Failed RPC and relay calls"), &["method"]).unwrap();
+
+        registry.register(Box::new(estimated_profit_eth.clone())).unwrap();
+        registry.register(Box::new(realized_profit_eth.clone())).unwrap();
+        registry.register(Box::new(pending_tx_queue_depth.clone())).unwrap();
+        registry.register(Box::new(pending_tx_dropped.clone())).unwrap();
+        registry.register(Box::new(pending_tx_queue_lag_seconds.clone())).unwrap();
+        registry.register(Box::new(pending_tx_blocked_seconds.clone())).unwrap();
+        registry.register(Box::new(pending_txs_seen.clone())).unwrap();
+        registry.register(Box::new(submission_latency_seconds.clone())).unwrap();
+        registry.register(Box::new(opportunities_found.clone())).unwrap();
+        registry.register(Box::new(bundles_submitted.clone())).unwrap();
+        registry.register(Box::new(bundles_landed.clone())).unwrap();
+        registry.register(Box::new(gas_wars.clone())).unwrap();
+        let rpc_budget_queued = IntGaugeVec::new(
+            Opts::new("mev_rpc_budget_queued", "RPC calls waiting for the requests-per-second budget"),
+            &["priority"],
+        )
+        .unwrap();
+
+        registry.register(Box::new(rpc_errors.clone())).unwrap();
+        registry.register(Box::new(rpc_budget_queued.clone())).unwrap();
+
+        Self {
+            registry,
+            estimated_profit_eth,
+            realized_profit_eth,
+            pending_tx_queue_depth,
+            pending_tx_dropped,
+            pending_tx_queue_lag_seconds,
+            pending_tx_blocked_seconds,
+            pending_txs_seen,
+            submission_latency_seconds,
+            opportunities_found,
+            bundles_submitted,
+            bundles_landed,
+            gas_wars,
+            rpc_errors,
+            rpc_budget_queued,
+        }
+    }
+
+    /// Renders every registered metric in the Prometheus text format.
+    pub fn render(&self) -> String {
+        let mut buffer = Vec::new();
+        TextEncoder::new().encode(&self.registry.gather(), &mut buffer).unwrap();
+        String::from_utf8(buffer).unwrap_or_default()
+    }
+}
+
+/// Initializes the global metrics from settings. Later calls are no-ops.
+pub fn init(settings: &Settings) {
+    let _ = METRICS.set(Metrics::new(settings.profit_histogram_buckets.clone()));
+}
+
+/// Global metrics handle, falling back to default buckets if `init` was never called.
+pub fn metrics() -> &'static Metrics {
+    METRICS.get_or_init(|| Metrics::new(DEFAULT_PROFIT_BUCKETS.to_vec()))
+}
+
+/// Converts a wei amount to (lossy) ETH for histogram observations.
+pub fn wei_to_eth(wei: ethers::types::U256) -> f64 {
+    ethers::utils::format_ether(wei).parse().unwrap_or_default()
+}
+
+/// Serves `/metrics` for Prometheus scrapes on `port`.
+pub async fn serve(port: u16) {
+    let listener = match TcpListener::bind(("0.0.0.0", port)).await {
+        Ok(listener) => listener,
+        Err(e) => {
+            warn!("Could not start metrics endpoint on port {}, err: {}", port, e);
+            return;
+        }
+    };
+
+    info!("📈 Metrics endpoint listening on :{}/metrics", port);
+    serve_on(listener).await;
+}
+
+async fn serve_on(listener: TcpListener) {
+    loop {
+        let Ok((mut socket, _)) = listener.accept().await else {
+            continue;
+        };
+
+        tokio::spawn(async move {
+            // Every path gets the metrics page; the request itself is not inspected
+            let mut request = [0u8; 1024];
+            let _ = socket.read(&mut request).await;
+
+            let body = metrics().render();
+            let response = format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                body.len(),
+                body
+            );
+            let _ = socket.write_all(response.as_bytes()).await;
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn profit_histograms_use_the_configured_buckets() {
+        let metrics = Metrics::new(vec![0.5, 2.0]);
+        metrics.estimated_profit_eth.observe(wei_to_eth(ethers::utils::parse_ether("0.75").unwrap()));
+
+        let rendered = metrics.render();
+        assert!(rendered.contains("mev_estimated_profit_eth_bucket{le=\"0.5\"} 0"), "{}", rendered);
+        assert!(rendered.contains("mev_estimated_profit_eth_bucket{le=\"2\"} 1"), "{}", rendered);
+        assert!(rendered.contains("mev_estimated_profit_eth_sum 0.75"), "{}", rendered);
+    }
+
+    #[tokio::test]
+    async fn the_endpoint_serves_the_global_counters() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/metrics", listener.local_addr().unwrap());
+        tokio::spawn(serve_on(listener));
+
+        // Labels no other test uses, as the counters are process-wide
+        metrics().opportunities_found.with_label_values(&["scrape_test"]).inc_by(3);
+        metrics().rpc_errors.with_label_values(&["scrape_test"]).inc();
+
+        let response = reqwest::get(&url).await.unwrap();
+        assert!(response.status().is_success());
+        assert_eq!(response.headers()["content-type"], "text/plain; version=0.0.4");
+        let body = response.text().await.unwrap();
+        assert!(body.contains("mev_opportunities_found_total{strategy=\"scrape_test\"} 3"), "{}", body);
+        assert!(body.contains("mev_rpc_errors_total{method=\"scrape_test\"} 1"), "{}", body);
+        assert!(body.contains("# TYPE mev_pending_txs_seen_total counter"), "{}", body);
+    }
+}
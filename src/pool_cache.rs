@@ -0,0 +1,159 @@
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+
+use ethers::prelude::{*};
+
+use crate::address_book::LpPair;
+use crate::block_height::BlockHeight;
+use crate::signer::SignerClient;
+
+/// A V2 pair's reserves, in the pair's own token order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PoolReserves {
+    pub token0: Address,
+    pub reserve0: U256,
+    pub reserve1: U256,
+}
+
+impl PoolReserves {
+    /// Reserves as `(reserve_in, reserve_out)` for a swap selling `token_in`.
+    pub fn oriented(&self, token_in: Address) -> (U256, U256) {
+        if token_in == self.token0 {
+            (self.reserve0, self.reserve1)
+        } else {
+            (self.reserve1, self.reserve0)
+        }
+    }
+}
+
+/// V2 pair reserves, read at most once per pool per block.
+///
+/// Entries are tagged with the block they were read at and only served for
+/// that block; the block scanner drops older ones as the chain advances.
+#[derive(Debug)]
+pub struct PoolCache {
+    provider: Arc<SignerClient>,
+    block_height: Arc<BlockHeight>,
+    reserves: RwLock<HashMap<Address, (U64, PoolReserves)>>,
+    /// token0 never changes, so it outlives the reserves it was read with.
+    token0s: RwLock<HashMap<Address, Address>>,
+}
+
+impl PoolCache {
+    pub fn new(provider: Arc<SignerClient>, block_height: Arc<BlockHeight>) -> Self {
+        Self {
+            provider,
+            block_height,
+            reserves: RwLock::new(HashMap::new()),
+            token0s: RwLock::new(HashMap::new()),
+        }
+    }
+
+    /// Reserves of `pair` at the current block, from the cache if already read this block.
+    pub async fn reserves(&self, pair: Address) -> Option<PoolReserves> {
+        let block = self.block_height.get();
+        if let Some(reserves) = self.get(pair, block) {
+            return Some(reserves);
+        }
+
+        let contract = LpPair::new(pair, self.provider.clone());
+        let known_token0 = self.token0s.read().unwrap().get(&pair).copied();
+        let token0 = match known_token0 {
+            Some(token0) => token0,
+            None => {
+                let token0 = contract.token_0().call().await.ok()?;
+                self.token0s.write().unwrap().insert(pair, token0);
+                token0
+            }
+        };
+        let (reserve0, reserve1, _) = contract.get_reserves().call().await.ok()?;
+
+        let reserves = PoolReserves {
+            token0,
+            reserve0: U256::from(reserve0),
+            reserve1: U256::from(reserve1),
+        };
+        self.insert(pair, block, reserves);
+        Some(reserves)
+    }
+
+    /// Cached reserves of `pair`, only if they were read at `block`.
+    pub fn get(&self, pair: Address, block: U64) -> Option<PoolReserves> {
+        self.reserves
+            .read()
+            .unwrap()
+            .get(&pair)
+            .filter(|(read_at, _)| *read_at == block)
+            .map(|(_, reserves)| *reserves)
+    }
+
+    pub fn insert(&self, pair: Address, block: U64, reserves: PoolReserves) {
+        let mut cached = self.reserves.write().unwrap();
+        // A slower read from an older block must not overwrite a fresher one
+        if cached.get(&pair).is_none_or(|(read_at, _)| *read_at <= block) {
+            cached.insert(pair, (block, reserves));
+        }
+    }
+
+    /// Drops every entry read before `block`.
+    pub fn advance(&self, block: U64) {
+        self.reserves.write().unwrap().retain(|_, (read_at, _)| *read_at >= block);
+    }
+
+    /// Drops all reserves, e.g. after a reorg replaced the blocks they were read at.
+    pub fn clear(&self) {
+        self.reserves.write().unwrap().clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rpc::RetryHttp;
+    use crate::settings::Settings;
+    use crate::signer::BotSigner;
+
+    /// A cache whose RPC is never reached: these tests only read what they insert.
+    fn cache() -> PoolCache {
+        let http: Http = "http://127.0.0.1:1".parse().unwrap();
+        let wallet: LocalWallet = "4c0883a69102937d6231471b5dbb6204fe5129617082792ae468d01a3f362318".parse().unwrap();
+        let provider = Provider::new(RetryHttp::from_settings(http, &Settings::default()));
+        PoolCache::new(Arc::new(SignerMiddleware::new(provider, BotSigner::new(wallet))), Arc::new(BlockHeight::new(U64::zero())))
+    }
+
+    fn reserves(reserve0: u64) -> PoolReserves {
+        PoolReserves { token0: Address::from_low_u64_be(1), reserve0: U256::from(reserve0), reserve1: U256::from(7) }
+    }
+
+    #[test]
+    fn reserves_are_oriented_by_the_token_sold() {
+        assert_eq!(reserves(5).oriented(Address::from_low_u64_be(1)), (U256::from(5), U256::from(7)));
+        assert_eq!(reserves(5).oriented(Address::from_low_u64_be(2)), (U256::from(7), U256::from(5)));
+    }
+
+    #[test]
+    fn entries_are_only_served_for_the_block_they_were_read_at() {
+        let (cache, pair) = (cache(), Address::from_low_u64_be(0x9001));
+        cache.insert(pair, U64::from(10), reserves(5));
+        assert_eq!(cache.get(pair, U64::from(10)), Some(reserves(5)));
+        assert_eq!(cache.get(pair, U64::from(11)), None);
+
+        // A late read from an older block doesn't replace the fresher one
+        cache.insert(pair, U64::from(11), reserves(6));
+        cache.insert(pair, U64::from(10), reserves(5));
+        assert_eq!(cache.get(pair, U64::from(11)), Some(reserves(6)));
+    }
+
+    #[test]
+    fn advancing_drops_older_entries() {
+        let cache = cache();
+        let (old, new) = (Address::from_low_u64_be(1), Address::from_low_u64_be(2));
+        cache.insert(old, U64::from(10), reserves(5));
+        cache.insert(new, U64::from(11), reserves(6));
+        cache.advance(U64::from(11));
+        assert_eq!(cache.reserves.read().unwrap().len(), 1);
+
+        cache.clear();
+        assert_eq!(cache.get(new, U64::from(11)), None);
+    }
+}
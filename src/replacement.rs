@@ -0,0 +1,149 @@
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use ethers::prelude::*;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReplacementKind {
+    /// Same trade resent with a higher fee.
+    SpeedUp,
+    /// A zero-value self-send that just burns the nonce.
+    Cancel,
+}
+
+/// A pending tx superseded by another from the same sender with the same nonce.
+#[derive(Debug, Clone)]
+pub struct Replacement {
+    pub from: Address,
+    pub nonce: U256,
+    pub replaced: H256,
+    pub replacement: H256,
+    pub kind: ReplacementKind,
+}
+
+#[derive(Debug)]
+struct Seen {
+    hash: H256,
+    fee: U256,
+    seen_at: Instant,
+}
+
+/// Tracks pending txs by `(from, nonce)` and spots when one is replaced, so
+/// anything built around the original can be dropped before it reverts.
+#[derive(Debug)]
+pub struct ReplacementTracker {
+    window: Duration,
+    pending: HashMap<(Address, U256), Seen>,
+    /// Every pending tx lands here, so expiry sweeps run at most once a second.
+    last_prune: Instant,
+}
+
+impl ReplacementTracker {
+    pub fn new(window: Duration) -> Self {
+        Self {
+            window,
+            pending: HashMap::new(),
+            last_prune: Instant::now(),
+        }
+    }
+
+    /// Records `tx`, returning the replacement if it supersedes a tx seen earlier.
+    /// A same-nonce tx paying less arrived out of order and is ignored; the node
+    /// would have rejected it as an underpriced replacement.
+    pub fn observe(&mut self, tx: &Transaction) -> Option<Replacement> {
+        self.prune();
+        let fee = bid(tx);
+        let key = (tx.from, tx.nonce);
+        if let Some(seen) = self.pending.get(&key) {
+            if seen.hash == tx.hash || fee < seen.fee {
+                return None;
+            }
+        }
+
+        let previous = self.pending.insert(key, Seen { hash: tx.hash, fee, seen_at: Instant::now() })?;
+        Some(Replacement {
+            from: tx.from,
+            nonce: tx.nonce,
+            replaced: previous.hash,
+            replacement: tx.hash,
+            kind: if is_cancellation(tx) { ReplacementKind::Cancel } else { ReplacementKind::SpeedUp },
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.pending.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pending.is_empty()
+    }
+
+    fn prune(&mut self) {
+        if self.last_prune.elapsed() < Duration::from_secs(1) {
+            return;
+        }
+        self.last_prune = Instant::now();
+        let window = self.window;
+        self.pending.retain(|_, seen| seen.seen_at.elapsed() <= window);
+    }
+}
+
+/// Wallets cancel by sending nothing to themselves on the nonce to free.
+pub fn is_cancellation(tx: &Transaction) -> bool {
+    tx.to == Some(tx.from) && tx.value.is_zero() && tx.input.is_empty()
+}
+
+/// What the tx offers per gas: its fee cap if EIP-1559, else its gas price.
+fn bid(tx: &Transaction) -> U256 {
+    tx.max_fee_per_gas.or(tx.gas_price).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tx(hash: u64, nonce: u64, gas_price: u64) -> Transaction {
+        Transaction {
+            hash: H256::from_low_u64_be(hash),
+            from: Address::from_low_u64_be(0xa11ce),
+            to: Some(Address::from_low_u64_be(0x7a11)),
+            nonce: U256::from(nonce),
+            gas_price: Some(U256::from(gas_price)),
+            input: vec![0x38, 0xed].into(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn a_higher_fee_on_the_same_nonce_is_a_speed_up() {
+        let mut tracker = ReplacementTracker::new(Duration::from_secs(60));
+        assert!(tracker.observe(&tx(1, 7, 10)).is_none());
+        assert!(tracker.observe(&tx(2, 8, 10)).is_none());
+
+        let replacement = tracker.observe(&tx(3, 7, 11)).unwrap();
+        assert_eq!(replacement.replaced, H256::from_low_u64_be(1));
+        assert_eq!(replacement.replacement, H256::from_low_u64_be(3));
+        assert_eq!(replacement.kind, ReplacementKind::SpeedUp);
+        assert_eq!(tracker.len(), 2);
+    }
+
+    #[test]
+    fn repeats_and_underpriced_late_arrivals_are_ignored() {
+        let mut tracker = ReplacementTracker::new(Duration::from_secs(60));
+        tracker.observe(&tx(1, 7, 10));
+        assert!(tracker.observe(&tx(1, 7, 10)).is_none());
+        assert!(tracker.observe(&tx(2, 7, 9)).is_none());
+        assert!(tracker.observe(&tx(3, 7, 12)).is_some_and(|r| r.replaced == H256::from_low_u64_be(1)));
+    }
+
+    #[test]
+    fn a_zero_value_self_send_is_a_cancel() {
+        let mut tracker = ReplacementTracker::new(Duration::from_secs(60));
+        tracker.observe(&tx(1, 7, 10));
+        let mut cancel = tx(2, 7, 20);
+        cancel.to = Some(cancel.from);
+        cancel.input = Bytes::default();
+        assert!(is_cancellation(&cancel));
+        assert_eq!(tracker.observe(&cancel).unwrap().kind, ReplacementKind::Cancel);
+    }
+}
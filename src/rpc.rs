@@ -0,0 +1,382 @@
+use std::fmt::Debug;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+use ethers::providers::{Http, JsonRpcClient, RpcError};
+use serde::{de::DeserializeOwned, Serialize};
+use tracing::{info, warn};
+
+use crate::metrics::metrics;
+use crate::settings::Settings;
+
+/// The HTTP transport behind `Config::http`: retries and fails over across
+/// endpoints, each paced by its own request budget.
+pub type RetryHttp = FailoverClient<BudgetedProvider<Http>>;
+
+/// Retries transient RPC failures with exponential backoff, rotating to the next
+/// endpoint after repeated failures on the current one.
+///
+/// Only transport-level failures are retried. Anything the node actually answered
+/// with (a revert, a bad nonce, ...) is returned straight away.
+#[derive(Debug)]
+pub struct FailoverClient<C> {
+    endpoints: Vec<C>,
+    /// Index of the endpoint requests go to.
+    active: AtomicUsize,
+    max_retries: u32,
+    initial_backoff: Duration,
+    /// Consecutive failures on one endpoint before moving to the next.
+    failover_after: u32,
+}
+
+impl<C> FailoverClient<C> {
+    /// Requests go to `endpoints[0]` until it keeps failing. Panics if `endpoints` is empty.
+    pub fn new(endpoints: Vec<C>, max_retries: u32, initial_backoff: Duration, failover_after: u32) -> Self {
+        assert!(!endpoints.is_empty(), "FailoverClient needs at least one endpoint");
+        Self {
+            endpoints,
+            active: AtomicUsize::new(0),
+            max_retries,
+            initial_backoff,
+            failover_after: failover_after.max(1),
+        }
+    }
+
+    /// The endpoint requests currently go to.
+    pub fn active(&self) -> &C {
+        &self.endpoints[self.active.load(Ordering::Relaxed)]
+    }
+
+    /// Moves on from endpoint `from`, unless another request already did.
+    fn fail_over(&self, from: usize) {
+        let next = (from + 1) % self.endpoints.len();
+        if self.active.compare_exchange(from, next, Ordering::Relaxed, Ordering::Relaxed).is_ok() {
+            info!("🔁 RPC endpoint #{} keeps failing, failing over to #{}", from, next);
+        }
+    }
+}
+
+impl RetryHttp {
+    /// `primary` followed by the configured fallback URLs, with the configured retry policy.
+    pub fn from_settings(primary: Http, settings: &Settings) -> Self {
+        let mut endpoints = vec![BudgetedProvider::from_settings(primary, settings)];
+        for url in &settings.rpc_fallback_urls {
+            match url.parse::<Http>() {
+                Ok(http) => endpoints.push(BudgetedProvider::from_settings(http, settings)),
+                Err(e) => warn!("⚠️ Ignoring invalid fallback RPC {}: {}", url, e),
+            }
+        }
+        Self::new(
+            endpoints,
+            settings.rpc_max_retries,
+            Duration::from_millis(settings.rpc_backoff_ms),
+            settings.rpc_failover_after,
+        )
+    }
+
+    pub fn url(&self) -> &reqwest::Url {
+        self.active().inner().url()
+    }
+}
+
+#[async_trait]
+impl<C> JsonRpcClient for FailoverClient<C>
+where
+    C: JsonRpcClient,
+{
+    type Error = C::Error;
+
+    async fn request<T, R>(&self, method: &str, params: T) -> Result<R, Self::Error>
+    where
+        T: Debug + Serialize + Send + Sync,
+        R: DeserializeOwned + Send,
+    {
+        let mut failures_here = 0;
+        let mut attempt = 0;
+        loop {
+            let index = self.active.load(Ordering::Relaxed);
+            let error = match self.endpoints[index].request(method, &params).await {
+                Ok(response) => return Ok(response),
+                // The node answered; asking again won't change its mind
+                Err(e) if e.as_error_response().is_some() => return Err(e),
+                Err(e) => e,
+            };
+
+            if attempt >= self.max_retries {
+                return Err(error);
+            }
+            failures_here += 1;
+            if failures_here >= self.failover_after && self.endpoints.len() > 1 {
+                self.fail_over(index);
+                failures_here = 0;
+            }
+            tokio::time::sleep(self.initial_backoff * 2u32.saturating_pow(attempt)).await;
+            attempt += 1;
+        }
+    }
+}
+
+/// Methods on the path that gets our own transactions out; everything else is analysis.
+const SUBMISSION_METHODS: &[&str] = &[
+    "eth_sendRawTransaction",
+    "eth_sendTransaction",
+    "eth_sendPrivateTransaction",
+    "eth_getTransactionCount",
+];
+
+/// Which queue an RPC call waits in when the budget is spent.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RequestPriority {
+    /// Sending our transactions; served before any waiting analysis call.
+    Submission,
+    /// Reading state to find and size opportunities; dropped when too many are queued.
+    Analysis,
+}
+
+impl RequestPriority {
+    pub fn of(method: &str) -> Self {
+        if SUBMISSION_METHODS.contains(&method) {
+            RequestPriority::Submission
+        } else {
+            RequestPriority::Analysis
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            RequestPriority::Submission => "submission",
+            RequestPriority::Analysis => "analysis",
+        }
+    }
+}
+
+/// Token bucket of `rate` requests per second, holding up to one second's worth.
+/// Callers over budget wait for a token instead of failing; analysis calls wait
+/// behind any queued submission call, so they are paced, never rejected.
+#[derive(Debug)]
+pub struct RequestBudget {
+    rate: f64,
+    burst: f64,
+    state: Mutex<BudgetState>,
+}
+
+#[derive(Debug)]
+struct BudgetState {
+    tokens: f64,
+    refilled: Instant,
+    submissions_queued: usize,
+    analysis_queued: usize,
+}
+
+impl RequestBudget {
+    pub fn new(requests_per_second: f64) -> Self {
+        let burst = requests_per_second.max(1.0);
+        Self {
+            rate: requests_per_second,
+            burst,
+            state: Mutex::new(BudgetState {
+                tokens: burst,
+                refilled: Instant::now(),
+                submissions_queued: 0,
+                analysis_queued: 0,
+            }),
+        }
+    }
+
+    /// Waits for a token.
+    pub async fn acquire(&self, priority: RequestPriority) {
+        let mut slot: Option<QueueSlot> = None;
+        loop {
+            let wait = {
+                let mut state = self.state.lock().unwrap();
+                let now = Instant::now();
+                state.tokens = (state.tokens + now.duration_since(state.refilled).as_secs_f64() * self.rate).min(self.burst);
+                state.refilled = now;
+
+                // A queued submission call goes first, even if this one got here earlier
+                let yields = priority == RequestPriority::Analysis && state.submissions_queued > 0;
+                if state.tokens >= 1.0 && !yields {
+                    state.tokens -= 1.0;
+                    return;
+                }
+                if slot.is_none() {
+                    slot = Some(QueueSlot::enter(self, &mut state, priority));
+                }
+                Duration::from_secs_f64(((1.0 - state.tokens) / self.rate).max(0.0)).max(Duration::from_millis(1))
+            };
+            tokio::time::sleep(wait).await;
+        }
+    }
+
+    /// Calls currently waiting for a token.
+    pub fn queued(&self, priority: RequestPriority) -> usize {
+        let state = self.state.lock().unwrap();
+        match priority {
+            RequestPriority::Submission => state.submissions_queued,
+            RequestPriority::Analysis => state.analysis_queued,
+        }
+    }
+}
+
+/// A caller's place in the budget queue, given up when it gets a token or is cancelled.
+struct QueueSlot<'a> {
+    budget: &'a RequestBudget,
+    priority: RequestPriority,
+}
+
+impl<'a> QueueSlot<'a> {
+    fn enter(budget: &'a RequestBudget, state: &mut BudgetState, priority: RequestPriority) -> Self {
+        match priority {
+            RequestPriority::Submission => state.submissions_queued += 1,
+            RequestPriority::Analysis => state.analysis_queued += 1,
+        }
+        metrics().rpc_budget_queued.with_label_values(&[priority.name()]).inc();
+        Self { budget, priority }
+    }
+}
+
+impl Drop for QueueSlot<'_> {
+    fn drop(&mut self) {
+        let mut state = self.budget.state.lock().unwrap();
+        match self.priority {
+            RequestPriority::Submission => state.submissions_queued -= 1,
+            RequestPriority::Analysis => state.analysis_queued -= 1,
+        }
+        metrics().rpc_budget_queued.with_label_values(&[self.priority.name()]).dec();
+    }
+}
+
+/// Paces calls to `inner` through a `RequestBudget`; without one, calls pass straight through.
+#[derive(Debug)]
+pub struct BudgetedProvider<C> {
+    inner: C,
+    budget: Option<RequestBudget>,
+}
+
+impl<C> BudgetedProvider<C> {
+    pub fn new(inner: C, budget: Option<RequestBudget>) -> Self {
+        Self { inner, budget }
+    }
+
+    /// Budgeted at `RPC_REQUESTS_PER_SECOND`, or unlimited if that is 0.
+    pub fn from_settings(inner: C, settings: &Settings) -> Self {
+        let budget = (settings.rpc_requests_per_second > 0.0)
+            .then(|| RequestBudget::new(settings.rpc_requests_per_second));
+        Self::new(inner, budget)
+    }
+
+    pub fn inner(&self) -> &C {
+        &self.inner
+    }
+
+    pub fn budget(&self) -> Option<&RequestBudget> {
+        self.budget.as_ref()
+    }
+}
+
+#[async_trait]
+impl<C> JsonRpcClient for BudgetedProvider<C>
+where
+    C: JsonRpcClient + 'static,
+{
+    type Error = C::Error;
+
+    async fn request<T, R>(&self, method: &str, params: T) -> Result<R, Self::Error>
+    where
+        T: Debug + Serialize + Send + Sync,
+        R: DeserializeOwned + Send,
+    {
+        if let Some(budget) = &self.budget {
+            budget.acquire(RequestPriority::of(method)).await;
+        }
+        self.inner.request(method, params).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethers::providers::{JsonRpcError, MockProvider, MockResponse};
+    use ethers::types::U64;
+
+    fn failover(endpoints: Vec<MockProvider>, max_retries: u32, failover_after: u32) -> FailoverClient<MockProvider> {
+        FailoverClient::new(endpoints, max_retries, Duration::ZERO, failover_after)
+    }
+
+    #[test]
+    fn only_sending_our_txs_is_a_submission() {
+        assert_eq!(RequestPriority::of("eth_sendRawTransaction"), RequestPriority::Submission);
+        assert_eq!(RequestPriority::of("eth_getTransactionCount"), RequestPriority::Submission);
+        assert_eq!(RequestPriority::of("eth_call"), RequestPriority::Analysis);
+        assert_eq!(RequestPriority::of("eth_getStorageAt"), RequestPriority::Analysis);
+    }
+
+    #[tokio::test]
+    async fn budget_serves_a_burst_then_paces() {
+        let budget = RequestBudget::new(100.0);
+        let start = Instant::now();
+        for _ in 0..100 {
+            budget.acquire(RequestPriority::Analysis).await;
+        }
+        assert!(start.elapsed() < Duration::from_millis(5));
+
+        // The bucket is empty: the next call waits for a token, about 10ms at 100/s
+        budget.acquire(RequestPriority::Analysis).await;
+        assert!(start.elapsed() >= Duration::from_millis(8));
+    }
+
+    #[tokio::test]
+    async fn cancelled_callers_leave_the_queue() {
+        let budget = RequestBudget::new(1.0);
+        budget.acquire(RequestPriority::Submission).await;
+
+        let mut waiting = Box::pin(budget.acquire(RequestPriority::Analysis));
+        assert!(tokio::time::timeout(Duration::from_millis(5), &mut waiting).await.is_err());
+        assert_eq!(budget.queued(RequestPriority::Analysis), 1);
+
+        drop(waiting);
+        assert_eq!(budget.queued(RequestPriority::Analysis), 0);
+    }
+
+    #[tokio::test]
+    async fn transport_errors_fail_over_to_the_next_endpoint() {
+        // An empty mock fails every call at the transport, never reaching a node
+        let (down, up) = (MockProvider::new(), MockProvider::new());
+        up.push(U64::from(7)).unwrap();
+        let client = failover(vec![down, up], 3, 2);
+
+        let block: U64 = client.request("eth_blockNumber", ()).await.unwrap();
+        assert_eq!(block, U64::from(7));
+        assert_eq!(client.active.load(Ordering::Relaxed), 1);
+    }
+
+    #[tokio::test]
+    async fn transport_errors_give_up_after_max_retries() {
+        let (down, up) = (MockProvider::new(), MockProvider::new());
+        up.push(U64::from(7)).unwrap();
+        // Fails over only after 5 failures, but gives up after the first retry
+        let client = failover(vec![down, up], 1, 5);
+
+        let result: Result<U64, _> = client.request("eth_blockNumber", ()).await;
+        assert!(result.is_err());
+        assert_eq!(client.active.load(Ordering::Relaxed), 0);
+    }
+
+    #[tokio::test]
+    async fn node_errors_are_returned_without_retrying() {
+        let (reverting, up) = (MockProvider::new(), MockProvider::new());
+        reverting.push_response(MockResponse::Error(JsonRpcError {
+            code: 3,
+            message: "execution reverted".into(),
+            data: None,
+        }));
+        up.push(U64::from(7)).unwrap();
+        let client = failover(vec![reverting, up], 3, 1);
+
+        let error = client.request::<_, U64>("eth_call", ()).await.unwrap_err();
+        assert_eq!(error.as_error_response().map(|e| e.code), Some(3));
+        assert_eq!(client.active.load(Ordering::Relaxed), 0);
+    }
+}
@@ -0,0 +1,767 @@
+use std::str::FromStr;
+
+use ethers::prelude::*;
+
+use crate::backpressure::BackpressurePolicy;
+use crate::gas_model::GasEstimates;
+use crate::helpers::env_or;
+use crate::mempool_source::MempoolSourceKind;
+use crate::metrics::DEFAULT_PROFIT_BUCKETS;
+use crate::signer::SignerKind;
+use crate::strategy::{
+    AuthHeader, BidStrategy, BlockSource, FlashloanProvider, GasWarPolicy, OracleFeed, RelayAuth, SettlementPolicy, SigningMode,
+};
+
+/// Runtime knobs for the strategies and executor, read from env vars.
+#[derive(Debug, Clone)]
+pub struct Settings {
+    /// Record a decision trace for every analyzed tx (expensive).
+    pub trace_all: bool,
+    /// Record a decision trace only for this victim tx hash.
+    pub trace_tx: Option<H256>,
+    /// Try `eth_subscribe("newPendingTransactions", true)` before the hash-then-fetch stream.
+    pub full_pending_txs: bool,
+    /// Feed pending txs are read from (`node`, `bloxroute`, `eden`, `replay`).
+    pub mempool_source: MempoolSourceKind,
+    /// Stream endpoint of a private mempool source; bloXroute defaults to its cloud API.
+    pub mempool_source_url: Option<String>,
+    /// `Authorization` header sent to the private mempool source.
+    pub mempool_source_auth: Option<String>,
+    /// Append every pending tx the live source streams to this NDJSON file.
+    pub mempool_record_path: Option<String>,
+    /// Recording the `replay` source plays back.
+    pub mempool_replay_path: Option<String>,
+    /// How many times faster than recorded the `replay` source plays; 0 plays without pauses.
+    pub mempool_replay_speed: f64,
+    /// Keep the victim's output this many bps above their `amountOutMin` when sizing a frontrun.
+    pub victim_safety_margin_bps: u64,
+    /// Largest sandwich frontrun, as bps of the pool's input-side reserve.
+    pub max_frontrun_reserve_bps: u64,
+    /// Memoize CREATE2 pair derivation for hot pairs.
+    pub cache_pair_addresses: bool,
+    /// Tokens whose decimals and symbol are kept in memory.
+    pub token_info_cache_size: usize,
+    /// Track approvals to routers and fast-track the swap that follows.
+    pub track_approvals: bool,
+    /// How long an approval stays eligible for correlation with a swap.
+    pub approval_window_secs: u64,
+    /// How long a pending tx is remembered by `(from, nonce)` to spot its replacement.
+    pub replacement_window_secs: u64,
+    /// DEX factory/router preset the arbitrage strategy scans (`mainnet`, `arbitrum`, `base`, `bsc`);
+    /// `None` uses the DEXes of the chain the RPC serves.
+    pub dex_preset: Option<String>,
+    /// Port serving Prometheus metrics.
+    pub metrics_port: u16,
+    /// Port serving `/healthz` and `/readyz`.
+    pub health_port: u16,
+    /// `/readyz` fails once no pending tx has arrived for this long.
+    pub readiness_max_tx_age_secs: u64,
+    /// Bucket boundaries (ETH) of the profit histograms.
+    pub profit_histogram_buckets: Vec<f64>,
+    /// Flash loan recipient contract that runs callback instructions and repays the vault.
+    pub executor_address: Option<Address>,
+    /// Contract running arbitrage routes atomically; without it arbs go through a single router.
+    pub arb_executor_address: Option<Address>,
+    /// Unwrap WETH profit to ETH once an opportunity lands, so it can pay for gas.
+    pub auto_unwrap_weth: bool,
+    /// What to swap profit landing in a token into once an opportunity lands.
+    pub settlement_policy: SettlementPolicy,
+    /// Most a settlement swap may return under its quote, in bps.
+    pub settlement_max_slippage_bps: u64,
+    /// Bracket several victims on the same pool in one block with a single frontrun/backrun.
+    pub multi_victim_sandwich: bool,
+    /// Most victims a single sandwich brackets.
+    pub max_victims_per_sandwich: usize,
+    /// Most opportunities returned for one pending tx, after keeping the best per victim.
+    pub max_opportunities_per_tx: usize,
+    /// Chance a submission is assumed to land before any of ours have resolved;
+    /// opportunities rank on net profit times their landing probability.
+    pub landing_probability_prior: f64,
+    /// Strategies `StrategyManager` runs on each pending tx.
+    pub strategies_enabled: StrategiesEnabled,
+    /// SQLite file recording every submitted opportunity; `None` disables the ledger.
+    pub ledger_path: Option<String>,
+    /// Pending txs (or hashes) queued ahead of the fetch/analysis pipeline.
+    pub tx_queue_capacity: usize,
+    /// What to do when that queue is full.
+    pub backpressure_policy: BackpressurePolicy,
+    /// Max in-flight tx body fetches on the hash-then-fetch path.
+    pub tx_fetch_concurrency: usize,
+    /// Force V3 sandwiches onto this fee tier; `None` uses the tier the victim swaps on.
+    pub v3_sandwich_fee_tier: Option<u32>,
+    /// Provider whose head block bundle targets are computed from.
+    pub bundle_block_source: BlockSource,
+    /// Sandwich single-hop token-to-token swaps, with profit normalized to ETH for ranking.
+    pub token_to_token_sandwich: bool,
+    /// Reject sandwiches that stop paying if the victim re-broadcasts with bumped gas.
+    pub victim_replacement_check: bool,
+    /// Gas bump (bps) assumed for a victim replacement; nodes require at least 10%.
+    pub victim_replacement_bump_bps: u64,
+    /// Simulate each sandwich as a bundle via the relay's `eth_callBundle`, price it
+    /// by what the backrun returns, and drop it if a victim would revert behind
+    /// our frontrun.
+    pub simulate_victim_after_frontrun: bool,
+    /// Send arbitrage, which needs no bundle ordering, through the relay's
+    /// `eth_sendPrivateTransaction` instead of the public mempool.
+    pub private_transactions: bool,
+    /// Blocks the relay keeps trying to include a private tx for.
+    pub private_tx_max_blocks: u64,
+    /// Sustained alert rate; bursts above it are dropped.
+    pub alert_rate_per_minute: u32,
+    /// Alerts that may go out back to back before rate limiting kicks in.
+    pub alert_burst: u32,
+    /// Identical alerts within this window are coalesced into one summary.
+    pub alert_dedup_window_secs: u64,
+    /// TOML file of token/pool allow- and blocklists, reloaded when it changes.
+    pub access_lists_file: Option<String>,
+    /// Chain the RPC is expected to serve; startup fails on a mismatch.
+    pub chain_id: Option<u64>,
+    /// Where the key that signs our transactions lives.
+    pub signer: SignerKind,
+    /// KMS key id or ARN, when `signer` is `aws_kms`.
+    pub aws_kms_key_id: Option<String>,
+    /// Ledger Live account index, when `signer` is `ledger`.
+    pub ledger_index: usize,
+    /// Minimum estimated profit (ETH) per strategy before an opportunity is emitted.
+    pub sandwich_min_profit_eth: f64,
+    pub enhanced_sandwich_min_profit_eth: f64,
+    pub arbitrage_min_profit_eth: f64,
+    pub flashloan_min_profit_eth: f64,
+    /// Minimum simulated profit net of gas (ETH) per opportunity type, checked after
+    /// the strategies' own thresholds as a final margin against gas variance.
+    pub sandwich_min_net_profit_eth: f64,
+    pub arbitrage_min_net_profit_eth: f64,
+    pub flashloan_min_net_profit_eth: f64,
+    pub backrun_min_net_profit_eth: f64,
+    /// Pools shallower than this (ETH) are left out of arbitrage: their price gaps
+    /// look large but can't absorb a trade. 0 disables the filter.
+    pub arbitrage_min_pool_liquidity_eth: f64,
+    /// Slippage sandwich legs allow is this many times the token's per-block
+    /// volatility, clamped to `slippage_min_bps`..`slippage_max_bps`.
+    pub slippage_volatility_multiplier: f64,
+    pub slippage_min_bps: u64,
+    pub slippage_max_bps: u64,
+    /// Slippage for tokens not yet seen across two blocks.
+    pub slippage_default_bps: u64,
+    /// Alert channel credentials; channels left unset are skipped.
+    pub alert_channels: AlertChannels,
+    /// Skip re-executing an opportunity still in flight from an earlier block.
+    pub opportunity_dedup: bool,
+    /// Longest an unsettled opportunity stays suppressed.
+    pub opportunity_dedup_cooldown_secs: u64,
+    /// Skip sandwich victims whose swap deadline passes before the target block.
+    pub check_victim_deadline: bool,
+    /// Seconds past the head block's timestamp our swaps' `deadline` allows. Too
+    /// long and a delayed tx can still land blocks later at a worse price; too
+    /// short and it reverts before inclusion.
+    pub swap_deadline_secs: u64,
+    /// Expected block interval, used to project the target block's timestamp.
+    pub block_time_secs: u64,
+    /// Submit opportunities on their estimates, unsimulated, when simulating
+    /// would leave too little time to reach the block being built.
+    pub latency_budget: bool,
+    /// Time kept back for signing and relay submission before the block boundary.
+    pub submit_latency_reserve_ms: u64,
+    /// Expected duration of one full simulation, until real ones have been timed.
+    pub simulation_latency_ms: u64,
+    /// How long shutdown waits for in-flight analysis and submissions.
+    pub shutdown_timeout_secs: u64,
+    /// Also size sandwiches on a flash loan from this provider and keep whichever nets more.
+    pub sandwich_flashloan: Option<FlashloanProvider>,
+    /// Recent block hashes kept to detect reorgs; also the deepest reorg measured.
+    pub reorg_history_blocks: usize,
+    /// Also count nonces used by our own landed bundles when the node's pending nonce trails them.
+    pub reconcile_nonce: bool,
+    /// Index every pair of the preset's V2 factories and follow `PairCreated` for new ones.
+    pub index_pairs: bool,
+    /// Directory the pair indexes are persisted in (one file per factory); `None` keeps them in memory.
+    pub pair_index_dir: Option<String>,
+    /// Forked node (anvil/hardhat) tokens are probed on before sandwiching; `None` skips the check.
+    pub token_safety_rpc: Option<String>,
+    /// ETH each probe buys the token with.
+    pub token_safety_probe_eth: f64,
+    /// Largest buy-then-sell loss (bps) a token may show before it's flagged.
+    pub token_safety_max_loss_bps: u64,
+    /// Chain whose bridge deposits and withdrawals are priced against mainnet; `None` disables it.
+    pub bridge_dest_rpc: Option<String>,
+    /// Bridge and relayer fees, in bps of the amount bridged.
+    pub bridge_fee_bps: u64,
+    /// Price risk of holding a position while it crosses, in bps per hour.
+    pub bridge_time_cost_bps_per_hour: u64,
+    /// Price difference (bps) that must be left after bridge costs to report an opportunity.
+    pub bridge_min_net_bps: u64,
+    /// Chainlink feeds whose updates are backrun on the feed's V2 pool.
+    pub oracle_feeds: Vec<OracleFeed>,
+    /// Retries of an HTTP RPC call that failed in transport, with exponential backoff.
+    pub rpc_max_retries: u32,
+    /// Backoff before the first retry (ms); doubled on each retry after.
+    pub rpc_backoff_ms: u64,
+    /// Consecutive failures on one RPC endpoint before rotating to the next.
+    pub rpc_failover_after: u32,
+    /// HTTP RPC endpoints tried after `NETWORK_RPC`, in order.
+    pub rpc_fallback_urls: Vec<String>,
+    /// Requests per second allowed to each RPC endpoint; 0 leaves them unpaced.
+    pub rpc_requests_per_second: f64,
+    /// Bundle relays and how requests to each are signed.
+    pub relay: RelaySettings,
+    /// How bundles are bid and the gas figures that pricing relies on.
+    pub gas: GasSettings,
+    /// Limits on capital, losses and stale state that halt or shrink execution.
+    pub risk: RiskSettings,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            trace_all: false,
+            trace_tx: None,
+            full_pending_txs: true,
+            mempool_source: MempoolSourceKind::Node,
+            mempool_source_url: None,
+            mempool_source_auth: None,
+            mempool_record_path: None,
+            mempool_replay_path: None,
+            mempool_replay_speed: 1.0,
+            victim_safety_margin_bps: 50, // 0.5%
+            max_frontrun_reserve_bps: 1000, // 10%
+            cache_pair_addresses: true,
+            token_info_cache_size: 4096,
+            track_approvals: true,
+            approval_window_secs: 60,
+            replacement_window_secs: 300,
+            dex_preset: None,
+            metrics_port: 9464,
+            health_port: 9465,
+            readiness_max_tx_age_secs: 30,
+            profit_histogram_buckets: DEFAULT_PROFIT_BUCKETS.to_vec(),
+            executor_address: None,
+            arb_executor_address: None,
+            auto_unwrap_weth: false,
+            settlement_policy: SettlementPolicy::HoldWeth,
+            settlement_max_slippage_bps: 100,
+            multi_victim_sandwich: true,
+            max_victims_per_sandwich: 3,
+            max_opportunities_per_tx: 3,
+            landing_probability_prior: 0.5,
+            strategies_enabled: StrategiesEnabled::all(),
+            ledger_path: Some("mev_ledger.db".to_string()),
+            tx_queue_capacity: 4096,
+            backpressure_policy: BackpressurePolicy::DropOldest,
+            tx_fetch_concurrency: 512,
+            v3_sandwich_fee_tier: None,
+            bundle_block_source: BlockSource::Freshest,
+            token_to_token_sandwich: true,
+            victim_replacement_check: false,
+            simulate_victim_after_frontrun: true,
+            private_transactions: false,
+            private_tx_max_blocks: 25,
+            victim_replacement_bump_bps: 1000,
+            alert_rate_per_minute: 20,
+            alert_burst: 5,
+            alert_dedup_window_secs: 60,
+            access_lists_file: None,
+            chain_id: None,
+            signer: SignerKind::Local,
+            aws_kms_key_id: None,
+            ledger_index: 0,
+            sandwich_min_profit_eth: 0.1,
+            enhanced_sandwich_min_profit_eth: 0.05,
+            arbitrage_min_profit_eth: 0.1,
+            flashloan_min_profit_eth: 0.1,
+            sandwich_min_net_profit_eth: 0.0,
+            arbitrage_min_net_profit_eth: 0.0,
+            flashloan_min_net_profit_eth: 0.0,
+            backrun_min_net_profit_eth: 0.0,
+            arbitrage_min_pool_liquidity_eth: 10.0,
+            slippage_volatility_multiplier: 3.0,
+            slippage_min_bps: 10,
+            slippage_max_bps: 1000,
+            slippage_default_bps: 300,
+            alert_channels: AlertChannels::default(),
+            opportunity_dedup: true,
+            opportunity_dedup_cooldown_secs: 36, // 3 blocks
+            check_victim_deadline: true,
+            swap_deadline_secs: 60,
+            block_time_secs: 12,
+            latency_budget: true,
+            submit_latency_reserve_ms: 300,
+            simulation_latency_ms: 150,
+            shutdown_timeout_secs: 30,
+            sandwich_flashloan: None,
+            reorg_history_blocks: 64,
+            reconcile_nonce: true,
+            index_pairs: false,
+            pair_index_dir: None,
+            token_safety_rpc: None,
+            token_safety_probe_eth: 0.01,
+            token_safety_max_loss_bps: 500, // 5%
+            bridge_dest_rpc: None,
+            bridge_fee_bps: 10,
+            bridge_time_cost_bps_per_hour: 20,
+            bridge_min_net_bps: 50,
+            oracle_feeds: OracleFeed::mainnet_defaults(),
+            rpc_max_retries: 3,
+            rpc_backoff_ms: 100,
+            rpc_failover_after: 2,
+            rpc_fallback_urls: Vec::new(),
+            rpc_requests_per_second: 0.0,
+            relay: RelaySettings::default(),
+            gas: GasSettings::default(),
+            risk: RiskSettings::default(),
+        }
+    }
+}
+
+impl Settings {
+    pub fn from_env() -> Self {
+        Self::from_env_over(Self::default())
+    }
+
+    /// `defaults` (e.g. parsed from `bot.toml`) with any env vars that are set taking precedence.
+    pub fn from_env_over(defaults: Self) -> Self {
+        Self {
+            trace_all: env_or("DECISION_TRACE", defaults.trace_all),
+            trace_tx: std::env::var("DECISION_TRACE_TX").ok().and_then(|hash| hash.parse().ok()).or(defaults.trace_tx),
+            full_pending_txs: env_or("FULL_PENDING_TXS", defaults.full_pending_txs),
+            mempool_source: env_or("MEMPOOL_SOURCE", defaults.mempool_source),
+            mempool_source_url: std::env::var("MEMPOOL_SOURCE_URL")
+                .ok()
+                .filter(|url| !url.is_empty())
+                .or(defaults.mempool_source_url),
+            mempool_source_auth: std::env::var("MEMPOOL_SOURCE_AUTH")
+                .ok()
+                .filter(|auth| !auth.is_empty())
+                .or(defaults.mempool_source_auth),
+            mempool_record_path: std::env::var("MEMPOOL_RECORD_PATH")
+                .ok()
+                .filter(|path| !path.is_empty())
+                .or(defaults.mempool_record_path),
+            mempool_replay_path: std::env::var("MEMPOOL_REPLAY_PATH")
+                .ok()
+                .filter(|path| !path.is_empty())
+                .or(defaults.mempool_replay_path),
+            mempool_replay_speed: env_or("MEMPOOL_REPLAY_SPEED", defaults.mempool_replay_speed),
+            victim_safety_margin_bps: env_or("VICTIM_SAFETY_MARGIN_BPS", defaults.victim_safety_margin_bps),
+            max_frontrun_reserve_bps: env_or("MAX_FRONTRUN_RESERVE_BPS", defaults.max_frontrun_reserve_bps),
+            cache_pair_addresses: env_or("CACHE_PAIR_ADDRESSES", defaults.cache_pair_addresses),
+            token_info_cache_size: env_or("TOKEN_INFO_CACHE_SIZE", defaults.token_info_cache_size),
+            track_approvals: env_or("TRACK_APPROVALS", defaults.track_approvals),
+            approval_window_secs: env_or("APPROVAL_WINDOW_SECS", defaults.approval_window_secs),
+            replacement_window_secs: env_or("REPLACEMENT_WINDOW_SECS", defaults.replacement_window_secs),
+            dex_preset: std::env::var("DEX_PRESET").ok().filter(|preset| !preset.is_empty()).or(defaults.dex_preset),
+            metrics_port: env_or("METRICS_PORT", defaults.metrics_port),
+            health_port: env_or("HEALTH_PORT", defaults.health_port),
+            readiness_max_tx_age_secs: env_or("READINESS_MAX_TX_AGE_SECS", defaults.readiness_max_tx_age_secs),
+            profit_histogram_buckets: std::env::var("PROFIT_HISTOGRAM_BUCKETS")
+                .ok()
+                .and_then(|buckets| buckets.split(',').map(|b| b.trim().parse().ok()).collect())
+                .unwrap_or(defaults.profit_histogram_buckets),
+            executor_address: std::env::var("EXECUTOR_ADDRESS")
+                .ok()
+                .and_then(|addr| addr.parse().ok())
+                .or(defaults.executor_address),
+            arb_executor_address: std::env::var("ARB_EXECUTOR_ADDRESS")
+                .ok()
+                .and_then(|addr| addr.parse().ok())
+                .or(defaults.arb_executor_address),
+            auto_unwrap_weth: env_or("AUTO_UNWRAP_WETH", defaults.auto_unwrap_weth),
+            settlement_policy: env_or("SETTLEMENT_POLICY", defaults.settlement_policy),
+            settlement_max_slippage_bps: env_or("SETTLEMENT_MAX_SLIPPAGE_BPS", defaults.settlement_max_slippage_bps),
+            multi_victim_sandwich: env_or("MULTI_VICTIM_SANDWICH", defaults.multi_victim_sandwich),
+            max_victims_per_sandwich: env_or("MAX_VICTIMS_PER_SANDWICH", defaults.max_victims_per_sandwich),
+            max_opportunities_per_tx: env_or("MAX_OPPORTUNITIES_PER_TX", defaults.max_opportunities_per_tx),
+            landing_probability_prior: env_or("LANDING_PROBABILITY_PRIOR", defaults.landing_probability_prior),
+            strategies_enabled: std::env::var("STRATEGIES_ENABLED")
+                .map(|list| StrategiesEnabled::from_list(&list))
+                .unwrap_or(defaults.strategies_enabled),
+            ledger_path: match std::env::var("LEDGER_PATH") {
+                Ok(path) if path.is_empty() => None,
+                Ok(path) => Some(path),
+                Err(_) => defaults.ledger_path,
+            },
+            tx_queue_capacity: env_or("TX_QUEUE_CAPACITY", defaults.tx_queue_capacity),
+            backpressure_policy: env_or("BACKPRESSURE_POLICY", defaults.backpressure_policy),
+            tx_fetch_concurrency: env_or("TX_FETCH_CONCURRENCY", defaults.tx_fetch_concurrency),
+            v3_sandwich_fee_tier: std::env::var("V3_SANDWICH_FEE_TIER")
+                .ok()
+                .and_then(|tier| tier.parse().ok())
+                .or(defaults.v3_sandwich_fee_tier),
+            bundle_block_source: env_or("BUNDLE_BLOCK_SOURCE", defaults.bundle_block_source),
+            token_to_token_sandwich: env_or("TOKEN_TO_TOKEN_SANDWICH", defaults.token_to_token_sandwich),
+            victim_replacement_check: env_or("VICTIM_REPLACEMENT_CHECK", defaults.victim_replacement_check),
+            simulate_victim_after_frontrun: env_or("SIMULATE_VICTIM_AFTER_FRONTRUN", defaults.simulate_victim_after_frontrun),
+            private_transactions: env_or("PRIVATE_TRANSACTIONS", defaults.private_transactions),
+            private_tx_max_blocks: env_or("PRIVATE_TX_MAX_BLOCKS", defaults.private_tx_max_blocks),
+            victim_replacement_bump_bps: env_or("VICTIM_REPLACEMENT_BUMP_BPS", defaults.victim_replacement_bump_bps),
+            alert_rate_per_minute: env_or("ALERT_RATE_PER_MINUTE", defaults.alert_rate_per_minute),
+            alert_burst: env_or("ALERT_BURST", defaults.alert_burst),
+            alert_dedup_window_secs: env_or("ALERT_DEDUP_WINDOW_SECS", defaults.alert_dedup_window_secs),
+            access_lists_file: std::env::var("ACCESS_LISTS_FILE").ok().filter(|path| !path.is_empty()).or(defaults.access_lists_file),
+            chain_id: std::env::var("CHAIN_ID").ok().and_then(|id| id.parse().ok()).or(defaults.chain_id),
+            signer: env_or("SIGNER", defaults.signer),
+            aws_kms_key_id: std::env::var("AWS_KMS_KEY_ID").ok().filter(|id| !id.is_empty()).or(defaults.aws_kms_key_id),
+            ledger_index: env_or("LEDGER_INDEX", defaults.ledger_index),
+            sandwich_min_profit_eth: env_or("SANDWICH_MIN_PROFIT_ETH", defaults.sandwich_min_profit_eth),
+            enhanced_sandwich_min_profit_eth: env_or("ENHANCED_SANDWICH_MIN_PROFIT_ETH", defaults.enhanced_sandwich_min_profit_eth),
+            arbitrage_min_profit_eth: env_or("ARBITRAGE_MIN_PROFIT_ETH", defaults.arbitrage_min_profit_eth),
+            flashloan_min_profit_eth: env_or("FLASHLOAN_MIN_PROFIT_ETH", defaults.flashloan_min_profit_eth),
+            sandwich_min_net_profit_eth: env_or("SANDWICH_MIN_NET_PROFIT_ETH", defaults.sandwich_min_net_profit_eth),
+            arbitrage_min_net_profit_eth: env_or("ARBITRAGE_MIN_NET_PROFIT_ETH", defaults.arbitrage_min_net_profit_eth),
+            flashloan_min_net_profit_eth: env_or("FLASHLOAN_MIN_NET_PROFIT_ETH", defaults.flashloan_min_net_profit_eth),
+            backrun_min_net_profit_eth: env_or("BACKRUN_MIN_NET_PROFIT_ETH", defaults.backrun_min_net_profit_eth),
+            arbitrage_min_pool_liquidity_eth: env_or("ARBITRAGE_MIN_POOL_LIQUIDITY_ETH", defaults.arbitrage_min_pool_liquidity_eth),
+            slippage_volatility_multiplier: env_or("SLIPPAGE_VOLATILITY_MULTIPLIER", defaults.slippage_volatility_multiplier),
+            slippage_min_bps: env_or("SLIPPAGE_MIN_BPS", defaults.slippage_min_bps),
+            slippage_max_bps: env_or("SLIPPAGE_MAX_BPS", defaults.slippage_max_bps),
+            slippage_default_bps: env_or("SLIPPAGE_DEFAULT_BPS", defaults.slippage_default_bps),
+            alert_channels: AlertChannels::from_env_over(defaults.alert_channels),
+            opportunity_dedup: env_or("OPPORTUNITY_DEDUP", defaults.opportunity_dedup),
+            opportunity_dedup_cooldown_secs: env_or("OPPORTUNITY_DEDUP_COOLDOWN_SECS", defaults.opportunity_dedup_cooldown_secs),
+            check_victim_deadline: env_or("CHECK_VICTIM_DEADLINE", defaults.check_victim_deadline),
+            swap_deadline_secs: env_or("SWAP_DEADLINE_SECS", defaults.swap_deadline_secs),
+            block_time_secs: env_or("BLOCK_TIME_SECS", defaults.block_time_secs),
+            latency_budget: env_or("LATENCY_BUDGET", defaults.latency_budget),
+            submit_latency_reserve_ms: env_or("SUBMIT_LATENCY_RESERVE_MS", defaults.submit_latency_reserve_ms),
+            simulation_latency_ms: env_or("SIMULATION_LATENCY_MS", defaults.simulation_latency_ms),
+            shutdown_timeout_secs: env_or("SHUTDOWN_TIMEOUT_SECS", defaults.shutdown_timeout_secs),
+            sandwich_flashloan: std::env::var("SANDWICH_FLASHLOAN")
+                .ok()
+                .and_then(|provider| provider.trim().parse().ok())
+                .or(defaults.sandwich_flashloan),
+            reorg_history_blocks: env_or("REORG_HISTORY_BLOCKS", defaults.reorg_history_blocks),
+            reconcile_nonce: env_or("RECONCILE_NONCE", defaults.reconcile_nonce),
+            index_pairs: env_or("INDEX_PAIRS", defaults.index_pairs),
+            pair_index_dir: std::env::var("PAIR_INDEX_DIR")
+                .ok()
+                .filter(|dir| !dir.is_empty())
+                .or(defaults.pair_index_dir),
+            token_safety_rpc: std::env::var("TOKEN_SAFETY_RPC")
+                .ok()
+                .filter(|url| !url.is_empty())
+                .or(defaults.token_safety_rpc),
+            token_safety_probe_eth: env_or("TOKEN_SAFETY_PROBE_ETH", defaults.token_safety_probe_eth),
+            token_safety_max_loss_bps: env_or("TOKEN_SAFETY_MAX_LOSS_BPS", defaults.token_safety_max_loss_bps),
+            bridge_dest_rpc: std::env::var("BRIDGE_DEST_RPC")
+                .ok()
+                .filter(|url| !url.is_empty())
+                .or(defaults.bridge_dest_rpc),
+            bridge_fee_bps: env_or("BRIDGE_FEE_BPS", defaults.bridge_fee_bps),
+            bridge_time_cost_bps_per_hour: env_or("BRIDGE_TIME_COST_BPS_PER_HOUR", defaults.bridge_time_cost_bps_per_hour),
+            bridge_min_net_bps: env_or("BRIDGE_MIN_NET_BPS", defaults.bridge_min_net_bps),
+            oracle_feeds: std::env::var("ORACLE_FEEDS")
+                .ok()
+                .map(|list| list.split(',').filter_map(|feed| feed.trim().parse().ok()).collect::<Vec<_>>())
+                .filter(|feeds| !feeds.is_empty())
+                .unwrap_or(defaults.oracle_feeds),
+            rpc_max_retries: env_or("RPC_MAX_RETRIES", defaults.rpc_max_retries),
+            rpc_backoff_ms: env_or("RPC_BACKOFF_MS", defaults.rpc_backoff_ms),
+            rpc_failover_after: env_or("RPC_FAILOVER_AFTER", defaults.rpc_failover_after),
+            rpc_fallback_urls: std::env::var("RPC_FALLBACK_URLS")
+                .ok()
+                .map(|list| list.split(',').map(|url| url.trim().to_string()).filter(|url| !url.is_empty()).collect())
+                .unwrap_or(defaults.rpc_fallback_urls),
+            rpc_requests_per_second: env_or("RPC_REQUESTS_PER_SECOND", defaults.rpc_requests_per_second),
+            relay: RelaySettings::from_env_over(defaults.relay),
+            gas: GasSettings::from_env_over(defaults.gas),
+            risk: RiskSettings::from_env_over(defaults.risk),
+        }
+    }
+
+    /// Whether a decision trace should be recorded for `tx_hash`.
+    pub fn trace_enabled_for(&self, tx_hash: &H256) -> bool {
+        self.trace_all || self.trace_tx.as_ref() == Some(tx_hash)
+    }
+}
+
+/// Bundle relays, in order of preference, and how each authenticates requests.
+#[derive(Debug, Clone)]
+pub struct RelaySettings {
+    /// Bundle relay endpoints, in order of preference.
+    pub urls: Vec<String>,
+    /// How each relay wants request bodies signed; unlisted relays get `RawKeccak`.
+    pub signing_modes: Vec<(String, SigningMode)>,
+    /// Header each relay reads the signature from; unlisted relays get `X-Flashbots-Signature`.
+    pub auth_headers: Vec<(String, AuthHeader)>,
+}
+
+impl Default for RelaySettings {
+    fn default() -> Self {
+        Self {
+            urls: vec!["https://relay.flashbots.net".to_string()],
+            signing_modes: Vec::new(),
+            auth_headers: Vec::new(),
+        }
+    }
+}
+
+impl RelaySettings {
+    pub fn from_env_over(defaults: Self) -> Self {
+        Self {
+            urls: std::env::var("RELAYS")
+                .ok()
+                .map(|list| list.split(',').map(|relay| relay.trim().to_string()).filter(|relay| !relay.is_empty()).collect::<Vec<_>>())
+                .filter(|relays| !relays.is_empty())
+                .unwrap_or(defaults.urls),
+            signing_modes: std::env::var("RELAY_SIGNING_MODES")
+                .ok()
+                .map(|list| relay_map(&list))
+                .filter(|modes| !modes.is_empty())
+                .unwrap_or(defaults.signing_modes),
+            auth_headers: std::env::var("RELAY_AUTH_HEADERS")
+                .ok()
+                .map(|list| relay_map(&list))
+                .filter(|headers| !headers.is_empty())
+                .unwrap_or(defaults.auth_headers),
+        }
+    }
+
+    /// How `relay` wants request bodies signed.
+    pub fn signing_mode(&self, relay: &str) -> SigningMode {
+        self.signing_modes
+            .iter()
+            .find(|(url, _)| url == relay)
+            .map_or(SigningMode::RawKeccak, |(_, mode)| *mode)
+    }
+
+    /// How requests to `relay` are signed and which header carries the signature.
+    pub fn auth(&self, relay: &str) -> RelayAuth {
+        let header = self
+            .auth_headers
+            .iter()
+            .find(|(url, _)| url == relay)
+            .map_or_else(AuthHeader::flashbots, |(_, header)| header.clone());
+        RelayAuth { signing_mode: self.signing_mode(relay), header }
+    }
+}
+
+/// Parses a comma-separated `relay_url=value` list. Entries split on the first
+/// `=`, since relay URLs don't contain one but values may; entries that don't
+/// parse are skipped.
+fn relay_map<T: FromStr>(list: &str) -> Vec<(String, T)> {
+    list.split(',')
+        .filter_map(|entry| entry.trim().split_once('='))
+        .filter_map(|(relay, value)| Some((relay.trim().to_string(), value.trim().parse().ok()?)))
+        .collect()
+}
+
+/// How bundles are bid, and the gas figures bids and profit checks are priced with.
+#[derive(Debug, Clone)]
+pub struct GasSettings {
+    /// How bundle txs are priced: a share of profit or a premium over recent inclusion prices.
+    pub bid_strategy: BidStrategy,
+    /// Percentile of recent inclusion prices the `floor` strategy bids over.
+    pub bid_floor_percentile: f64,
+    /// How far above that floor to bid, in bps.
+    pub bid_floor_premium_bps: u64,
+    /// Share of a bundle's net profit paid straight to `block.coinbase` by a closing
+    /// tx through the executor, in bps; 0 pays the builder through gas only.
+    pub coinbase_bribe_bps: u64,
+    /// Track competing frontruns per pool and re-bid sandwiches to stay ahead, up to break-even.
+    pub competitor_rebid: bool,
+    /// How far above the top competing bid our frontrun goes.
+    pub competitor_outbid_gwei: u64,
+    /// How sandwiches meet a gas war on their pool (`ignore`, `bow_out`, `capped`).
+    pub war_policy: GasWarPolicy,
+    /// Raises of a pool's top pending bid within a block that make it a gas war.
+    pub war_escalations: usize,
+    /// With the `capped` policy, most our bid may exceed the war's opening bid by (bps).
+    pub war_max_escalation_bps: u64,
+    /// Recent blocks the gas oracle's base and priority fee statistics cover.
+    pub oracle_window_blocks: usize,
+    /// ETH kept back from the wallet's balance for gas; own-capital frontruns never spend it.
+    pub reserve_eth: f64,
+    /// Gas units each kind of tx is estimated to burn, priced by the gas oracle.
+    pub estimates: GasEstimates,
+}
+
+impl Default for GasSettings {
+    fn default() -> Self {
+        Self {
+            bid_strategy: BidStrategy::Floor,
+            bid_floor_percentile: 75.0,
+            bid_floor_premium_bps: 1000, // 10%
+            coinbase_bribe_bps: 0,
+            competitor_rebid: false,
+            competitor_outbid_gwei: 1,
+            war_policy: GasWarPolicy::Ignore,
+            war_escalations: 3,
+            war_max_escalation_bps: 2_000, // 20%
+            oracle_window_blocks: 20,
+            reserve_eth: 0.05,
+            estimates: GasEstimates::default(),
+        }
+    }
+}
+
+impl GasSettings {
+    pub fn from_env_over(defaults: Self) -> Self {
+        Self {
+            bid_strategy: env_or("BID_STRATEGY", defaults.bid_strategy),
+            bid_floor_percentile: env_or("BID_FLOOR_PERCENTILE", defaults.bid_floor_percentile),
+            bid_floor_premium_bps: env_or("BID_FLOOR_PREMIUM_BPS", defaults.bid_floor_premium_bps),
+            coinbase_bribe_bps: env_or("COINBASE_BRIBE_BPS", defaults.coinbase_bribe_bps),
+            competitor_rebid: env_or("COMPETITOR_REBID", defaults.competitor_rebid),
+            competitor_outbid_gwei: env_or("COMPETITOR_OUTBID_GWEI", defaults.competitor_outbid_gwei),
+            war_policy: env_or("GAS_WAR_POLICY", defaults.war_policy),
+            war_escalations: env_or("GAS_WAR_ESCALATIONS", defaults.war_escalations),
+            war_max_escalation_bps: env_or("GAS_WAR_MAX_ESCALATION_BPS", defaults.war_max_escalation_bps),
+            oracle_window_blocks: env_or("GAS_ORACLE_WINDOW_BLOCKS", defaults.oracle_window_blocks),
+            reserve_eth: env_or("GAS_RESERVE_ETH", defaults.reserve_eth),
+            estimates: GasEstimates::from_env_over(defaults.estimates),
+        }
+    }
+}
+
+/// Limits that halt execution or cap the capital and gas it may commit.
+#[derive(Debug, Clone)]
+pub struct RiskSettings {
+    /// Re-validate an opportunity before execution if its reserves are more than this many blocks old.
+    pub max_reserve_age_blocks: u64,
+    /// Halt execution after this many losing opportunities in a row.
+    pub breaker_max_consecutive_losses: u32,
+    /// Halt execution once losses within the breaker window exceed this (ETH).
+    pub breaker_max_loss_eth: f64,
+    /// Rolling window the loss threshold applies to.
+    pub breaker_window_secs: u64,
+    /// How long a tripped breaker keeps execution halted.
+    pub breaker_cooldown_secs: u64,
+    /// Execution halts while this file exists.
+    pub halt_file: Option<String>,
+    /// ETH available to fund sandwich frontruns; `None` allows the whole wallet balance.
+    pub sandwich_capital_eth: Option<f64>,
+    /// Own ETH the opportunities dispatched for one block may tie up; `None` is unlimited.
+    pub scheduler_capital_budget_eth: Option<f64>,
+    /// Gas cost in ETH the opportunities dispatched for one block may spend; `None` is unlimited.
+    pub scheduler_gas_budget_eth: Option<f64>,
+}
+
+impl Default for RiskSettings {
+    fn default() -> Self {
+        Self {
+            max_reserve_age_blocks: 1,
+            breaker_max_consecutive_losses: 5,
+            breaker_max_loss_eth: 0.5,
+            breaker_window_secs: 3600,
+            breaker_cooldown_secs: 600,
+            halt_file: None,
+            sandwich_capital_eth: None,
+            scheduler_capital_budget_eth: None,
+            scheduler_gas_budget_eth: None,
+        }
+    }
+}
+
+impl RiskSettings {
+    pub fn from_env_over(defaults: Self) -> Self {
+        Self {
+            max_reserve_age_blocks: env_or("MAX_RESERVE_AGE_BLOCKS", defaults.max_reserve_age_blocks),
+            breaker_max_consecutive_losses: env_or("BREAKER_MAX_CONSECUTIVE_LOSSES", defaults.breaker_max_consecutive_losses),
+            breaker_max_loss_eth: env_or("BREAKER_MAX_LOSS_ETH", defaults.breaker_max_loss_eth),
+            breaker_window_secs: env_or("BREAKER_WINDOW_SECS", defaults.breaker_window_secs),
+            breaker_cooldown_secs: env_or("BREAKER_COOLDOWN_SECS", defaults.breaker_cooldown_secs),
+            halt_file: std::env::var("HALT_FILE").ok().filter(|path| !path.is_empty()).or(defaults.halt_file),
+            sandwich_capital_eth: std::env::var("SANDWICH_CAPITAL_ETH")
+                .ok()
+                .and_then(|eth| eth.trim().parse().ok())
+                .or(defaults.sandwich_capital_eth),
+            scheduler_capital_budget_eth: std::env::var("SCHEDULER_CAPITAL_BUDGET_ETH")
+                .ok()
+                .and_then(|eth| eth.trim().parse().ok())
+                .or(defaults.scheduler_capital_budget_eth),
+            scheduler_gas_budget_eth: std::env::var("SCHEDULER_GAS_BUDGET_ETH")
+                .ok()
+                .and_then(|eth| eth.trim().parse().ok())
+                .or(defaults.scheduler_gas_budget_eth),
+        }
+    }
+}
+
+/// Credentials of the channels alerts fan out to.
+#[derive(Debug, Clone, Default)]
+pub struct AlertChannels {
+    pub discord_webhook: Option<String>,
+    pub telegram_bot_token: Option<String>,
+    pub telegram_chat_id: Option<String>,
+    pub slack_webhook: Option<String>,
+}
+
+impl AlertChannels {
+    pub fn from_env_over(defaults: Self) -> Self {
+        let var = |key: &str| std::env::var(key).ok().filter(|value| !value.is_empty());
+        Self {
+            discord_webhook: var("DISCORD_WEBHOOK").or(defaults.discord_webhook),
+            telegram_bot_token: var("TELEGRAM_BOT_TOKEN").or(defaults.telegram_bot_token),
+            telegram_chat_id: var("TELEGRAM_CHAT_ID").or(defaults.telegram_chat_id),
+            slack_webhook: var("SLACK_WEBHOOK").or(defaults.slack_webhook),
+        }
+    }
+}
+
+/// Per-strategy on/off switches.
+#[derive(Debug, Clone)]
+pub struct StrategiesEnabled {
+    pub sandwich: bool,
+    pub arbitrage: bool,
+    pub flashloan: bool,
+    pub enhanced_sandwich: bool,
+    pub advanced: bool,
+}
+
+impl StrategiesEnabled {
+    pub fn all() -> Self {
+        Self {
+            sandwich: true,
+            arbitrage: true,
+            flashloan: true,
+            enhanced_sandwich: true,
+            advanced: true,
+        }
+    }
+
+    /// Enables exactly the strategies named in a comma-separated list, e.g. `sandwich,arbitrage`.
+    pub fn from_list(list: &str) -> Self {
+        let names: Vec<&str> = list.split(',').map(str::trim).collect();
+        Self {
+            sandwich: names.contains(&"sandwich"),
+            arbitrage: names.contains(&"arbitrage"),
+            flashloan: names.contains(&"flashloan"),
+            enhanced_sandwich: names.contains(&"enhanced_sandwich"),
+            advanced: names.contains(&"advanced"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn relay_map_splits_on_the_first_equals_sign() {
+        let headers: Vec<(String, AuthHeader)> = relay_map("https://relay.flashbots.net=flashbots, https://builder.example=X-Sig:address");
+        assert_eq!(headers.len(), 2);
+        assert_eq!(headers[1].0, "https://builder.example");
+        assert!(headers[1].1.with_address);
+
+        // Everything after the first `=` is the value, so one carrying `=` still reaches its parser
+        let raw: Vec<(String, String)> = relay_map("https://relay.example=a2V5=");
+        assert_eq!(raw, vec![("https://relay.example".to_string(), "a2V5=".to_string())]);
+
+        let modes: Vec<(String, SigningMode)> = relay_map("no-separator,https://relay.example=bogus");
+        assert!(modes.is_empty());
+    }
+
+    #[test]
+    fn relay_auth_falls_back_to_flashbots_for_unlisted_relays() {
+        let relay = RelaySettings {
+            signing_modes: relay_map("https://builder.example=eip712"),
+            auth_headers: relay_map("https://builder.example=X-Sig"),
+            ..RelaySettings::default()
+        };
+        let auth = relay.auth("https://builder.example");
+        assert_eq!(auth.signing_mode, SigningMode::Eip712);
+        assert_eq!(auth.header.name, "X-Sig");
+
+        let auth = relay.auth("https://relay.flashbots.net");
+        assert_eq!(auth, RelayAuth { signing_mode: SigningMode::RawKeccak, header: AuthHeader::flashbots() });
+    }
+}
@@ -0,0 +1,119 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::sync::{watch, Notify};
+
+/// Shutdown token shared by the mempool monitor, block scanner and executor,
+/// plus a count of in-flight analysis/submission tasks to drain before exit.
+#[derive(Debug, Clone)]
+pub struct Shutdown {
+    trigger: Arc<watch::Sender<bool>>,
+    in_flight: Arc<InFlight>,
+}
+
+#[derive(Debug, Default)]
+struct InFlight {
+    count: AtomicUsize,
+    drained: Notify,
+}
+
+/// Marks one task as in flight until dropped.
+#[derive(Debug)]
+pub struct InFlightGuard {
+    in_flight: Arc<InFlight>,
+}
+
+impl Drop for InFlightGuard {
+    fn drop(&mut self) {
+        if self.in_flight.count.fetch_sub(1, Ordering::AcqRel) == 1 {
+            self.in_flight.drained.notify_waiters();
+        }
+    }
+}
+
+impl Default for Shutdown {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Shutdown {
+    pub fn new() -> Self {
+        Self {
+            trigger: Arc::new(watch::channel(false).0),
+            in_flight: Arc::new(InFlight::default()),
+        }
+    }
+
+    /// Asks every holder of this token to stop taking on new work.
+    pub fn trigger(&self) {
+        self.trigger.send_replace(true);
+    }
+
+    pub fn is_triggered(&self) -> bool {
+        *self.trigger.borrow()
+    }
+
+    /// Resolves once shutdown has been triggered.
+    pub async fn wait(&self) {
+        let mut rx = self.trigger.subscribe();
+        let _ = rx.wait_for(|triggered| *triggered).await;
+    }
+
+    /// Registers a task that shutdown should wait for.
+    pub fn enter(&self) -> InFlightGuard {
+        self.in_flight.count.fetch_add(1, Ordering::AcqRel);
+        InFlightGuard { in_flight: self.in_flight.clone() }
+    }
+
+    pub fn in_flight(&self) -> usize {
+        self.in_flight.count.load(Ordering::Acquire)
+    }
+
+    /// Waits for in-flight tasks to finish, up to `timeout`. True if all drained.
+    pub async fn drain(&self, timeout: Duration) -> bool {
+        let drained = async {
+            loop {
+                let notified = self.in_flight.drained.notified();
+                if self.in_flight() == 0 {
+                    return;
+                }
+                notified.await;
+            }
+        };
+        tokio::time::timeout(timeout, drained).await.is_ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn clones_share_the_trigger() {
+        let shutdown = Shutdown::new();
+        let monitor = shutdown.clone();
+        let waiting = tokio::spawn(async move { monitor.wait().await });
+
+        assert!(!shutdown.is_triggered());
+        shutdown.trigger();
+        waiting.await.unwrap();
+        assert!(shutdown.is_triggered());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn drain_waits_for_in_flight_tasks_up_to_the_timeout() {
+        let shutdown = Shutdown::new();
+        let guard = shutdown.enter();
+        assert_eq!(shutdown.in_flight(), 1);
+        assert!(!shutdown.drain(Duration::from_secs(1)).await);
+
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            drop(guard);
+        });
+        assert!(shutdown.drain(Duration::from_secs(1)).await);
+        assert_eq!(shutdown.in_flight(), 0);
+    }
+}
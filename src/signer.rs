@@ -0,0 +1,210 @@
+use std::fmt;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use ethers::prelude::*;
+use ethers::types::transaction::eip2718::TypedTransaction;
+use ethers::types::transaction::eip712::Eip712;
+
+use crate::config_error::ConfigError;
+use crate::rpc::RetryHttp;
+use crate::settings::Settings;
+
+/// The HTTP provider, signing with whichever key `SIGNER` selects.
+pub type SignerClient = SignerMiddleware<Provider<RetryHttp>, BotSigner>;
+
+/// Where the key that signs our transactions lives.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SignerKind {
+    /// `PRIVATE_KEY`, held in memory.
+    Local,
+    /// An AWS KMS secp256k1 key (`AWS_KMS_KEY_ID`); needs the `aws` feature.
+    AwsKms,
+    /// A Ledger over USB (`LEDGER_INDEX`); needs the `ledger` feature.
+    Ledger,
+}
+
+impl FromStr for SignerKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "local" => Ok(SignerKind::Local),
+            "aws_kms" => Ok(SignerKind::AwsKms),
+            "ledger" => Ok(SignerKind::Ledger),
+            _ => Err(format!("unknown signer {}, expected local, aws_kms or ledger", s)),
+        }
+    }
+}
+
+/// A failed signing request, from whichever backend served it.
+#[derive(Debug)]
+pub struct SignerError(pub String);
+
+impl fmt::Display for SignerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "signing failed: {}", self.0)
+    }
+}
+
+impl std::error::Error for SignerError {}
+
+/// What the bot needs from a signer. Unlike `ethers::signers::Signer` this is
+/// object safe, so the backend can be picked at runtime (or mocked).
+#[async_trait]
+pub trait TxSigner: fmt::Debug + Send + Sync {
+    fn address(&self) -> Address;
+    fn chain_id(&self) -> u64;
+    async fn sign_transaction(&self, tx: &TypedTransaction) -> Result<Signature, SignerError>;
+    async fn sign_message(&self, message: &[u8]) -> Result<Signature, SignerError>;
+}
+
+#[async_trait]
+impl<S: Signer + 'static> TxSigner for S {
+    fn address(&self) -> Address {
+        Signer::address(self)
+    }
+
+    fn chain_id(&self) -> u64 {
+        Signer::chain_id(self)
+    }
+
+    async fn sign_transaction(&self, tx: &TypedTransaction) -> Result<Signature, SignerError> {
+        Signer::sign_transaction(self, tx).await.map_err(|e| SignerError(e.to_string()))
+    }
+
+    async fn sign_message(&self, message: &[u8]) -> Result<Signature, SignerError> {
+        Signer::sign_message(self, message).await.map_err(|e| SignerError(e.to_string()))
+    }
+}
+
+/// The signer behind `SignerClient`: any `TxSigner`, chosen at startup.
+#[derive(Debug, Clone)]
+pub struct BotSigner {
+    inner: Arc<dyn TxSigner>,
+    chain_id: u64,
+}
+
+impl BotSigner {
+    pub fn new(inner: impl TxSigner + 'static) -> Self {
+        let chain_id = inner.chain_id();
+        Self { inner: Arc::new(inner), chain_id }
+    }
+
+    /// The signer `settings.signer` selects, for `chain_id`.
+    pub async fn from_settings(settings: &Settings, chain_id: u64) -> Result<Self, ConfigError> {
+        match settings.signer {
+            SignerKind::Local => {
+                let priv_key = std::env::var("PRIVATE_KEY").map_err(|_| ConfigError::MissingEnv("PRIVATE_KEY"))?;
+                let wallet = priv_key
+                    .parse::<LocalWallet>()
+                    .map_err(|_| ConfigError::InvalidPrivateKey)?
+                    .with_chain_id(chain_id);
+                Ok(Self::new(wallet))
+            }
+            SignerKind::AwsKms => Self::aws_kms(settings, chain_id).await,
+            SignerKind::Ledger => Self::ledger(settings, chain_id).await,
+        }
+    }
+
+    /// The KMS key in `AWS_KMS_KEY_ID`, with region and credentials from the usual AWS env vars.
+    #[cfg(feature = "aws")]
+    async fn aws_kms(settings: &Settings, chain_id: u64) -> Result<Self, ConfigError> {
+        let key_id = settings.aws_kms_key_id.as_deref().ok_or(ConfigError::MissingEnv("AWS_KMS_KEY_ID"))?;
+        let kms = rusoto_kms::KmsClient::new(rusoto_core::Region::default());
+        let signer = AwsSigner::new(kms, key_id, chain_id)
+            .await
+            .map_err(|e| ConfigError::Signer { kind: "aws_kms", reason: e.to_string() })?;
+        Ok(Self::new(signer))
+    }
+
+    #[cfg(not(feature = "aws"))]
+    async fn aws_kms(_settings: &Settings, _chain_id: u64) -> Result<Self, ConfigError> {
+        Err(ConfigError::Signer { kind: "aws_kms", reason: "built without the `aws` feature".to_string() })
+    }
+
+    /// Ledger Live account `LEDGER_INDEX` on the first connected device.
+    #[cfg(feature = "ledger")]
+    async fn ledger(settings: &Settings, chain_id: u64) -> Result<Self, ConfigError> {
+        let signer = Ledger::new(HDPath::LedgerLive(settings.ledger_index), chain_id)
+            .await
+            .map_err(|e| ConfigError::Signer { kind: "ledger", reason: e.to_string() })?;
+        Ok(Self::new(signer))
+    }
+
+    #[cfg(not(feature = "ledger"))]
+    async fn ledger(_settings: &Settings, _chain_id: u64) -> Result<Self, ConfigError> {
+        Err(ConfigError::Signer { kind: "ledger", reason: "built without the `ledger` feature".to_string() })
+    }
+}
+
+#[async_trait]
+impl Signer for BotSigner {
+    type Error = SignerError;
+
+    async fn sign_message<S: Send + Sync + AsRef<[u8]>>(&self, message: S) -> Result<Signature, Self::Error> {
+        self.inner.sign_message(message.as_ref()).await
+    }
+
+    async fn sign_transaction(&self, tx: &TypedTransaction) -> Result<Signature, Self::Error> {
+        // The backend signs for the chain it was built with unless the tx names one
+        let mut tx = tx.clone();
+        if tx.chain_id().is_none() {
+            tx.set_chain_id(self.chain_id);
+        }
+        self.inner.sign_transaction(&tx).await
+    }
+
+    /// Nothing in the bot signs typed data, and remote signers can't share one implementation of it.
+    async fn sign_typed_data<T: Eip712 + Send + Sync>(&self, _payload: &T) -> Result<Signature, Self::Error> {
+        Err(SignerError("typed data signing is not supported".to_string()))
+    }
+
+    fn address(&self) -> Address {
+        self.inner.address()
+    }
+
+    fn chain_id(&self) -> u64 {
+        self.chain_id
+    }
+
+    fn with_chain_id<T: Into<u64>>(mut self, chain_id: T) -> Self {
+        self.chain_id = chain_id.into();
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn wallet() -> LocalWallet {
+        "4c0883a69102937d6231471b5dbb6204fe5129617082792ae468d01a3f362318".parse().unwrap()
+    }
+
+    #[test]
+    fn signer_kinds_parse_by_name() {
+        assert_eq!("aws_kms".parse::<SignerKind>(), Ok(SignerKind::AwsKms));
+        assert_eq!("ledger".parse::<SignerKind>(), Ok(SignerKind::Ledger));
+        assert!("trezor".parse::<SignerKind>().is_err());
+    }
+
+    #[tokio::test]
+    async fn txs_without_a_chain_id_are_signed_for_the_signers_chain() {
+        let signer = BotSigner::new(wallet().with_chain_id(1u64)).with_chain_id(8453u64);
+        let mut tx: TypedTransaction = TransactionRequest::new().to(Address::zero()).value(1).nonce(0).into();
+        let signature = Signer::sign_transaction(&signer, &tx).await.unwrap();
+
+        // EIP-155: v carries the chain id
+        assert!(signature.v == 8453 * 2 + 35 || signature.v == 8453 * 2 + 36);
+        tx.set_chain_id(8453u64);
+        assert_eq!(signature.recover(tx.sighash()).unwrap(), Signer::address(&wallet()));
+    }
+
+    #[tokio::test]
+    async fn messages_are_signed_by_the_inner_key() {
+        let signature = Signer::sign_message(&BotSigner::new(wallet()), "hello").await.unwrap();
+        assert_eq!(signature.recover("hello").unwrap(), Signer::address(&wallet()));
+    }
+}
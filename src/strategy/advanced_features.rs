@@ -1,51 +1,78 @@
 use ethers::types::transaction::eip2718::TypedTransaction;
 
+use ethers::abi::AbiEncode;
 use ethers::prelude::*;
 use std::sync::Arc;
 use std::collections::HashMap;
+use std::time::Instant;
+use tokio::sync::OnceCell;
+use super::oracle_backrun::{self, OracleFeed, OraclePrice};
+use super::price_oracle::PriceOracle;
 use super::types::*;
+use crate::classifier::TxClass;
+use crate::address_book::{AggregatorProxy, SwapExactTokensForTokensCall, UniV2Factory};
 use crate::Config;
 
+/// Uniswap V2 swap fee, in hundredths of a bip.
+const V2_FEE_PPM: u32 = 3000;
+/// Slippage allowed on an oracle backrun swap, in bps.
+const ORACLE_BACKRUN_SLIPPAGE_BPS: u64 = 50;
+
 /// Advanced MEV strategies for maximum profitability
+#[derive(Debug)]
 pub struct AdvancedMEVFeatures {
     config: Arc<Config>,
-    dex_routers: HashMap<String, Address>,
     min_arb_profit: U256,
     jit_threshold: U256,
+    /// Configured feeds by the aggregator that receives their updates, with the
+    /// answer's decimals; looked up through the proxies on first use.
+    oracle_aggregators: OnceCell<HashMap<Address, (OracleFeed, u8)>>,
+    price_oracle: PriceOracle,
 }
 
 impl AdvancedMEVFeatures {
     pub fn new(config: Arc<Config>) -> Self {
-        let mut dex_routers = HashMap::new();
-        
-        // Add more DEX routers for cross-DEX arbitrage
-        dex_routers.insert("uniswap_v2".to_string(), 
-            "0x7a250d5630B4cF539739dF2C5dAcb4c659F2488D".parse().unwrap());
-        dex_routers.insert("sushiswap".to_string(), 
-            "0xd9e1cE17f2641f24aE83637ab66a2cca9C378B9F".parse().unwrap());
-        dex_routers.insert("uniswap_v3".to_string(), 
-            "0xE592427A0AEce92De3Edee1F18E0157C05861564".parse().unwrap());
-        dex_routers.insert("balancer_v2".to_string(), 
-            "0xBA12222222228d8Ba445958a75a0704d566BF2C8".parse().unwrap());
-        dex_routers.insert("curve".to_string(), 
-            "0x99a58482BD75cbab83b27EC03CA68fF489b5788f".parse().unwrap());
-        dex_routers.insert("1inch".to_string(), 
-            "0x1111111254fb6c44bAC0beD2854e76F90643097d".parse().unwrap());
-        
         Self {
+            price_oracle: PriceOracle::new(config.http.clone(), config.pool_cache.clone(), &config.chain),
+            oracle_aggregators: OnceCell::new(),
             config,
-            dex_routers,
             min_arb_profit: U256::from(10).pow(U256::from(16)).saturating_mul(U256::from(5)), // 0.05 ETH minimum
             jit_threshold: U256::from(10).pow(U256::from(18)).saturating_mul(U256::from(5)), // 5 ETH threshold for JIT
         }
     }
 
+    /// Backruns for `tx` in the shape the strategy pipeline executes.
+    /// Backruns whose execution tx hasn't been built yet are left out.
+    pub async fn analyze(&self, tx: &Transaction, class: &TxClass) -> Vec<MEVOpportunity> {
+        let current_block = self.config.block_height.get();
+
+        self.find_backrun_opportunities(tx, class)
+            .await
+            .into_iter()
+            .filter(|backrun| backrun.execution_tx.to().is_some())
+            .map(|backrun| MEVOpportunity {
+                id: format!("backrun_{:?}_{:?}", backrun.strategy, tx.hash),
+                target_tx: tx.clone(),
+                strategy_type: StrategyType::Backrun(BackrunDetails {
+                    victim_tx: tx.clone(),
+                    backrun_tx: backrun.execution_tx,
+                }),
+                estimated_profit: backrun.expected_profit,
+                gas_cost: self.config.gas_model.cost(self.config.gas_model.estimates().backrun, 50.0),
+                priority: 6,
+                reserve_block: current_block,
+                expiry_block: current_block + 1,
+                observed_at: Instant::now(),
+                landing_probability: 1.0,
+            })
+            .collect()
+    }
+
     /// Multi-DEX arbitrage with up to 5 hops
     pub async fn find_multi_dex_arbitrage(&self, token: Address) -> Vec<ArbitragePath> {
         let mut paths = Vec::new();
-        let weth: Address = "0xC02aaA39b223FE8D0A0e5C4F27eAD9083C756Cc2".parse().unwrap();
-        let usdc: Address = "0xA0b86991c6218b36c1d19D4a2e9Eb0cE3606eB48".parse().unwrap();
-        let dai: Address = "0x6B175474E89094C44Da98b954EedeAC495271d0F".parse().unwrap();
+        let chain = &self.config.chain;
+        let (weth, usdc, dai) = (chain.weth, chain.usdc, chain.dai);
         
         // Complex arbitrage paths
         let complex_paths = vec![
@@ -71,7 +98,7 @@ impl AdvancedMEVFeatures {
         }
         
         // Sort by profit
-        paths.sort_by(|a, b| b.expected_profit.cmp(&a.expected_profit));
+        paths.sort_by_key(|path| std::cmp::Reverse(path.expected_profit));
         paths
     }
 
@@ -87,7 +114,7 @@ impl AdvancedMEVFeatures {
         let expected_fees = liquidity_amount.saturating_mul(U256::from(3)) / 1000; // 0.3% fee
         
         // Check if profitable after gas - use safe arithmetic
-        let gas_cost = U256::from(600_000).saturating_mul(U256::from(50_000_000_000u64)); // 600k gas @ 50 gwei
+        let gas_cost = self.config.gas_model.cost(self.config.gas_model.estimates().jit_liquidity, 50.0);
         
         if expected_fees > gas_cost.saturating_mul(U256::from(2)) {
             Some(JITOpportunity {
@@ -104,11 +131,11 @@ impl AdvancedMEVFeatures {
     }
 
     /// Backrun-only opportunities (no frontrun risk)
-    pub async fn find_backrun_opportunities(&self, tx: &Transaction) -> Vec<BackrunOpportunity> {
+    pub async fn find_backrun_opportunities(&self, tx: &Transaction, class: &TxClass) -> Vec<BackrunOpportunity> {
         let mut opportunities = Vec::new();
         
         // 1. Liquidation backruns
-        if self.is_liquidation(tx) {
+        if matches!(class, TxClass::Liquidation(_)) {
             if let Some(opp) = self.calculate_liquidation_backrun(tx).await {
                 opportunities.push(opp);
             }
@@ -122,9 +149,11 @@ impl AdvancedMEVFeatures {
         }
         
         // 3. Oracle update backruns
-        if self.is_oracle_update(tx) {
-            if let Some(opp) = self.calculate_oracle_backrun(tx).await {
-                opportunities.push(opp);
+        if let TxClass::OracleUpdate { answer } = class {
+            if let Some(feed) = self.oracle_feed(tx).await {
+                if let Some(opp) = self.calculate_oracle_backrun(tx, *answer, feed).await {
+                    opportunities.push(opp);
+                }
             }
         }
         
@@ -158,26 +187,6 @@ impl AdvancedMEVFeatures {
         opportunities
     }
 
-    /// Cross-chain MEV opportunities
-    pub async fn find_cross_chain_mev(&self) -> Vec<CrossChainOpportunity> {
-        let mut opportunities = Vec::new();
-        
-        // Monitor bridge transactions for arbitrage
-        let bridges = vec![
-            "0x4Dbd4fc535Ac27206064B68FfCf827b0A60BAB3f", // Arbitrum Bridge
-            "0x99C9fc46f92E8a1c0deC1b1747d010903E884bE1", // Optimism Bridge
-            "0x10E6593CDda8c58a1d0f14C5164B376352a55f2F", // Polygon Bridge
-        ];
-        
-        for bridge in bridges {
-            if let Some(opp) = self.monitor_bridge_arbitrage(bridge).await {
-                opportunities.push(opp);
-            }
-        }
-        
-        opportunities
-    }
-
     // Helper methods
     async fn calculate_path_profit(&self, path: &[Address]) -> Option<ArbitragePath> {
         // Implement path profit calculation
@@ -185,25 +194,7 @@ impl AdvancedMEVFeatures {
             path: path.to_vec(),
             dexes: vec![DexType::UniswapV2; path.len().saturating_sub(1)],
             expected_profit: U256::from(10).pow(U256::from(17)), // Placeholder
-            gas_estimate: 300_000u64.saturating_mul(path.len() as u64),
-        })
-    }
-
-    fn is_liquidation(&self, tx: &Transaction) -> bool {
-        // Check if transaction has input data before accessing
-        if tx.input.0.len() < 4 {
-            return false;
-        }
-        
-        // Check if transaction is calling liquidation functions
-        let liquidation_sigs = vec![
-            "0x96cd4ddb", // Compound liquidateBorrow
-            "0x00a718a9", // Aave liquidationCall
-        ];
-        
-        let sig = &tx.input.0[..4];
-        liquidation_sigs.iter().any(|&ls| {
-            hex::decode(&ls[2..]).ok().map_or(false, |decoded| sig == decoded.as_slice())
+            gas_estimate: self.config.gas_model.arbitrage_gas(path.len().saturating_sub(1)),
         })
     }
 
@@ -213,17 +204,24 @@ impl AdvancedMEVFeatures {
         tx.value > threshold
     }
 
-    fn is_oracle_update(&self, tx: &Transaction) -> bool {
-        // Check if updating price oracles
-        let oracle_addresses = vec![
-            "0x5f4eC3Df9cbd43714FE2740f5E3616155c5b8419", // Chainlink ETH/USD
-        ];
-        
-        tx.to.map_or(false, |to| {
-            oracle_addresses.iter().any(|&oracle| {
-                oracle.parse::<Address>().ok().map_or(false, |addr| to == addr)
-            })
-        })
+    /// The configured feed `tx` updates, with its answer's decimals, if it goes to one of their aggregators.
+    async fn oracle_feed(&self, tx: &Transaction) -> Option<(OracleFeed, u8)> {
+        let to = tx.to?;
+        let aggregators = self.oracle_aggregators.get_or_init(|| self.resolve_aggregators()).await;
+        aggregators.get(&to).copied()
+    }
+
+    /// Current aggregator and decimals behind each configured feed proxy. An
+    /// address that doesn't answer `aggregator()` is taken to be the aggregator itself.
+    async fn resolve_aggregators(&self) -> HashMap<Address, (OracleFeed, u8)> {
+        let mut aggregators = HashMap::new();
+        for feed in &self.config.settings.oracle_feeds {
+            let proxy = AggregatorProxy::new(feed.proxy, self.config.http.clone());
+            let aggregator = proxy.aggregator().call().await.unwrap_or(feed.proxy);
+            let decimals = proxy.decimals().call().await.unwrap_or(oracle_backrun::USD_FEED_DECIMALS);
+            aggregators.insert(aggregator, (*feed, decimals));
+        }
+        aggregators
     }
 
     async fn calculate_liquidation_backrun(&self, tx: &Transaction) -> Option<BackrunOpportunity> {
@@ -244,12 +242,55 @@ impl AdvancedMEVFeatures {
         })
     }
 
-    async fn calculate_oracle_backrun(&self, tx: &Transaction) -> Option<BackrunOpportunity> {
+    /// Trades the feed's V2 pool to the `answer` `tx` is about to report. Assumes we
+    /// hold the token sold and value the gain at the new oracle price.
+    async fn calculate_oracle_backrun(&self, tx: &Transaction, answer: U256, (feed, feed_decimals): (OracleFeed, u8)) -> Option<BackrunOpportunity> {
+        let chain = &self.config.chain;
+        let pair = UniV2Factory::new(chain.v2_factory, self.config.http.clone())
+            .get_pair(feed.token, feed.quote)
+            .call()
+            .await
+            .ok()?;
+        if pair.is_zero() {
+            return None;
+        }
+        let (reserve_token, reserve_quote) = self.config.pool_cache.reserves(pair).await?.oriented(feed.token);
+
+        let token_decimals = self.config.token_info.decimals(feed.token).await;
+        let quote_decimals = self.config.token_info.decimals(feed.quote).await;
+        let price = OraclePrice::new(answer, feed_decimals, token_decimals, quote_decimals);
+        let arb = oracle_backrun::oracle_arb(feed.token, feed.quote, reserve_token, reserve_quote, price, V2_FEE_PPM)?;
+
+        let expected_profit = if feed.quote == chain.weth {
+            arb.profit_in_quote
+        } else if feed.token == chain.weth {
+            price.quote_to_token(arb.profit_in_quote)
+        } else {
+            self.price_oracle.to_eth(feed.quote, arb.profit_in_quote).await?
+        };
+
+        let mut execution_tx = TypedTransaction::default();
+        execution_tx
+            .set_to(chain.v2_router)
+            .set_from(self.config.http.address())
+            .set_data(
+                SwapExactTokensForTokensCall {
+                    amount_in: arb.amount_in,
+                    amount_out_min: arb.amount_out * (10_000 - ORACLE_BACKRUN_SLIPPAGE_BPS) / 10_000,
+                    path: vec![arb.token_in, arb.token_out],
+                    to: self.config.http.address(),
+                    deadline: self.config.swap_deadline(),
+                }
+                .encode()
+                .into(),
+            )
+            .set_gas(self.config.gas_model.estimates().backrun);
+
         Some(BackrunOpportunity {
             target_tx: tx.hash,
             strategy: BackrunStrategy::OracleUpdate,
-            expected_profit: U256::from(10).pow(U256::from(17)).saturating_mul(U256::from(3)),
-            execution_tx: TypedTransaction::default(),
+            expected_profit,
+            execution_tx,
         })
     }
 
@@ -257,19 +298,6 @@ impl AdvancedMEVFeatures {
         // Calculate price deviation between token pairs
         Some(0.01) // 1% deviation placeholder
     }
-
-    async fn monitor_bridge_arbitrage(&self, bridge: &str) -> Option<CrossChainOpportunity> {
-        bridge.parse::<Address>().ok().map(|bridge_address| {
-            CrossChainOpportunity {
-                source_chain: "ethereum".to_string(),
-                target_chain: "arbitrum".to_string(),
-                token: Address::zero(),
-                price_difference: 0.02,
-                bridge_address,
-                estimated_time: 600, // 10 minutes
-            }
-        })
-    }
 }
 
 // Additional types for advanced features
@@ -313,13 +341,3 @@ pub struct StatArbOpportunity {
     pub expected_reversion: f64,
     pub confidence: f64,
 }
-
-#[derive(Debug, Clone)]
-pub struct CrossChainOpportunity {
-    pub source_chain: String,
-    pub target_chain: String,
-    pub token: Address,
-    pub price_difference: f64,
-    pub bridge_address: Address,
-    pub estimated_time: u64,
-}
@@ -1,43 +1,65 @@
 use ethers::prelude::*;
+use ethers::abi::AbiDecode;
 use std::sync::Arc;
 use std::collections::HashMap;
-use crate::{Config, uni};
+use std::time::Instant;
+use crate::{Config, uni, uni_v3};
+use crate::address_book::{
+    LpPair, LpPairCalls, UniV2Factory, UniV2RouterCalls, UniV3Factory, UniV3Pool,
+};
+use crate::classifier::TxClass;
+use crate::helpers::eth_to_wei;
 use super::types::*;
+use super::dex_registry::DexRegistry;
+use super::price_oracle::PriceOracle;
 
 #[derive(Debug)]
 pub struct ArbitrageStrategy {
     config: Arc<Config>,
     dex_factories: HashMap<DexType, Vec<Address>>,
+    dex_routers: HashMap<DexType, Address>,
     min_profit_threshold: U256,
+    min_pool_liquidity: U256,
+    price_oracle: PriceOracle,
 }
 
 impl ArbitrageStrategy {
     pub fn new(config: Arc<Config>) -> Self {
-        let mut dex_factories = HashMap::new();
-        
-        // Initialize known DEX factories
-        dex_factories.insert(DexType::UniswapV2, vec![
-            "0x5C69bEe701ef814a2B6a3EDD4B1652CB9cc5aA6f".parse().unwrap(),
-        ]);
-        dex_factories.insert(DexType::SushiSwap, vec![
-            "0xC0AEe478e3658e2610c5F7A4A2E1777cE9e4f2Ac".parse().unwrap(),
-        ]);
-        dex_factories.insert(DexType::PancakeSwap, vec![
-            "0xcA143Ce32Fe78f1f7019d7d551a6402fC5350c73".parse().unwrap(),
-        ]);
+        let registry = config.chain.dex_registry(config.settings.dex_preset.as_deref());
+        Self::with_registry(config, registry)
+    }
 
+    /// Builds the strategy over an arbitrary set of DEXs, e.g. a chain preset or custom forks.
+    pub fn with_registry(config: Arc<Config>, registry: DexRegistry) -> Self {
         Self {
+            dex_factories: registry.factories,
+            dex_routers: registry.routers,
+            min_profit_threshold: eth_to_wei(config.settings.arbitrage_min_profit_eth),
+            min_pool_liquidity: eth_to_wei(config.settings.arbitrage_min_pool_liquidity_eth),
+            price_oracle: PriceOracle::new(config.http.clone(), config.pool_cache.clone(), &config.chain),
             config,
-            dex_factories,
-            min_profit_threshold: U256::from(10).pow(U256::from(17)), // 0.1 ETH
         }
     }
 
-    pub async fn analyze(&self, _tx: &Transaction) -> Vec<MEVOpportunity> {
+    /// Router to trade through on `dex`, if one is registered.
+    pub fn router_for(&self, dex: DexType) -> Option<Address> {
+        self.dex_routers.get(&dex).copied()
+    }
+
+    /// The one V2 router that can trade every hop in `pools`, if they're all on the same DEX.
+    fn shared_router(&self, pools: &[PoolInfo]) -> Option<Address> {
+        let dex = pools.first()?.dex_type;
+        if dex == DexType::UniswapV3 || pools.iter().any(|pool| pool.dex_type != dex) {
+            return None;
+        }
+        self.router_for(dex)
+    }
+
+    pub async fn analyze(&self, tx: &Transaction, class: &TxClass) -> Vec<MEVOpportunity> {
         let mut opportunities = Vec::new();
 
         // Extract token addresses from transaction
-        let tokens = self.extract_tokens_from_tx(_tx);
+        let tokens = self.extract_tokens_from_tx(tx, class).await;
         
         for token in tokens {
             // Check triangular arbitrage opportunities
@@ -56,12 +78,13 @@ impl ArbitrageStrategy {
 
     async fn find_triangular_arbitrage(&self, token: &Address) -> Option<MEVOpportunity> {
         // Common triangular paths: WETH -> Token -> USDC -> WETH
-        let weth: Address = "0xC02aaA39b223FE8D0A0e5C4F27eAD9083C756Cc2".parse().unwrap();
-        let usdc: Address = "0xA0b86991c6218b36c1d19D4a2e9Eb0cE3606eB48".parse().unwrap();
+        let weth = self.config.chain.weth;
+        let usdc = self.config.chain.usdc;
         
         let path = vec![weth, *token, usdc, weth];
         
         // Get pool info for each hop
+        let reserve_block = self.get_current_block().await;
         let mut pools = Vec::new();
         for i in 0..path.len()-1 {
             if let Some(pool_info) = self.get_pool_info(path[i], path[i+1], DexType::UniswapV2).await {
@@ -76,6 +99,7 @@ impl ArbitrageStrategy {
         let profit = self.calculate_arbitrage_profit(&path, &pools, test_amount);
         
         if profit.profit > self.min_profit_threshold {
+            let gas = self.config.gas_model.arbitrage_gas(pools.len());
             Some(MEVOpportunity {
                 id: format!("arb_tri_{}_{}", token, self.get_timestamp()),
                 target_tx: Transaction::default(), // Not directly tied to a tx
@@ -84,12 +108,17 @@ impl ArbitrageStrategy {
                     pools: pools.clone(),
                     amount_in: profit.optimal_amount,
                     expected_profit: profit.profit,
-                    gas_estimate: U256::from(400000),
+                    gas_estimate: U256::from(gas),
+                    router: self.shared_router(&pools),
                 }),
                 estimated_profit: profit.profit,
-                gas_cost: U256::from(400000) * U256::from(100) * U256::from(10).pow(U256::from(9)),
+                // Arbs are raced by every searcher watching the pool: price near the top of the block
+                gas_cost: self.config.gas_model.cost(gas, 90.0),
                 priority: 7,
-                expiry_block: self.get_current_block().await + 1,
+                reserve_block,
+                expiry_block: reserve_block + 1,
+                observed_at: Instant::now(),
+                landing_probability: 1.0,
             })
         } else {
             None
@@ -97,14 +126,15 @@ impl ArbitrageStrategy {
     }
 
     async fn find_cross_dex_arbitrage(&self, token: &Address) -> Option<MEVOpportunity> {
-        let weth: Address = "0xC02aaA39b223FE8D0A0e5C4F27eAD9083C756Cc2".parse().unwrap();
+        let weth = self.config.chain.weth;
         
         // Get prices across different DEXs
+        let reserve_block = self.get_current_block().await;
         let mut dex_prices = HashMap::new();
         
-        for (dex_type, _) in &self.dex_factories {
+        for dex_type in self.dex_factories.keys() {
             if let Some(pool_info) = self.get_pool_info(weth, *token, *dex_type).await {
-                let price = self.calculate_price(&pool_info, true);
+                let price = self.calculate_price(&pool_info, pool_info.token0 == weth);
                 dex_prices.insert(*dex_type, (price, pool_info));
             }
         }
@@ -115,15 +145,16 @@ impl ArbitrageStrategy {
 
         for (buy_dex, (buy_price, buy_pool)) in &dex_prices {
             for (sell_dex, (sell_price, sell_pool)) in &dex_prices {
-                if buy_dex != sell_dex && sell_price > buy_price {
-                    let price_diff_percent: U256 = ((sell_price - buy_price) * 10000) / buy_price;
+                if buy_dex != sell_dex && sell_price > buy_price && !buy_price.is_zero() {
+                    let price_diff_percent: U256 = (sell_price - buy_price).saturating_mul(U256::from(10000)) / buy_price;
                     
                     // Need at least 0.3% price difference to be profitable after gas
                     if price_diff_percent > U256::from(30) {
                         let optimal_amount = self.calculate_optimal_arb_amount(
                             buy_pool,
                             sell_pool,
-                            price_diff_percent.as_u64(),
+                            // Real pools can be wildly mispriced; past u64 the spread is moot anyway
+                            price_diff_percent.min(U256::from(u64::MAX)).low_u64(),
                         );
                         
                         let profit = self.simulate_cross_dex_arb(
@@ -147,6 +178,8 @@ impl ArbitrageStrategy {
 
         if let Some((path, pools, amount)) = best_opportunity {
             if best_profit > self.min_profit_threshold {
+                let router = self.shared_router(&pools);
+                let gas = self.config.gas_model.arbitrage_gas(pools.len());
                 return Some(MEVOpportunity {
                     id: format!("arb_cross_{}_{}", token, self.get_timestamp()),
                     target_tx: Transaction::default(),
@@ -155,12 +188,16 @@ impl ArbitrageStrategy {
                         pools,
                         amount_in: amount,
                         expected_profit: best_profit,
-                        gas_estimate: U256::from(350000),
+                        gas_estimate: U256::from(gas),
+                        router,
                     }),
                     estimated_profit: best_profit,
-                    gas_cost: U256::from(350000) * U256::from(100) * U256::from(10).pow(U256::from(9)),
+                    gas_cost: self.config.gas_model.cost(gas, 90.0),
                     priority: 8,
-                    expiry_block: self.get_current_block().await + 1,
+                    reserve_block,
+                    expiry_block: reserve_block + 1,
+                    observed_at: Instant::now(),
+                    landing_probability: 1.0,
                 });
             }
         }
@@ -181,13 +218,7 @@ impl ArbitrageStrategy {
             let token_in = path[i];
             let _token_out = path[i + 1];
             
-            let (amount_out, _, _) = if token_in == pool.token0 {
-                uni::get_amount_out(current_amount, pool.reserve0, pool.reserve1)
-            } else {
-                uni::get_amount_out(current_amount, pool.reserve1, pool.reserve0)
-            };
-            
-            current_amount = amount_out;
+            current_amount = Self::quote(pool, current_amount, token_in);
         }
         
         let profit = if current_amount > test_amount {
@@ -196,8 +227,12 @@ impl ArbitrageStrategy {
             U256::from(0)
         };
 
-        // Use binary search to find optimal amount
-        let optimal_amount = self.binary_search_optimal_amount(path, pools, profit > U256::from(0));
+        // Two-pool cycles have a closed-form optimum; longer paths fall back to binary search
+        let optimal_amount = if pools.len() == 2 {
+            Self::closed_form_optimal_amount(&pools[0], &pools[1], path[0])
+        } else {
+            self.binary_search_optimal_amount(path, pools, profit > U256::from(0))
+        };
         
         ArbitrageProfit {
             profit,
@@ -246,12 +281,31 @@ impl ArbitrageStrategy {
         sell_pool: &PoolInfo,
         _price_diff_basis_points: u64,
     ) -> U256 {
-        // Simplified optimal amount calculation
-        // In production, use more sophisticated math
-        let max_impact = U256::from(100); // 1% max price impact
-        let pool_liquidity = buy_pool.reserve0.min(sell_pool.reserve0);
-        
-        pool_liquidity * max_impact / 10000
+        Self::closed_form_optimal_amount(buy_pool, sell_pool, self.get_weth_address())
+    }
+
+    /// Optimal input of `token` for buying on `buy_pool` and selling back on `sell_pool`.
+    fn closed_form_optimal_amount(buy_pool: &PoolInfo, sell_pool: &PoolInfo, token: Address) -> U256 {
+        let (buy_reserve_in, buy_reserve_out) = Self::reserves_for(buy_pool, token);
+        let (sell_reserve_out, sell_reserve_in) = Self::reserves_for(sell_pool, token);
+
+        uni::optimal_arb_input(
+            buy_reserve_in,
+            buy_reserve_out,
+            buy_pool.fee,
+            sell_reserve_in,
+            sell_reserve_out,
+            sell_pool.fee,
+        )
+    }
+
+    /// Returns `(reserve of token_in, reserve of the other side)` for `pool`.
+    fn reserves_for(pool: &PoolInfo, token_in: Address) -> (U256, U256) {
+        if pool.token0 == token_in {
+            (pool.reserve0, pool.reserve1)
+        } else {
+            (pool.reserve1, pool.reserve0)
+        }
     }
 
     fn simulate_cross_dex_arb(
@@ -260,19 +314,14 @@ impl ArbitrageStrategy {
         buy_pool: &PoolInfo,
         sell_pool: &PoolInfo,
     ) -> U256 {
+        let weth = self.get_weth_address();
+        let token = if buy_pool.token0 == weth { buy_pool.token1 } else { buy_pool.token0 };
+
         // Buy on first DEX
-        let (tokens_bought, _, _) = uni::get_amount_out(
-            *amount,
-            buy_pool.reserve0,
-            buy_pool.reserve1,
-        );
+        let tokens_bought = Self::quote(buy_pool, *amount, weth);
         
         // Sell on second DEX
-        let (eth_received, _, _) = uni::get_amount_out(
-            tokens_bought,
-            sell_pool.reserve1,
-            sell_pool.reserve0,
-        );
+        let eth_received = Self::quote(sell_pool, tokens_bought, token);
         
         if eth_received > *amount {
             eth_received - amount
@@ -281,6 +330,21 @@ impl ArbitrageStrategy {
         }
     }
 
+    /// Output of swapping `amount_in` of `token_in` through `pool`, using V2 or V3 math as appropriate.
+    fn quote(pool: &PoolInfo, amount_in: U256, token_in: Address) -> U256 {
+        let zero_for_one = token_in == pool.token0;
+        if let Some(v3) = &pool.v3 {
+            return uni_v3::get_amount_out(amount_in, v3.sqrt_price_x96, v3.liquidity, v3.fee_tier, zero_for_one);
+        }
+
+        let (amount_out, _, _) = if zero_for_one {
+            uni::get_amount_out(amount_in, pool.reserve0, pool.reserve1)
+        } else {
+            uni::get_amount_out(amount_in, pool.reserve1, pool.reserve0)
+        };
+        amount_out
+    }
+
     fn calculate_price(&self, pool: &PoolInfo, is_token0_weth: bool) -> U256 {
         if is_token0_weth {
             (pool.reserve0 * U256::from(10).pow(U256::from(18))) / pool.reserve1
@@ -289,28 +353,169 @@ impl ArbitrageStrategy {
         }
     }
 
-    fn extract_tokens_from_tx(&self, _tx: &Transaction) -> Vec<Address> {
-        // Extract token addresses from transaction data
-        // This is simplified - in production, decode all relevant calls
-        Vec::new()
+    /// Non-WETH tokens touched by a V2/V3 router swap or a direct pair `swap`.
+    async fn extract_tokens_from_tx(&self, tx: &Transaction, class: &TxClass) -> Vec<Address> {
+        let path = match class {
+            TxClass::SwapV2(call) => Self::v2_swap_path(call.clone()),
+            TxClass::SwapV3(v3_swaps) => v3_swaps.iter().flat_map(|swap| swap.tokens.iter().copied()).collect(),
+            TxClass::Unknown if matches!(LpPairCalls::decode(&tx.input), Ok(LpPairCalls::Swap(_))) => {
+                self.pair_tokens(tx.to).await
+            }
+            _ => Vec::new(),
+        };
+
+        let weth = self.get_weth_address();
+        let mut tokens = Vec::new();
+        for token in path {
+            if token != weth && !tokens.contains(&token) {
+                tokens.push(token);
+            }
+        }
+
+        tokens
+    }
+
+    pub(crate) fn v2_swap_path(call: UniV2RouterCalls) -> Vec<Address> {
+        match call {
+            UniV2RouterCalls::SwapExactETHForTokens(c) => c.path,
+            UniV2RouterCalls::SwapExactETHForTokensSupportingFeeOnTransferTokens(c) => c.path,
+            UniV2RouterCalls::SwapExactTokensForETH(c) => c.path,
+            UniV2RouterCalls::SwapExactTokensForETHSupportingFeeOnTransferTokens(c) => c.path,
+            UniV2RouterCalls::SwapExactTokensForTokens(c) => c.path,
+            UniV2RouterCalls::SwapExactTokensForTokensSupportingFeeOnTransferTokens(c) => c.path,
+            UniV2RouterCalls::SwapETHForExactTokens(c) => c.path,
+            UniV2RouterCalls::SwapTokensForExactETH(c) => c.path,
+            UniV2RouterCalls::SwapTokensForExactTokens(c) => c.path,
+            _ => Vec::new(),
+        }
+    }
+
+    /// Tokens of the pair a direct `swap` call targets.
+    async fn pair_tokens(&self, pair_address: Option<Address>) -> Vec<Address> {
+        let Some(pair_address) = pair_address else {
+            return Vec::new();
+        };
+
+        let pair = LpPair::new(pair_address, self.config.http.clone());
+        match (pair.token_0().call().await, pair.token_1().call().await) {
+            (Ok(token0), Ok(token1)) => vec![token0, token1],
+            _ => Vec::new(),
+        }
+    }
+
+    /// The `token_a`/`token_b` pool on `dex`, unless it's too shallow to trade against.
+    async fn get_pool_info(&self, token_a: Address, token_b: Address, dex: DexType) -> Option<PoolInfo> {
+        let pool = if dex == DexType::UniswapV3 {
+            self.get_v3_pool_info(token_a, token_b).await?
+        } else {
+            self.get_v2_pool_info(token_a, token_b, dex).await?
+        };
+        if self.min_pool_liquidity.is_zero() {
+            return Some(pool);
+        }
+        let liquidity = match pool_weth_liquidity(&pool, self.get_weth_address()) {
+            Some(liquidity) => liquidity,
+            None => self.price_oracle.to_eth(pool.token0, pool.reserve0).await?,
+        };
+        (liquidity >= self.min_pool_liquidity).then_some(pool)
     }
 
-    async fn get_pool_info(&self, token0: Address, token1: Address, dex: DexType) -> Option<PoolInfo> {
-        // Get pool information from chain
-        // In production, this should query the actual pool contract
+    async fn get_v2_pool_info(&self, token_a: Address, token_b: Address, dex: DexType) -> Option<PoolInfo> {
+
+        // Resolve the pair from this DEX's factories; the first one that knows the pair wins
+        let factories = self.dex_factories.get(&dex)?;
+        let mut pair_address = Address::zero();
+        for factory_address in factories {
+            let factory = UniV2Factory::new(*factory_address, self.config.http.clone());
+            if let Ok(pair) = factory.get_pair(token_a, token_b).call().await {
+                if !pair.is_zero() {
+                    pair_address = pair;
+                    break;
+                }
+            }
+        }
+
+        if pair_address.is_zero() {
+            return None;
+        }
+
+        // Read this block's reserves, keeping token0/token1 in the pair's own ordering
+        let reserves = self.config.pool_cache.reserves(pair_address).await?;
+        let token0 = reserves.token0;
+        let token1 = if token0 == token_a { token_b } else { token_a };
+
         Some(PoolInfo {
-            address: Address::zero(),
+            address: pair_address,
             token0,
             token1,
-            reserve0: U256::from(1000000) * U256::from(10).pow(U256::from(18)),
-            reserve1: U256::from(2000000) * U256::from(10).pow(U256::from(18)),
-            fee: 30, // 0.3%
+            reserve0: reserves.reserve0,
+            reserve1: reserves.reserve1,
+            fee: Self::dex_fee_bps(dex),
             dex_type: dex,
+            v3: None,
         })
     }
 
+    /// Picks the V3 fee tier with the most in-range liquidity for the pair.
+    async fn get_v3_pool_info(&self, token_a: Address, token_b: Address) -> Option<PoolInfo> {
+        let factory_address = *self.dex_factories.get(&DexType::UniswapV3)?.first()?;
+        let factory = UniV3Factory::new(factory_address, self.config.http.clone());
+
+        let mut best: Option<PoolInfo> = None;
+        for fee_tier in uni_v3::FEE_TIERS {
+            let pool_address = match factory.get_pool(token_a, token_b, fee_tier).call().await {
+                Ok(pool) if !pool.is_zero() => pool,
+                _ => continue,
+            };
+
+            let pool = UniV3Pool::new(pool_address, self.config.http.clone());
+            let (Ok(slot0), Ok(liquidity), Ok(token0)) = (
+                pool.slot_0().call().await,
+                pool.liquidity().call().await,
+                pool.token_0().call().await,
+            ) else {
+                continue;
+            };
+
+            if best.as_ref().and_then(|b| b.v3.as_ref()).is_some_and(|b| b.liquidity >= liquidity) {
+                continue;
+            }
+
+            let sqrt_price_x96 = slot0.0;
+            let (reserve0, reserve1) = uni_v3::virtual_reserves(sqrt_price_x96, liquidity);
+            best = Some(PoolInfo {
+                address: pool_address,
+                token0,
+                token1: if token0 == token_a { token_b } else { token_a },
+                reserve0,
+                reserve1,
+                fee: (fee_tier / 100) as u16,
+                dex_type: DexType::UniswapV3,
+                v3: Some(V3PoolState {
+                    sqrt_price_x96,
+                    liquidity,
+                    fee_tier,
+                }),
+            });
+        }
+
+        best
+    }
+
+    /// Swap fee charged by each DEX, in basis points.
+    fn dex_fee_bps(dex: DexType) -> u16 {
+        match dex {
+            DexType::PancakeSwap => 25, // 0.25%
+            _ => 30, // 0.3%
+        }
+    }
+
+    fn get_weth_address(&self) -> Address {
+        self.config.chain.weth
+    }
+
     async fn get_current_block(&self) -> U64 {
-        self.config.http.get_block_number().await.unwrap_or_default()
+        self.config.block_height.get()
     }
 
     fn get_timestamp(&self) -> u64 {
@@ -326,3 +531,174 @@ struct ArbitrageProfit {
     profit: U256,
     optimal_amount: U256,
 }
+
+/// `pool`'s WETH reserve, if it holds WETH.
+pub fn pool_weth_liquidity(pool: &PoolInfo, weth: Address) -> Option<U256> {
+    if pool.token0 == weth {
+        Some(pool.reserve0)
+    } else if pool.token1 == weth {
+        Some(pool.reserve1)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethers::abi::AbiEncode;
+    use serde_json::json;
+
+    use crate::address_book::{SwapCall, SwapExactTokensForTokensCall};
+    use crate::classifier::classify;
+    use crate::settings::Settings;
+    use crate::test_support;
+
+    /// A mainnet fork pinned at a block, e.g. `anvil --fork-url <archive> --fork-block-number <n>`.
+    const FORK_RPC_ENV: &str = "FORK_RPC_URL";
+
+    fn token(n: u64) -> Address {
+        Address::from_low_u64_be(n)
+    }
+
+    fn pool(token0: Address, token1: Address, reserve0: u64, reserve1: u64) -> PoolInfo {
+        PoolInfo {
+            address: Address::random(),
+            token0,
+            token1,
+            reserve0: eth_to_wei(reserve0 as f64),
+            reserve1: eth_to_wei(reserve1 as f64),
+            fee: 30,
+            dex_type: DexType::UniswapV2,
+            v3: None,
+        }
+    }
+
+    #[test]
+    fn pool_weth_liquidity_reads_the_weth_side() {
+        let weth = token(1);
+        assert_eq!(pool_weth_liquidity(&pool(weth, token(2), 10, 20), weth), Some(eth_to_wei(10.0)));
+        assert_eq!(pool_weth_liquidity(&pool(token(2), weth, 10, 20), weth), Some(eth_to_wei(20.0)));
+        assert_eq!(pool_weth_liquidity(&pool(token(2), token(3), 10, 20), weth), None);
+    }
+
+    #[test]
+    fn closed_form_amount_beats_its_neighbours() {
+        let (weth, other) = (token(1), token(2));
+        // Tokens are cheaper on the buy pool; the sell pool lists them the other way round
+        let buy = pool(weth, other, 1_000, 2_200_000);
+        let sell = pool(other, weth, 2_000_000, 1_000);
+        let profit = |amount: U256| {
+            let bought = ArbitrageStrategy::quote(&buy, amount, weth);
+            ArbitrageStrategy::quote(&sell, bought, other).saturating_sub(amount)
+        };
+
+        let optimal = ArbitrageStrategy::closed_form_optimal_amount(&buy, &sell, weth);
+        assert!(!optimal.is_zero());
+        assert!(profit(optimal) > profit(optimal * 9 / 10));
+        assert!(profit(optimal) > profit(optimal * 11 / 10));
+    }
+
+    #[test]
+    fn closed_form_amount_is_zero_without_a_gap() {
+        let (weth, other) = (token(1), token(2));
+        let buy = pool(weth, other, 1_000, 2_000_000);
+        let sell = pool(other, weth, 2_000_000, 1_000);
+        assert!(ArbitrageStrategy::closed_form_optimal_amount(&buy, &sell, weth).is_zero());
+    }
+
+    #[tokio::test]
+    async fn the_tokens_a_victim_swaps_through_are_extracted_without_weth() {
+        let mut node = test_support::MockNode::new().await;
+        let config = Arc::new(test_support::config(&node.url(), test_support::settings()).await);
+        let strategy = ArbitrageStrategy::new(config);
+        let weth = strategy.get_weth_address();
+        let (dai, usdc) = (token(0xda1), token(0x05dc));
+
+        // DAI -> WETH -> USDC through the router, with WETH only as the hop
+        let input = SwapExactTokensForTokensCall {
+            amount_in: U256::exp10(21),
+            amount_out_min: U256::zero(),
+            path: vec![dai, weth, usdc],
+            to: token(0xbeef),
+            deadline: U256::MAX,
+        }
+        .encode();
+        let tx = Transaction { to: Some(token(0x7a25)), input: input.into(), ..Default::default() };
+        let class = classify(&tx);
+        assert_eq!(strategy.extract_tokens_from_tx(&tx, &class).await, vec![dai, usdc]);
+
+        // A direct `swap` on a pair asks the pair for its tokens
+        let pair = token(0x9a1);
+        for (selector, token) in [("0x0dfe1681", usdc), ("0xd21220a7", weth)] {
+            let request = json!({ "method": "eth_call", "params": [{ "to": pair, "data": selector }] });
+            node.rpc_matching(request, json!(H256::from(token))).await;
+        }
+        let input = LpPairCalls::Swap(SwapCall {
+            amount_0_out: U256::exp10(9),
+            amount_1_out: U256::zero(),
+            to: token(0xbeef),
+            data: Bytes::new(),
+        })
+        .encode();
+        let tx = Transaction { to: Some(pair), input: input.into(), ..Default::default() };
+        let class = classify(&tx);
+        assert_eq!(strategy.extract_tokens_from_tx(&tx, &class).await, vec![usdc]);
+    }
+
+    #[tokio::test]
+    async fn a_custom_dex_factory_is_consulted_for_cross_dex_arbitrage() {
+        let mut node = test_support::MockNode::new().await;
+        let settings = Settings { arbitrage_min_pool_liquidity_eth: 0.0, ..test_support::settings() };
+        let config = Arc::new(test_support::config(&node.url(), settings).await);
+        let weth = config.chain.weth;
+        // Sorts below WETH, so it's token0 of both pairs
+        let token_out = token(0x70c);
+        let fork = DexType::Custom(7);
+        let registry = DexRegistry::new(HashMap::new(), HashMap::new())
+            .with_dex(DexType::UniswapV2, token(0xf1), token(0xa1))
+            .with_dex(fork, token(0xf2), token(0xa2));
+
+        // The fork sells 10% more tokens per WETH than Uniswap buys them back at
+        for (factory, pair, token_reserve) in [(token(0xf1), token(0x9a1), 2_000_000), (token(0xf2), token(0x9a2), 2_200_000)] {
+            node.rpc_matching(json!({ "method": "eth_call", "params": [{ "to": factory }] }), json!(H256::from(pair))).await;
+            node.rpc_matching(json!({ "method": "eth_call", "params": [{ "to": pair, "data": "0x0dfe1681" }] }), json!(H256::from(token_out))).await;
+            let reserves = (eth_to_wei(token_reserve as f64), eth_to_wei(1_000.0), 0u32).encode();
+            node.rpc_matching(json!({ "method": "eth_call", "params": [{ "to": pair, "data": "0x0902f1ac" }] }), json!(Bytes::from(reserves))).await;
+        }
+        let strategy = ArbitrageStrategy::with_registry(config, registry);
+
+        let opportunity = strategy.find_cross_dex_arbitrage(&token_out).await.unwrap();
+        let StrategyType::Arbitrage(details) = opportunity.strategy_type else {
+            panic!("expected an arbitrage, got {:?}", opportunity.strategy_type);
+        };
+        let route: Vec<_> = details.pools.iter().map(|pool| (pool.dex_type, pool.address)).collect();
+        assert_eq!(route, vec![(fork, token(0x9a2)), (DexType::UniswapV2, token(0x9a1))]);
+        assert_eq!(details.path, vec![weth, token_out, weth]);
+        // Across two DEXs there's no one router to send it through
+        assert_eq!(details.router, None);
+    }
+
+    #[tokio::test]
+    #[ignore = "needs FORK_RPC_URL, a pinned mainnet fork"]
+    async fn reads_the_uniswap_weth_usdc_pair_from_a_fork() {
+        let url = std::env::var(FORK_RPC_ENV).expect("FORK_RPC_URL must point at a mainnet fork");
+        let head = Provider::<Http>::try_from(url.as_str()).unwrap().get_block_number().await.unwrap();
+        let settings = Settings { arbitrage_min_pool_liquidity_eth: 0.0, ..test_support::settings() };
+        let config = Arc::new(test_support::config_with(&url, HashMap::new(), settings, head.as_u64()).await);
+        let strategy = ArbitrageStrategy::new(config.clone());
+        let usdc: Address = "0xA0b86991c6218b36c1d19D4a2e9Eb0cE3606eB48".parse().unwrap();
+        let weth = strategy.get_weth_address();
+
+        let pool = strategy.get_pool_info(weth, usdc, DexType::UniswapV2).await.unwrap();
+        assert_eq!(pool.address, "0xB4e16d0168e52d35CaCD2c6185b44281Ec28C9Dc".parse().unwrap());
+        // The pair's own ordering, whichever way round we asked
+        assert_eq!((pool.token0, pool.token1), (usdc, weth));
+        let (reserve0, reserve1, _) = LpPair::new(pool.address, config.http.clone()).get_reserves().call().await.unwrap();
+        assert_eq!((pool.reserve0, pool.reserve1), (U256::from(reserve0), U256::from(reserve1)));
+        assert_eq!(pool.fee, 30);
+
+        // No factory knows a pair with a token that was never listed
+        assert!(strategy.get_pool_info(weth, token(0xdead), DexType::UniswapV2).await.is_none());
+    }
+}
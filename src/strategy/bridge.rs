@@ -0,0 +1,341 @@
+use ethers::abi::RawLog;
+use ethers::contract::EthLogDecode;
+use ethers::prelude::*;
+use std::sync::Arc;
+use tracing::{info, warn};
+
+use crate::address_book::{L1Bridge, L1BridgeEvents, LpPair, UniV2Factory};
+use crate::alert::alert;
+use crate::chain::ChainConfig;
+use crate::settings::Settings;
+use crate::Config;
+use super::price_oracle::PriceOracle;
+
+/// Which way tokens cross a bridge, relative to mainnet.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BridgeDirection {
+    /// Locked on mainnet, minted on the destination chain once the deposit arrives.
+    Deposit,
+    /// Released on mainnet after the withdrawal finalized.
+    Withdrawal,
+}
+
+/// A token transfer read off a bridge's deposit or withdrawal event.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BridgeTransfer {
+    pub bridge: Address,
+    pub tx_hash: Option<H256>,
+    pub direction: BridgeDirection,
+    pub l1_token: Address,
+    /// Only OP-stack bridges name the L2 token in the event.
+    pub l2_token: Option<Address>,
+    pub from: Address,
+    pub to: Address,
+    pub amount: U256,
+}
+
+/// Decodes an OP-stack `ERC20DepositInitiated`/`ERC20WithdrawalFinalized` or an
+/// Arbitrum gateway `DepositInitiated`/`WithdrawalFinalized` log.
+pub fn decode_bridge_log(log: &Log) -> Option<BridgeTransfer> {
+    let raw = RawLog { topics: log.topics.clone(), data: log.data.to_vec() };
+    let (direction, l1_token, l2_token, from, to, amount) = match L1BridgeEvents::decode_log(&raw).ok()? {
+        L1BridgeEvents::Erc20DepositInitiatedFilter(event) => {
+            (BridgeDirection::Deposit, event.l_1_token, Some(event.l_2_token), event.from, event.to, event.amount)
+        }
+        L1BridgeEvents::Erc20WithdrawalFinalizedFilter(event) => {
+            (BridgeDirection::Withdrawal, event.l_1_token, Some(event.l_2_token), event.from, event.to, event.amount)
+        }
+        L1BridgeEvents::DepositInitiatedFilter(event) => {
+            (BridgeDirection::Deposit, event.l_1_token, None, event.from, event.to, event.amount)
+        }
+        L1BridgeEvents::WithdrawalFinalizedFilter(event) => {
+            (BridgeDirection::Withdrawal, event.l_1_token, None, event.from, event.to, event.amount)
+        }
+    };
+
+    Some(BridgeTransfer {
+        bridge: log.address,
+        tx_hash: log.transaction_hash,
+        direction,
+        l1_token,
+        l2_token,
+        from,
+        to,
+        amount,
+    })
+}
+
+/// What crossing a bridge costs, and how much must be left over to act on it.
+#[derive(Debug, Clone, Copy)]
+pub struct BridgeCosts {
+    /// Bridge and relayer fees, in bps of the amount.
+    pub fee_bps: u64,
+    /// Price risk of holding the position while it crosses, in bps per hour.
+    pub time_cost_bps_per_hour: u64,
+    /// Differential left after costs needed to report an opportunity.
+    pub min_net_bps: i64,
+}
+
+impl BridgeCosts {
+    pub fn from_settings(settings: &Settings) -> Self {
+        Self {
+            fee_bps: settings.bridge_fee_bps,
+            time_cost_bps_per_hour: settings.bridge_time_cost_bps_per_hour,
+            min_net_bps: settings.bridge_min_net_bps as i64,
+        }
+    }
+
+    /// `differential_bps` (either sign) minus fees and the cost of `time_secs` in transit.
+    pub fn net_bps(&self, differential_bps: i64, time_secs: u64) -> i64 {
+        let time_cost = self.time_cost_bps_per_hour * time_secs / 3600;
+        differential_bps.abs() - self.fee_bps as i64 - time_cost as i64
+    }
+}
+
+/// A token priced differently on both ends of a bridge it is crossing.
+#[derive(Debug, Clone)]
+pub struct CrossChainOpportunity {
+    pub source_chain: String,
+    pub target_chain: String,
+    pub token: Address,
+    pub amount: U256,
+    /// Target chain price over source chain price, minus one.
+    pub price_difference: f64,
+    /// What is left of the difference after bridge fees and transit time.
+    pub net_bps: i64,
+    pub bridge_address: Address,
+    pub source_tx: Option<H256>,
+    pub estimated_time: u64,
+}
+
+/// `to_value` relative to `from_value` in bps; positive when the token is dearer at `to`.
+pub fn differential_bps(from_value: U256, to_value: U256) -> Option<i64> {
+    if from_value.is_zero() {
+        return None;
+    }
+    let bps = |delta: U256| -> Option<i64> { i64::try_from((delta.full_mul(U256::from(10_000)) / from_value).low_u128()).ok() };
+    if to_value >= from_value {
+        bps(to_value - from_value)
+    } else {
+        bps(from_value - to_value).map(|bps| -bps)
+    }
+}
+
+/// The opportunity in `transfer`, given what its amount is worth in ETH on
+/// mainnet and on `dest`, if the differential clears `costs`.
+pub fn evaluate(
+    transfer: &BridgeTransfer,
+    mainnet_value: U256,
+    dest_value: U256,
+    dest: &ChainConfig,
+    costs: &BridgeCosts,
+) -> Option<CrossChainOpportunity> {
+    let (source_chain, target_chain, from_value, to_value, estimated_time) = match transfer.direction {
+        BridgeDirection::Deposit => ("mainnet", dest.name, mainnet_value, dest_value, dest.deposit_time_secs),
+        // Already released on mainnet: nothing left to wait for
+        BridgeDirection::Withdrawal => (dest.name, "mainnet", dest_value, mainnet_value, 0),
+    };
+    let differential = differential_bps(from_value, to_value)?;
+    let net_bps = costs.net_bps(differential, estimated_time);
+    if net_bps < costs.min_net_bps {
+        return None;
+    }
+
+    Some(CrossChainOpportunity {
+        source_chain: source_chain.to_string(),
+        target_chain: target_chain.to_string(),
+        token: transfer.l1_token,
+        amount: transfer.amount,
+        price_difference: differential as f64 / 10_000.0,
+        net_bps,
+        bridge_address: transfer.bridge,
+        source_tx: transfer.tx_hash,
+        estimated_time,
+    })
+}
+
+/// Follows deposits into and withdrawals out of the `BRIDGE_DEST_RPC` chain and
+/// prices each bridged token on both sides.
+#[derive(Debug)]
+pub struct BridgeMonitor {
+    config: Arc<Config>,
+    dest: Arc<Provider<Http>>,
+    dest_chain: ChainConfig,
+    price_oracle: PriceOracle,
+    costs: BridgeCosts,
+}
+
+impl BridgeMonitor {
+    /// A monitor for the chain at `BRIDGE_DEST_RPC`, if one is set, reachable and has bridges we know.
+    pub async fn from_config(config: Arc<Config>) -> Option<Self> {
+        let url = config.settings.bridge_dest_rpc.as_deref()?;
+        let dest = match Provider::<Http>::try_from(url) {
+            Ok(provider) => provider,
+            Err(e) => {
+                warn!("⚠️ Invalid BRIDGE_DEST_RPC {}: {}", url, e);
+                return None;
+            }
+        };
+        let chain_id = match dest.get_chainid().await {
+            Ok(chain_id) => chain_id.as_u64(),
+            Err(e) => {
+                warn!("⚠️ Could not reach BRIDGE_DEST_RPC {}: {}", url, e);
+                return None;
+            }
+        };
+        let Some(dest_chain) = ChainConfig::for_chain_id(chain_id).filter(|chain| !chain.l1_bridges.is_empty()) else {
+            warn!("⚠️ No known bridges into chain {}, bridge monitoring disabled", chain_id);
+            return None;
+        };
+
+        Some(Self {
+            price_oracle: PriceOracle::new(config.http.clone(), config.pool_cache.clone(), &config.chain),
+            costs: BridgeCosts::from_settings(&config.settings),
+            dest: Arc::new(dest),
+            dest_chain,
+            config,
+        })
+    }
+
+    /// Streams the bridges' events until the subscription drops.
+    pub async fn watch(self) {
+        let filter = Filter::new().address(self.dest_chain.l1_bridges.clone());
+        let mut logs = match self.config.wss.subscribe_logs(&filter).await {
+            Ok(logs) => logs,
+            Err(e) => {
+                warn!("⚠️ Bridge log subscription failed: {}", e);
+                return;
+            }
+        };
+
+        info!("🌉 Watching {} bridge(s) into {}", self.dest_chain.l1_bridges.len(), self.dest_chain.name);
+        while let Some(log) = logs.next().await {
+            let Some(opportunity) = self.monitor_bridge_arbitrage(&log).await else {
+                continue;
+            };
+            let token = self.config.token_info.resolve(opportunity.token).await;
+            let msg = format!(
+                "🌉 {} crossing {} -> {}: {:.2}% price difference, {} bps net after ~{}s",
+                token.format(opportunity.amount),
+                opportunity.source_chain,
+                opportunity.target_chain,
+                opportunity.price_difference * 100.0,
+                opportunity.net_bps,
+                opportunity.estimated_time
+            );
+            info!("{}", msg);
+            alert(&msg, &log.block_number.unwrap_or_default().as_u64()).await;
+        }
+    }
+
+    /// Prices the transfer in `log` on both chains and returns it if the gap pays for the crossing.
+    pub async fn monitor_bridge_arbitrage(&self, log: &Log) -> Option<CrossChainOpportunity> {
+        let transfer = decode_bridge_log(log)?;
+        if transfer.l1_token == self.config.chain.weth || transfer.amount.is_zero() {
+            return None;
+        }
+
+        let l2_token = match transfer.l2_token {
+            Some(l2_token) => l2_token,
+            None => L1Bridge::new(transfer.bridge, self.config.http.clone())
+                .calculate_l2_token_address(transfer.l1_token)
+                .call()
+                .await
+                .ok()?,
+        };
+        let mainnet_value = self.price_oracle.to_eth(transfer.l1_token, transfer.amount).await?;
+        let dest_value = self.dest_value(l2_token, transfer.amount).await?;
+
+        evaluate(&transfer, mainnet_value, dest_value, &self.dest_chain, &self.costs)
+    }
+
+    /// Spot value in ETH of `amount` of `token` on the destination chain's V2 DEX.
+    async fn dest_value(&self, token: Address, amount: U256) -> Option<U256> {
+        let pair = UniV2Factory::new(self.dest_chain.v2_factory, self.dest.clone())
+            .get_pair(token, self.dest_chain.weth)
+            .call()
+            .await
+            .ok()?;
+        if pair.is_zero() {
+            return None;
+        }
+
+        let pair = LpPair::new(pair, self.dest.clone());
+        let token0 = pair.token_0().call().await.ok()?;
+        let (reserve0, reserve1, _) = pair.get_reserves().call().await.ok()?;
+        let (token_reserve, weth_reserve) = if token0 == token {
+            (U256::from(reserve0), U256::from(reserve1))
+        } else {
+            (U256::from(reserve1), U256::from(reserve0))
+        };
+        PriceOracle::quote(amount, token_reserve, weth_reserve)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::address_book::{DepositInitiatedFilter, Erc20DepositInitiatedFilter};
+    use ethers::abi::{encode, Token};
+
+    fn addr(n: u64) -> Address {
+        Address::from_low_u64_be(n)
+    }
+
+    fn op_deposit(amount: u64) -> Log {
+        Log {
+            address: addr(0xb1),
+            topics: vec![Erc20DepositInitiatedFilter::signature(), addr(1).into(), addr(2).into(), addr(3).into()],
+            data: encode(&[Token::Address(addr(4)), Token::Uint(amount.into()), Token::Bytes(Vec::new())]).into(),
+            ..Default::default()
+        }
+    }
+
+    fn costs() -> BridgeCosts {
+        BridgeCosts { fee_bps: 10, time_cost_bps_per_hour: 12, min_net_bps: 20 }
+    }
+
+    #[test]
+    fn decodes_op_stack_and_arbitrum_deposits() {
+        let transfer = decode_bridge_log(&op_deposit(500)).unwrap();
+        assert_eq!(transfer.direction, BridgeDirection::Deposit);
+        assert_eq!((transfer.l1_token, transfer.l2_token, transfer.from, transfer.to), (addr(1), Some(addr(2)), addr(3), addr(4)));
+        assert_eq!((transfer.bridge, transfer.amount), (addr(0xb1), U256::from(500)));
+
+        let arbitrum = Log {
+            topics: vec![DepositInitiatedFilter::signature(), addr(3).into(), addr(4).into(), H256::from_low_u64_be(9)],
+            data: encode(&[Token::Address(addr(1)), Token::Uint(700.into())]).into(),
+            ..Default::default()
+        };
+        let transfer = decode_bridge_log(&arbitrum).unwrap();
+        assert_eq!((transfer.l1_token, transfer.l2_token, transfer.amount), (addr(1), None, U256::from(700)));
+
+        assert_eq!(decode_bridge_log(&Log::default()), None);
+    }
+
+    #[test]
+    fn differential_is_signed_by_which_end_is_dearer() {
+        assert_eq!(differential_bps(U256::from(1_000), U256::from(1_050)), Some(500));
+        assert_eq!(differential_bps(U256::from(1_000), U256::from(950)), Some(-500));
+        assert_eq!(differential_bps(U256::zero(), U256::from(950)), None);
+    }
+
+    #[test]
+    fn deposits_pay_for_their_time_in_transit() {
+        let transfer = decode_bridge_log(&op_deposit(500)).unwrap();
+        // Arbitrum deposits take 10 minutes: 2 bps of time cost, 10 of fees
+        let opportunity = evaluate(&transfer, U256::from(10_000), U256::from(10_050), &ChainConfig::arbitrum(), &costs()).unwrap();
+        assert_eq!((opportunity.source_chain.as_str(), opportunity.target_chain.as_str()), ("mainnet", "arbitrum"));
+        assert_eq!(opportunity.net_bps, 50 - 10 - 2);
+
+        assert!(evaluate(&transfer, U256::from(10_000), U256::from(10_030), &ChainConfig::arbitrum(), &costs()).is_none());
+    }
+
+    #[test]
+    fn withdrawals_compare_the_destination_against_mainnet() {
+        let transfer = BridgeTransfer { direction: BridgeDirection::Withdrawal, ..decode_bridge_log(&op_deposit(500)).unwrap() };
+        let opportunity = evaluate(&transfer, U256::from(10_050), U256::from(10_000), &ChainConfig::base(), &costs()).unwrap();
+        assert_eq!((opportunity.source_chain.as_str(), opportunity.target_chain.as_str()), ("base", "mainnet"));
+        assert_eq!((opportunity.net_bps, opportunity.estimated_time), (40, 0));
+    }
+}
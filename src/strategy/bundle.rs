@@ -1,75 +1,298 @@
+use ethers::abi::{AbiEncode, Token};
 use ethers::prelude::*;
 use ethers::types::transaction::eip2718::TypedTransaction;
 use ethers::utils::keccak256;
+use std::str::FromStr;
 use std::sync::Arc;
 use serde::{Serialize, Deserialize};
+use crate::address_book::{SwapExactETHForTokensCall, SwapExactTokensForTokensCall, UniV2RouterCalls, WithdrawCall, WETH9};
+use crate::executor;
+use crate::gas_oracle::GasOracle;
+use crate::metrics::metrics;
+use crate::signer::SignerClient;
+use crate::settings::Settings;
+use super::nonce::NonceManager;
 use super::types::*;
 
+/// Which provider's head a bundle's target block is derived from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlockSource {
+    /// The higher of the HTTP and WS heads; a lagging node can't make us target a past block.
+    Freshest,
+    Http,
+    Ws,
+}
+
+impl FromStr for BlockSource {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "freshest" => Ok(Self::Freshest),
+            "http" => Ok(Self::Http),
+            "ws" => Ok(Self::Ws),
+            other => Err(format!("unknown block source: {}", other)),
+        }
+    }
+}
+
+/// How the gas price of our bundle txs is chosen.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BidStrategy {
+    /// 80% of the break-even gas price.
+    ProfitShare,
+    /// A premium over the floor recent bundles were included at, capped at break-even.
+    Floor,
+}
+
+impl FromStr for BidStrategy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "profit_share" => Ok(Self::ProfitShare),
+            "floor" => Ok(Self::Floor),
+            other => Err(format!("unknown bid strategy: {}", other)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct BidPolicy {
+    pub strategy: BidStrategy,
+    /// Percentile of recent inclusion prices taken as the floor.
+    pub floor_percentile: f64,
+    /// Bid this far above the floor, in bps.
+    pub floor_premium_bps: u64,
+    /// Share of net profit paid to `block.coinbase` at the end of a bundle, in bps.
+    /// Zero without an executor, the only way we have to reach the builder.
+    pub coinbase_bribe_bps: u64,
+}
+
+impl BidPolicy {
+    pub fn from_settings(settings: &Settings) -> Self {
+        Self {
+            strategy: settings.gas.bid_strategy,
+            floor_percentile: settings.gas.bid_floor_percentile,
+            floor_premium_bps: settings.gas.bid_floor_premium_bps,
+            coinbase_bribe_bps: if settings.arb_executor_address.is_some() { settings.gas.coinbase_bribe_bps } else { 0 },
+        }
+    }
+}
+
+/// How a relay expects the signature over a request body to be computed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SigningMode {
+    /// `keccak256(body)`, signed as is.
+    RawKeccak,
+    /// The hex string of `keccak256(body)` under the EIP-191 personal-sign prefix,
+    /// as Flashbots and MEV-Share verify `X-Flashbots-Signature`.
+    Eip191,
+    /// `RelayRequest(bytes32 payloadHash)` typed data over `keccak256(body)`, in the
+    /// `RelayAuth` v1 domain on the signer's chain.
+    Eip712,
+}
+
+impl FromStr for SigningMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "raw_keccak" => Ok(Self::RawKeccak),
+            "eip191" => Ok(Self::Eip191),
+            "eip712" => Ok(Self::Eip712),
+            other => Err(format!("unknown signing mode: {}", other)),
+        }
+    }
+}
+
+/// Header Flashbots, and the builders that copy its API, read request signatures from.
+pub const FLASHBOTS_SIGNATURE_HEADER: &str = "X-Flashbots-Signature";
+
+/// Header a relay reads the request signature from, and how it's written there.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AuthHeader {
+    pub name: String,
+    /// `<signer address>:<signature>` rather than the signature alone.
+    pub with_address: bool,
+}
+
+impl AuthHeader {
+    /// `X-Flashbots-Signature: <signer address>:<signature>`.
+    pub fn flashbots() -> Self {
+        Self { name: FLASHBOTS_SIGNATURE_HEADER.to_string(), with_address: true }
+    }
+
+    /// Header value carrying `signature`, made by `signer`.
+    pub fn value(&self, signer: Address, signature: &str) -> String {
+        if self.with_address {
+            format!("{:?}:{}", signer, signature)
+        } else {
+            signature.to_string()
+        }
+    }
+}
+
+impl FromStr for AuthHeader {
+    type Err = String;
+
+    /// `flashbots`, a header name for the signature alone, or `<name>:address`
+    /// for `<signer address>:<signature>`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if s == "flashbots" {
+            return Ok(Self::flashbots());
+        }
+        let (name, with_address) = match s.split_once(':') {
+            Some((name, "address")) => (name, true),
+            Some((_, format)) => return Err(format!("unknown signature header format: {}", format)),
+            None => (s, false),
+        };
+        if name.is_empty() || !name.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'-' || b == b'_') {
+            return Err(format!("invalid signature header name: {:?}", name));
+        }
+        Ok(Self { name: name.to_string(), with_address })
+    }
+}
+
+/// How requests to one relay are authenticated: the digest signed over the
+/// body, and the header the signature goes out in.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RelayAuth {
+    pub signing_mode: SigningMode,
+    pub header: AuthHeader,
+}
+
+impl RelayAuth {
+    /// The header to send with request body `payload`, signed by `signer`.
+    pub fn sign(
+        &self,
+        signer: &Wallet<k256::ecdsa::SigningKey>,
+        payload: &str,
+    ) -> Result<(String, String), Box<dyn std::error::Error + Send + Sync>> {
+        let signature = signer.sign_hash(signing_digest(self.signing_mode, payload, signer.chain_id()))?;
+        let signature = format!("0x{}", hex::encode(signature.to_vec()));
+        Ok((self.header.name.clone(), self.header.value(signer.address(), &signature)))
+    }
+}
+
+/// Name and version of the EIP-712 domain `Eip712` signatures are made in.
+pub const EIP712_DOMAIN_NAME: &str = "RelayAuth";
+pub const EIP712_DOMAIN_VERSION: &str = "1";
+
+/// The hash a relay expects signed for request body `payload` under `mode`,
+/// by a signer on `chain_id`.
+pub fn signing_digest(mode: SigningMode, payload: &str, chain_id: u64) -> H256 {
+    let payload_hash = H256::from(keccak256(payload.as_bytes()));
+    match mode {
+        SigningMode::RawKeccak => payload_hash,
+        SigningMode::Eip191 => ethers::utils::hash_message(format!("{:?}", payload_hash)),
+        SigningMode::Eip712 => {
+            let struct_hash = keccak256(ethers::abi::encode(&[
+                Token::FixedBytes(keccak256("RelayRequest(bytes32 payloadHash)").to_vec()),
+                Token::FixedBytes(payload_hash.as_bytes().to_vec()),
+            ]));
+            let digest = [&[0x19, 0x01][..], eip712_domain_separator(chain_id).as_bytes(), &struct_hash[..]].concat();
+            H256::from(keccak256(digest))
+        }
+    }
+}
+
+/// `hashStruct` of `EIP712Domain(string name,string version,uint256 chainId)` with
+/// `EIP712_DOMAIN_NAME` and `EIP712_DOMAIN_VERSION` on `chain_id`.
+pub fn eip712_domain_separator(chain_id: u64) -> H256 {
+    H256::from(keccak256(ethers::abi::encode(&[
+        Token::FixedBytes(keccak256("EIP712Domain(string name,string version,uint256 chainId)").to_vec()),
+        Token::FixedBytes(keccak256(EIP712_DOMAIN_NAME).to_vec()),
+        Token::FixedBytes(keccak256(EIP712_DOMAIN_VERSION).to_vec()),
+        Token::Uint(U256::from(chain_id)),
+    ])))
+}
+
 #[derive(Debug)]
 pub struct BundleBuilder {
-    provider: Arc<SignerMiddleware<Provider<Http>, Wallet<k256::ecdsa::SigningKey>>>,
+    provider: Arc<SignerClient>,
+    ws_provider: Arc<Provider<Ws>>,
+    block_source: BlockSource,
+    gas_oracle: Arc<GasOracle>,
     flashbots_signer: Wallet<k256::ecdsa::SigningKey>,
     flashbots_relay: String,
+    relay_auth: RelayAuth,
+    nonces: Arc<NonceManager>,
+    /// Contract arbitrage routes run through atomically, if deployed.
+    arb_executor: Option<Address>,
+    /// The chain's wrapped native token.
+    weth: Address,
+    bid_policy: BidPolicy,
+}
+
+/// What a `BundleBuilder` submits through and prices with.
+#[derive(Debug)]
+pub struct BundleBuilderParams {
+    pub provider: Arc<SignerClient>,
+    pub ws_provider: Arc<Provider<Ws>>,
+    pub block_source: BlockSource,
+    pub gas_oracle: Arc<GasOracle>,
+    pub relay: String,
+    pub relay_auth: RelayAuth,
+    pub nonces: Arc<NonceManager>,
+    pub arb_executor: Option<Address>,
+    pub weth: Address,
+    pub bid_policy: BidPolicy,
 }
 
 impl BundleBuilder {
-    pub fn new(provider: Arc<SignerMiddleware<Provider<Http>, Wallet<k256::ecdsa::SigningKey>>>) -> Self {
+    pub fn new(params: BundleBuilderParams) -> Self {
         // Create a separate signer for Flashbots authentication
-        let flashbots_signer = Wallet::new(&mut rand::thread_rng());
+        let flashbots_signer = Wallet::new(&mut rand::thread_rng()).with_chain_id(params.provider.signer().chain_id());
         
         Self {
-            provider,
+            provider: params.provider,
+            ws_provider: params.ws_provider,
+            block_source: params.block_source,
+            gas_oracle: params.gas_oracle,
             flashbots_signer,
-            flashbots_relay: "https://relay.flashbots.net".to_string(),
+            flashbots_relay: params.relay,
+            relay_auth: params.relay_auth,
+            nonces: params.nonces,
+            arb_executor: params.arb_executor,
+            weth: params.weth,
+            bid_policy: params.bid_policy,
         }
     }
 
+    /// Oracle bundle txs are bid off.
+    pub fn gas_oracle(&self) -> &Arc<GasOracle> {
+        &self.gas_oracle
+    }
+
     pub async fn build_sandwich_bundle(
         &self,
         victim_tx: &Transaction,
         details: &SandwichDetails,
-        _estimated_profit: U256,
+        net_profit: U256,
     ) -> Result<Bundle, Box<dyn std::error::Error + Send + Sync>> {
-        let block_number = self.provider.get_block_number().await?;
-        let signer_address = self.provider.address();
+        let block_number = self.latest_block().await?;
+        let bribe_tx = self.coinbase_bribe_tx(net_profit);
+        let nonces = self.nonces.reserve(if bribe_tx.is_some() { 3 } else { 2 }).await?;
         
         // Prepare bundle transactions
         let mut bundle_txs = Vec::new();
         
         // 1. Frontrun transaction
-        let frontrun_signed = self.sign_transaction(details.frontrun_tx.clone()).await?;
-        bundle_txs.push(BundleTransaction {
-            signer: signer_address,
-            tx: frontrun_signed,
-            can_revert: false,
-        });
+        bundle_txs.push(self.own_bundle_tx(details.frontrun_tx.clone(), nonces[0]).await?);
         
-        // 2. Victim transaction (convert to TypedTransaction)
-        let mut victim_typed = TypedTransaction::default();
-        victim_typed.set_from(victim_tx.from)
-            .set_to(victim_tx.to.unwrap())
-            .set_value(victim_tx.value)
-            .set_data(victim_tx.input.clone())
-            .set_gas(victim_tx.gas)
-            .set_nonce(victim_tx.nonce);
-        
-        if let Some(gas_price) = victim_tx.gas_price {
-            victim_typed.set_gas_price(gas_price);
-        }
-        
-        bundle_txs.push(BundleTransaction {
-            signer: victim_tx.from,
-            tx: victim_typed,
-            can_revert: true,
-        });
+        // 2. Victim transactions, as their senders signed them
+        bundle_txs.push(victim_bundle_tx(victim_tx));
+        bundle_txs.extend(details.co_victims.iter().map(victim_bundle_tx));
         
         // 3. Backrun transaction
-        let backrun_signed = self.sign_transaction(details.backrun_tx.clone()).await?;
-        bundle_txs.push(BundleTransaction {
-            signer: signer_address,
-            tx: backrun_signed,
-            can_revert: false,
-        });
+        bundle_txs.push(self.own_bundle_tx(details.backrun_tx.clone(), nonces[1]).await?);
+
+        // 4. Builder payment, last so it only pays out if the sandwich landed
+        if let Some(bribe_tx) = bribe_tx {
+            bundle_txs.push(self.own_bundle_tx(bribe_tx, nonces[2]).await?);
+        }
         
         Ok(Bundle {
             txs: bundle_txs,
@@ -77,148 +300,418 @@ impl BundleBuilder {
         })
     }
 
+    /// Bundles `backrun_tx` right behind the victim.
+    pub async fn build_backrun_bundle(
+        &self,
+        victim_tx: &Transaction,
+        backrun_tx: &TypedTransaction,
+    ) -> Result<Bundle, Box<dyn std::error::Error + Send + Sync>> {
+        let block_number = self.latest_block().await?;
+        let nonce = self.nonces.reserve(1).await?[0];
+
+        Ok(Bundle {
+            txs: vec![victim_bundle_tx(victim_tx), self.own_bundle_tx(backrun_tx.clone(), nonce).await?],
+            block_number: block_number + 1,
+        })
+    }
+
     pub async fn build_arbitrage_tx(
         &self,
         details: &ArbitrageDetails,
-        _estimated_profit: U256,
+        estimated_profit: U256,
+        deadline: U256,
+    ) -> Result<TypedTransaction, Box<dyn std::error::Error + Send + Sync>> {
+        let mut tx = self.arbitrage_tx(details, estimated_profit, deadline).await?;
+        tx.set_nonce(self.nonces.reserve(1).await?[0]);
+        Ok(tx)
+    }
+
+    /// The arbitrage followed by a `block.coinbase` payment of `coinbase_bribe_bps`
+    /// of its net profit, as one bundle. Gas is bid on the profit the bribe leaves,
+    /// so gas and bribe together never exceed `estimated_profit`.
+    pub async fn build_arbitrage_bundle(
+        &self,
+        details: &ArbitrageDetails,
+        estimated_profit: U256,
+        deadline: U256,
+    ) -> Result<Bundle, Box<dyn std::error::Error + Send + Sync>> {
+        let block_number = self.latest_block().await?;
+        let bid_profit = estimated_profit.saturating_sub(coinbase_bribe(estimated_profit, self.bid_policy.coinbase_bribe_bps));
+        let arb_tx = self.arbitrage_tx(details, bid_profit, deadline).await?;
+        let gas_cost = details.gas_estimate * arb_tx.gas_price().unwrap_or_default();
+        let bribe_tx = self.coinbase_bribe_tx(estimated_profit.saturating_sub(gas_cost));
+        let nonces = self.nonces.reserve(if bribe_tx.is_some() { 2 } else { 1 }).await?;
+
+        let mut txs = vec![self.own_bundle_tx(arb_tx, nonces[0]).await?];
+        if let Some(bribe_tx) = bribe_tx {
+            txs.push(self.own_bundle_tx(bribe_tx, nonces[1]).await?);
+        }
+        Ok(Bundle { txs, block_number: block_number + 1 })
+    }
+
+    /// Share of net profit bundles pay to `block.coinbase`, in bps; 0 when they don't.
+    pub fn coinbase_bribe_bps(&self) -> u64 {
+        self.bid_policy.coinbase_bribe_bps
+    }
+
+    /// Whether bundles close with a `block.coinbase` payment.
+    pub fn pays_coinbase(&self) -> bool {
+        self.coinbase_bribe_bps() > 0
+    }
+
+    /// `payCoinbase` through the executor with `coinbase_bribe_bps` of `net_profit`,
+    /// priced at the base fee since the bribe itself is the builder's payment. `None`
+    /// when bribes are off or there's nothing to pay.
+    fn coinbase_bribe_tx(&self, net_profit: U256) -> Option<TypedTransaction> {
+        let bribe = coinbase_bribe(net_profit, self.bid_policy.coinbase_bribe_bps);
+        let arb_executor = self.arb_executor.filter(|_| !bribe.is_zero())?;
+        let mut tx = TypedTransaction::default();
+        tx.set_to(arb_executor)
+            .set_from(self.provider.address())
+            .set_value(bribe)
+            .set_data(executor::encode_pay_coinbase())
+            .set_gas(COINBASE_BRIBE_GAS)
+            .set_gas_price(self.gas_oracle.next_base_fee().unwrap_or_else(|| self.gas_oracle.gas_price(50.0)));
+        Some(tx)
+    }
+
+    /// The arbitrage tx, bidding gas against `profit`, without a nonce.
+    async fn arbitrage_tx(
+        &self,
+        details: &ArbitrageDetails,
+        profit: U256,
+        deadline: U256,
     ) -> Result<TypedTransaction, Box<dyn std::error::Error + Send + Sync>> {
         // Build an optimized arbitrage transaction
         let mut tx = TypedTransaction::default();
         
         // Set transaction parameters
-        tx.set_from(self.provider.address())
-            .set_to(details.pools[0].address) // First pool in path
-            .set_gas(details.gas_estimate)
-            .set_value(if details.path[0] == self.get_weth_address() { details.amount_in } else { U256::from(0) })
-            .set_data(self.encode_arbitrage_data(details)?);
+        // The executor trades all hops from its own balance in one call; a lone
+        // router can only take routes that stay on one DEX
+        if let Some(arb_executor) = self.arb_executor {
+            tx.set_to(arb_executor).set_value(U256::zero()).set_data(executor::encode_execute(details, deadline)?);
+        } else {
+            let router = details.router.ok_or("arbitrage route spans DEXes and no executor is configured")?;
+            tx.set_to(router)
+                .set_value(if details.path[0] == self.get_weth_address() { details.amount_in } else { U256::from(0) })
+                .set_data(encode_arbitrage_route(details, self.get_weth_address(), self.provider.address(), deadline)?);
+        }
+        tx.set_from(self.provider.address()).set_gas(details.gas_estimate);
         
         // Set competitive gas price
-        let gas_price = self.calculate_optimal_gas_price(_estimated_profit, details.gas_estimate).await?;
+        let gas_price = self.calculate_optimal_gas_price(profit, details.gas_estimate).await?;
         tx.set_gas_price(gas_price);
         
         Ok(tx)
     }
 
     pub async fn send_bundle(&self, bundle: Bundle) -> Result<TxHash, Box<dyn std::error::Error + Send + Sync>> {
-        // Serialize bundle for Flashbots
-        let bundle_body = self.serialize_bundle(&bundle).await?;
-        
-        // Sign the bundle with Flashbots signer
-        let signature = self.sign_bundle_body(&bundle_body)?;
-        
-        // Send to Flashbots relay
-        let response = self.submit_to_flashbots(bundle_body, signature, bundle.block_number).await?;
-        
-        // Parse bundle hash from response
-        if let Some(result) = response.result {
-            Ok(result.bundle_hash.parse()?)
-        } else {
-            Err("No bundle hash in response".into())
+        let result = self.post_signed_rpc("eth_sendBundle", serde_json::json!([serialize_bundle(&bundle)])).await?;
+        let Ok(result) = serde_json::from_value::<FlashbotsResult>(result) else {
+            return Err("No bundle hash in response".into());
+        };
+        metrics().bundles_submitted.inc();
+        Ok(result.bundle_hash.parse()?)
+    }
+
+    /// Head block per the configured source; bundles target the block after it.
+    async fn latest_block(&self) -> Result<U64, Box<dyn std::error::Error + Send + Sync>> {
+        match self.block_source {
+            BlockSource::Http => Ok(self.provider.get_block_number().await?),
+            BlockSource::Ws => Ok(self.ws_provider.get_block_number().await?),
+            BlockSource::Freshest => {
+                let (http, ws) = tokio::join!(self.provider.get_block_number(), self.ws_provider.get_block_number());
+                match (http, ws) {
+                    (Ok(http), Ok(ws)) => Ok(http.max(ws)),
+                    (Ok(block), Err(_)) | (Err(_), Ok(block)) => Ok(block),
+                    (Err(e), Err(_)) => Err(e.into()),
+                }
+            }
         }
     }
 
-    async fn sign_transaction(&self, mut tx: TypedTransaction) -> Result<TypedTransaction, Box<dyn std::error::Error + Send + Sync>> {
+    /// Hashes of our own txs in `bundle`, for spotting them on-chain.
+    pub fn own_tx_hashes(&self, bundle: &Bundle) -> Vec<H256> {
+        let signer = self.provider.address();
+        bundle.txs.iter().filter(|bundle_tx| bundle_tx.signer == signer).map(BundleTransaction::hash).collect()
+    }
+
+    /// Relay-side view of a submitted bundle via `flashbots_getBundleStats`.
+    pub async fn get_bundle_stats(
+        &self,
+        bundle_hash: TxHash,
+        block_number: U64,
+    ) -> Result<BundleStats, Box<dyn std::error::Error + Send + Sync>> {
+        let params = serde_json::json!([{
+            "bundleHash": format!("{:?}", bundle_hash),
+            "blockNumber": format!("0x{:x}", block_number.as_u64()),
+        }]);
+        Ok(serde_json::from_value(self.post_signed_rpc("flashbots_getBundleStats", params).await?)?)
+    }
+
+    /// Simulates `raw_txs` in order on top of the latest block via `eth_callBundle`,
+    /// as if mined in `block_number`. One result per tx.
+    pub async fn call_bundle(
+        &self,
+        raw_txs: &[Bytes],
+        block_number: U64,
+    ) -> Result<Vec<CallBundleTxResult>, Box<dyn std::error::Error + Send + Sync>> {
+        let params = serde_json::json!([{
+            "txs": raw_txs,
+            "blockNumber": format!("0x{:x}", block_number.as_u64()),
+            "stateBlockNumber": "latest",
+        }]);
+        let result = self.post_signed_rpc("eth_callBundle", params).await?;
+        Ok(serde_json::from_value(result.get("results").cloned().unwrap_or_default())?)
+    }
+
+    /// Sends `tx` on its own via the relay's `eth_sendPrivateTransaction`, so it
+    /// never reaches the public mempool. The relay keeps trying to include it
+    /// until `max_block_number`. `tx` must already carry its nonce.
+    pub async fn send_private_transaction(
+        &self,
+        tx: &TypedTransaction,
+        max_block_number: U64,
+    ) -> Result<TxHash, Box<dyn std::error::Error + Send + Sync>> {
+        let nonce = tx.nonce().copied().ok_or("private transaction has no nonce")?;
+        let raw_tx = self.sign_raw(tx, nonce).await?;
+        let params = serde_json::json!([{
+            "tx": raw_tx,
+            "maxBlockNumber": format!("0x{:x}", max_block_number.as_u64()),
+        }]);
+        Ok(serde_json::from_value(self.post_signed_rpc("eth_sendPrivateTransaction", params).await?)?)
+    }
+
+    /// `tx` filled with `nonce` and signed by our key, RLP-encoded for a bundle.
+    pub async fn sign_raw(&self, tx: &TypedTransaction, nonce: U256) -> Result<Bytes, Box<dyn std::error::Error + Send + Sync>> {
+        Ok(self.own_bundle_tx(tx.clone(), nonce).await?.raw)
+    }
+
+    /// `tx` filled with `nonce` and signed by our key, as a bundle entry.
+    async fn own_bundle_tx(
+        &self,
+        tx: TypedTransaction,
+        nonce: U256,
+    ) -> Result<BundleTransaction, Box<dyn std::error::Error + Send + Sync>> {
+        let tx = self.sign_transaction(tx, nonce).await?;
+        let signature = self.provider.signer().sign_transaction(&tx).await?;
+        Ok(BundleTransaction { signer: self.provider.address(), raw: tx.rlp_signed(&signature), tx, can_revert: false })
+    }
+
+    async fn sign_transaction(
+        &self,
+        mut tx: TypedTransaction,
+        nonce: U256,
+    ) -> Result<TypedTransaction, Box<dyn std::error::Error + Send + Sync>> {
+        tx.set_nonce(nonce);
         // Fill transaction details
         self.provider.fill_transaction(&mut tx, None).await?;
         
         Ok(tx)
     }
 
-    fn encode_arbitrage_data(&self, _details: &ArbitrageDetails) -> Result<Bytes, Box<dyn std::error::Error + Send + Sync>> {
-        // Encode the arbitrage swap data
-        // In production, this should encode proper router calls
-        Ok(Bytes::default())
-    }
-
     async fn calculate_optimal_gas_price(
         &self,
         profit: U256,
         gas_estimate: U256,
     ) -> Result<U256, Box<dyn std::error::Error + Send + Sync>> {
-        // Get base fee and priority fee
-        let base_fee = self.provider.get_block(BlockNumber::Latest)
-            .await?
-            .unwrap()
-            .base_fee_per_gas
-            .unwrap_or_default();
-        
-        // Calculate maximum viable gas price based on profit
-        let max_gas_price = profit / gas_estimate;
-        
-        // Use 80% of profit for gas to ensure profitability
-        let target_gas_price: U256 = max_gas_price * 80 / 100;
-        
-        // Ensure we pay at least base fee + priority
-        let min_gas_price = base_fee + U256::from(2_000_000_000); // 2 gwei priority
-        
-        Ok(target_gas_price.max(min_gas_price))
+        // Next block's base fee and a typical priority fee, per recent blocks
+        let min_gas_price = self.gas_oracle.gas_price(50.0);
+        match self.bid_policy.strategy {
+            BidStrategy::ProfitShare => Ok(optimal_gas_price(profit, gas_estimate, min_gas_price)?),
+            BidStrategy::Floor => {
+                let floor = self.gas_oracle.bid_floor(self.bid_policy.floor_percentile);
+                Ok(floor_gas_price(profit, gas_estimate, min_gas_price, floor, self.bid_policy.floor_premium_bps)?)
+            }
+        }
     }
 
-    async fn serialize_bundle(&self, bundle: &Bundle) -> Result<FlashbotsBundle, Box<dyn std::error::Error + Send + Sync>> {
-        let mut signed_transactions = Vec::new();
-        
-        for bundle_tx in &bundle.txs {
-            // Get raw signed transaction
-            let raw_tx = self.provider.signer().sign_transaction(&bundle_tx.tx).await?;
-            signed_transactions.push(format!("0x{}", hex::encode(raw_tx.to_vec())));
+    /// Calls `method` on the relay and returns its `result`. The relay checks the
+    /// signature against the exact body it receives. Failures count as `rpc_errors`.
+    async fn post_signed_rpc(
+        &self,
+        method: &'static str,
+        params: serde_json::Value,
+    ) -> Result<serde_json::Value, Box<dyn std::error::Error + Send + Sync>> {
+        let request_body = serde_json::to_string(&serde_json::json!({
+            "jsonrpc": "2.0",
+            "method": method,
+            "params": params,
+            "id": 1,
+        }))?;
+        let (auth_header, signature) = self.relay_auth.sign(&self.flashbots_signer, &request_body)?;
+
+        let response: Result<serde_json::Value, reqwest::Error> = async {
+            reqwest::Client::new()
+                .post(&self.flashbots_relay)
+                .header("Content-Type", "application/json")
+                .header(auth_header, signature)
+                .body(request_body)
+                .send()
+                .await?
+                .json()
+                .await
         }
-        
-        Ok(FlashbotsBundle {
-            signed_transactions,
-            block_number: format!("0x{:x}", bundle.block_number.as_u64()),
-            min_timestamp: None,
-            max_timestamp: None,
-            reverting_tx_hashes: Vec::new(),
-        })
+        .await;
+        let response = match response {
+            Ok(response) if response.get("error").is_none() => response,
+            failed => {
+                metrics().rpc_errors.with_label_values(&[method]).inc();
+                return Err(match failed {
+                    Ok(response) => format!("Flashbots error: {}", response["error"]).into(),
+                    Err(e) => e.into(),
+                });
+            }
+        };
+        Ok(response.get("result").cloned().unwrap_or_default())
     }
 
-    fn sign_bundle_body(&self, bundle: &FlashbotsBundle) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
-        // Create EIP-191 message
-        let message = serde_json::to_string(bundle)?;
-        let message_hash = keccak256(message.as_bytes());
-        
-        // Sign with Flashbots signer
-        let signature = self.flashbots_signer.sign_hash(H256::from(message_hash))?;
-        
-        Ok(format!("0x{}", hex::encode(signature.to_vec())))
+    fn get_weth_address(&self) -> Address {
+        self.weth
     }
 
-    async fn submit_to_flashbots(
-        &self,
-        bundle: FlashbotsBundle,
-        signature: String,
-        _target_block: U64,
-    ) -> Result<FlashbotsResponse, Box<dyn std::error::Error + Send + Sync>> {
-        let client = reqwest::Client::new();
-        
-        let request_body = FlashbotsRequest {
-            jsonrpc: "2.0".to_string(),
-            method: "eth_sendBundle".to_string(),
-            params: vec![bundle],
-            id: 1,
-        };
-        
-        let response = client
-            .post(&self.flashbots_relay)
-            .header("X-Flashbots-Signature", format!("{}:{}", self.flashbots_signer.address(), signature))
-            .json(&request_body)
-            .send()
+    /// A `WETH.withdraw(amount)` from our account, priced off the gas oracle.
+    pub fn build_unwrap_tx(&self, amount: U256) -> TypedTransaction {
+        let mut tx = TypedTransaction::default();
+        tx.set_to(self.get_weth_address())
+            .set_from(self.provider.address())
+            .set_data(WithdrawCall { wad: amount }.encode().into())
+            .set_gas(U256::from(UNWRAP_GAS))
+            .set_gas_price(self.gas_oracle.gas_price(50.0));
+        tx
+    }
+
+    /// Unwraps up to `amount` of our WETH to ETH, as much as we hold.
+    pub async fn unwrap_weth(&self, amount: U256) -> Result<Option<TxHash>, Box<dyn std::error::Error + Send + Sync>> {
+        let held = WETH9::new(self.get_weth_address(), self.provider.clone())
+            .balance_of(self.provider.address())
+            .call()
             .await?;
-        
-        let response_body: FlashbotsResponse = response.json().await?;
-        
-        if let Some(error) = response_body.error {
-            return Err(format!("Flashbots error: {:?}", error).into());
+        let amount = amount.min(held);
+        if amount.is_zero() {
+            return Ok(None);
         }
-        
-        Ok(response_body)
+        let pending = self.provider.send_transaction(self.build_unwrap_tx(amount), None).await?;
+        Ok(Some(pending.tx_hash()))
     }
+}
 
-    fn get_weth_address(&self) -> Address {
-        "0xC02aaA39b223FE8D0A0e5C4F27eAD9083C756Cc2".parse().unwrap()
+/// `victim_tx` in a bundle exactly as its sender signed and broadcast it.
+fn victim_bundle_tx(victim_tx: &Transaction) -> BundleTransaction {
+    BundleTransaction { signer: victim_tx.from, tx: victim_tx.into(), raw: victim_tx.rlp(), can_revert: true }
+}
+
+/// The `eth_sendBundle` params for `bundle`: each tx's signed raw bytes, in order.
+fn serialize_bundle(bundle: &Bundle) -> FlashbotsBundle {
+    FlashbotsBundle {
+        signed_transactions: bundle.txs.iter().map(|bundle_tx| format!("0x{}", hex::encode(&bundle_tx.raw))).collect(),
+        block_number: format!("0x{:x}", bundle.block_number.as_u64()),
+        min_timestamp: None,
+        max_timestamp: None,
+        reverting_tx_hashes: Vec::new(),
     }
 }
 
+/// Router calldata swapping `details.amount_in` along `details.path` back to `recipient`.
+///
+/// `amountOutMin` is the input plus the expected profit, so the tx reverts rather
+/// than closing the cycle at a loss. Routes starting from WETH are paid in ETH.
+pub fn encode_arbitrage_route(details: &ArbitrageDetails, weth: Address, recipient: Address, deadline: U256) -> Result<Bytes, String> {
+    if details.path.len() < 2 {
+        return Err("arbitrage path needs at least two tokens".to_string());
+    }
+    let amount_out_min = details.amount_in.saturating_add(details.expected_profit);
+
+    let call = if details.path[0] == weth {
+        UniV2RouterCalls::SwapExactETHForTokens(SwapExactETHForTokensCall {
+            amount_out_min,
+            path: details.path.clone(),
+            to: recipient,
+            deadline,
+        })
+    } else {
+        UniV2RouterCalls::SwapExactTokensForTokens(SwapExactTokensForTokensCall {
+            amount_in: details.amount_in,
+            amount_out_min,
+            path: details.path.clone(),
+            to: recipient,
+            deadline,
+        })
+    };
+    Ok(call.encode().into())
+}
+
+/// Gas price for a tx burning `gas_estimate` to net `profit`: 80% of the break-even
+/// price, but at least `min_gas_price` and never past break-even. Errors when even
+/// `min_gas_price` would eat the whole profit.
+pub fn optimal_gas_price(profit: U256, gas_estimate: U256, min_gas_price: U256) -> Result<U256, String> {
+    if gas_estimate.is_zero() {
+        return Err("gas estimate is zero".to_string());
+    }
+
+    let break_even = profit / gas_estimate;
+    if break_even <= min_gas_price {
+        return Err(format!(
+            "unprofitable: break-even gas price {} is not above the minimum {}",
+            break_even, min_gas_price
+        ));
+    }
+
+    // Use 80% of profit for gas to ensure profitability
+    let target = break_even * U256::from(80) / U256::from(100);
+    Ok(target.max(min_gas_price).min(break_even))
+}
+
+/// Gas price `premium_bps` above the inclusion `floor`, at least `min_gas_price`
+/// and never past break-even. Errors like `optimal_gas_price` when nothing pays.
+pub fn floor_gas_price(
+    profit: U256,
+    gas_estimate: U256,
+    min_gas_price: U256,
+    floor: U256,
+    premium_bps: u64,
+) -> Result<U256, String> {
+    if gas_estimate.is_zero() {
+        return Err("gas estimate is zero".to_string());
+    }
+
+    let break_even = profit / gas_estimate;
+    if break_even <= min_gas_price {
+        return Err(format!(
+            "unprofitable: break-even gas price {} is not above the minimum {}",
+            break_even, min_gas_price
+        ));
+    }
+
+    let target = floor.saturating_mul(U256::from(10_000 + premium_bps)) / 10_000;
+    Ok(target.max(min_gas_price).min(break_even))
+}
+
+/// Gas of the executor's `payCoinbase` closing a bribing bundle.
+pub const COINBASE_BRIBE_GAS: u64 = 30_000;
+
+/// `bps` of `net_profit`, paid to the block's builder.
+pub fn coinbase_bribe(net_profit: U256, bps: u64) -> U256 {
+    net_profit * U256::from(bps.min(10_000)) / 10_000
+}
+
+/// What closing a bundle netting `net_profit` with a bribe of `bps` costs: the
+/// bribe plus the gas of the tx paying it at `gas_price`. Zero when bribes are off.
+pub fn coinbase_bribe_cost(net_profit: U256, bps: u64, gas_price: U256) -> U256 {
+    if bps == 0 {
+        return U256::zero();
+    }
+    coinbase_bribe(net_profit, bps) + U256::from(COINBASE_BRIBE_GAS) * gas_price
+}
+
+/// Gas of a standalone `WETH.withdraw`.
+pub const UNWRAP_GAS: u64 = 45_000;
+
+/// What it costs to unwrap WETH profit to ETH at `gas_price`.
+pub fn unwrap_cost(gas_price: U256) -> U256 {
+    U256::from(UNWRAP_GAS) * gas_price
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 struct FlashbotsBundle {
     #[serde(rename = "txs")]
@@ -233,20 +726,42 @@ struct FlashbotsBundle {
     reverting_tx_hashes: Vec<String>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-struct FlashbotsRequest {
-    jsonrpc: String,
-    method: String,
-    params: Vec<FlashbotsBundle>,
-    id: u64,
+/// Subset of `flashbots_getBundleStats` we act on.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct BundleStats {
+    #[serde(rename = "isSimulated", default)]
+    pub is_simulated: bool,
+    #[serde(rename = "isHighPriority", default)]
+    pub is_high_priority: bool,
+    #[serde(rename = "consideredByBuildersAt", default)]
+    pub considered_by_builders_at: Vec<serde_json::Value>,
+    #[serde(rename = "sealedByBuildersAt", default)]
+    pub sealed_by_builders_at: Vec<serde_json::Value>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-struct FlashbotsResponse {
-    jsonrpc: String,
-    id: u64,
-    result: Option<FlashbotsResult>,
-    error: Option<FlashbotsError>,
+/// One tx's outcome in an `eth_callBundle` simulation.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CallBundleTxResult {
+    #[serde(rename = "txHash")]
+    pub tx_hash: H256,
+    #[serde(rename = "gasUsed", default)]
+    pub gas_used: u64,
+    /// Set when the tx failed outside the EVM, e.g. a bad nonce or too little balance.
+    #[serde(default)]
+    pub error: Option<String>,
+    /// Set when the tx reverted.
+    #[serde(default)]
+    pub revert: Option<String>,
+    /// What the call returned.
+    #[serde(default)]
+    pub value: Option<Bytes>,
+}
+
+impl CallBundleTxResult {
+    /// Why the tx didn't execute, if it didn't.
+    pub fn failure(&self) -> Option<&str> {
+        self.revert.as_deref().or(self.error.as_deref())
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -255,8 +770,223 @@ struct FlashbotsResult {
     bundle_hash: String,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-struct FlashbotsError {
-    code: i32,
-    message: String,
-} 
\ No newline at end of file
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use mockito::Matcher;
+    use serde_json::json;
+
+    use super::*;
+    use crate::test_support::{self, MockNode};
+
+    fn signer() -> Wallet<k256::ecdsa::SigningKey> {
+        "4c0883a69102937d6231471b5dbb6204fe5129617082792ae468d01a3f362318".parse().unwrap()
+    }
+
+    /// An EIP-1559 tx signed by `wallet`, as a node returns it from the mempool.
+    fn signed_tx(wallet: &LocalWallet, nonce: u64) -> Transaction {
+        let tx: TypedTransaction = Eip1559TransactionRequest::new()
+            .to(Address::from_low_u64_be(0xbeef))
+            .value(1)
+            .nonce(nonce)
+            .gas(21_000)
+            .max_fee_per_gas(2)
+            .max_priority_fee_per_gas(1)
+            .chain_id(1)
+            .into();
+        let signature = wallet.sign_transaction_sync(&tx).unwrap();
+        let mut signed: Transaction = ethers::utils::rlp::decode(&tx.rlp_signed(&signature)).unwrap();
+        signed.from = wallet.address();
+        signed
+    }
+
+    fn signature_of(header_value: &str) -> Signature {
+        let (_, signature) = header_value.split_once(':').unwrap();
+        signature.parse().unwrap()
+    }
+
+    #[test]
+    fn eip191_signature_recovers_the_signer_the_way_flashbots_checks_it() {
+        let body = r#"{"jsonrpc":"2.0","method":"eth_sendBundle","params":[],"id":1}"#;
+        let auth = RelayAuth { signing_mode: SigningMode::Eip191, header: AuthHeader::flashbots() };
+        let (name, value) = auth.sign(&signer(), body).unwrap();
+        assert_eq!(name, FLASHBOTS_SIGNATURE_HEADER);
+        assert!(value.starts_with(&format!("{:?}:", signer().address())));
+
+        // The relay personal-sign recovers over the hex of keccak256(body)
+        let message = format!("{:?}", H256::from(keccak256(body.as_bytes())));
+        assert_eq!(signature_of(&value).recover(message).unwrap(), signer().address());
+    }
+
+    #[test]
+    fn raw_keccak_signature_recovers_over_the_body_hash() {
+        let body = "{}";
+        let auth = RelayAuth { signing_mode: SigningMode::RawKeccak, header: AuthHeader::flashbots() };
+        let (_, value) = auth.sign(&signer(), body).unwrap();
+        let digest = H256::from(keccak256(body.as_bytes()));
+        assert_eq!(signature_of(&value).recover(digest).unwrap(), signer().address());
+    }
+
+    #[test]
+    fn eip712_signature_recovers_over_the_typed_data_hash() {
+        let body = "{}";
+        let signer = signer().with_chain_id(8453u64);
+        let auth = RelayAuth { signing_mode: SigningMode::Eip712, header: AuthHeader::flashbots() };
+        let (_, value) = auth.sign(&signer, body).unwrap();
+        assert_eq!(signature_of(&value).recover(signing_digest(SigningMode::Eip712, body, 8453)).unwrap(), signer.address());
+
+        // Domain-bound: the same body on another chain is a different digest
+        assert_ne!(signing_digest(SigningMode::Eip712, body, 1), signing_digest(SigningMode::Eip712, body, 8453));
+    }
+
+    #[test]
+    fn eip712_domain_separator_matches_the_ethers_typed_data_domain() {
+        let domain = ethers::types::transaction::eip712::EIP712Domain {
+            name: Some(EIP712_DOMAIN_NAME.to_string()),
+            version: Some(EIP712_DOMAIN_VERSION.to_string()),
+            chain_id: Some(U256::from(1)),
+            verifying_contract: None,
+            salt: None,
+        };
+        assert_eq!(eip712_domain_separator(1), H256::from(domain.separator()));
+    }
+
+    #[test]
+    fn signing_modes_parse() {
+        assert_eq!("raw_keccak".parse::<SigningMode>().unwrap(), SigningMode::RawKeccak);
+        assert_eq!("eip191".parse::<SigningMode>().unwrap(), SigningMode::Eip191);
+        assert_eq!("eip712".parse::<SigningMode>().unwrap(), SigningMode::Eip712);
+        assert!("eip-712".parse::<SigningMode>().is_err());
+    }
+
+    #[test]
+    fn auth_header_parses_names_and_formats() {
+        assert_eq!("flashbots".parse::<AuthHeader>().unwrap(), AuthHeader::flashbots());
+        assert_eq!(
+            "X-Builder-Sig".parse::<AuthHeader>().unwrap(),
+            AuthHeader { name: "X-Builder-Sig".to_string(), with_address: false }
+        );
+        assert!("X-Builder-Sig:address".parse::<AuthHeader>().unwrap().with_address);
+        assert!("X-Builder-Sig:other".parse::<AuthHeader>().is_err());
+        assert!("bad header".parse::<AuthHeader>().is_err());
+    }
+
+    #[test]
+    fn victims_go_out_as_their_senders_signed_them() {
+        let victim = signed_tx(&signer(), 7);
+        let bundle_tx = victim_bundle_tx(&victim);
+        assert_eq!(bundle_tx.signer, signer().address());
+        assert_eq!(bundle_tx.hash(), victim.hash);
+        assert_eq!(bundle_tx.tx.nonce(), Some(&U256::from(7)));
+        assert!(bundle_tx.can_revert);
+    }
+
+    #[test]
+    fn serialized_bundles_carry_each_signed_raw_tx_in_order() {
+        let (first, second) = (signed_tx(&signer(), 1), signed_tx(&signer(), 2));
+        let bundle = Bundle { txs: vec![victim_bundle_tx(&first), victim_bundle_tx(&second)], block_number: U64::from(0x10) };
+
+        let serialized = serialize_bundle(&bundle);
+        assert_eq!(serialized.block_number, "0x10");
+        assert_eq!(serialized.signed_transactions, vec![
+            format!("0x{}", hex::encode(first.rlp())),
+            format!("0x{}", hex::encode(second.rlp())),
+        ]);
+        // A raw tx the relay can decode back into the one that was broadcast
+        let decoded: Transaction = ethers::utils::rlp::decode(&hex::decode(&serialized.signed_transactions[0][2..]).unwrap()).unwrap();
+        assert_eq!(decoded.hash, first.hash);
+    }
+
+    #[tokio::test]
+    async fn relay_calls_are_signed_and_return_the_result() {
+        let node = MockNode::new().await;
+        let mut relay = MockNode::new().await;
+        let bundle_hash = H256::from_low_u64_be(0xb0);
+        let send = relay
+            .server
+            .mock("POST", "/")
+            .match_header(FLASHBOTS_SIGNATURE_HEADER, Matcher::Regex("^0x[0-9a-f]{40}:0x[0-9a-f]{130}$".to_string()))
+            .match_body(Matcher::PartialJson(json!({ "method": "eth_sendBundle", "params": [{ "blockNumber": "0x65" }] })))
+            .with_body(json!({ "jsonrpc": "2.0", "id": 1, "result": { "bundleHash": bundle_hash } }).to_string())
+            .create_async()
+            .await;
+        let config = test_support::config(&node.url(), test_support::settings()).await;
+        let builder = test_support::bundle_builder(&config, &relay.url());
+
+        let sent = builder.send_bundle(Bundle { txs: Vec::new(), block_number: U64::from(101) }).await.unwrap();
+        assert_eq!(sent, bundle_hash);
+        send.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn relay_error_responses_are_errors() {
+        let node = MockNode::new().await;
+        let mut relay = MockNode::new().await;
+        let _call = relay
+            .server
+            .mock("POST", "/")
+            .with_body(json!({ "jsonrpc": "2.0", "id": 1, "error": { "code": -32000, "message": "unknown block" } }).to_string())
+            .create_async()
+            .await;
+        let config = test_support::config(&node.url(), test_support::settings()).await;
+        let builder = test_support::bundle_builder(&config, &relay.url());
+
+        let error = builder.call_bundle(&[], U64::from(101)).await.unwrap_err();
+        assert!(error.to_string().contains("unknown block"));
+    }
+
+    #[tokio::test]
+    async fn a_lagging_http_node_cannot_make_bundles_target_a_past_block() {
+        let mut node = MockNode::new().await;
+        node.rpc("eth_blockNumber", json!("0x64")).await;
+        // WS has seen ten more blocks than HTTP
+        let ws_results = HashMap::from([("eth_blockNumber".to_string(), json!("0x6e"))]);
+        let config = test_support::config_with(&node.url(), ws_results, test_support::settings(), 100).await;
+        let mut builder = test_support::bundle_builder(&config, &node.url());
+
+        assert_eq!(builder.latest_block().await.unwrap(), U64::from(100));
+        builder.block_source = BlockSource::Freshest;
+        assert_eq!(builder.latest_block().await.unwrap(), U64::from(110));
+    }
+
+    #[tokio::test]
+    async fn the_freshest_source_falls_back_to_whichever_provider_answers() {
+        // The HTTP node answers nothing
+        let node = MockNode::new().await;
+        let ws_results = HashMap::from([("eth_blockNumber".to_string(), json!("0x6e"))]);
+        let config = test_support::config_with(&node.url(), ws_results, test_support::settings(), 100).await;
+        let mut builder = test_support::bundle_builder(&config, &node.url());
+        builder.block_source = BlockSource::Freshest;
+
+        assert_eq!(builder.latest_block().await.unwrap(), U64::from(110));
+    }
+
+    #[test]
+    fn optimal_gas_price_bids_80_percent_of_break_even() {
+        let gwei = U256::exp10(9);
+        // 0.01 ETH over 200k gas breaks even at 50 gwei
+        let profit = U256::exp10(16);
+        let gas = U256::from(200_000);
+        assert_eq!(optimal_gas_price(profit, gas, gwei * 10).unwrap(), gwei * 40);
+        // The minimum wins over 80%, but break-even still caps it
+        assert_eq!(optimal_gas_price(profit, gas, gwei * 45).unwrap(), gwei * 45);
+    }
+
+    #[test]
+    fn optimal_gas_price_rejects_zero_gas_and_unprofitable_bids() {
+        let gwei = U256::exp10(9);
+        assert!(optimal_gas_price(U256::exp10(16), U256::zero(), gwei).is_err());
+        assert!(optimal_gas_price(U256::exp10(16), U256::from(200_000), gwei * 50).is_err());
+    }
+
+    #[test]
+    fn coinbase_bribe_pays_its_share_plus_the_gas_to_send_it() {
+        let net_profit = U256::from(1_000_000);
+        assert_eq!(coinbase_bribe(net_profit, 2_500), U256::from(250_000));
+        // Capped at all of it
+        assert_eq!(coinbase_bribe(net_profit, 20_000), net_profit);
+        assert_eq!(coinbase_bribe_cost(net_profit, 2_500, U256::from(2)), U256::from(250_000 + 2 * COINBASE_BRIBE_GAS));
+        assert_eq!(coinbase_bribe_cost(net_profit, 0, U256::from(2)), U256::zero());
+    }
+}
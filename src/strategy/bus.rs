@@ -0,0 +1,84 @@
+use ethers::prelude::*;
+use tokio::sync::broadcast;
+
+use super::types::MEVOpportunity;
+
+/// Opportunities a slow consumer may fall behind by before it starts missing them.
+const BUS_CAPACITY: usize = 1024;
+
+/// An opportunity that cleared analysis, with the profit it is ranked on.
+#[derive(Debug, Clone)]
+pub struct PublishedOpportunity {
+    pub opportunity: MEVOpportunity,
+    /// Profit reaching our ETH balance, after unwrapping and any builder payment.
+    pub expected_profit: U256,
+}
+
+/// Fans opportunities out from the analyzers to whatever consumes them (the
+/// executor, and any logging or bookkeeping added later), so analysis doesn't
+/// know who acts on what it finds. Every subscriber sees every opportunity
+/// published after it subscribed.
+#[derive(Debug, Clone)]
+pub struct OpportunityBus {
+    sender: broadcast::Sender<PublishedOpportunity>,
+}
+
+impl Default for OpportunityBus {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl OpportunityBus {
+    pub fn new() -> Self {
+        Self { sender: broadcast::channel(BUS_CAPACITY).0 }
+    }
+
+    /// Hands `opportunity` to every current subscriber; returns how many there were.
+    pub fn publish(&self, opportunity: MEVOpportunity, expected_profit: U256) -> usize {
+        self.sender.send(PublishedOpportunity { opportunity, expected_profit }).unwrap_or(0)
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<PublishedOpportunity> {
+        self.sender.subscribe()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::strategy::types::{BackrunDetails, StrategyType};
+    use std::time::Instant;
+
+    fn opportunity(id: &str) -> MEVOpportunity {
+        MEVOpportunity {
+            id: id.to_string(),
+            target_tx: Transaction::default(),
+            strategy_type: StrategyType::Backrun(BackrunDetails {
+                victim_tx: Transaction::default(),
+                backrun_tx: Default::default(),
+            }),
+            estimated_profit: U256::from(1_000),
+            gas_cost: U256::from(200),
+            priority: 0,
+            reserve_block: U64::zero(),
+            expiry_block: U64::one(),
+            observed_at: Instant::now(),
+            landing_probability: 1.0,
+        }
+    }
+
+    #[tokio::test]
+    async fn every_subscriber_sees_what_is_published_after_it_subscribed() {
+        let bus = OpportunityBus::new();
+        assert_eq!(bus.publish(opportunity("unseen"), U256::one()), 0);
+
+        let (mut executor, mut logger) = (bus.subscribe(), bus.subscribe());
+        assert_eq!(bus.publish(opportunity("seen"), U256::from(800)), 2);
+        for subscriber in [&mut executor, &mut logger] {
+            let published = subscriber.recv().await.unwrap();
+            assert_eq!((published.opportunity.id.as_str(), published.expected_profit), ("seen", U256::from(800)));
+            assert!(subscriber.try_recv().is_err());
+        }
+    }
+}
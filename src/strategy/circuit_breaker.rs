@@ -0,0 +1,142 @@
+use ethers::prelude::*;
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::settings::Settings;
+
+/// Halts execution after a losing streak or too much loss in a rolling window,
+/// and re-arms itself after a cooldown.
+#[derive(Debug)]
+pub struct CircuitBreaker {
+    max_consecutive_losses: u32,
+    max_window_loss: U256,
+    window: Duration,
+    cooldown: Duration,
+    state: Mutex<BreakerState>,
+}
+
+#[derive(Debug, Default)]
+struct BreakerState {
+    consecutive_losses: u32,
+    /// Losses (wei) inside the rolling window, oldest first.
+    losses: VecDeque<(Instant, U256)>,
+    tripped_at: Option<Instant>,
+}
+
+impl CircuitBreaker {
+    pub fn new(max_consecutive_losses: u32, max_window_loss: U256, window: Duration, cooldown: Duration) -> Self {
+        Self {
+            max_consecutive_losses,
+            max_window_loss,
+            window,
+            cooldown,
+            state: Mutex::new(BreakerState::default()),
+        }
+    }
+
+    pub fn from_settings(settings: &Settings) -> Self {
+        Self::new(
+            settings.risk.breaker_max_consecutive_losses,
+            ethers::utils::parse_ether(settings.risk.breaker_max_loss_eth).unwrap_or(U256::MAX),
+            Duration::from_secs(settings.risk.breaker_window_secs),
+            Duration::from_secs(settings.risk.breaker_cooldown_secs),
+        )
+    }
+
+    /// Whether execution is currently halted. Resets once the cooldown has passed.
+    pub fn is_open(&self) -> bool {
+        let mut state = self.state.lock().unwrap();
+        match state.tripped_at {
+            Some(tripped_at) if tripped_at.elapsed() < self.cooldown => true,
+            Some(_) => {
+                *state = BreakerState::default();
+                false
+            }
+            None => false,
+        }
+    }
+
+    /// Records a settled outcome. Returns why the breaker tripped if this outcome tripped it.
+    pub fn record(&self, net_profit: I256) -> Option<String> {
+        let mut state = self.state.lock().unwrap();
+        let now = Instant::now();
+
+        while state.losses.front().is_some_and(|(at, _)| now.duration_since(*at) > self.window) {
+            state.losses.pop_front();
+        }
+
+        if !net_profit.is_negative() {
+            if !net_profit.is_zero() {
+                state.consecutive_losses = 0;
+            }
+            return None;
+        }
+
+        state.consecutive_losses += 1;
+        state.losses.push_back((now, net_profit.unsigned_abs()));
+        if state.tripped_at.is_some() {
+            return None;
+        }
+
+        let window_loss = state.losses.iter().fold(U256::zero(), |sum, (_, loss)| sum.saturating_add(*loss));
+        let reason = if state.consecutive_losses >= self.max_consecutive_losses {
+            format!("{} consecutive losses", state.consecutive_losses)
+        } else if window_loss > self.max_window_loss {
+            format!(
+                "{} ETH lost in the last {}s",
+                ethers::utils::format_ether(window_loss),
+                self.window.as_secs()
+            )
+        } else {
+            return None;
+        };
+
+        state.tripped_at = Some(now);
+        Some(reason)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn loss(wei: u64) -> I256 {
+        -I256::from(wei)
+    }
+
+    #[test]
+    fn trips_on_a_losing_streak_and_a_win_resets_it() {
+        let breaker = CircuitBreaker::new(3, U256::MAX, Duration::from_secs(60), Duration::from_secs(60));
+        assert_eq!(breaker.record(loss(1)), None);
+        assert_eq!(breaker.record(loss(1)), None);
+        assert_eq!(breaker.record(I256::from(1)), None);
+        assert_eq!(breaker.record(loss(1)), None);
+        // Break-even neither counts as a loss nor ends the streak
+        assert_eq!(breaker.record(I256::zero()), None);
+        assert_eq!(breaker.record(loss(1)), None);
+        assert!(!breaker.is_open());
+
+        assert_eq!(breaker.record(loss(1)).as_deref(), Some("3 consecutive losses"));
+        assert!(breaker.is_open());
+        // Already tripped: further losses don't report again
+        assert_eq!(breaker.record(loss(1)), None);
+    }
+
+    #[test]
+    fn trips_on_the_loss_in_the_window() {
+        let breaker = CircuitBreaker::new(u32::MAX, U256::from(100), Duration::from_secs(60), Duration::from_secs(60));
+        assert_eq!(breaker.record(loss(60)), None);
+        assert!(breaker.record(loss(41)).unwrap().contains("lost in the last 60s"));
+        assert!(breaker.is_open());
+    }
+
+    #[test]
+    fn rearms_after_the_cooldown() {
+        let breaker = CircuitBreaker::new(1, U256::MAX, Duration::from_secs(60), Duration::ZERO);
+        assert!(breaker.record(loss(1)).is_some());
+        assert!(!breaker.is_open());
+        // The streak started over
+        assert!(breaker.record(loss(1)).is_some());
+    }
+}
@@ -0,0 +1,358 @@
+use ethers::prelude::*;
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::Mutex;
+use tokio::sync::broadcast;
+
+use crate::settings::Settings;
+
+/// A new highest bid seen on a pool.
+#[derive(Debug, Clone, Copy)]
+pub struct BidUpdate {
+    pub pool: Address,
+    pub gas_price: U256,
+}
+
+/// What a strategy does once a pool's bids escalate into a gas war.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GasWarPolicy {
+    /// Bid as if there were no war.
+    Ignore,
+    /// Drop the opportunity.
+    BowOut,
+    /// Keep outbidding, but never more than a capped escalation over the pre-war price.
+    Capped,
+}
+
+impl FromStr for GasWarPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "ignore" => Ok(Self::Ignore),
+            "bow_out" => Ok(Self::BowOut),
+            "capped" => Ok(Self::Capped),
+            other => Err(format!("unknown gas war policy: {}", other)),
+        }
+    }
+}
+
+/// Several searchers repeatedly outbidding each other on one pool.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GasWar {
+    pub pool: Address,
+    /// Pending swaps seen on the pool this block.
+    pub bidders: usize,
+    /// How many of them raised the pool's top bid.
+    pub escalations: usize,
+    /// Bid the war started from.
+    pub opening_bid: U256,
+    pub top_bid: U256,
+}
+
+/// A strategy's answer to a gas war, from `CompetitionTracker::respond_to_war`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GasWarResponse {
+    /// Keep our bid as it is.
+    Proceed,
+    BowOut,
+    /// Raise our bid to this.
+    Bid(U256),
+}
+
+/// Pending bids on one pool, for one block.
+#[derive(Debug, Default)]
+struct PoolBids {
+    block: U64,
+    bids: HashMap<H256, U256>,
+    /// Gas prices in the order they arrived: the pool's gas curve.
+    curve: Vec<U256>,
+}
+
+impl PoolBids {
+    /// Arrivals that beat every bid before them.
+    fn escalations(&self) -> usize {
+        let mut top = None;
+        let mut escalations = 0;
+        for bid in &self.curve {
+            if top.is_some_and(|top| bid > top) {
+                escalations += 1;
+            }
+            top = top.max(Some(bid));
+        }
+        escalations
+    }
+}
+
+/// Tracks the gas prices of pending swaps per pool so our frontrun can just
+/// outbid whoever else is racing for the same victim.
+///
+/// Anything priced above the victim on its pool is treated as a competing
+/// frontrun: that's the only place a frontrun can sit. A pool whose top bid
+/// keeps getting raised is in a gas war, which `war_policy` decides how to meet.
+#[derive(Debug)]
+pub struct CompetitionTracker {
+    enabled: bool,
+    /// How far above the top competitor we bid.
+    outbid_by: U256,
+    war_policy: GasWarPolicy,
+    /// Raises of a pool's top bid within a block that make it a gas war.
+    war_escalations: usize,
+    /// Most a `Capped` bid may exceed the war's opening bid by, in bps.
+    war_max_escalation_bps: u64,
+    pools: Mutex<HashMap<Address, PoolBids>>,
+    updates: broadcast::Sender<BidUpdate>,
+}
+
+impl CompetitionTracker {
+    pub fn new(enabled: bool, outbid_by: U256) -> Self {
+        Self {
+            enabled,
+            outbid_by,
+            war_policy: GasWarPolicy::Ignore,
+            war_escalations: 3,
+            war_max_escalation_bps: 0,
+            pools: Mutex::new(HashMap::new()),
+            updates: broadcast::channel(256).0,
+        }
+    }
+
+    /// Meets gas wars of `escalations` raises with `policy`.
+    pub fn with_gas_war_policy(mut self, policy: GasWarPolicy, escalations: usize, max_escalation_bps: u64) -> Self {
+        self.war_policy = policy;
+        self.war_escalations = escalations.max(1);
+        self.war_max_escalation_bps = max_escalation_bps;
+        self
+    }
+
+    pub fn from_settings(settings: &Settings) -> Self {
+        Self::new(
+            settings.gas.competitor_rebid,
+            U256::from(settings.gas.competitor_outbid_gwei) * U256::exp10(9),
+        )
+        .with_gas_war_policy(
+            settings.gas.war_policy,
+            settings.gas.war_escalations,
+            settings.gas.war_max_escalation_bps,
+        )
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Whether pending swaps are being recorded at all.
+    fn is_tracking(&self) -> bool {
+        self.enabled || self.war_policy != GasWarPolicy::Ignore
+    }
+
+    /// Records `tx` as a pending swap on `pool`, announcing it if it's the new top bid.
+    /// Returns the gas war it tipped the pool into, once per pool and block.
+    pub fn observe(&self, pool: Address, tx: &Transaction, block: U64) -> Option<GasWar> {
+        if !self.is_tracking() {
+            return None;
+        }
+        let gas_price = bid_of(tx);
+
+        let mut pools = self.pools.lock().unwrap();
+        let entry = pools.entry(pool).or_default();
+        if entry.block != block {
+            *entry = PoolBids { block, ..PoolBids::default() };
+        }
+        if entry.bids.contains_key(&tx.hash) {
+            return None;
+        }
+        let was_top = entry.bids.values().all(|bid| gas_price > *bid);
+        entry.bids.insert(tx.hash, gas_price);
+        entry.curve.push(gas_price);
+        let war = (was_top && entry.escalations() == self.war_escalations).then(|| war_on(pool, entry));
+        drop(pools);
+
+        if was_top {
+            let _ = self.updates.send(BidUpdate { pool, gas_price });
+        }
+        war
+    }
+
+    /// Gas prices of the swaps seen on `pool` this block, in arrival order.
+    pub fn gas_curve(&self, pool: Address, block: U64) -> Vec<U256> {
+        let pools = self.pools.lock().unwrap();
+        pools.get(&pool).filter(|entry| entry.block == block).map(|entry| entry.curve.clone()).unwrap_or_default()
+    }
+
+    /// The gas war on `pool` this block, if its top bid has been raised often enough.
+    pub fn gas_war(&self, pool: Address, block: U64) -> Option<GasWar> {
+        let pools = self.pools.lock().unwrap();
+        let entry = pools.get(&pool).filter(|entry| entry.block == block)?;
+        (entry.escalations() >= self.war_escalations).then(|| war_on(pool, entry))
+    }
+
+    /// How to meet `war` with our frontrun bid at `current`, never paying past `ceiling`.
+    pub fn respond_to_war(&self, war: &GasWar, current: U256, ceiling: U256) -> GasWarResponse {
+        match self.war_policy {
+            GasWarPolicy::Ignore => GasWarResponse::Proceed,
+            GasWarPolicy::BowOut => GasWarResponse::BowOut,
+            GasWarPolicy::Capped => {
+                let escalation = war.opening_bid * U256::from(self.war_max_escalation_bps) / U256::from(10_000);
+                let cap = war.opening_bid.saturating_add(escalation).min(ceiling);
+                let bid = war.top_bid.saturating_add(self.outbid_by);
+                if bid > cap {
+                    GasWarResponse::BowOut
+                } else if bid > current {
+                    GasWarResponse::Bid(bid)
+                } else {
+                    GasWarResponse::Proceed
+                }
+            }
+        }
+    }
+
+    /// Highest pending bid on `pool` this block above `floor`, ignoring `exclude` (the victims).
+    pub fn top_bid(&self, pool: Address, block: U64, exclude: &[H256], floor: U256) -> Option<U256> {
+        let pools = self.pools.lock().unwrap();
+        let entry = pools.get(&pool).filter(|entry| entry.block == block)?;
+        entry
+            .bids
+            .iter()
+            .filter(|(hash, bid)| !exclude.contains(hash) && **bid > floor)
+            .map(|(_, bid)| *bid)
+            .max()
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<BidUpdate> {
+        self.updates.subscribe()
+    }
+
+    /// Our bid after seeing `competitor`, or `None` if we should leave it at `current`:
+    /// either we're already ahead, or outbidding would take us past `ceiling`.
+    /// The bid never exceeds `ceiling`, our break-even gas price.
+    pub fn rebid(&self, current: U256, competitor: U256, ceiling: U256) -> Option<U256> {
+        if competitor < current {
+            return None;
+        }
+        let bid = competitor.saturating_add(self.outbid_by).min(ceiling);
+        (bid > current).then_some(bid)
+    }
+}
+
+/// Highest gas price we can pay on a frontrun using `frontrun_gas` and still break even
+/// on `profit`, after the backrun's `backrun_cost`.
+pub fn bid_ceiling(profit: U256, frontrun_gas: U256, backrun_cost: U256) -> U256 {
+    if frontrun_gas.is_zero() {
+        return U256::zero();
+    }
+    profit.saturating_sub(backrun_cost) / frontrun_gas
+}
+
+fn war_on(pool: Address, entry: &PoolBids) -> GasWar {
+    GasWar {
+        pool,
+        bidders: entry.curve.len(),
+        escalations: entry.escalations(),
+        opening_bid: entry.curve.first().copied().unwrap_or_default(),
+        top_bid: entry.curve.iter().max().copied().unwrap_or_default(),
+    }
+}
+
+/// What `tx` pays per gas at most: its legacy gas price or its EIP-1559 fee cap.
+fn bid_of(tx: &Transaction) -> U256 {
+    tx.gas_price.or(tx.max_fee_per_gas).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn gwei(n: u64) -> U256 {
+        U256::from(n) * U256::exp10(9)
+    }
+
+    fn swap(hash: u64, gas_price_gwei: u64) -> Transaction {
+        Transaction { hash: H256::from_low_u64_be(hash), gas_price: Some(gwei(gas_price_gwei)), ..Default::default() }
+    }
+
+    fn pool() -> Address {
+        Address::from_low_u64_be(0x9001)
+    }
+
+    #[test]
+    fn rebid_outbids_the_competitor_up_to_the_ceiling() {
+        let tracker = CompetitionTracker::new(true, gwei(1));
+        assert_eq!(tracker.rebid(gwei(20), gwei(25), gwei(100)), Some(gwei(26)));
+        assert_eq!(tracker.rebid(gwei(20), gwei(25), gwei(23)), Some(gwei(23)));
+        // Already ahead, or unable to get ahead
+        assert_eq!(tracker.rebid(gwei(30), gwei(25), gwei(100)), None);
+        assert_eq!(tracker.rebid(gwei(20), gwei(25), gwei(20)), None);
+    }
+
+    #[test]
+    fn top_bid_ignores_the_victims_and_other_blocks() {
+        let tracker = CompetitionTracker::new(true, gwei(1));
+        let block = U64::from(10);
+        tracker.observe(pool(), &swap(1, 30), block);
+        tracker.observe(pool(), &swap(2, 50), block);
+        tracker.observe(pool(), &swap(3, 40), block);
+
+        let victim = H256::from_low_u64_be(2);
+        assert_eq!(tracker.top_bid(pool(), block, &[victim], gwei(30)), Some(gwei(40)));
+        assert_eq!(tracker.top_bid(pool(), block, &[victim], gwei(40)), None);
+        assert_eq!(tracker.top_bid(pool(), block + 1, &[], U256::zero()), None);
+    }
+
+    #[test]
+    fn announces_only_new_top_bids() {
+        let tracker = CompetitionTracker::new(true, gwei(1));
+        let mut updates = tracker.subscribe();
+        tracker.observe(pool(), &swap(1, 30), U64::from(10));
+        tracker.observe(pool(), &swap(2, 20), U64::from(10));
+        tracker.observe(pool(), &swap(3, 35), U64::from(10));
+
+        assert_eq!(updates.try_recv().unwrap().gas_price, gwei(30));
+        assert_eq!(updates.try_recv().unwrap().gas_price, gwei(35));
+        assert!(updates.try_recv().is_err());
+    }
+
+    #[test]
+    fn bid_ceiling_is_break_even_after_the_backrun() {
+        assert_eq!(bid_ceiling(gwei(1_000_000), U256::from(100_000), gwei(200_000)), gwei(8));
+        assert_eq!(bid_ceiling(gwei(1), U256::zero(), U256::zero()), U256::zero());
+    }
+
+    #[test]
+    fn a_run_of_raises_is_a_gas_war_reported_once() {
+        let tracker = CompetitionTracker::new(false, gwei(1)).with_gas_war_policy(GasWarPolicy::Capped, 2, 5_000);
+        let block = U64::from(10);
+        assert_eq!(tracker.observe(pool(), &swap(1, 20), block), None);
+        assert_eq!(tracker.observe(pool(), &swap(2, 15), block), None);
+        assert_eq!(tracker.observe(pool(), &swap(3, 25), block), None);
+        let war = tracker.observe(pool(), &swap(4, 28), block).unwrap();
+        assert_eq!((war.bidders, war.escalations, war.opening_bid, war.top_bid), (4, 2, gwei(20), gwei(28)));
+        assert_eq!(tracker.observe(pool(), &swap(5, 29), block), None);
+        assert!(tracker.gas_war(pool(), block).is_some());
+        assert_eq!(tracker.gas_curve(pool(), block).len(), 5);
+        assert!(tracker.gas_war(pool(), block + 1).is_none());
+    }
+
+    #[test]
+    fn capped_policy_outbids_within_the_escalation_cap() {
+        let war = GasWar { pool: pool(), bidders: 3, escalations: 2, opening_bid: gwei(20), top_bid: gwei(28) };
+        let capped = CompetitionTracker::new(false, gwei(1)).with_gas_war_policy(GasWarPolicy::Capped, 2, 5_000);
+        assert_eq!(capped.respond_to_war(&war, gwei(20), gwei(100)), GasWarResponse::Bid(gwei(29)));
+        assert_eq!(capped.respond_to_war(&war, gwei(29), gwei(100)), GasWarResponse::Proceed);
+        // Past break-even, or past 50% over the opening bid
+        assert_eq!(capped.respond_to_war(&war, gwei(20), gwei(28)), GasWarResponse::BowOut);
+        let war = GasWar { top_bid: gwei(30), ..war };
+        assert_eq!(capped.respond_to_war(&war, gwei(20), gwei(100)), GasWarResponse::BowOut);
+
+        let bow_out = CompetitionTracker::new(false, gwei(1)).with_gas_war_policy(GasWarPolicy::BowOut, 2, 0);
+        assert_eq!(bow_out.respond_to_war(&war, gwei(20), gwei(100)), GasWarResponse::BowOut);
+        assert_eq!(CompetitionTracker::new(false, gwei(1)).respond_to_war(&war, gwei(20), gwei(100)), GasWarResponse::Proceed);
+    }
+
+    #[test]
+    fn nothing_is_recorded_when_disabled() {
+        let tracker = CompetitionTracker::new(false, gwei(1));
+        tracker.observe(pool(), &swap(1, 30), U64::from(10));
+        assert!(tracker.gas_curve(pool(), U64::from(10)).is_empty());
+    }
+}
@@ -0,0 +1,89 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::settings::Settings;
+
+/// Suppresses re-executing an opportunity we've already acted on while it
+/// persists across blocks, until its outcome is settled or a cooldown passes.
+#[derive(Debug)]
+pub struct OpportunityDedup {
+    enabled: bool,
+    cooldown: Duration,
+    acted: Mutex<HashMap<String, Instant>>,
+}
+
+impl OpportunityDedup {
+    pub fn new(enabled: bool, cooldown: Duration) -> Self {
+        Self {
+            enabled,
+            cooldown,
+            acted: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn from_settings(settings: &Settings) -> Self {
+        Self::new(
+            settings.opportunity_dedup,
+            Duration::from_secs(settings.opportunity_dedup_cooldown_secs),
+        )
+    }
+
+    /// Claims `fingerprint` for execution. False if it's already in flight.
+    pub fn try_acquire(&self, fingerprint: &str) -> bool {
+        if !self.enabled {
+            return true;
+        }
+
+        let mut acted = self.acted.lock().unwrap();
+        let now = Instant::now();
+        acted.retain(|_, at| now.duration_since(*at) < self.cooldown);
+        if acted.contains_key(fingerprint) {
+            return false;
+        }
+        acted.insert(fingerprint.to_string(), now);
+        true
+    }
+
+    /// Forgets everything in flight, e.g. after a reorg re-exposes opportunities.
+    pub fn clear(&self) {
+        self.acted.lock().unwrap().clear();
+    }
+
+    /// Our attempt at `fingerprint` is resolved (settled or never sent): allow it again.
+    pub fn release(&self, fingerprint: &str) {
+        self.acted.lock().unwrap().remove(fingerprint);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn suppresses_a_fingerprint_until_released() {
+        let dedup = OpportunityDedup::new(true, Duration::from_secs(60));
+        assert!(dedup.try_acquire("sandwich:0x01"));
+        assert!(!dedup.try_acquire("sandwich:0x01"));
+        assert!(dedup.try_acquire("sandwich:0x02"));
+
+        dedup.release("sandwich:0x01");
+        assert!(dedup.try_acquire("sandwich:0x01"));
+        dedup.clear();
+        assert!(dedup.try_acquire("sandwich:0x02"));
+    }
+
+    #[test]
+    fn claims_expire_after_the_cooldown() {
+        let dedup = OpportunityDedup::new(true, Duration::ZERO);
+        assert!(dedup.try_acquire("arbitrage:a>b"));
+        assert!(dedup.try_acquire("arbitrage:a>b"));
+    }
+
+    #[test]
+    fn everything_passes_when_disabled() {
+        let dedup = OpportunityDedup::new(false, Duration::from_secs(60));
+        assert!(dedup.try_acquire("backrun:0x01"));
+        assert!(dedup.try_acquire("backrun:0x01"));
+    }
+}
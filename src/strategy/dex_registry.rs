@@ -0,0 +1,190 @@
+use ethers::prelude::*;
+use std::collections::HashMap;
+
+use crate::address_book::{
+    SUSHISWAP_ROUTER, UNISWAP_V2_FACTORY, UNISWAP_V2_ROUTER, UNISWAP_V3_FACTORY, UNISWAP_V3_ROUTER,
+};
+use crate::helpers::address;
+use super::pairs::{
+    PANCAKESWAP_ETHEREUM_INIT_CODE_HASH, PANCAKESWAP_INIT_CODE_HASH, SUSHISWAP_INIT_CODE_HASH, UNISWAP_V2_INIT_CODE_HASH,
+};
+use super::types::DexType;
+
+/// Factory and router addresses per DEX, so other chains and forks need no source edits.
+#[derive(Debug, Clone, Default)]
+pub struct DexRegistry {
+    pub factories: HashMap<DexType, Vec<Address>>,
+    pub routers: HashMap<DexType, Address>,
+    /// Pair contract init code hash per V2-style DEX, to derive pair addresses with CREATE2.
+    pub init_code_hashes: HashMap<DexType, H256>,
+}
+
+impl DexRegistry {
+    pub fn new(factories: HashMap<DexType, Vec<Address>>, routers: HashMap<DexType, Address>) -> Self {
+        Self { factories, routers, init_code_hashes: HashMap::new() }
+    }
+
+    /// Registers a DEX (e.g. a `DexType::Custom` fork). Extra factories for a known DEX are appended.
+    pub fn with_dex(mut self, dex: DexType, factory: Address, router: Address) -> Self {
+        self.factories.entry(dex).or_default().push(factory);
+        self.routers.insert(dex, router);
+        self
+    }
+
+    /// Sets the pair init code hash `dex`'s pair addresses are derived with.
+    pub fn with_init_code_hash(mut self, dex: DexType, init_code_hash: &str) -> Self {
+        self.init_code_hashes.insert(dex, init_code_hash.parse().unwrap());
+        self
+    }
+
+    pub fn mainnet() -> Self {
+        Self::default()
+            .with_dex(DexType::UniswapV2, address(UNISWAP_V2_FACTORY), address(UNISWAP_V2_ROUTER))
+            .with_dex(
+                DexType::SushiSwap,
+                "0xC0AEe478e3658e2610c5F7A4A2E1777cE9e4f2Ac".parse().unwrap(),
+                address(SUSHISWAP_ROUTER),
+            )
+            .with_dex(
+                DexType::PancakeSwap,
+                "0x1097053Fd2ea711dad45caCcc45EfF7548fCB362".parse().unwrap(),
+                "0xEfF92A263d31888d860bD50809A8D171709b7b1c".parse().unwrap(),
+            )
+            .with_dex(DexType::UniswapV3, address(UNISWAP_V3_FACTORY), address(UNISWAP_V3_ROUTER))
+            .with_init_code_hash(DexType::UniswapV2, UNISWAP_V2_INIT_CODE_HASH)
+            .with_init_code_hash(DexType::SushiSwap, SUSHISWAP_INIT_CODE_HASH)
+            .with_init_code_hash(DexType::PancakeSwap, PANCAKESWAP_ETHEREUM_INIT_CODE_HASH)
+    }
+
+    pub fn arbitrum() -> Self {
+        Self::default()
+            .with_dex(
+                DexType::UniswapV2,
+                "0xf1D7CC64Fb4452F05c498126312eBE29f30Fbcf9".parse().unwrap(),
+                "0x4752ba5DBc23f44D87826276BF6Fd6b1C372aD24".parse().unwrap(),
+            )
+            .with_dex(
+                DexType::SushiSwap,
+                "0xc35DADB65012eC5796536bD9864eD8773aBc74C4".parse().unwrap(),
+                "0x1b02dA8Cb0d097eB8D57A175b88c7D8b47997506".parse().unwrap(),
+            )
+            .with_dex(
+                DexType::UniswapV3,
+                "0x1F98431c8aD98523631AE4a59f267346ea31F984".parse().unwrap(),
+                "0xE592427A0AEce92De3Edee1F18E0157C05861564".parse().unwrap(),
+            )
+            .with_init_code_hash(DexType::UniswapV2, UNISWAP_V2_INIT_CODE_HASH)
+            .with_init_code_hash(DexType::SushiSwap, SUSHISWAP_INIT_CODE_HASH)
+    }
+
+    pub fn bsc() -> Self {
+        Self::default()
+            .with_dex(
+                DexType::PancakeSwap,
+                "0xcA143Ce32Fe78f1f7019d7d551a6402fC5350c73".parse().unwrap(),
+                "0x10ED43C718714eb63d5aA57B78B54704E256024E".parse().unwrap(),
+            )
+            .with_dex(
+                DexType::SushiSwap,
+                "0xc35DADB65012eC5796536bD9864eD8773aBc74C4".parse().unwrap(),
+                "0x1b02dA8Cb0d097eB8D57A175b88c7D8b47997506".parse().unwrap(),
+            )
+            .with_init_code_hash(DexType::PancakeSwap, PANCAKESWAP_INIT_CODE_HASH)
+            .with_init_code_hash(DexType::SushiSwap, SUSHISWAP_INIT_CODE_HASH)
+    }
+
+    pub fn base() -> Self {
+        Self::default()
+            .with_dex(
+                DexType::UniswapV2,
+                "0x8909Dc15e40173Ff4699343b6eB8132c65e18eC6".parse().unwrap(),
+                "0x4752ba5DBc23f44D87826276BF6Fd6b1C372aD24".parse().unwrap(),
+            )
+            .with_dex(
+                DexType::SushiSwap,
+                "0x71524B4f93c58fcbF659783284E38825f0622859".parse().unwrap(),
+                "0x6BDED42c6DA8FBf0d2bA55B2fa120C5e0c8D7891".parse().unwrap(),
+            )
+            .with_dex(
+                DexType::UniswapV3,
+                "0x33128a8fC17869897dcE68Ed026d694621f6FDfD".parse().unwrap(),
+                "0x2626664c2603336E57B271c5C0b26F421741e481".parse().unwrap(),
+            )
+            .with_init_code_hash(DexType::UniswapV2, UNISWAP_V2_INIT_CODE_HASH)
+            .with_init_code_hash(DexType::SushiSwap, SUSHISWAP_INIT_CODE_HASH)
+    }
+
+    /// Looks up a chain preset by name (`mainnet`, `arbitrum`, `base`, `bsc`).
+    pub fn preset(name: &str) -> Option<Self> {
+        match name {
+            "mainnet" => Some(Self::mainnet()),
+            "arbitrum" => Some(Self::arbitrum()),
+            "base" => Some(Self::base()),
+            "bsc" => Some(Self::bsc()),
+            _ => None,
+        }
+    }
+
+    pub fn router(&self, dex: DexType) -> Option<Address> {
+        self.routers.get(&dex).copied()
+    }
+
+    /// The DEX `router` belongs to, if it is registered.
+    pub fn dex_of_router(&self, router: Address) -> Option<DexType> {
+        self.routers.iter().find_map(|(dex, registered)| (*registered == router).then_some(*dex))
+    }
+
+    /// The DEX `factory` belongs to, if it is registered.
+    pub fn dex_of_factory(&self, factory: Address) -> Option<DexType> {
+        self.factories.iter().find_map(|(dex, factories)| factories.contains(&factory).then_some(*dex))
+    }
+
+    /// Factory and init code hash to derive `dex`'s pair addresses from. Only
+    /// V2-style DEXes with a known hash have one; extra factories beyond the
+    /// first aren't derived from.
+    pub fn pair_derivation(&self, dex: DexType) -> Option<(Address, H256)> {
+        Some((*self.factories.get(&dex)?.first()?, *self.init_code_hashes.get(&dex)?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::address_book::WETH;
+    use crate::strategy::pairs::derive_pair_address;
+    use std::collections::HashSet;
+
+    #[test]
+    fn presets_look_up_dexes_by_router_and_factory() {
+        let registry = DexRegistry::preset("mainnet").unwrap();
+        assert_eq!(registry.dex_of_router(address(SUSHISWAP_ROUTER)), Some(DexType::SushiSwap));
+        assert_eq!(registry.dex_of_factory(address(UNISWAP_V3_FACTORY)), Some(DexType::UniswapV3));
+        assert_eq!(registry.dex_of_router(Address::zero()), None);
+        assert!(DexRegistry::preset("optimism").is_none());
+    }
+
+    #[test]
+    fn with_dex_appends_factories_of_a_known_dex() {
+        let fork = Address::from_low_u64_be(1);
+        let registry = DexRegistry::mainnet().with_dex(DexType::UniswapV2, fork, address(UNISWAP_V2_ROUTER));
+        assert_eq!(registry.factories[&DexType::UniswapV2], vec![address(UNISWAP_V2_FACTORY), fork]);
+        assert_eq!(registry.dex_of_factory(fork), Some(DexType::UniswapV2));
+    }
+
+    #[test]
+    fn each_v2_dex_derives_its_own_pair() {
+        let registry = DexRegistry::mainnet();
+        let usdc = address("0xA0b86991c6218b36c1d19D4a2e9Eb0cE3606eB48");
+        let pairs: HashSet<_> = [DexType::UniswapV2, DexType::SushiSwap, DexType::PancakeSwap]
+            .into_iter()
+            .map(|dex| {
+                let (factory, hash) = registry.pair_derivation(dex).unwrap();
+                derive_pair_address(factory, hash, address(WETH), usdc)
+            })
+            .collect();
+        assert_eq!(pairs.len(), 3);
+        assert!(pairs.contains(&address("0xB4e16d0168e52d35CaCD2c6185b44281Ec28C9Dc")));
+        // V3 pools aren't CREATE2 pairs of this shape
+        assert_eq!(registry.pair_derivation(DexType::UniswapV3), None);
+    }
+}
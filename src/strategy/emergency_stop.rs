@@ -0,0 +1,116 @@
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{info, warn};
+
+use crate::alert::alert;
+use crate::settings::Settings;
+
+/// How often the halt file is checked for.
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Operator kill-switch: halts all execution while monitoring carries on.
+///
+/// Halted while SIGUSR1 has toggled it on (send it again to clear), or while
+/// the configured halt file exists.
+#[derive(Debug)]
+pub struct EmergencyStop {
+    signalled: AtomicBool,
+    halt_file: Option<PathBuf>,
+}
+
+impl EmergencyStop {
+    pub fn new(halt_file: Option<PathBuf>) -> Self {
+        Self {
+            signalled: AtomicBool::new(false),
+            halt_file,
+        }
+    }
+
+    pub fn from_settings(settings: &Settings) -> Self {
+        Self::new(settings.risk.halt_file.as_ref().map(PathBuf::from))
+    }
+
+    pub fn is_halted(&self) -> bool {
+        self.signalled.load(Ordering::Relaxed) || self.halt_file.as_ref().is_some_and(|path| path.exists())
+    }
+
+    /// Flips the signal-driven halt, returning the new state.
+    pub fn toggle(&self) -> bool {
+        !self.signalled.fetch_xor(true, Ordering::Relaxed)
+    }
+
+    /// Listens for SIGUSR1 and polls the halt file, alerting on every transition.
+    pub async fn watch(self: Arc<Self>) {
+        #[cfg(unix)]
+        let mut usr1 = match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::user_defined1()) {
+            Ok(signal) => Some(signal),
+            Err(e) => {
+                warn!("⚠️ Could not listen for SIGUSR1, halt file only: {}", e);
+                None
+            }
+        };
+
+        let mut halted = self.is_halted();
+        let mut interval = tokio::time::interval(POLL_INTERVAL);
+        loop {
+            #[cfg(unix)]
+            {
+                let signal = async {
+                    match usr1.as_mut() {
+                        Some(signal) => signal.recv().await,
+                        None => std::future::pending().await,
+                    }
+                };
+                tokio::select! {
+                    _ = signal => {
+                        self.toggle();
+                    }
+                    _ = interval.tick() => {}
+                }
+            }
+            #[cfg(not(unix))]
+            interval.tick().await;
+
+            let now_halted = self.is_halted();
+            if now_halted != halted {
+                halted = now_halted;
+                let msg = if halted {
+                    "🚨🚨 EMERGENCY STOP: execution halted, still monitoring".to_string()
+                } else {
+                    "🟢 Emergency stop cleared, execution resumed".to_string()
+                };
+                info!("{}", msg);
+                alert(&msg, &0).await;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_signal_toggles_the_halt() {
+        let stop = EmergencyStop::new(None);
+        assert!(!stop.is_halted());
+        assert!(stop.toggle());
+        assert!(stop.is_halted());
+        assert!(!stop.toggle());
+        assert!(!stop.is_halted());
+    }
+
+    #[test]
+    fn halted_while_the_halt_file_exists() {
+        let path = std::env::temp_dir().join(format!("mev-halt-{}", std::process::id()));
+        let stop = EmergencyStop::new(Some(path.clone()));
+        assert!(!stop.is_halted());
+
+        std::fs::write(&path, b"").unwrap();
+        assert!(stop.is_halted());
+        std::fs::remove_file(&path).unwrap();
+        assert!(!stop.is_halted());
+    }
+}
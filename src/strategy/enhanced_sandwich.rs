@@ -1,36 +1,155 @@
+use ethers::abi::AbiEncode;
 use ethers::prelude::*;
+use ethers::types::transaction::eip2718::TypedTransaction;
 use std::sync::Arc;
-use crate::Config;
+use std::time::Instant;
+use crate::address_book::{
+    SwapExactETHForTokensCall, SwapExactTokensForETHCall, UniV2Factory, UniV2Router,
+    UniV2RouterCalls,
+};
+use crate::{uni, Config};
+use crate::classifier::TxClass;
+use crate::gas_oracle::GasOracle;
+use crate::helpers::eth_to_wei;
+use crate::token_info::TokenInfo;
+use super::types::*;
+use super::volatility::VolatilityTracker;
+
+/// Uniswap V2 swap fee, in hundredths of a bip.
+const V2_FEE_PPM: u32 = 3000;
 
 #[derive(Debug)]
 pub struct EnhancedSandwichStrategy {
     config: Arc<Config>,
     min_profit_wei: U256,
     max_position_size: U256,
-    slippage_tolerance: u64,
-    gas_price_premium: U256,
+    /// Frontruns are capped at this many bps of the pool's input reserve.
+    max_frontrun_reserve_bps: u64,
+    volatility: VolatilityTracker,
 }
 
 impl EnhancedSandwichStrategy {
     pub fn new(config: Arc<Config>) -> Self {
         Self {
-            config,
-            min_profit_wei: U256::from(5) * U256::from(10).pow(U256::from(16)), // 0.05 ETH minimum
+            min_profit_wei: eth_to_wei(config.settings.enhanced_sandwich_min_profit_eth),
             max_position_size: U256::from(50) * U256::from(10).pow(U256::from(18)), // 50 ETH max
-            slippage_tolerance: 300, // 3% slippage tolerance
-            gas_price_premium: U256::from(2_000_000_000u64), // 2 gwei premium
+            max_frontrun_reserve_bps: config.settings.max_frontrun_reserve_bps,
+            volatility: VolatilityTracker::from_settings(&config.settings),
+            config,
         }
     }
 
+    /// Oracle the legs are bid off; the one in the shared `Config`.
+    pub fn gas_oracle(&self) -> &Arc<GasOracle> {
+        &self.config.gas_oracle
+    }
+
+    pub async fn analyze(&self, tx: &Transaction, class: &TxClass) -> Vec<MEVOpportunity> {
+        let mut ops = Vec::new();
+        if !self.validate_victim_transaction(tx) { return ops; }
+        if let Some(opp) = self.build_eth_in_sandwich(tx, class).await { ops.push(opp); }
+        ops
+    }
+
+    /// Sandwiches a single-hop ETH -> token swap on any V2-style router.
+    async fn build_eth_in_sandwich(&self, victim_tx: &Transaction, class: &TxClass) -> Option<MEVOpportunity> {
+        let router = victim_tx.to?;
+        let path = match class {
+            TxClass::SwapV2(UniV2RouterCalls::SwapExactETHForTokens(call)) => &call.path,
+            TxClass::SwapV2(UniV2RouterCalls::SwapExactETHForTokensSupportingFeeOnTransferTokens(call)) => &call.path,
+            _ => return None,
+        };
+        if path.len() != 2 {
+            return None;
+        }
+        let (weth, token) = (path[0], path[1]);
+
+        let current_block = self.config.block_height.get();
+
+        // Resolve the pair through the router's own factory so forks work too
+        let factory = UniV2Router::new(router, self.config.http.clone()).factory().call().await.ok()?;
+        let pair_address = UniV2Factory::new(factory, self.config.http.clone())
+            .get_pair(weth, token)
+            .call()
+            .await
+            .ok()?;
+        if pair_address.is_zero() {
+            return None;
+        }
+        let reserves = self.config.pool_cache.reserves(pair_address).await?;
+        let (reserve_in, reserve_out) = reserves.oriented(weth);
+        self.volatility.observe(token, current_block, reserve_in.as_u128() as f64 / reserve_out.as_u128() as f64);
+        let slippage_bps = self.volatility.slippage_bps(token);
+
+        let weth_info = self.config.token_info.resolve(weth).await;
+        let sandwich = self.calculate_advanced_sandwich(victim_tx.value, reserve_in, reserve_out, &weth_info, slippage_bps).await?;
+        let (frontrun_out, _, _) = uni::get_amount_out(sandwich.frontrun_amount, reserve_in, reserve_out);
+        let (frontrun_gas_price, backrun_gas_price) = self.calculate_safe_gas_prices(self.config.gas_oracle.effective_gas_price(victim_tx));
+        let recipient = self.config.http.address();
+        let deadline = self.config.swap_deadline();
+
+        let mut frontrun_tx = TypedTransaction::default();
+        frontrun_tx.set_to(router)
+            .set_value(sandwich.frontrun_amount)
+            .set_data(SwapExactETHForTokensCall {
+                amount_out_min: frontrun_out * (10_000 - slippage_bps) / 10_000,
+                path: vec![weth, token],
+                to: recipient,
+                deadline,
+            }.encode().into())
+            .set_gas(U256::from(200_000))
+            .set_gas_price(frontrun_gas_price);
+
+        // Assumes the router is already approved to spend our token balance
+        let mut backrun_tx = TypedTransaction::default();
+        backrun_tx.set_to(router)
+            .set_data(SwapExactTokensForETHCall {
+                amount_in: frontrun_out,
+                amount_out_min: sandwich.backrun_amount,
+                path: vec![token, weth],
+                to: recipient,
+                deadline,
+            }.encode().into())
+            .set_gas(U256::from(200_000))
+            .set_gas_price(backrun_gas_price);
+
+        Some(MEVOpportunity {
+            id: format!("enhanced_sandwich_{:?}", victim_tx.hash),
+            target_tx: victim_tx.clone(),
+            strategy_type: StrategyType::Sandwich(SandwichDetails {
+                victim_tx: victim_tx.clone(),
+                co_victims: Vec::new(),
+                frontrun_tx,
+                backrun_tx,
+                target_pool: pair_address,
+                token_in: weth,
+                token_out: token,
+                optimal_amount: sandwich.frontrun_amount,
+                victim_amount_in: victim_tx.value,
+                victim_amount_out_min: U256::zero(),
+                price_impact: sandwich.price_impact,
+                funding: Funding::OwnCapital,
+            }),
+            estimated_profit: sandwich.profit,
+            gas_cost: sandwich.gas_cost,
+            priority: 6,
+            reserve_block: current_block,
+            expiry_block: current_block + 1,
+            observed_at: Instant::now(),
+            landing_probability: 1.0,
+        })
+    }
+
     pub fn calculate_safe_gas_prices(&self, victim_gas_price: Option<U256>) -> (U256, U256) {
-        let base_price = victim_gas_price.unwrap_or(U256::from(20_000_000_000u64)); // 20 gwei default
+        let base_price = victim_gas_price.unwrap_or_else(|| self.config.gas_oracle.gas_price(50.0));
+        let premium = self.config.gas_oracle.bid_step();
         
         // Frontrun: Add premium, but check for overflow
-        let frontrun_price = base_price.saturating_add(self.gas_price_premium);
+        let frontrun_price = base_price.saturating_add(premium);
         
         // Backrun: Subtract premium, but ensure we don't underflow
-        let backrun_price = if base_price > self.gas_price_premium {
-            base_price - self.gas_price_premium
+        let backrun_price = if base_price > premium {
+            base_price - premium
         } else {
             base_price / 2 // If too low, use half the price
         };
@@ -45,7 +164,7 @@ impl EnhancedSandwichStrategy {
         }
         
         // Check gas price is reasonable
-        if let Some(gas_price) = tx.gas_price {
+        if let Some(gas_price) = self.config.gas_oracle.effective_gas_price(tx) {
             if gas_price > U256::from(500_000_000_000u64) { // Over 500 gwei
                 return false;
             }
@@ -60,9 +179,35 @@ impl EnhancedSandwichStrategy {
         victim_amount: U256,
         reserve_in: U256,
         reserve_out: U256,
-        _token_decimals: u8,
+        token_in: &TokenInfo,
+        slippage_bps: u64,
     ) -> Option<OptimalSandwich> {
-        // Use Newton's method for more accurate optimization
+        // The position limit is set in 18-decimal units; a 6-decimal token_in needs it scaled down
+        let cap = token_in.from_18_decimals(self.max_position_size).min(uni::max_frontrun(reserve_in, self.max_frontrun_reserve_bps));
+
+        // Profit rises up to the closed-form optimum and falls after, so the capped optimum is the best in range
+        let best_x = match uni::optimal_sandwich_input(victim_amount, reserve_in, V2_FEE_PPM) {
+            Some(x) => x.min(cap),
+            None => self.newton_sandwich_amount(victim_amount, reserve_in, reserve_out, cap),
+        };
+        let best_profit = self.calculate_simple_profit(best_x, victim_amount, reserve_in, reserve_out);
+        
+        if best_x.is_zero() || best_profit < self.min_profit_wei {
+            return None;
+        }
+        
+        Some(OptimalSandwich {
+            frontrun_amount: best_x,
+            // Least the backrun may return: what we expect back, less the token's slippage
+            backrun_amount: (best_x + best_profit) * (10_000 - slippage_bps) / 10_000,
+            profit: best_profit,
+            gas_cost: self.estimate_gas_cost().await,
+            price_impact: uni::price_impact(best_x, reserve_in),
+        })
+    }
+
+    /// Newton's method on finite differences, for when the closed form can't be evaluated.
+    fn newton_sandwich_amount(&self, victim_amount: U256, reserve_in: U256, reserve_out: U256, cap: U256) -> U256 {
         let mut x = reserve_in / 20; // Start with 5% of reserves
         let mut best_profit = U256::zero();
         let mut best_x = U256::zero();
@@ -86,20 +231,9 @@ impl EnhancedSandwichStrategy {
             }
             
             // Ensure x doesn't exceed max position or reserves
-            x = x.min(self.max_position_size).min(reserve_in / 5);
-        }
-        
-        if best_profit < self.min_profit_wei {
-            return None;
+            x = x.min(cap);
         }
-        
-        Some(OptimalSandwich {
-            frontrun_amount: best_x,
-            backrun_amount: best_x * 98 / 100, // 2% slippage buffer
-            profit: best_profit,
-            gas_cost: self.estimate_gas_cost().await,
-            price_impact: (best_x.as_u128() as f64) / (reserve_in.as_u128() as f64),
-        })
+        best_x
     }
 
     fn calculate_profit_and_derivative(
@@ -120,6 +254,8 @@ impl EnhancedSandwichStrategy {
         (profit, derivative)
     }
 
+    /// Frontrun `x`, let the victim swap `victim_amount`, sell everything back;
+    /// every leg pays the pool fee.
     fn calculate_simple_profit(
         &self,
         x: U256,
@@ -127,44 +263,15 @@ impl EnhancedSandwichStrategy {
         r_in: U256,
         r_out: U256,
     ) -> U256 {
-        let k = r_in * r_out;
-        let new_r_in = r_in + x;
-        if new_r_in == U256::zero() {
-            return U256::zero();
-        }
-        
-        let new_r_out = k / new_r_in;
-        let amount_out = r_out.saturating_sub(new_r_out);
-        
-        let new_r_in_2 = new_r_in + victim_amount;
-        if new_r_in_2 == U256::zero() {
-            return U256::zero();
-        }
-        
-        let new_r_out_2 = k / new_r_in_2;
-        
-        let final_r_out = new_r_out_2 + amount_out * 997 / 1000;
-        if final_r_out == U256::zero() {
-            return U256::zero();
-        }
-        
-        let final_r_in = k / final_r_out;
-        let amount_back = new_r_in_2.saturating_sub(final_r_in);
+        let (bought, r_in, r_out) = uni::get_amount_out_with_fee(x, r_in, r_out, V2_FEE_PPM);
+        let (_, r_in, r_out) = uni::get_amount_out_with_fee(victim_amount, r_in, r_out, V2_FEE_PPM);
+        let (amount_back, _, _) = uni::get_amount_out_with_fee(bought, r_out, r_in, V2_FEE_PPM);
         
         amount_back.saturating_sub(x)
     }
 
     async fn estimate_gas_cost(&self) -> U256 {
-        let base_fee = self.config.http
-            .get_block(BlockNumber::Latest)
-            .await
-            .ok()
-            .and_then(|b| b)
-            .and_then(|b| b.base_fee_per_gas)
-            .unwrap_or(U256::from(30_000_000_000u64)); // 30 gwei default
-        
-        // Sandwich typically uses ~400k gas total
-        U256::from(400_000) * base_fee
+        self.config.gas_model.cost(self.config.gas_model.sandwich_gas(false), 50.0)
     }
 }
 
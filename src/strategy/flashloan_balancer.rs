@@ -1,77 +1,314 @@
+use ethers::abi::{self, AbiEncode, Token};
 use ethers::prelude::*;
 use ethers::types::transaction::eip2718::TypedTransaction;
+use std::str::FromStr;
 use std::sync::Arc;
+use std::time::Instant;
+use crate::address_book::erc20::{ApproveCall, TransferCall};
+use crate::address_book::{
+    FlashLoanCall, SwapExactETHForTokensCall, SwapExactTokensForTokensCall, UniV2Router,
+    UniV2RouterCalls,
+};
+use crate::classifier::TxClass;
+use crate::helpers::eth_to_wei;
 use crate::Config;
 use super::types::*;
 
+/// Where a flash loan is borrowed from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FlashloanProvider {
+    /// Balancer V2 currently charges no flash loan fee.
+    Balancer,
+    /// Aave V3, 0.09% of the borrowed amount.
+    Aave,
+}
+
+impl FlashloanProvider {
+    pub fn fee_bps(&self) -> u64 {
+        match self {
+            Self::Balancer => 0,
+            Self::Aave => 9,
+        }
+    }
+
+    /// Fee owed on top of `amount` at repayment, rounded up.
+    pub fn fee(&self, amount: U256) -> U256 {
+        (amount * self.fee_bps() + 9_999) / 10_000
+    }
+}
+
+impl FromStr for FlashloanProvider {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "balancer" => Ok(Self::Balancer),
+            "aave" => Ok(Self::Aave),
+            other => Err(format!("unknown flash loan provider: {}", other)),
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct FlashloanBalancerStrategy {
     config: Arc<Config>,
-    flashloan_provider: Address,
-    balancer_vault: Address,
+    /// `None` on chains without a Balancer deployment, where nothing is borrowed.
+    balancer_vault: Option<Address>,
     min_profit: U256,
 }
 
 impl FlashloanBalancerStrategy {
     pub fn new(config: Arc<Config>) -> Self {
         Self {
-            flashloan_provider: "0x7d2768dE32b0b80b7a3454c06Bdac2DCf34d8a51".parse().unwrap(), // Aave V2 pool
-            balancer_vault: "0xBA12222222228d8Ba445958a75a0704d566BF2C8".parse().unwrap(), // Balancer vault
+            balancer_vault: config.chain.balancer_vault,
+            min_profit: eth_to_wei(config.settings.flashloan_min_profit_eth),
             config,
-            min_profit: U256::from(10).pow(U256::from(17)), // 0.1 ETH
         }
     }
 
-    pub async fn analyze(&self, tx: &Transaction) -> Vec<MEVOpportunity> {
+    pub async fn analyze(&self, tx: &Transaction, class: &TxClass) -> Vec<MEVOpportunity> {
         let mut ops = Vec::new();
         if tx.value < self.min_profit { return ops; }
-        if let Some(opp) = self.build_flashloan_sandwich(tx).await { ops.push(opp); }
+        if let Some(opp) = self.build_flashloan_backrun(tx, class).await { ops.push(opp); }
         ops
     }
 
-    async fn build_flashloan_sandwich(&self, victim_tx: &Transaction) -> Option<MEVOpportunity> {
-        let flashloan_tx = self.build_flashloan_tx(victim_tx);
-        let repay_tx = self.build_repay_tx();
+    /// Backruns an ETH-in V2 swap with borrowed WETH: buy the token on the other
+    /// V2 router, sell it into the victim's pool, repay the vault - all inside
+    /// one `flashLoan` callback so the loan can never be left open.
+    async fn build_flashloan_backrun(&self, victim_tx: &Transaction, class: &TxClass) -> Option<MEVOpportunity> {
+        let executor = self.config.settings.executor_address?;
+        let vault = self.balancer_vault?;
+        let victim_router = victim_tx.to?;
+
+        let TxClass::SwapV2(UniV2RouterCalls::SwapExactETHForTokens(SwapExactETHForTokensCall { path, .. })) = class else {
+            return None;
+        };
+        let weth = self.get_weth_address();
+        let token = *path.last()?;
+        if path.first() != Some(&weth) || token == weth {
+            return None;
+        }
+
+        // Buy on another of the chain's V2 routers
+        let mut v2_routers: Vec<Address> = self
+            .config
+            .chain
+            .dexes
+            .routers
+            .iter()
+            .filter(|(dex, _)| **dex != DexType::UniswapV3)
+            .map(|(_, router)| *router)
+            .collect();
+        v2_routers.sort();
+        if !v2_routers.contains(&victim_router) {
+            return None;
+        }
+        let buy_router = v2_routers.into_iter().find(|router| *router != victim_router)?;
+
+        // The buy-side pool is untouched by the victim, so its current quote holds
+        let quote_block = self.get_current_block().await;
+        let loan_amount = victim_tx.value;
+        let amounts = UniV2Router::new(buy_router, self.config.http.clone())
+            .get_amounts_out(loan_amount, vec![weth, token])
+            .call()
+            .await
+            .ok()?;
+        let token_amount = *amounts.last()?;
+
+        let steps = self.backrun_steps(vault, executor, weth, token, loan_amount, token_amount, buy_router, victim_router);
+        let flashloan_tx = self.build_flashloan_tx(vault, executor, weth, loan_amount, &steps, victim_tx);
+
         Some(MEVOpportunity {
             id: format!("flashloan_balancer_{:?}", victim_tx.hash),
             target_tx: victim_tx.clone(),
-            strategy_type: StrategyType::Sandwich(SandwichDetails {
+            strategy_type: StrategyType::Flashloan(FlashloanDetails {
                 victim_tx: victim_tx.clone(),
-                frontrun_tx: flashloan_tx,
-                backrun_tx: repay_tx,
-                target_pool: self.balancer_vault,
-                token_in: Address::zero(),
-                token_out: Address::zero(),
-                optimal_amount: U256::zero(),
-                victim_amount_in: victim_tx.value,
-                victim_amount_out_min: U256::zero(),
-                price_impact: 0.0,
+                flashloan_tx,
+                loan_token: weth,
+                loan_amount,
+                steps,
             }),
             estimated_profit: self.min_profit,
-            gas_cost: U256::from(750_000),
+            gas_cost: self.config.gas_model.cost(self.config.gas_model.estimates().flashloan_backrun, 50.0),
             priority: 7,
-            expiry_block: self.get_current_block().await + 1,
+            reserve_block: quote_block,
+            expiry_block: quote_block + 1,
+            observed_at: Instant::now(),
+            landing_probability: 1.0,
         })
     }
 
-    fn build_flashloan_tx(&self, victim_tx: &Transaction) -> TypedTransaction {
+    /// Instructions the executor runs inside `receiveFlashLoan`. The last step
+    /// repays the vault, and the sell leg's `amountOutMin` guarantees there is
+    /// enough WETH to do so, otherwise the whole tx reverts.
+    #[allow(clippy::too_many_arguments)]
+    fn backrun_steps(
+        &self,
+        vault: Address,
+        executor: Address,
+        weth: Address,
+        token: Address,
+        loan_amount: U256,
+        token_amount: U256,
+        buy_router: Address,
+        sell_router: Address,
+    ) -> Vec<FlashloanStep> {
+        let repay_amount = loan_amount + FlashloanProvider::Balancer.fee(loan_amount);
+        let deadline = self.config.swap_deadline();
+
+        vec![
+            FlashloanStep {
+                target: weth,
+                data: ApproveCall { spender: buy_router, value: loan_amount }.encode().into(),
+            },
+            FlashloanStep {
+                target: buy_router,
+                data: UniV2RouterCalls::SwapExactTokensForTokens(SwapExactTokensForTokensCall {
+                    amount_in: loan_amount,
+                    amount_out_min: token_amount,
+                    path: vec![weth, token],
+                    to: executor,
+                    deadline,
+                })
+                .encode()
+                .into(),
+            },
+            FlashloanStep {
+                target: token,
+                data: ApproveCall { spender: sell_router, value: token_amount }.encode().into(),
+            },
+            FlashloanStep {
+                target: sell_router,
+                data: UniV2RouterCalls::SwapExactTokensForTokens(SwapExactTokensForTokensCall {
+                    amount_in: token_amount,
+                    amount_out_min: repay_amount,
+                    path: vec![token, weth],
+                    to: executor,
+                    deadline,
+                })
+                .encode()
+                .into(),
+            },
+            FlashloanStep {
+                target: weth,
+                data: TransferCall { to: vault, value: repay_amount }.encode().into(),
+            },
+        ]
+    }
+
+    /// `Vault.flashLoan(recipient, tokens, amounts, userData)` with the steps
+    /// ABI-encoded as `(address target, bytes data)[]` in `userData`.
+    fn build_flashloan_tx(
+        &self,
+        vault: Address,
+        executor: Address,
+        token: Address,
+        amount: U256,
+        steps: &[FlashloanStep],
+        victim_tx: &Transaction,
+    ) -> TypedTransaction {
+        let user_data = abi::encode(&[Token::Array(
+            steps
+                .iter()
+                .map(|step| Token::Tuple(vec![Token::Address(step.target), Token::Bytes(step.data.to_vec())]))
+                .collect(),
+        )]);
+
+        let calldata = FlashLoanCall {
+            recipient: executor,
+            tokens: vec![token],
+            amounts: vec![amount],
+            user_data: user_data.into(),
+        }
+        .encode();
+
         let mut tx = TypedTransaction::default();
-        tx.set_to(self.flashloan_provider)
-            .set_data(Bytes::from_static(b"flashLoan"))
-            .set_gas(U256::from(500_000))
+        tx.set_to(vault)
+            .set_data(calldata.into())
+            .set_gas(U256::from(600_000))
             .set_gas_price(victim_tx.gas_price.unwrap_or_default());
         tx
     }
 
-    fn build_repay_tx(&self) -> TypedTransaction {
-        let mut tx = TypedTransaction::default();
-        tx.set_to(self.flashloan_provider)
-            .set_data(Bytes::from_static(b"repay"))
-            .set_gas(U256::from(300_000));
-        tx
+    fn get_weth_address(&self) -> Address {
+        self.config.chain.weth
     }
 
     async fn get_current_block(&self) -> U64 {
-        self.config.http.get_block_number().await.unwrap_or_default()
+        self.config.block_height.get()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ethers::abi::AbiDecode;
+    use serde_json::json;
+
+    use super::*;
+    use crate::settings::Settings;
+    use crate::test_support::{self, MockNode};
+
+    #[test]
+    fn flash_loan_fees_round_up() {
+        assert_eq!("aave".parse::<FlashloanProvider>(), Ok(FlashloanProvider::Aave));
+        assert!("dydx".parse::<FlashloanProvider>().is_err());
+
+        assert_eq!(FlashloanProvider::Aave.fee(U256::from(1_000_000)), U256::from(900));
+        // 0.09% of 1000 wei is 0.9, owed as a whole wei
+        assert_eq!(FlashloanProvider::Aave.fee(U256::from(1_000)), U256::one());
+        assert!(FlashloanProvider::Balancer.fee(U256::from(1_000_000)).is_zero());
+    }
+
+    #[tokio::test]
+    async fn the_backrun_is_one_balancer_flash_loan_carrying_its_steps() {
+        let mut node = MockNode::new().await;
+        let executor = Address::from_low_u64_be(0xe8ec);
+        let settings = Settings { executor_address: Some(executor), ..test_support::settings() };
+        let config = Arc::new(test_support::config(&node.url(), settings).await);
+        let (weth, token) = (config.chain.weth, Address::from_low_u64_be(0x70c));
+        let victim_router = config.chain.dexes.router(DexType::UniswapV2).unwrap();
+        let loan_amount = U256::exp10(18);
+        let token_amount = U256::exp10(21);
+        // The other router's getAmountsOut for the buy leg
+        node.rpc("eth_call", json!(Bytes::from(vec![loan_amount, token_amount].encode()))).await;
+
+        let call = UniV2RouterCalls::SwapExactETHForTokens(SwapExactETHForTokensCall {
+            amount_out_min: U256::zero(),
+            path: vec![weth, token],
+            to: Address::from_low_u64_be(0xbeef),
+            deadline: U256::MAX,
+        });
+        let victim = Transaction { to: Some(victim_router), value: loan_amount, input: call.clone().encode().into(), ..Default::default() };
+        let strategy = FlashloanBalancerStrategy::new(config.clone());
+        let opportunity = strategy.build_flashloan_backrun(&victim, &TxClass::SwapV2(call)).await.unwrap();
+        let StrategyType::Flashloan(details) = opportunity.strategy_type else {
+            panic!("expected a flash loan, got {:?}", opportunity.strategy_type);
+        };
+
+        let tx = details.flashloan_tx;
+        assert_eq!(tx.to_addr(), config.chain.balancer_vault.as_ref());
+        let calldata = tx.data().unwrap();
+        // flashLoan(address,address[],uint256[],bytes)
+        assert_eq!(calldata[..4], [0x5c, 0x38, 0x44, 0x9e]);
+        let flash_loan = FlashLoanCall::decode(calldata).unwrap();
+        assert_eq!(flash_loan.recipient, executor);
+        assert_eq!((flash_loan.tokens, flash_loan.amounts), (vec![weth], vec![loan_amount]));
+
+        // userData is the executor's (target, data)[] program, ending in the repayment
+        let program = abi::decode(
+            &[abi::ParamType::Array(Box::new(abi::ParamType::Tuple(vec![abi::ParamType::Address, abi::ParamType::Bytes])))],
+            &flash_loan.user_data,
+        )
+        .unwrap();
+        let Token::Array(decoded) = &program[0] else { unreachable!() };
+        let steps: Vec<Token> = details
+            .steps
+            .iter()
+            .map(|step| Token::Tuple(vec![Token::Address(step.target), Token::Bytes(step.data.to_vec())]))
+            .collect();
+        assert_eq!(decoded, &steps);
+        let repay = TransferCall { to: config.chain.balancer_vault.unwrap(), value: loan_amount };
+        assert_eq!(decoded.last(), Some(&Token::Tuple(vec![Token::Address(weth), Token::Bytes(repay.encode())])));
     }
 }
@@ -0,0 +1,228 @@
+use ethers::prelude::*;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{info, warn};
+
+use crate::address_book::ERC20;
+use crate::gas_oracle::GasOracle;
+use crate::metrics::metrics;
+use crate::signer::SignerClient;
+use super::bundle::BundleBuilder;
+
+/// How often to poll for the target block, and how many polls before giving up.
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+const MAX_POLLS: usize = 60;
+
+/// What actually happened to a submitted opportunity.
+#[derive(Debug, Clone)]
+pub struct InclusionOutcome {
+    pub landed: bool,
+    pub block: U64,
+    pub gross_profit: U256,
+    pub gas_spent: U256,
+}
+
+impl InclusionOutcome {
+    pub fn net_profit(&self) -> I256 {
+        I256::from_raw(self.gross_profit).saturating_sub(I256::from_raw(self.gas_spent))
+    }
+}
+
+/// Confirms whether bundles and txs landed and what they earned.
+///
+/// Realized profit is our account's balance delta over the inclusion block,
+/// so it already nets out gas; gas is added back to report the gross figure.
+/// Routes that pay out in WETH count its balance delta alongside ETH's.
+#[derive(Debug)]
+pub struct InclusionWatcher {
+    provider: Arc<SignerClient>,
+    bundle_builder: Arc<BundleBuilder>,
+    /// Fed the prices our landed bundles paid, which floor later bids.
+    gas_oracle: Arc<GasOracle>,
+}
+
+impl InclusionWatcher {
+    pub fn new(
+        provider: Arc<SignerClient>,
+        bundle_builder: Arc<BundleBuilder>,
+        gas_oracle: Arc<GasOracle>,
+    ) -> Self {
+        Self { provider, bundle_builder, gas_oracle }
+    }
+
+    /// Waits for `target_block` and checks whether any of our bundle txs made it in.
+    /// `weth` is set when the bundle's profit lands as WETH rather than ETH.
+    pub async fn watch_bundle(
+        &self,
+        bundle_hash: TxHash,
+        target_block: U64,
+        own_tx_hashes: &[H256],
+        weth: Option<Address>,
+    ) -> Option<InclusionOutcome> {
+        let block = self.wait_for_block(target_block).await?;
+
+        match self.bundle_builder.get_bundle_stats(bundle_hash, target_block).await {
+            Ok(stats) => info!(
+                "📦 Bundle {:?}: simulated={}, high_priority={}, sealed by {} builder(s)",
+                bundle_hash,
+                stats.is_simulated,
+                stats.is_high_priority,
+                stats.sealed_by_builders_at.len()
+            ),
+            Err(e) => warn!("⚠️ Could not fetch bundle stats for {:?}: {}", bundle_hash, e),
+        }
+
+        let landed = !own_tx_hashes.is_empty() && own_tx_hashes.iter().all(|hash| block.transactions.contains(hash));
+        if !landed {
+            return Some(InclusionOutcome {
+                landed: false,
+                block: target_block,
+                gross_profit: U256::zero(),
+                gas_spent: U256::zero(),
+            });
+        }
+
+        metrics().bundles_landed.inc();
+
+        let mut gas_spent = U256::zero();
+        for hash in own_tx_hashes {
+            if let Ok(Some(receipt)) = self.provider.get_transaction_receipt(*hash).await {
+                let effective_gas_price = receipt.effective_gas_price.unwrap_or_default();
+                gas_spent += receipt.gas_used.unwrap_or_default() * effective_gas_price;
+                self.gas_oracle.record_included_bid(effective_gas_price);
+            }
+        }
+        Some(self.realized(target_block, gas_spent, weth).await)
+    }
+
+    /// Waits for `tx_hash` to be mined; `weth` as for `watch_bundle`.
+    pub async fn watch_transaction(&self, tx_hash: TxHash, weth: Option<Address>) -> Option<InclusionOutcome> {
+        let receipt = PendingTransaction::new(tx_hash, self.provider.provider()).await.ok()??;
+        let block = receipt.block_number?;
+        let gas_spent = receipt.gas_used.unwrap_or_default() * receipt.effective_gas_price.unwrap_or_default();
+
+        if receipt.status != Some(U64::one()) {
+            return Some(InclusionOutcome { landed: false, block, gross_profit: U256::zero(), gas_spent });
+        }
+        Some(self.realized(block, gas_spent, weth).await)
+    }
+
+    async fn realized(&self, block: U64, gas_spent: U256, weth: Option<Address>) -> InclusionOutcome {
+        let before = self.holdings(block - 1, weth).await;
+        let after = self.holdings(block, weth).await;
+
+        // A negative delta means we lost more than gas: nothing gross to report
+        let gross_profit = (after + gas_spent).saturating_sub(before);
+        InclusionOutcome { landed: true, block, gross_profit, gas_spent }
+    }
+
+    /// Our ETH balance at `block`, plus our `weth` balance if given.
+    async fn holdings(&self, block: U64, weth: Option<Address>) -> U256 {
+        let account = self.provider.address();
+        let mut holdings = self.provider.get_balance(account, Some(block.into())).await.unwrap_or_default();
+        if let Some(weth) = weth {
+            let balance = ERC20::new(weth, self.provider.clone()).balance_of(account).block(block).call().await;
+            match balance {
+                Ok(balance) => holdings += balance,
+                Err(e) => warn!("⚠️ Could not read WETH balance at block {}: {}", block, e),
+            }
+        }
+        holdings
+    }
+
+    async fn wait_for_block(&self, number: U64) -> Option<Block<H256>> {
+        for _ in 0..MAX_POLLS {
+            if let Ok(Some(block)) = self.provider.get_block(number).await {
+                return Some(block);
+            }
+            tokio::time::sleep(POLL_INTERVAL).await;
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::test_support::{self, MockNode};
+
+    const GWEI: u64 = 1_000_000_000;
+
+    /// A node on which block 101 holds `included`, our txs each burn 100k gas at
+    /// 80 gwei, and our balance goes from 10 ETH to 10.05 ETH over the block.
+    async fn node(included: Vec<H256>) -> MockNode {
+        let mut node = MockNode::new().await;
+        let block = Block::<H256> { number: Some(U64::from(101)), transactions: included, ..Default::default() };
+        node.rpc("eth_getBlockByNumber", json!(block)).await;
+        let receipt = TransactionReceipt {
+            block_number: Some(U64::from(101)),
+            gas_used: Some(U256::from(100_000)),
+            effective_gas_price: Some(U256::from(80 * GWEI)),
+            status: Some(U64::one()),
+            ..Default::default()
+        };
+        node.rpc("eth_getTransactionReceipt", json!(receipt)).await;
+        let us = test_support::wallet().address();
+        for (block, balance) in [("0x64", U256::exp10(19)), ("0x65", U256::exp10(19) + U256::exp10(16) * 5)] {
+            node.rpc_matching(json!({ "method": "eth_getBalance", "params": [us, block] }), json!(balance)).await;
+        }
+        node
+    }
+
+    async fn watcher(node: &MockNode, relay: &MockNode) -> (InclusionWatcher, Arc<GasOracle>) {
+        let config = test_support::config(&node.url(), test_support::settings()).await;
+        let builder = Arc::new(test_support::bundle_builder(&config, &relay.url()));
+        (InclusionWatcher::new(config.http.clone(), builder, config.gas_oracle.clone()), config.gas_oracle.clone())
+    }
+
+    async fn relay() -> MockNode {
+        let mut relay = MockNode::new().await;
+        relay.rpc("flashbots_getBundleStats", json!({ "isSimulated": true, "sealedByBuildersAt": [] })).await;
+        relay
+    }
+
+    #[test]
+    fn net_profit_goes_negative_when_gas_exceeds_the_take() {
+        let outcome = |gross_profit: u64, gas_spent: u64| InclusionOutcome {
+            landed: true,
+            block: U64::one(),
+            gross_profit: U256::from(gross_profit),
+            gas_spent: U256::from(gas_spent),
+        };
+        assert_eq!(outcome(1_000, 300).net_profit(), I256::from(700));
+        assert_eq!(outcome(0, 300).net_profit(), I256::from(-300));
+    }
+
+    #[tokio::test]
+    async fn an_included_bundle_reports_its_balance_delta_and_gas() {
+        let ours = vec![H256::from_low_u64_be(0xa1), H256::from_low_u64_be(0xa2)];
+        let node = node(vec![H256::from_low_u64_be(0x71c), ours[0], ours[1]]).await;
+        let relay = relay().await;
+        let (watcher, gas_oracle) = watcher(&node, &relay).await;
+
+        let outcome = watcher.watch_bundle(H256::from_low_u64_be(0xb), U64::from(101), &ours, None).await.unwrap();
+        assert!(outcome.landed);
+        assert_eq!(outcome.block, U64::from(101));
+        // Two txs at 100k gas and 80 gwei, added back to the 0.05 ETH we netted
+        let gas_spent = U256::from(2 * 100_000 * 80 * GWEI);
+        assert_eq!(outcome.gas_spent, gas_spent);
+        assert_eq!(outcome.gross_profit, U256::exp10(16) * 5 + gas_spent);
+        // What landed now floors later bids, above the 50 gwei the empty oracle falls back to
+        assert_eq!(gas_oracle.bid_floor(50.0), U256::from(80 * GWEI));
+    }
+
+    #[tokio::test]
+    async fn a_bundle_missing_from_its_block_did_not_land() {
+        let ours = vec![H256::from_low_u64_be(0xa1), H256::from_low_u64_be(0xa2)];
+        // Only one of our txs made it, so the bundle as a whole didn't
+        let node = node(vec![H256::from_low_u64_be(0x71c), ours[0]]).await;
+        let relay = relay().await;
+        let (watcher, _) = watcher(&node, &relay).await;
+
+        let outcome = watcher.watch_bundle(H256::from_low_u64_be(0xb), U64::from(101), &ours, None).await.unwrap();
+        assert!(!outcome.landed);
+        assert!(outcome.gross_profit.is_zero() && outcome.gas_spent.is_zero());
+    }
+}
@@ -0,0 +1,123 @@
+use std::sync::Mutex;
+
+use crate::settings::Settings;
+
+/// Resolved submissions the prior counts as, so a bucket's first few outcomes
+/// move its estimate without swinging it to 0 or 1.
+const PRIOR_WEIGHT: f64 = 4.0;
+
+/// Gas percentile buckets: 0-9, 10-19, ... 90-100.
+const BUCKETS: usize = 10;
+
+#[derive(Debug, Clone, Copy, Default)]
+struct Tally {
+    submitted: u64,
+    landed: u64,
+}
+
+impl Tally {
+    fn record(&mut self, landed: bool) {
+        self.submitted += 1;
+        self.landed += landed as u64;
+    }
+
+    /// Landing rate, pulled toward `prior` while there are few outcomes.
+    fn rate(&self, prior: f64) -> f64 {
+        (self.landed as f64 + prior * PRIOR_WEIGHT) / (self.submitted as f64 + PRIOR_WEIGHT)
+    }
+}
+
+#[derive(Debug, Default)]
+struct Tallies {
+    by_percentile: [Tally; BUCKETS],
+    overall: Tally,
+}
+
+/// How often our submissions landed, by where their gas bid sat among what
+/// recent blocks paid, turned into the probability the next one at that
+/// percentile lands. Opportunities are ranked by their net profit weighted by
+/// it, so a likely small win can outrank an unlikely large one.
+#[derive(Debug)]
+pub struct LandingStats {
+    tallies: Mutex<Tallies>,
+    prior: f64,
+}
+
+impl LandingStats {
+    pub fn new(prior: f64) -> Self {
+        Self { tallies: Mutex::new(Tallies::default()), prior: prior.clamp(0.0, 1.0) }
+    }
+
+    pub fn from_settings(settings: &Settings) -> Self {
+        Self::new(settings.landing_probability_prior)
+    }
+
+    /// Records whether a submission bidding at `percentile` (0-100) landed.
+    /// Without a percentile it only counts toward the overall rate.
+    pub fn record(&self, percentile: Option<f64>, landed: bool) {
+        let mut tallies = self.tallies.lock().unwrap();
+        tallies.overall.record(landed);
+        if let Some(percentile) = percentile {
+            tallies.by_percentile[bucket(percentile)].record(landed);
+        }
+    }
+
+    /// Probability a submission bidding at `percentile` lands: its bucket's
+    /// rate, starting from the overall rate until the bucket fills in.
+    pub fn probability(&self, percentile: Option<f64>) -> f64 {
+        let tallies = self.tallies.lock().unwrap();
+        let overall = tallies.overall.rate(self.prior);
+        match percentile {
+            Some(percentile) => tallies.by_percentile[bucket(percentile)].rate(overall),
+            None => overall,
+        }
+    }
+}
+
+fn bucket(percentile: f64) -> usize {
+    ((percentile.clamp(0.0, 100.0) / 100.0 * BUCKETS as f64) as usize).min(BUCKETS - 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn probability_starts_at_the_prior() {
+        let stats = LandingStats::new(0.4);
+        assert_eq!(stats.probability(None), 0.4);
+        assert_eq!(stats.probability(Some(75.0)), 0.4);
+        assert_eq!(LandingStats::new(7.0).probability(None), 1.0);
+    }
+
+    #[test]
+    fn outcomes_move_their_bucket_more_than_the_others() {
+        let stats = LandingStats::new(0.5);
+        for _ in 0..8 {
+            stats.record(Some(92.0), true);
+            stats.record(Some(12.0), false);
+        }
+        let high = stats.probability(Some(95.0));
+        let low = stats.probability(Some(10.0));
+        assert!(high > 0.8 && low < 0.2, "high {}, low {}", high, low);
+        // An untouched bucket falls back to the overall rate
+        assert!((stats.probability(Some(50.0)) - stats.probability(None)).abs() < 1e-9);
+    }
+
+    #[test]
+    fn outcomes_without_a_percentile_only_count_overall() {
+        let stats = LandingStats::new(0.5);
+        stats.record(None, false);
+        // (0 + 0.5 * 4) / (1 + 4)
+        assert!((stats.probability(None) - 0.4).abs() < 1e-9);
+    }
+
+    #[test]
+    fn bucket_clamps_out_of_range_percentiles() {
+        assert_eq!(bucket(-5.0), 0);
+        assert_eq!(bucket(9.99), 0);
+        assert_eq!(bucket(10.0), 1);
+        assert_eq!(bucket(100.0), BUCKETS - 1);
+        assert_eq!(bucket(250.0), BUCKETS - 1);
+    }
+}
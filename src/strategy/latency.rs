@@ -0,0 +1,125 @@
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::settings::Settings;
+
+/// Weight of the newest timing in the simulation latency average.
+const EWMA_ALPHA: f64 = 0.2;
+
+/// Time left before the block boundary an opportunity has to beat, and whether
+/// a full simulation still fits in it.
+///
+/// The boundary is the next multiple of the block time after the newest head,
+/// as of when the victim was seen; time spent since then comes off the budget.
+/// A simulation is expected to take as long as recent ones did, so with less
+/// than that plus the submission reserve left, the opportunity goes out on the
+/// estimates it was found with.
+#[derive(Debug)]
+pub struct LatencyBudget {
+    enabled: bool,
+    block_time: Duration,
+    submit_reserve: Duration,
+    /// Moving average of simulation wall time, in seconds.
+    simulation_secs: Mutex<f64>,
+}
+
+impl LatencyBudget {
+    pub fn new(enabled: bool, block_time: Duration, submit_reserve: Duration, simulation_estimate: Duration) -> Self {
+        Self { enabled, block_time, submit_reserve, simulation_secs: Mutex::new(simulation_estimate.as_secs_f64()) }
+    }
+
+    pub fn from_settings(settings: &Settings) -> Self {
+        Self::new(
+            settings.latency_budget,
+            Duration::from_secs(settings.block_time_secs),
+            Duration::from_millis(settings.submit_latency_reserve_ms),
+            Duration::from_millis(settings.simulation_latency_ms),
+        )
+    }
+
+    /// Time left, from when a victim seen `since_observed` ago, until the first
+    /// block boundary after it closes. `None` before the first head, or with the budget off.
+    pub fn remaining(&self, head_timestamp: u64, since_observed: Duration) -> Option<Duration> {
+        self.remaining_at(head_timestamp, since_observed, SystemTime::now())
+    }
+
+    /// `remaining` as of `now`.
+    pub fn remaining_at(&self, head_timestamp: u64, since_observed: Duration, now: SystemTime) -> Option<Duration> {
+        if !self.enabled || head_timestamp == 0 || self.block_time.is_zero() {
+            return None;
+        }
+        let now = now.duration_since(UNIX_EPOCH).ok()?.as_secs_f64();
+        let observed = now - since_observed.as_secs_f64();
+        // A missed slot pushes the boundary out by whole block times
+        let block_secs = self.block_time.as_secs_f64();
+        let blocks = ((observed - head_timestamp as f64).max(0.0) / block_secs).floor() + 1.0;
+        let boundary = head_timestamp as f64 + blocks * block_secs;
+        Some(Duration::from_secs_f64((boundary - now).max(0.0)))
+    }
+
+    /// Expected wall time of one full simulation.
+    pub fn simulation_estimate(&self) -> Duration {
+        Duration::from_secs_f64(*self.simulation_secs.lock().unwrap())
+    }
+
+    /// Whether a simulation and the submission after it still fit in `remaining`.
+    /// Without a known boundary there is always time.
+    pub fn allows_simulation(&self, remaining: Option<Duration>) -> bool {
+        match remaining {
+            Some(left) => left >= self.simulation_estimate() + self.submit_reserve,
+            None => true,
+        }
+    }
+
+    /// Folds a finished simulation's wall time into the estimate.
+    pub fn record_simulation(&self, elapsed: Duration) {
+        let mut average = self.simulation_secs.lock().unwrap();
+        *average += EWMA_ALPHA * (elapsed.as_secs_f64() - *average);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn budget() -> LatencyBudget {
+        LatencyBudget::new(true, Duration::from_secs(12), Duration::from_millis(300), Duration::from_millis(150))
+    }
+
+    fn at(secs: f64) -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs_f64(secs)
+    }
+
+    #[test]
+    fn remaining_runs_to_the_next_boundary_after_the_victim_was_seen() {
+        let head = 1_700_000_000;
+        let left = budget().remaining_at(head, Duration::from_secs(1), at(head as f64 + 5.0)).unwrap();
+        assert_eq!(left, Duration::from_secs(7));
+        // Seen after a missed slot, the boundary is one block time later
+        let left = budget().remaining_at(head, Duration::ZERO, at(head as f64 + 13.0)).unwrap();
+        assert_eq!(left, Duration::from_secs(11));
+        // Seen before the boundary but handled after it, nothing is left
+        let left = budget().remaining_at(head, Duration::from_secs(3), at(head as f64 + 14.0)).unwrap();
+        assert_eq!(left, Duration::ZERO);
+    }
+
+    #[test]
+    fn remaining_is_unknown_before_the_first_head_or_when_disabled() {
+        assert_eq!(budget().remaining_at(0, Duration::ZERO, at(5.0)), None);
+        let off = LatencyBudget::new(false, Duration::from_secs(12), Duration::ZERO, Duration::ZERO);
+        assert_eq!(off.remaining_at(1_700_000_000, Duration::ZERO, at(1_700_000_001.0)), None);
+    }
+
+    #[test]
+    fn simulation_needs_its_estimate_plus_the_submit_reserve() {
+        let budget = budget();
+        assert!(budget.allows_simulation(None));
+        assert!(budget.allows_simulation(Some(Duration::from_millis(450))));
+        assert!(!budget.allows_simulation(Some(Duration::from_millis(449))));
+
+        // A slow simulation raises the estimate by a fifth of the difference
+        budget.record_simulation(Duration::from_millis(1_150));
+        assert_eq!(budget.simulation_estimate().as_millis(), 350);
+        assert!(!budget.allows_simulation(Some(Duration::from_millis(600))));
+    }
+}
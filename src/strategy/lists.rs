@@ -0,0 +1,249 @@
+use std::collections::HashSet;
+use std::path::PathBuf;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, SystemTime};
+
+use ethers::abi::AbiDecode;
+use ethers::prelude::*;
+use serde::Deserialize;
+use tracing::{info, warn};
+
+use super::arbitrage::ArbitrageStrategy;
+use super::types::{MEVOpportunity, StrategyType};
+use crate::address_book::UniV2RouterCalls;
+use crate::chain::ChainConfig;
+use crate::settings::Settings;
+use crate::universal_router::{self, Command};
+use crate::uni_v3;
+
+/// How often the lists file is checked for changes.
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Contents of the lists file. Empty allowlists allow everything.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TokenPoolLists {
+    pub blocked_tokens: HashSet<Address>,
+    pub blocked_pools: HashSet<Address>,
+    /// `weth` is always allowed: every route starts or ends in it.
+    pub allowed_tokens: HashSet<Address>,
+    pub allowed_pools: HashSet<Address>,
+    /// The chain's wrapped native token; not read from the file.
+    #[serde(skip)]
+    pub weth: Address,
+}
+
+impl TokenPoolLists {
+    pub fn parse(contents: &str) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        Ok(toml::from_str(contents)?)
+    }
+
+    pub fn token_allowed(&self, token: Address) -> bool {
+        !self.blocked_tokens.contains(&token)
+            && (self.allowed_tokens.is_empty() || self.allowed_tokens.contains(&token) || token == self.weth)
+    }
+
+    pub fn pool_allowed(&self, pool: Address) -> bool {
+        !self.blocked_pools.contains(&pool) && (self.allowed_pools.is_empty() || self.allowed_pools.contains(&pool))
+    }
+
+    /// Whether every token and pool `opportunity` trades is allowed.
+    pub fn permits(&self, opportunity: &MEVOpportunity) -> bool {
+        let (tokens, pools) = match &opportunity.strategy_type {
+            StrategyType::Sandwich(details) => (vec![details.token_in, details.token_out], vec![details.target_pool]),
+            StrategyType::Arbitrage(details) => {
+                (details.path.clone(), details.pools.iter().map(|pool| pool.address).collect())
+            }
+            StrategyType::Flashloan(details) => (vec![details.loan_token], Vec::new()),
+            StrategyType::Backrun(_) => (Vec::new(), Vec::new()),
+        };
+        tokens.into_iter().all(|token| self.token_allowed(token)) && pools.into_iter().all(|pool| self.pool_allowed(pool))
+    }
+
+    /// Whether a pending swap only touches allowed tokens, and isn't sent
+    /// straight to a blocked pool.
+    pub fn permits_tx(&self, tx: &Transaction) -> bool {
+        if tx.to.is_some_and(|to| self.blocked_pools.contains(&to)) {
+            return false;
+        }
+        swap_tokens(tx).into_iter().all(|token| self.token_allowed(token))
+    }
+}
+
+/// Tokens a V2, V3, or Universal Router swap trades through.
+fn swap_tokens(tx: &Transaction) -> Vec<Address> {
+    if let Ok(call) = UniV2RouterCalls::decode(&tx.input) {
+        return ArbitrageStrategy::v2_swap_path(call);
+    }
+    let v3_swaps = uni_v3::decode_router_swaps(&tx.input);
+    if !v3_swaps.is_empty() {
+        return v3_swaps.into_iter().flat_map(|swap| swap.tokens).collect();
+    }
+    let Some(execute) = universal_router::decode_execute(&tx.input) else {
+        return Vec::new();
+    };
+    execute
+        .swaps()
+        .flat_map(|command| match command {
+            Command::V2SwapExactIn { path, .. } | Command::V2SwapExactOut { path, .. } => path.clone(),
+            Command::V3Swap(swap) => swap.tokens.clone(),
+            _ => Vec::new(),
+        })
+        .collect()
+}
+
+/// Token and pool allow/blocklists, read from a TOML file and reloaded
+/// whenever it changes, so a scam token can be blocked without a restart.
+#[derive(Debug)]
+pub struct AccessLists {
+    path: Option<PathBuf>,
+    weth: Address,
+    lists: RwLock<TokenPoolLists>,
+    modified: RwLock<Option<SystemTime>>,
+}
+
+impl AccessLists {
+    pub fn new(path: Option<PathBuf>, weth: Address) -> Self {
+        let lists = Self {
+            path,
+            weth,
+            lists: RwLock::new(TokenPoolLists { weth, ..Default::default() }),
+            modified: RwLock::new(None),
+        };
+        lists.reload();
+        lists
+    }
+
+    pub fn from_settings(settings: &Settings, chain: &ChainConfig) -> Self {
+        Self::new(settings.access_lists_file.as_ref().map(PathBuf::from), chain.weth)
+    }
+
+    pub fn permits(&self, opportunity: &MEVOpportunity) -> bool {
+        self.lists.read().unwrap().permits(opportunity)
+    }
+
+    pub fn permits_tx(&self, tx: &Transaction) -> bool {
+        self.lists.read().unwrap().permits_tx(tx)
+    }
+
+    pub fn set(&self, lists: TokenPoolLists) {
+        *self.lists.write().unwrap() = TokenPoolLists { weth: self.weth, ..lists };
+    }
+
+    /// Re-reads the file if it changed since the last read. A file that fails to
+    /// parse leaves the previous lists in place.
+    pub fn reload(&self) -> bool {
+        let Some(path) = &self.path else {
+            return false;
+        };
+        let modified = std::fs::metadata(path).and_then(|meta| meta.modified()).ok();
+        if modified.is_none() || *self.modified.read().unwrap() == modified {
+            return false;
+        }
+        *self.modified.write().unwrap() = modified;
+
+        let parsed = std::fs::read_to_string(path)
+            .map_err(|e| e.to_string())
+            .and_then(|contents| TokenPoolLists::parse(&contents).map_err(|e| e.to_string()));
+        match parsed {
+            Ok(lists) => {
+                info!(
+                    "📋 Loaded lists from {}: {} blocked tokens, {} blocked pools, {} allowed tokens, {} allowed pools",
+                    path.display(),
+                    lists.blocked_tokens.len(),
+                    lists.blocked_pools.len(),
+                    lists.allowed_tokens.len(),
+                    lists.allowed_pools.len()
+                );
+                self.set(lists);
+                true
+            }
+            Err(e) => {
+                warn!("⚠️ Could not load lists from {}, keeping the previous ones: {}", path.display(), e);
+                false
+            }
+        }
+    }
+
+    /// Polls the file for changes.
+    pub async fn watch(self: Arc<Self>) {
+        if self.path.is_none() {
+            return;
+        }
+        let mut interval = tokio::time::interval(POLL_INTERVAL);
+        loop {
+            interval.tick().await;
+            self.reload();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::address_book::SwapExactTokensForTokensCall;
+    use ethers::abi::AbiEncode;
+
+    fn token(n: u64) -> Address {
+        Address::from_low_u64_be(n)
+    }
+
+    fn swap(to: Address, path: Vec<Address>) -> Transaction {
+        let call = SwapExactTokensForTokensCall {
+            amount_in: 1.into(),
+            amount_out_min: 0.into(),
+            path,
+            to: token(99),
+            deadline: 0.into(),
+        };
+        Transaction { to: Some(to), input: call.encode().into(), ..Default::default() }
+    }
+
+    #[test]
+    fn parse_reads_addresses_and_rejects_unknown_keys() {
+        let lists = TokenPoolLists::parse(&format!("blocked_tokens = [\"{:?}\"]", token(1))).unwrap();
+        assert_eq!(lists.blocked_tokens, HashSet::from([token(1)]));
+        assert!(TokenPoolLists::parse("blocked_token = []").is_err());
+    }
+
+    #[test]
+    fn allowlists_always_allow_weth() {
+        let lists = TokenPoolLists {
+            allowed_tokens: HashSet::from([token(1)]),
+            blocked_tokens: HashSet::from([token(2)]),
+            weth: token(10),
+            ..Default::default()
+        };
+        assert!(lists.token_allowed(token(1)) && lists.token_allowed(token(10)));
+        assert!(!lists.token_allowed(token(2)) && !lists.token_allowed(token(3)));
+        // No allowlist allows anything not blocked
+        assert!(TokenPoolLists::default().pool_allowed(token(3)));
+    }
+
+    #[test]
+    fn permits_tx_checks_every_token_swapped_and_the_pool_sent_to() {
+        let lists = TokenPoolLists {
+            blocked_tokens: HashSet::from([token(2)]),
+            blocked_pools: HashSet::from([token(50)]),
+            ..Default::default()
+        };
+        assert!(lists.permits_tx(&swap(token(60), vec![token(1), token(3)])));
+        assert!(!lists.permits_tx(&swap(token(60), vec![token(1), token(2), token(3)])));
+        assert!(!lists.permits_tx(&swap(token(50), vec![token(1), token(3)])));
+    }
+
+    #[test]
+    fn reload_keeps_the_previous_lists_when_the_file_is_broken() {
+        let path = std::env::temp_dir().join(format!("mev-bot-lists-{}.toml", std::process::id()));
+        std::fs::write(&path, format!("blocked_tokens = [\"{:?}\"]", token(1))).unwrap();
+        let lists = AccessLists::new(Some(path.clone()), token(10));
+        assert!(!lists.lists.read().unwrap().token_allowed(token(1)));
+
+        std::fs::write(&path, "blocked_tokens = [").unwrap();
+        // Make sure the change is seen even on filesystems with coarse timestamps
+        *lists.modified.write().unwrap() = None;
+        assert!(!lists.reload());
+        assert!(!lists.lists.read().unwrap().token_allowed(token(1)));
+        std::fs::remove_file(path).unwrap();
+    }
+}
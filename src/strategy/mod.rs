@@ -4,106 +4,923 @@ pub mod types;
 pub mod simulator;
 pub mod bundle;
 pub mod flashloan_balancer;
+pub mod trace;
+pub mod pairs;
+pub mod dex_registry;
+pub mod inclusion;
+pub mod circuit_breaker;
+pub mod emergency_stop;
+pub mod dedup;
+pub mod price_oracle;
+pub mod competition;
+pub mod token_safety;
+pub mod nonce;
+pub mod scheduler;
+pub mod lists;
+pub mod bridge;
+pub mod oracle_backrun;
+pub mod settlement;
+pub mod volatility;
+pub mod bus;
+pub mod latency;
+pub mod landing;
 
 use ethers::prelude::*;
 use std::sync::Arc;
+use std::time::Instant;
 use tokio::sync::RwLock;
+use tracing::{info, warn, Instrument};
 use crate::Config;
+use crate::alert::alert;
+use crate::classifier::classify;
+use crate::helpers::eth_to_wei;
+use crate::ledger::Ledger;
+use crate::metrics::{metrics, wei_to_eth};
 
 pub use types::*;
 pub use sandwich::SandwichStrategy;
 pub use arbitrage::ArbitrageStrategy;
 pub use simulator::TxSimulator;
-pub use bundle::BundleBuilder;
+pub use bundle::{AuthHeader, BidPolicy, BidStrategy, BlockSource, BundleBuilder, BundleBuilderParams, CallBundleTxResult, RelayAuth, SigningMode};
+pub use dex_registry::DexRegistry;
+pub use trace::{DecisionTrace, TraceStep};
+pub use inclusion::{InclusionOutcome, InclusionWatcher};
+pub use circuit_breaker::CircuitBreaker;
+pub use emergency_stop::EmergencyStop;
+pub use dedup::OpportunityDedup;
+pub use price_oracle::PriceOracle;
+pub use competition::{CompetitionTracker, GasWar, GasWarPolicy, GasWarResponse};
+pub use token_safety::{TokenRisk, TokenSafety};
+pub use nonce::NonceManager;
+pub use scheduler::{OpportunityScheduler, SchedulerBudget};
+pub use lists::{AccessLists, TokenPoolLists};
+pub use bridge::{BridgeMonitor, CrossChainOpportunity};
+pub use oracle_backrun::OracleFeed;
+pub use settlement::{Settlement, SettlementPolicy, SettlementSwap};
+pub use bus::{OpportunityBus, PublishedOpportunity};
+pub use latency::LatencyBudget;
+pub use landing::LandingStats;
 
 #[derive(Debug, Clone)]
 pub struct StrategyManager {
     sandwich: Arc<RwLock<SandwichStrategy>>,
     arbitrage: Arc<RwLock<ArbitrageStrategy>>,
     flashloan: Arc<RwLock<FlashloanBalancerStrategy>>,
+    enhanced_sandwich: Arc<RwLock<EnhancedSandwichStrategy>>,
+    advanced: Arc<RwLock<AdvancedMEVFeatures>>,
     simulator: Arc<TxSimulator>,
     bundle_builder: Arc<BundleBuilder>,
+    nonces: Arc<NonceManager>,
+    ledger: Option<Arc<Ledger>>,
+    inclusion_watcher: Arc<InclusionWatcher>,
+    circuit_breaker: Arc<CircuitBreaker>,
+    emergency_stop: Arc<EmergencyStop>,
+    dedup: Arc<OpportunityDedup>,
+    competition: Arc<CompetitionTracker>,
+    access_lists: Arc<AccessLists>,
+    settlement: Arc<Settlement>,
+    latency: Arc<LatencyBudget>,
+    landing: Arc<LandingStats>,
     config: Arc<Config>,
 }
 
 impl StrategyManager {
     pub async fn new(config: Arc<Config>) -> Self {
-        let simulator = Arc::new(TxSimulator::new(config.http.clone()));
-        let bundle_builder = Arc::new(BundleBuilder::new(config.http.clone()));
+        let replacement_bump_bps = config
+            .settings
+            .victim_replacement_check
+            .then_some(config.settings.victim_replacement_bump_bps);
+        let nonces = Arc::new(NonceManager::new(config.http.clone(), config.settings.reconcile_nonce));
+        if let Err(e) = nonces.sync().await {
+            warn!("⚠️ Could not read our nonce at startup: {}", e);
+        }
+        if config.settings.gas.coinbase_bribe_bps > 0 && config.settings.arb_executor_address.is_none() {
+            warn!("⚠️ COINBASE_BRIBE_BPS needs ARB_EXECUTOR_ADDRESS to pay block.coinbase through; bribes are off");
+        }
+        let relay = config.settings.relay.urls.first().cloned().unwrap_or_else(|| "https://relay.flashbots.net".to_string());
+        let bundle_builder = Arc::new(BundleBuilder::new(BundleBuilderParams {
+            provider: config.http.clone(),
+            ws_provider: config.wss.clone(),
+            block_source: config.settings.bundle_block_source,
+            gas_oracle: config.gas_oracle.clone(),
+            relay_auth: config.settings.relay.auth(&relay),
+            relay,
+            nonces: nonces.clone(),
+            arb_executor: config.settings.arb_executor_address,
+            weth: config.chain.weth,
+            bid_policy: BidPolicy::from_settings(&config.settings),
+        }));
+        let simulator = Arc::new(TxSimulator::new(
+            config.http.clone(),
+            replacement_bump_bps,
+            config.gas_oracle.clone(),
+            config.gas_model.clone(),
+            config.settings.simulate_victim_after_frontrun.then(|| bundle_builder.clone()),
+            config.settings.arb_executor_address,
+            config.chain.weth,
+        ));
+        let ledger = config.settings.ledger_path.as_ref().and_then(|path| match Ledger::open(path) {
+            Ok(ledger) => Some(Arc::new(ledger)),
+            Err(e) => {
+                warn!("⚠️ Could not open ledger at {}: {}", path, e);
+                None
+            }
+        });
+
+        let inclusion_watcher = Arc::new(InclusionWatcher::new(config.http.clone(), bundle_builder.clone(), config.gas_oracle.clone()));
+        let competition = Arc::new(CompetitionTracker::from_settings(&config.settings));
 
         Self {
-            sandwich: Arc::new(RwLock::new(SandwichStrategy::new(config.clone()))),
+            sandwich: Arc::new(RwLock::new(SandwichStrategy::new(config.clone(), competition.clone()))),
             arbitrage: Arc::new(RwLock::new(ArbitrageStrategy::new(config.clone()))),
             flashloan: Arc::new(RwLock::new(FlashloanBalancerStrategy::new(config.clone()))),
+            enhanced_sandwich: Arc::new(RwLock::new(EnhancedSandwichStrategy::new(config.clone()))),
+            advanced: Arc::new(RwLock::new(AdvancedMEVFeatures::new(config.clone()))),
             simulator,
             bundle_builder,
+            nonces,
+            ledger,
+            inclusion_watcher,
+            circuit_breaker: Arc::new(CircuitBreaker::from_settings(&config.settings)),
+            emergency_stop: Arc::new(EmergencyStop::from_settings(&config.settings)),
+            dedup: Arc::new(OpportunityDedup::from_settings(&config.settings)),
+            competition,
+            access_lists: Arc::new(AccessLists::from_settings(&config.settings, &config.chain)),
+            settlement: Arc::new(Settlement::from_config(config.clone())),
+            latency: Arc::new(LatencyBudget::from_settings(&config.settings)),
+            landing: Arc::new(LandingStats::from_settings(&config.settings)),
             config,
         }
     }
 
-    pub async fn analyze_transaction(&self, tx: &Transaction) -> Vec<MEVOpportunity> {
+    /// Records a pending swap's bid on its pool, returning the gas war it set off, if any.
+    pub async fn observe_pending_swap(&self, tx: &Transaction) -> Option<GasWar> {
+        let pool = self.sandwich.read().await.pool_of(tx)?;
+        self.competition.observe(pool, tx, self.config.block_height.get())
+    }
+
+    /// Re-simulates `opportunity` against current state.
+    pub async fn simulate(&self, opportunity: &MEVOpportunity) -> Result<SimulationResult, String> {
+        self.simulator.simulate(opportunity).await.map_err(|e| e.to_string())
+    }
+
+    /// Starts a decision trace for `tx`, enabled only if configured for it.
+    pub fn decision_trace(&self, tx: &Transaction) -> DecisionTrace {
+        DecisionTrace::new(&self.config.settings, tx.hash)
+    }
+
+    pub async fn analyze_transaction(&self, tx: &Transaction, trace: &DecisionTrace) -> Vec<MEVOpportunity> {
+        self.analyze(tx, trace, None).await
+    }
+
+    /// `analyze_transaction` for a victim first seen at `observed_at`, racing the
+    /// next block: once a simulation would no longer fit before it, opportunities
+    /// are judged on their own estimates instead.
+    pub async fn analyze_observed(&self, tx: &Transaction, trace: &DecisionTrace, observed_at: Instant) -> Vec<MEVOpportunity> {
+        self.analyze(tx, trace, Some(observed_at)).await
+    }
+
+    async fn analyze(&self, tx: &Transaction, trace: &DecisionTrace, observed_at: Option<Instant>) -> Vec<MEVOpportunity> {
         let mut opportunities = Vec::new();
+        if !trace.gate("access_lists", self.access_lists.permits_tx(tx)) {
+            return opportunities;
+        }
 
-        // Run strategies in parallel
+        // Decode once; each strategy matches on the class it acts on
+        let class = classify(tx);
+
+        // Run enabled strategies in parallel
+        let enabled = &self.config.settings.strategies_enabled;
         let sandwich_lock = self.sandwich.read().await;
         let arb_lock = self.arbitrage.read().await;
         let flashloan_lock = self.flashloan.read().await;
+        let enhanced_lock = self.enhanced_sandwich.read().await;
+        let advanced_lock = self.advanced.read().await;
 
-        let (sandwich_ops, arb_ops, flash_ops) = tokio::join!(
-            sandwich_lock.analyze(tx),
-            arb_lock.analyze(tx),
-            flashloan_lock.analyze(tx)
+        let (sandwich_ops, arb_ops, flash_ops, enhanced_ops, advanced_ops) = tokio::join!(
+            async { if enabled.sandwich { sandwich_lock.analyze(tx, &class, trace).await } else { Vec::new() } },
+            async { if enabled.arbitrage { arb_lock.analyze(tx, &class).await } else { Vec::new() } },
+            async { if enabled.flashloan { flashloan_lock.analyze(tx, &class).await } else { Vec::new() } },
+            async { if enabled.enhanced_sandwich { enhanced_lock.analyze(tx, &class).await } else { Vec::new() } },
+            async { if enabled.advanced { advanced_lock.analyze(tx, &class).await } else { Vec::new() } }
         );
 
         opportunities.extend(sandwich_ops);
         opportunities.extend(arb_ops);
         opportunities.extend(flash_ops);
+        opportunities.extend(enhanced_ops);
+        opportunities.extend(advanced_ops);
+        opportunities.retain(|op| trace.gate("access_lists", self.access_lists.permits(op)));
+        opportunities.retain(|op| trace.gate("legs_encoded", op.is_executable()));
+        if let Some(observed_at) = observed_at {
+            for op in opportunities.iter_mut() {
+                op.observed_at = observed_at;
+            }
+        }
+
+        let mut profitable_ops = self.profitable(opportunities, observed_at, trace).await;
+
+        for op in profitable_ops.iter_mut() {
+            op.landing_probability = self.landing.probability(self.bid_percentile(op));
+            trace.record(TraceStep::Action(format!("{} lands with p={:.2}", op.id, op.landing_probability)));
+        }
 
-        // Simulate and filter profitable opportunities
+        let found = profitable_ops.len();
+        let kept = best_per_victim(profitable_ops, self.config.settings.max_opportunities_per_tx);
+        if kept.len() < found {
+            trace.record(TraceStep::Action(format!("drop {} overlapping or over-cap opportunities", found - kept.len())));
+        }
+        kept
+    }
+
+    /// Simulates `opportunities` and keeps those clearing their strategy's net
+    /// profit floor, on the estimate alone when there's no time left to simulate.
+    async fn profitable(
+        &self,
+        opportunities: Vec<MEVOpportunity>,
+        observed_at: Option<Instant>,
+        trace: &DecisionTrace,
+    ) -> Vec<MEVOpportunity> {
         let mut profitable_ops = Vec::new();
         for op in opportunities {
-            if let Ok(sim_result) = self.simulator.simulate(&op).await {
-                if sim_result.profit > U256::from(0) {
+            let remaining = observed_at.and_then(|at| self.latency.remaining(self.config.block_height.timestamp(), at.elapsed()));
+            if !self.latency.allows_simulation(remaining) {
+                // Simulating would miss the block; the estimate is all there is time for
+                trace.record(TraceStep::Action(format!("skip simulating {}, {:?} left before the block", op.id, remaining.unwrap_or_default())));
+                if trace.gate("min_net_profit", op.net_profit() >= self.min_net_profit(&op.strategy_type)) {
                     profitable_ops.push(op);
                 }
+                continue;
             }
-        }
 
+            let started = Instant::now();
+            let simulated = self.simulator.simulate(&op).await;
+            self.latency.record_simulation(started.elapsed());
+            if let Ok(sim_result) = simulated {
+                trace.record(TraceStep::SimulatedProfit { opportunity: op.id.clone(), profit: sim_result.profit });
+                if trace.gate("replacement_robust", !sim_result.fragile)
+                    && trace.gate("simulation_viable", sim_result.is_viable())
+                    && trace.gate("min_net_profit", sim_result.profit >= self.min_net_profit(&op.strategy_type))
+                {
+                    profitable_ops.push(op);
+                }
+            } else {
+                trace.gate("simulation", false);
+            }
+        }
         profitable_ops
     }
 
+    /// Where `opportunity`'s bid ranks among recent priority fees, if it is priced yet.
+    fn bid_percentile(&self, opportunity: &MEVOpportunity) -> Option<f64> {
+        self.config.gas_oracle.bid_percentile(opportunity.bid_gas_price()?)
+    }
+
+    /// Least simulated profit after gas an opportunity of this type must show to be executed.
+    fn min_net_profit(&self, strategy: &StrategyType) -> U256 {
+        let settings = &self.config.settings;
+        eth_to_wei(match strategy {
+            StrategyType::Sandwich(_) => settings.sandwich_min_net_profit_eth,
+            StrategyType::Arbitrage(_) => settings.arbitrage_min_net_profit_eth,
+            StrategyType::Flashloan(_) => settings.flashloan_min_net_profit_eth,
+            StrategyType::Backrun(_) => settings.backrun_min_net_profit_eth,
+        })
+    }
+
     pub async fn execute_opportunity(&self, opportunity: &MEVOpportunity) -> Result<TxHash, Box<dyn std::error::Error + Send + Sync>> {
-        match &opportunity.strategy_type {
+        if let Some(reason) = halt_reason(&self.emergency_stop, &self.circuit_breaker) {
+            return Err(format!("{}: execution halted", reason).into());
+        }
+        if !opportunity.is_executable() {
+            return Err(format!("opportunity {} has legs without calldata", opportunity.id).into());
+        }
+
+        // Own funds we don't hold would only revert on submission, gas and all
+        let balance = self.config.http.get_balance(self.config.http.address(), None).await?;
+        let spendable = own_capital_limit(None, balance, eth_to_wei(self.config.settings.gas.reserve_eth));
+        let capital = opportunity.capital();
+        if capital > spendable || capital.saturating_add(opportunity.gas_cost) > balance {
+            return Err(format!(
+                "opportunity {} needs {} ETH of own capital, {} ETH spendable",
+                opportunity.id,
+                ethers::utils::format_ether(capital),
+                ethers::utils::format_ether(spendable)
+            ).into());
+        }
+
+        // Persistent opportunities are re-detected every block; act on each only once
+        let fingerprint = opportunity.fingerprint();
+        if !self.dedup.try_acquire(&fingerprint) {
+            return Err(format!("opportunity {} already in flight ({})", opportunity.id, fingerprint).into());
+        }
+
+        let result = self.execute_unique(opportunity, &fingerprint).await;
+        if result.is_err() {
+            self.dedup.release(&fingerprint);
+        }
+        result
+    }
+
+    async fn execute_unique(
+        &self,
+        opportunity: &MEVOpportunity,
+        fingerprint: &str,
+    ) -> Result<TxHash, Box<dyn std::error::Error + Send + Sync>> {
+        let submitted_block = self.config.block_height.get();
+
+        // Reserves read too many blocks ago: re-simulate against current state before spending gas
+        let reserve_age = submitted_block.saturating_sub(opportunity.reserve_block);
+        if reserve_age > U64::from(self.config.settings.risk.max_reserve_age_blocks) {
+            let still_viable = self.simulator.simulate(opportunity).await.map(|sim| sim.is_viable()).unwrap_or(false);
+            if !still_viable {
+                return Err(format!(
+                    "opportunity {} is stale: reserves from block {}, now {}",
+                    opportunity.id, opportunity.reserve_block, submitted_block
+                ).into());
+            }
+        }
+
+        // Token profit to convert once landed: the token, and our balance of it beforehand
+        let mut settlement = None;
+        if let Some(token) = opportunity.profit_token().filter(|token| self.settlement.target(*token).is_some()) {
+            match self.settlement.balance(token).await {
+                Ok(before) => settlement = Some((token, before)),
+                Err(e) => warn!("⚠️ Could not read {:?} balance, profit won't be settled: {}", token, e),
+            }
+        }
+
+        let submission = self.submit_opportunity(opportunity).await?;
+        metrics().submission_latency_seconds.observe(opportunity.observed_at.elapsed().as_secs_f64());
+
+        let relay = match submission {
+            Submission::Transaction(_) => "public",
+            Submission::Private(_) => "private",
+            Submission::Bundle { .. } => "flashbots",
+        };
+        if let Some(ledger) = &self.ledger {
+            if let Err(e) = ledger.record_submission(opportunity, submitted_block, relay) {
+                warn!("⚠️ Ledger write failed: {}", e);
+            }
+        }
+
+        // Confirm inclusion in the background and reconcile against the estimate
+        let watcher = self.inclusion_watcher.clone();
+        let breaker = self.circuit_breaker.clone();
+        let stop = self.emergency_stop.clone();
+        let ledger = self.ledger.clone();
+        let dedup = self.dedup.clone();
+        let nonces = self.nonces.clone();
+        let bundle_builder = self.bundle_builder.clone();
+        // Profit swapped elsewhere by the settlement policy isn't WETH to unwrap
+        let unwrap = (self.config.settings.auto_unwrap_weth && settlement.is_none() && opportunity.settles_in_weth(self.config.chain.weth))
+            .then(|| opportunity.net_profit());
+        let settler = self.settlement.clone();
+        let fingerprint = fingerprint.to_string();
+        let opportunity_id = opportunity.id.clone();
+        let estimated_profit = opportunity.estimated_profit;
+        let landing = self.landing.clone();
+        let bid_percentile = self.bid_percentile(opportunity);
+        // WETH proceeds count toward realized profit like ETH
+        let weth = Some(self.config.chain.weth).filter(|weth| opportunity.profit_token() == Some(*weth));
+        let hash = match submission {
+            Submission::Transaction(tx_hash) | Submission::Private(tx_hash) => {
+                tokio::spawn(async move {
+                    if let Some(outcome) = watcher.watch_transaction(tx_hash, weth).await {
+                        landing.record(bid_percentile, outcome.landed);
+                        settle(&opportunity_id, estimated_profit, &outcome, ledger.as_deref(), &breaker).await;
+                        dedup.release(&fingerprint);
+                        if let Some(amount) = unwrap.filter(|_| outcome.landed) {
+                            unwrap_profit(&bundle_builder, amount, &stop, &breaker).await;
+                        }
+                        if let Some((token, before)) = settlement.filter(|_| outcome.landed) {
+                            settle_profit(&settler, token, before, &stop, &breaker).await;
+                        }
+                    }
+                }.in_current_span());
+                tx_hash
+            },
+            Submission::Bundle { bundle_hash, target_block, own_tx_hashes, next_nonce } => {
+                if let StrategyType::Sandwich(details) = &opportunity.strategy_type {
+                    if self.competition.is_enabled() {
+                        let manager = self.clone();
+                        let (opportunity, details) = (opportunity.clone(), details.clone());
+                        tokio::spawn(async move {
+                            manager.rebid_sandwich(opportunity, details, target_block).await;
+                        }.in_current_span());
+                    }
+                }
+                tokio::spawn(async move {
+                    if let Some(outcome) = watcher.watch_bundle(bundle_hash, target_block, &own_tx_hashes, weth).await {
+                        landing.record(bid_percentile, outcome.landed);
+                        if let Some(next_nonce) = next_nonce.filter(|_| outcome.landed) {
+                            nonces.confirm(next_nonce);
+                        }
+                        settle(&opportunity_id, estimated_profit, &outcome, ledger.as_deref(), &breaker).await;
+                        dedup.release(&fingerprint);
+                        if let Some(amount) = unwrap.filter(|_| outcome.landed) {
+                            unwrap_profit(&bundle_builder, amount, &stop, &breaker).await;
+                        }
+                        if let Some((token, before)) = settlement.filter(|_| outcome.landed) {
+                            settle_profit(&settler, token, before, &stop, &breaker).await;
+                        }
+                    }
+                }.in_current_span());
+                bundle_hash
+            }
+        };
+
+        Ok(hash)
+    }
+
+    /// Re-submits a sandwich with a higher frontrun bid whenever a competitor
+    /// outbids us on its pool, until the target block or our break-even price.
+    async fn rebid_sandwich(&self, opportunity: MEVOpportunity, mut details: SandwichDetails, target_block: U64) {
+        let mut updates = self.competition.subscribe();
+        let backrun_cost = details.backrun_tx.gas().copied().unwrap_or_default() * details.backrun_tx.gas_price().unwrap_or_default();
+        let frontrun_gas = details.frontrun_tx.gas().copied().unwrap_or_default();
+        // Leave room for the builder's cut when bundles close with a coinbase payment
+        let bribe = bundle::coinbase_bribe(opportunity.estimated_profit, self.bundle_builder.coinbase_bribe_bps());
+        let ceiling = competition::bid_ceiling(opportunity.estimated_profit.saturating_sub(bribe), frontrun_gas, backrun_cost);
+        let block_time = std::time::Duration::from_secs(self.config.settings.block_time_secs);
+
+        while self.config.block_height.get() < target_block {
+            let update = match tokio::time::timeout(block_time, updates.recv()).await {
+                Ok(Ok(update)) if update.pool == details.target_pool => update,
+                Ok(Err(tokio::sync::broadcast::error::RecvError::Closed)) => break,
+                _ => continue,
+            };
+
+            let current = details.frontrun_tx.gas_price().unwrap_or_default();
+            let Some(bid) = self.competition.rebid(current, update.gas_price, ceiling) else {
+                if update.gas_price >= ceiling {
+                    info!("🏳️ Outbid past break-even on {:?}, standing down", details.target_pool);
+                    break;
+                }
+                continue;
+            };
+
+            // A halt since the first submission stops the rebids with it
+            if let Some(reason) = halt_reason(&self.emergency_stop, &self.circuit_breaker) {
+                info!("🛑 Not rebidding {}: {}", opportunity.id, reason);
+                break;
+            }
+
+            details.frontrun_tx.set_gas_price(bid);
+            let net_profit = opportunity.estimated_profit.saturating_sub(frontrun_gas * bid + backrun_cost);
+            let resubmitted = match self
+                .bundle_builder
+                .build_sandwich_bundle(&opportunity.target_tx, &details, net_profit)
+                .await
+            {
+                Ok(bundle) => self.bundle_builder.send_bundle(bundle).await,
+                Err(e) => Err(e),
+            };
+            match resubmitted {
+                Ok(bundle_hash) => info!("🔼 Rebid {} to {} gwei: {:?}", opportunity.id, bid / U256::exp10(9), bundle_hash),
+                Err(e) => warn!("⚠️ Rebid of {} failed: {}", opportunity.id, e),
+            }
+        }
+    }
+
+    async fn submit_opportunity(&self, opportunity: &MEVOpportunity) -> Result<Submission, Box<dyn std::error::Error + Send + Sync>> {
+        let bundle = match &opportunity.strategy_type {
             StrategyType::Sandwich(details) => {
-                let bundle = self.bundle_builder.build_sandwich_bundle(
+                self.bundle_builder.build_sandwich_bundle(
                     &opportunity.target_tx,
                     details,
-                    opportunity.estimated_profit
-                ).await?;
-                
-                self.bundle_builder.send_bundle(bundle).await
+                    opportunity.net_profit()
+                ).await?
+            },
+            StrategyType::Arbitrage(details) if self.bundle_builder.pays_coinbase() => {
+                // The builder's payment has to land with the arbitrage, so both go as one bundle
+                self.bundle_builder.build_arbitrage_bundle(
+                    details,
+                    opportunity.estimated_profit,
+                    self.config.swap_deadline(),
+                ).await?
             },
             StrategyType::Arbitrage(details) => {
                 let tx = self.bundle_builder.build_arbitrage_tx(
                     details,
-                    opportunity.estimated_profit
+                    opportunity.estimated_profit,
+                    self.config.swap_deadline(),
                 ).await?;
                 
+                // Nothing to order it behind, so it needs no bundle; just keep it out of the mempool
+                if self.config.settings.private_transactions {
+                    let max_block = self.config.block_height.get() + self.config.settings.private_tx_max_blocks;
+                    let tx_hash = self.bundle_builder.send_private_transaction(&tx, max_block).await?;
+                    return Ok(Submission::Private(tx_hash));
+                }
                 let pending = self.config.http.send_transaction(tx, None).await?;
-                Ok(pending.tx_hash())
+                return Ok(Submission::Transaction(pending.tx_hash()));
+            },
+            StrategyType::Flashloan(details) => {
+                self.bundle_builder.build_backrun_bundle(
+                    &details.victim_tx,
+                    &details.flashloan_tx,
+                ).await?
+            },
+            StrategyType::Backrun(details) => {
+                self.bundle_builder.build_backrun_bundle(
+                    &details.victim_tx,
+                    &details.backrun_tx,
+                ).await?
             }
+        };
+
+        let target_block = bundle.block_number;
+        let own_tx_hashes = self.bundle_builder.own_tx_hashes(&bundle);
+        let next_nonce = self.nonces.next_after(&bundle);
+        let bundle_hash = self.bundle_builder.send_bundle(bundle).await?;
+        Ok(Submission::Bundle { bundle_hash, target_block, own_tx_hashes, next_nonce })
+    }
+}
+
+/// How an opportunity went out.
+enum Submission {
+    Transaction(TxHash),
+    /// A single tx sent through the relay's `eth_sendPrivateTransaction`.
+    Private(TxHash),
+    Bundle {
+        bundle_hash: TxHash,
+        target_block: U64,
+        own_tx_hashes: Vec<H256>,
+        /// Nonce after our last tx in the bundle, confirmed used if it lands.
+        next_nonce: Option<U256>,
+    },
+}
+
+/// Records an inclusion outcome in the ledger, metrics, the circuit breaker and an alert.
+async fn settle(
+    opportunity_id: &str,
+    estimated_profit: U256,
+    outcome: &InclusionOutcome,
+    ledger: Option<&Ledger>,
+    breaker: &CircuitBreaker,
+) {
+    if let Some(ledger) = ledger {
+        if let Err(e) = ledger.record_inclusion(opportunity_id, outcome.landed, outcome.gross_profit, outcome.gas_spent) {
+            warn!("⚠️ Ledger write failed: {}", e);
+        }
+    }
+
+    let msg = if outcome.landed {
+        let net_profit = outcome.net_profit();
+        if !net_profit.is_negative() {
+            metrics().realized_profit_eth.observe(wei_to_eth(net_profit.into_raw()));
+        }
+        format!(
+            "✅ Landed {}\nRealized: {} ETH (estimated {} ETH)\nGas: {} ETH",
+            opportunity_id,
+            format_signed_ether(net_profit),
+            ethers::utils::format_ether(estimated_profit),
+            ethers::utils::format_ether(outcome.gas_spent)
+        )
+    } else {
+        format!("❌ Not included: {}", opportunity_id)
+    };
+    info!("{}", msg);
+    alert(&msg, &outcome.block.as_u64()).await;
+
+    if let Some(reason) = breaker.record(outcome.net_profit()) {
+        let msg = format!("🛑 Circuit breaker tripped ({}), halting execution", reason);
+        info!("{}", msg);
+        alert(&msg, &outcome.block.as_u64()).await;
+    }
+}
+
+/// Unwraps a landed opportunity's WETH profit so it can pay for gas.
+async fn unwrap_profit(bundle_builder: &BundleBuilder, amount: U256, stop: &EmergencyStop, breaker: &CircuitBreaker) {
+    if let Some(reason) = halt_reason(stop, breaker) {
+        info!("🛑 Leaving {} WETH wrapped: {}", ethers::utils::format_ether(amount), reason);
+        return;
+    }
+    match bundle_builder.unwrap_weth(amount).await {
+        Ok(Some(tx_hash)) => info!("🔓 Unwrapping {} WETH: {:?}", ethers::utils::format_ether(amount), tx_hash),
+        Ok(None) => {}
+        Err(e) => warn!("⚠️ WETH unwrap failed: {}", e),
+    }
+}
+
+/// Why nothing new may be sent on chain, if something halted execution.
+fn halt_reason(stop: &EmergencyStop, breaker: &CircuitBreaker) -> Option<&'static str> {
+    if stop.is_halted() {
+        Some("emergency stop engaged")
+    } else if breaker.is_open() {
+        Some("circuit breaker open after recent losses")
+    } else {
+        None
+    }
+}
+
+/// Swaps a landed opportunity's token profit as the settlement policy says.
+async fn settle_profit(settlement: &Settlement, token: Address, before: U256, stop: &EmergencyStop, breaker: &CircuitBreaker) {
+    if let Some(reason) = halt_reason(stop, breaker) {
+        info!("🛑 Leaving {:?} profit unsettled: {}", token, reason);
+        return;
+    }
+    match settlement.settle(token, before).await {
+        Ok(Some(tx_hash)) => info!("💱 Settling {:?} profit: {:?}", token, tx_hash),
+        Ok(None) => {}
+        Err(e) => warn!("⚠️ Settlement swap of {:?} failed: {}", token, e),
+    }
+}
+
+/// The opportunity per victim with the highest expected value (net profit times
+/// landing probability), at most `cap` in all, best first. Opportunities that conflict with a better one are dropped.
+pub fn best_per_victim(mut opportunities: Vec<MEVOpportunity>, cap: usize) -> Vec<MEVOpportunity> {
+    opportunities.sort_by(|a, b| b.expected_value().cmp(&a.expected_value()).then_with(|| b.priority.cmp(&a.priority)));
+
+    let mut kept: Vec<MEVOpportunity> = Vec::new();
+    for op in opportunities {
+        if kept.len() >= cap {
+            break;
+        }
+        if !kept.iter().any(|better| better.conflicts_with(&op)) {
+            kept.push(op);
         }
     }
-} 
+    kept
+}
+
+/// Most of our own ETH an opportunity may spend: the configured `capital`, capped
+/// at what the wallet holds once `gas_reserve` is set aside to pay for gas.
+pub fn own_capital_limit(capital: Option<U256>, balance: U256, gas_reserve: U256) -> U256 {
+    let spendable = balance.saturating_sub(gas_reserve);
+    capital.map_or(spendable, |capital| capital.min(spendable))
+}
+
+fn format_signed_ether(amount: I256) -> String {
+    let sign = if amount.is_negative() { "-" } else { "" };
+    format!("{}{}", sign, ethers::utils::format_ether(amount.unsigned_abs()))
+}
+
 pub mod enhanced_sandwich;
 pub mod advanced_features;
 
 pub use enhanced_sandwich::EnhancedSandwichStrategy;
 pub use advanced_features::AdvancedMEVFeatures;
-pub use flashloan_balancer::FlashloanBalancerStrategy;
+pub use flashloan_balancer::{FlashloanBalancerStrategy, FlashloanProvider};
 
 impl StrategyManager {
+    /// Warms caches ahead of a swap of `token` we expect to see shortly.
+    pub async fn prewarm_token(&self, token: Address) {
+        self.sandwich.read().await.prewarm(token);
+    }
+
+    /// Flushes the PnL ledger, if any, to disk.
+    pub fn flush_ledger(&self) {
+        if let Some(ledger) = &self.ledger {
+            if let Err(e) = ledger.flush() {
+                warn!("⚠️ Ledger flush failed: {}", e);
+            }
+        }
+    }
+
+    /// Reacts to reorgs from the block scanner. Opportunities we acted on in
+    /// orphaned blocks may be live again, so nothing stays suppressed as in flight.
+    pub async fn watch_reorgs(self: Arc<Self>) {
+        let mut reorgs = self.config.reorgs.subscribe();
+        loop {
+            match reorgs.recv().await {
+                Ok(reorg) => {
+                    self.dedup.clear();
+                    // Bundles we counted as landed may have been orphaned
+                    self.nonces.reset();
+                    let msg = format!(
+                        "⚠️ Reorg of depth {} from block {:?}, re-evaluating in-flight opportunities",
+                        reorg.depth, reorg.fork_block
+                    );
+                    info!("{}", msg);
+                    alert(&msg, &reorg.new_tip.as_u64()).await;
+                }
+                Err(tokio::sync::broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(tokio::sync::broadcast::error::RecvError::Closed) => break,
+            }
+        }
+    }
+
+    pub fn emergency_stop(&self) -> Arc<EmergencyStop> {
+        self.emergency_stop.clone()
+    }
+
+    pub fn access_lists(&self) -> Arc<AccessLists> {
+        self.access_lists.clone()
+    }
+
     pub fn config(&self) -> Arc<Config> {
         self.config.clone()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use ethers::abi::AbiEncode;
+    use ethers::types::transaction::eip2718::TypedTransaction;
+    use serde_json::json;
+
+    use super::*;
+    use crate::address_book::{SwapExactETHForTokensCall, UniV2RouterCalls};
+    use crate::settings::{RelaySettings, Settings, StrategiesEnabled};
+    use crate::test_support::{self, MockNode};
+
+    async fn manager(node: &MockNode, settings: Settings) -> StrategyManager {
+        StrategyManager::new(Arc::new(test_support::config(&node.url(), settings).await)).await
+    }
+
+    /// A backrun the strategy expects `estimated_profit` from, before gas.
+    fn backrun(estimated_profit: U256) -> MEVOpportunity {
+        let mut backrun_tx = TypedTransaction::default();
+        backrun_tx.set_to(Address::from_low_u64_be(0x7a)).set_data(vec![0x38, 0xed, 0x17, 0x39].into());
+        MEVOpportunity {
+            id: format!("backrun_{}", estimated_profit),
+            target_tx: Transaction::default(),
+            strategy_type: StrategyType::Backrun(BackrunDetails { victim_tx: Transaction::default(), backrun_tx }),
+            estimated_profit,
+            gas_cost: U256::zero(),
+            priority: 5,
+            reserve_block: U64::from(100),
+            expiry_block: U64::from(101),
+            observed_at: Instant::now(),
+            landing_probability: 1.0,
+        }
+    }
+
+    /// A WETH -> token -> WETH round trip through the V2 router.
+    fn arbitrage(manager: &StrategyManager) -> MEVOpportunity {
+        let weth = manager.config.chain.weth;
+        let details = ArbitrageDetails {
+            path: vec![weth, Address::from_low_u64_be(0x70c), weth],
+            pools: Vec::new(),
+            amount_in: U256::exp10(18),
+            expected_profit: U256::exp10(17),
+            gas_estimate: U256::from(150_000),
+            router: Some(manager.config.chain.v2_router),
+        };
+        MEVOpportunity {
+            id: "arbitrage".to_string(),
+            target_tx: Transaction::default(),
+            strategy_type: StrategyType::Arbitrage(details),
+            estimated_profit: U256::exp10(17),
+            gas_cost: U256::zero(),
+            priority: 5,
+            reserve_block: U64::from(100),
+            expiry_block: U64::from(101),
+            observed_at: Instant::now(),
+            landing_probability: 1.0,
+        }
+    }
+
+    #[test]
+    fn own_capital_limit_keeps_the_gas_reserve() {
+        let (balance, reserve) = (U256::from(100), U256::from(5));
+        assert_eq!(own_capital_limit(None, balance, reserve), U256::from(95));
+        assert_eq!(own_capital_limit(Some(U256::from(40)), balance, reserve), U256::from(40));
+        assert_eq!(own_capital_limit(Some(U256::from(400)), balance, reserve), U256::from(95));
+        assert!(own_capital_limit(None, U256::from(3), reserve).is_zero());
+    }
+
+    #[test]
+    fn a_second_opportunity_on_the_same_victim_is_suppressed() {
+        let on_victim = |victim: u64, profit: u64| {
+            let mut op = backrun(U256::from(profit));
+            if let StrategyType::Backrun(details) = &mut op.strategy_type {
+                details.victim_tx.hash = H256::from_low_u64_be(victim);
+            }
+            op.id = format!("{}_{}", victim, profit);
+            op
+        };
+        let ids = |kept: Vec<MEVOpportunity>| kept.into_iter().map(|op| op.id).collect::<Vec<_>>();
+
+        let found = vec![on_victim(1, 100), on_victim(2, 200), on_victim(1, 300)];
+        assert_eq!(ids(best_per_victim(found.clone(), 10)), ["1_300", "2_200"]);
+        assert_eq!(ids(best_per_victim(found, 1)), ["1_300"]);
+    }
+
+    #[tokio::test]
+    async fn profits_above_zero_but_under_the_strategy_floor_are_dropped() {
+        let mut node = MockNode::new().await;
+        node.rpc("eth_call", json!("0x")).await;
+        node.rpc("eth_estimateGas", json!("0x5208")).await;
+        let settings = Settings { backrun_min_net_profit_eth: 0.01, ..test_support::settings() };
+        let manager = manager(&node, settings).await;
+        let gas_cost = U256::from(21_000) * manager.config.gas_oracle.gas_price(50.0);
+
+        // Nets 0.001 ETH after gas: viable, but under the backrun floor
+        let thin = backrun(gas_cost + U256::exp10(15));
+        let fat = backrun(gas_cost + U256::exp10(17));
+        let trace = DecisionTrace::enabled(H256::zero());
+        let kept = manager.profitable(vec![thin, fat.clone()], None, &trace).await;
+
+        assert_eq!(kept.iter().map(|op| op.id.as_str()).collect::<Vec<_>>(), [fat.id.as_str()]);
+        let floor_gates: Vec<bool> = trace
+            .steps()
+            .into_iter()
+            .filter_map(|step| match step {
+                TraceStep::Gate { name: "min_net_profit", passed } => Some(passed),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(floor_gates, [false, true]);
+    }
+
+    #[tokio::test]
+    async fn private_transactions_go_to_the_relay_on_their_own_not_as_a_bundle() {
+        let mut node = MockNode::new().await;
+        node.rpc("eth_blockNumber", json!("0x64")).await;
+        node.rpc("eth_getTransactionCount", json!("0x7")).await;
+        let mut relay = MockNode::new().await;
+        let private_hash = H256::from_low_u64_be(0xa7b);
+        relay.rpc("eth_sendPrivateTransaction", json!(private_hash)).await;
+        let bundles = relay
+            .server
+            .mock("POST", "/")
+            .match_body(mockito::Matcher::PartialJson(json!({ "method": "eth_sendBundle" })))
+            .expect(0)
+            .create_async()
+            .await;
+        let settings = Settings {
+            private_transactions: true,
+            relay: RelaySettings { urls: vec![relay.url()], ..RelaySettings::default() },
+            ..test_support::settings()
+        };
+        let manager = manager(&node, settings).await;
+
+        let submission = manager.submit_opportunity(&arbitrage(&manager)).await.unwrap();
+        assert!(matches!(submission, Submission::Private(hash) if hash == private_hash));
+        bundles.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn strategies_simulator_and_bundles_price_off_one_gas_oracle() {
+        let node = MockNode::new().await;
+        let manager = manager(&node, test_support::settings()).await;
+        let oracle = &manager.config.gas_oracle;
+
+        assert!(Arc::ptr_eq(manager.sandwich.read().await.gas_oracle(), oracle));
+        assert!(Arc::ptr_eq(manager.enhanced_sandwich.read().await.gas_oracle(), oracle));
+        assert!(Arc::ptr_eq(manager.simulator.gas_oracle(), oracle));
+        assert!(Arc::ptr_eq(manager.bundle_builder.gas_oracle(), oracle));
+    }
+
+    #[tokio::test]
+    async fn opportunities_on_stale_reserves_are_resimulated_before_submission() {
+        let mut node = MockNode::new().await;
+        node.rpc("eth_getBalance", json!(U256::exp10(21))).await;
+        // Current state no longer has the opportunity
+        let resimulated = node
+            .server
+            .mock("POST", "/")
+            .match_body(mockito::Matcher::PartialJson(json!({ "method": "eth_call" })))
+            .with_body(json!({ "jsonrpc": "2.0", "id": 1, "error": { "code": 3, "message": "execution reverted" } }).to_string())
+            .expect(1)
+            .create_async()
+            .await;
+        let mut relay = MockNode::new().await;
+        let untouched = relay.server.mock("POST", "/").expect(0).create_async().await;
+        let settings = Settings {
+            relay: RelaySettings { urls: vec![relay.url()], ..RelaySettings::default() },
+            ..test_support::settings()
+        };
+        let manager = manager(&node, settings).await;
+
+        // Read at block 90, ten blocks before the head
+        let mut stale = backrun(U256::exp10(17));
+        stale.reserve_block = U64::from(90);
+        let error = manager.execute_opportunity(&stale).await.unwrap_err();
+        assert_eq!(error.to_string(), format!("opportunity {} is stale: reserves from block 90, now 100", stale.id));
+        resimulated.assert_async().await;
+        untouched.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn enabling_the_flashloan_strategy_surfaces_its_backrun() {
+        let mut node = MockNode::new().await;
+        let loan_amount = U256::exp10(18);
+        // getAmountsOut for the buy leg; the flash loan's own eth_call only needs to succeed
+        node.rpc("eth_call", json!(Bytes::from(vec![loan_amount, U256::exp10(21)].encode()))).await;
+        node.rpc("eth_estimateGas", json!("0x927c0")).await;
+
+        for (enabled, found) in [("sandwich,arbitrage", 0), ("flashloan", 1)] {
+            let settings = Settings {
+                strategies_enabled: StrategiesEnabled::from_list(enabled),
+                executor_address: Some(Address::from_low_u64_be(0xe8ec)),
+                ..test_support::settings()
+            };
+            let manager = manager(&node, settings).await;
+            let chain = &manager.config.chain;
+            let call = UniV2RouterCalls::SwapExactETHForTokens(SwapExactETHForTokensCall {
+                amount_out_min: U256::zero(),
+                path: vec![chain.weth, Address::from_low_u64_be(0x70c)],
+                to: Address::from_low_u64_be(0xbeef),
+                deadline: U256::MAX,
+            });
+            let victim = Transaction { to: chain.dexes.router(DexType::UniswapV2), value: loan_amount, input: call.encode().into(), ..Default::default() };
+
+            let opportunities = manager.analyze_transaction(&victim, &manager.decision_trace(&victim)).await;
+            assert_eq!(opportunities.len(), found, "with {}", enabled);
+            assert!(opportunities.iter().all(|op| matches!(op.strategy_type, StrategyType::Flashloan(_))));
+        }
+    }
+}
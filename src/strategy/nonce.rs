@@ -0,0 +1,120 @@
+use ethers::prelude::*;
+use std::sync::{Arc, Mutex};
+use tracing::info;
+
+use super::types::Bundle;
+use crate::signer::SignerClient;
+
+/// Hands out the nonces our submissions sign with, so the txs of one bundle get
+/// consecutive nonces (frontrun N, backrun N + 1) instead of all being filled
+/// with the same pending nonce.
+#[derive(Debug)]
+pub struct NonceManager {
+    provider: Arc<SignerClient>,
+    /// Also count nonces used by our own landed bundles, which the node's pending
+    /// count can trail when it only learned of them from a block.
+    reconcile: bool,
+    /// Lowest nonce the next submission may use.
+    floor: Mutex<U256>,
+}
+
+impl NonceManager {
+    pub fn new(
+        provider: Arc<SignerClient>,
+        reconcile: bool,
+    ) -> Self {
+        Self {
+            provider,
+            reconcile,
+            floor: Mutex::new(U256::zero()),
+        }
+    }
+
+    /// Starts the lane at our pending nonce, past any txs a previous run left in
+    /// the mempool, so new ones aren't dropped as underpriced replacements of them.
+    pub async fn sync(&self) -> Result<U256, Box<dyn std::error::Error + Send + Sync>> {
+        let address = self.provider.address();
+        let (latest, pending) = tokio::try_join!(
+            self.provider.get_transaction_count(address, Some(BlockNumber::Latest.into())),
+            self.provider.get_transaction_count(address, Some(BlockNumber::Pending.into())),
+        )?;
+        if pending > latest {
+            info!("⏳ {} txs from a previous run still pending, starting at nonce {}", pending - latest, pending);
+        }
+        let mut floor = self.floor.lock().unwrap();
+        *floor = (*floor).max(starting_nonce(latest, pending));
+        Ok(*floor)
+    }
+
+    /// `count` consecutive nonces for our txs in the next submission, starting at
+    /// the chain's pending nonce (or past our landed bundles, if that's higher).
+    pub async fn reserve(&self, count: usize) -> Result<Vec<U256>, Box<dyn std::error::Error + Send + Sync>> {
+        let pending = self
+            .provider
+            .get_transaction_count(self.provider.address(), Some(BlockNumber::Pending.into()))
+            .await?;
+        let first = {
+            let mut floor = self.floor.lock().unwrap();
+            *floor = (*floor).max(pending);
+            *floor
+        };
+        Ok(sequence(first, count))
+    }
+
+    /// Nonce after the highest of our own txs in `bundle`.
+    pub fn next_after(&self, bundle: &Bundle) -> Option<U256> {
+        let signer = self.provider.address();
+        bundle
+            .txs
+            .iter()
+            .filter(|bundle_tx| bundle_tx.signer == signer)
+            .filter_map(|bundle_tx| bundle_tx.tx.nonce().copied())
+            .max()
+            .map(|nonce| nonce + 1)
+    }
+
+    /// Our bundle landed, so every nonce below `next` is used even if the node's
+    /// pending count hasn't caught up yet.
+    pub fn confirm(&self, next: U256) {
+        if !self.reconcile {
+            return;
+        }
+        let mut floor = self.floor.lock().unwrap();
+        *floor = (*floor).max(next);
+    }
+
+    /// Forgets what our landed bundles used, e.g. after a reorg may have undone
+    /// them; the next reservation starts from chain state again.
+    pub fn reset(&self) {
+        *self.floor.lock().unwrap() = U256::zero();
+    }
+}
+
+/// First nonce a new run may use: our pending txs' nonces are taken even though
+/// they haven't been mined yet.
+pub fn starting_nonce(latest: U256, pending: U256) -> U256 {
+    latest.max(pending)
+}
+
+/// `count` consecutive nonces from `first`.
+pub fn sequence(first: U256, count: usize) -> Vec<U256> {
+    (0..count).map(|i| first + i).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sequence_hands_out_consecutive_nonces() {
+        assert_eq!(sequence(U256::from(7), 3), vec![U256::from(7), U256::from(8), U256::from(9)]);
+        assert!(sequence(U256::from(7), 0).is_empty());
+    }
+
+    #[test]
+    fn starting_nonce_skips_txs_still_pending() {
+        assert_eq!(starting_nonce(U256::from(5), U256::from(8)), U256::from(8));
+        // A node that dropped our pending txs reports fewer pending than mined
+        assert_eq!(starting_nonce(U256::from(5), U256::from(3)), U256::from(5));
+    }
+}
@@ -0,0 +1,185 @@
+use ethers::prelude::*;
+use ethers::types::U512;
+use std::str::FromStr;
+
+use crate::helpers::address;
+use crate::uni;
+
+/// Decimals of Chainlink USD feeds' answers.
+pub const USD_FEED_DECIMALS: u8 = 8;
+
+/// ETH/USD and BTC/USD feed proxies on mainnet.
+pub const ETH_USD_PROXY: &str = "0x5f4eC3Df9cbd43714FE2740f5E3616155c5b8419";
+pub const BTC_USD_PROXY: &str = "0xF4030086522a5bEEa4988F8cA5B36dbC97BeE88c";
+const WBTC: &str = "0x2260FAC5E5542a773Aa44fBCfeDf7C193bc2C599";
+const USDC: &str = "0xA0b86991c6218b36c1d19D4a2e9Eb0cE3606eB48";
+const WETH: &str = "0xC02aaA39b223FE8D0A0e5C4F27eAD9083C756Cc2";
+
+/// A Chainlink feed and the V2 pool that should trade at its price.
+///
+/// `proxy` is the stable address consumers read; the transmits we see go to the
+/// aggregator behind it, which is looked up at startup.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OracleFeed {
+    pub proxy: Address,
+    /// Asset the feed prices, e.g. WETH for ETH/USD.
+    pub token: Address,
+    /// Token the price is paid in on the pool, e.g. USDC for a USD feed.
+    pub quote: Address,
+}
+
+impl OracleFeed {
+    pub fn mainnet_defaults() -> Vec<Self> {
+        vec![
+            Self { proxy: address(ETH_USD_PROXY), token: address(WETH), quote: address(USDC) },
+            Self { proxy: address(BTC_USD_PROXY), token: address(WBTC), quote: address(USDC) },
+        ]
+    }
+}
+
+/// `proxy:token:quote`, as listed in `ORACLE_FEEDS`.
+impl FromStr for OracleFeed {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parts: Vec<&str> = s.split(':').map(str::trim).collect();
+        let [proxy, token, quote] = parts[..] else {
+            return Err(format!("expected proxy:token:quote, got {}", s));
+        };
+        let parse = |a: &str| a.parse::<Address>().map_err(|e| format!("invalid address {}: {}", a, e));
+        Ok(Self { proxy: parse(proxy)?, token: parse(token)?, quote: parse(quote)? })
+    }
+}
+
+/// A swap that moves a V2 pool back to the oracle price.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OracleArb {
+    pub token_in: Address,
+    pub token_out: Address,
+    pub amount_in: U256,
+    pub amount_out: U256,
+    /// What the swap gains valued at the oracle price, in quote base units.
+    pub profit_in_quote: U256,
+}
+
+/// Oracle price as `num / den` quote base units per token base unit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OraclePrice {
+    pub num: U256,
+    pub den: U256,
+}
+
+impl OraclePrice {
+    /// `answer` (with `feed_decimals`) of one whole token, in whole quote tokens.
+    pub fn new(answer: U256, feed_decimals: u8, token_decimals: u8, quote_decimals: u8) -> Self {
+        Self {
+            num: answer.saturating_mul(U256::exp10(quote_decimals as usize)),
+            den: U256::exp10(feed_decimals as usize + token_decimals as usize),
+        }
+    }
+
+    /// `amount` of token in quote base units.
+    pub fn token_to_quote(&self, amount: U256) -> U256 {
+        (amount.full_mul(self.num) / U512::from(self.den)).try_into().unwrap_or(U256::MAX)
+    }
+
+    /// `amount` of quote in token base units.
+    pub fn quote_to_token(&self, amount: U256) -> U256 {
+        if self.num.is_zero() {
+            return U256::zero();
+        }
+        (amount.full_mul(self.den) / U512::from(self.num)).try_into().unwrap_or(U256::MAX)
+    }
+}
+
+/// The swap that trades a `token`/`quote` V2 pool with `reserve_token`/`reserve_quote`
+/// to `price`, if it pays after the pool fee. The size ignores the fee, which
+/// only makes it slightly larger than optimal.
+pub fn oracle_arb(
+    token: Address,
+    quote: Address,
+    reserve_token: U256,
+    reserve_quote: U256,
+    price: OraclePrice,
+    fee_ppm: u32,
+) -> Option<OracleArb> {
+    if reserve_token.is_zero() || reserve_quote.is_zero() || price.num.is_zero() {
+        return None;
+    }
+    let k = reserve_token.full_mul(reserve_quote);
+
+    // Pool cheaper than the oracle: buy the token until rq'/rt' = num/den, i.e. rq' = sqrt(k * num / den)
+    let pool_cheaper = reserve_quote.full_mul(price.den) < reserve_token.full_mul(price.num);
+    let arb = if pool_cheaper {
+        let target: U256 = (k * U512::from(price.num) / U512::from(price.den)).integer_sqrt().try_into().ok()?;
+        let amount_in = target.checked_sub(reserve_quote)?;
+        let (amount_out, _, _) = uni::get_amount_out_with_fee(amount_in, reserve_quote, reserve_token, fee_ppm);
+        OracleArb {
+            token_in: quote,
+            token_out: token,
+            amount_in,
+            amount_out,
+            profit_in_quote: price.token_to_quote(amount_out).checked_sub(amount_in)?,
+        }
+    } else {
+        let target: U256 = (k * U512::from(price.den) / U512::from(price.num)).integer_sqrt().try_into().ok()?;
+        let amount_in = target.checked_sub(reserve_token)?;
+        let (amount_out, _, _) = uni::get_amount_out_with_fee(amount_in, reserve_token, reserve_quote, fee_ppm);
+        OracleArb {
+            token_in: token,
+            token_out: quote,
+            amount_in,
+            amount_out,
+            profit_in_quote: amount_out.checked_sub(price.token_to_quote(amount_in))?,
+        }
+    };
+
+    (!arb.amount_in.is_zero() && !arb.profit_in_quote.is_zero()).then_some(arb)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn eth_usd(dollars: u64) -> OraclePrice {
+        OraclePrice::new(U256::from(dollars) * U256::exp10(8), USD_FEED_DECIMALS, 18, 6)
+    }
+
+    #[test]
+    fn feeds_parse_from_proxy_token_quote() {
+        let feed: OracleFeed = format!("{} : {} : {}", ETH_USD_PROXY, WETH, USDC).parse().unwrap();
+        assert_eq!(feed, OracleFeed::mainnet_defaults()[0]);
+        assert!(format!("{}:{}", ETH_USD_PROXY, WETH).parse::<OracleFeed>().is_err());
+        assert!("0x1:0x2:0x3".parse::<OracleFeed>().is_err());
+    }
+
+    #[test]
+    fn oracle_price_converts_between_base_units() {
+        let price = eth_usd(2_000);
+        assert_eq!(price.token_to_quote(U256::exp10(18)), U256::from(2_000_000_000u64));
+        assert_eq!(price.quote_to_token(U256::from(1_000_000_000u64)), U256::exp10(18) / 2);
+    }
+
+    #[test]
+    fn a_pool_below_the_oracle_is_bought_up_to_it() {
+        let (weth, usdc) = (address(WETH), address(USDC));
+        // 1000 ETH against 1M USDC: $1000 a token, half the oracle's $2000
+        let (reserve_token, reserve_quote) = (U256::exp10(21), U256::exp10(12));
+        let arb = oracle_arb(weth, usdc, reserve_token, reserve_quote, eth_usd(2_000), 3_000).unwrap();
+        assert_eq!((arb.token_in, arb.token_out), (usdc, weth));
+        assert!(!arb.profit_in_quote.is_zero());
+
+        // Afterwards the pool trades within a percent of the oracle
+        let pool_price = (reserve_quote + arb.amount_in) * U256::exp10(18) / (reserve_token - arb.amount_out);
+        assert!(pool_price.abs_diff(U256::from(2_000_000_000u64)) < U256::from(20_000_000u64), "{}", pool_price);
+    }
+
+    #[test]
+    fn gaps_inside_the_pool_fee_arent_worth_trading() {
+        let (weth, usdc) = (address(WETH), address(USDC));
+        let (reserve_token, reserve_quote) = (U256::exp10(21), U256::exp10(12));
+        assert_eq!(oracle_arb(weth, usdc, reserve_token, reserve_quote, eth_usd(1_001), 3_000), None);
+        assert_eq!(oracle_arb(weth, usdc, reserve_token, reserve_quote, eth_usd(999), 3_000), None);
+        assert_eq!(oracle_arb(weth, usdc, U256::zero(), reserve_quote, eth_usd(2_000), 3_000), None);
+    }
+}
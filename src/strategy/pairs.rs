@@ -0,0 +1,107 @@
+use ethers::prelude::*;
+use ethers::utils::{get_create2_address_from_hash, keccak256};
+use std::collections::HashMap;
+use std::sync::RwLock;
+
+/// Init code hash of the Uniswap V2 pair contract.
+pub const UNISWAP_V2_INIT_CODE_HASH: &str = "0x96e8ac4277198ff8b6f785478aa9a39f403cb768dd02cbee326c3e7da348845f";
+/// Init code hash of the SushiSwap pair contract, the same on every chain it is deployed to.
+pub const SUSHISWAP_INIT_CODE_HASH: &str = "0xe18a34eb0e04b04f7a0ac29a6e80748dca96319b42c520056e0b8b07b35e1c2b";
+/// Init code hash of the PancakeSwap V2 pair contract on BSC.
+pub const PANCAKESWAP_INIT_CODE_HASH: &str = "0x00fb7f630766e6a796048ea87d01acd3068e8ff67d078148a3fa3f4a84f69bd5";
+/// Init code hash of the PancakeSwap V2 pair contract on Ethereum, which differs from BSC's.
+pub const PANCAKESWAP_ETHEREUM_INIT_CODE_HASH: &str = "0x57224589c67f3f30a6b0d7a1b54cf3153ab84563bc609ef41dfb34f8b2974d2d";
+
+/// Orders two tokens the way V2 factories do (lower address first).
+pub fn sort_tokens(token_a: Address, token_b: Address) -> (Address, Address) {
+    if token_a < token_b {
+        (token_a, token_b)
+    } else {
+        (token_b, token_a)
+    }
+}
+
+/// Derives a V2-style pair address: CREATE2 over `keccak256(token0 ++ token1)`.
+pub fn derive_pair_address(factory: Address, init_code_hash: H256, token_a: Address, token_b: Address) -> Address {
+    let (token0, token1) = sort_tokens(token_a, token_b);
+    let mut packed = [0u8; 40];
+    packed[..20].copy_from_slice(token0.as_bytes());
+    packed[20..].copy_from_slice(token1.as_bytes());
+
+    get_create2_address_from_hash(factory, keccak256(packed), init_code_hash)
+}
+
+/// Memoizes pair derivation per `(factory, token0, token1)`, so hot pairs are hashed once.
+#[derive(Debug)]
+pub struct PairAddressCache {
+    enabled: bool,
+    pairs: RwLock<HashMap<(Address, Address, Address), Address>>,
+}
+
+impl PairAddressCache {
+    pub fn new(enabled: bool) -> Self {
+        Self {
+            enabled,
+            pairs: RwLock::new(HashMap::new()),
+        }
+    }
+
+    pub fn pair_address(&self, factory: Address, init_code_hash: H256, token_a: Address, token_b: Address) -> Address {
+        if !self.enabled {
+            return derive_pair_address(factory, init_code_hash, token_a, token_b);
+        }
+
+        let (token0, token1) = sort_tokens(token_a, token_b);
+        let key = (factory, token0, token1);
+        if let Some(pair) = self.pairs.read().unwrap().get(&key) {
+            return *pair;
+        }
+
+        let pair = derive_pair_address(factory, init_code_hash, token0, token1);
+        self.pairs.write().unwrap().insert(key, pair);
+        pair
+    }
+
+    /// Number of pairs derived and cached so far.
+    pub fn len(&self) -> usize {
+        self.pairs.read().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::address_book::{UNISWAP_V2_FACTORY, WETH};
+
+    const USDC: &str = "0xA0b86991c6218b36c1d19D4a2e9Eb0cE3606eB48";
+
+    fn address(hex: &str) -> Address {
+        hex.parse().unwrap()
+    }
+
+    #[test]
+    fn derives_the_uniswap_weth_usdc_pair() {
+        let hash: H256 = UNISWAP_V2_INIT_CODE_HASH.parse().unwrap();
+        let pair = derive_pair_address(address(UNISWAP_V2_FACTORY), hash, address(WETH), address(USDC));
+        assert_eq!(pair, address("0xB4e16d0168e52d35CaCD2c6185b44281Ec28C9Dc"));
+        // Token order doesn't matter
+        assert_eq!(derive_pair_address(address(UNISWAP_V2_FACTORY), hash, address(USDC), address(WETH)), pair);
+    }
+
+    #[test]
+    fn cache_stores_each_pair_once_in_either_order() {
+        let hash: H256 = UNISWAP_V2_INIT_CODE_HASH.parse().unwrap();
+        let cache = PairAddressCache::new(true);
+        let pair = cache.pair_address(address(UNISWAP_V2_FACTORY), hash, address(WETH), address(USDC));
+        assert_eq!(cache.pair_address(address(UNISWAP_V2_FACTORY), hash, address(USDC), address(WETH)), pair);
+        assert_eq!(cache.len(), 1);
+
+        let uncached = PairAddressCache::new(false);
+        assert_eq!(uncached.pair_address(address(UNISWAP_V2_FACTORY), hash, address(WETH), address(USDC)), pair);
+        assert!(uncached.is_empty());
+    }
+}
@@ -0,0 +1,73 @@
+use ethers::prelude::*;
+use std::sync::Arc;
+
+use crate::address_book::UniV2Factory;
+use crate::chain::ChainConfig;
+use crate::pool_cache::PoolCache;
+use crate::signer::SignerClient;
+
+/// Prices token amounts in ETH from the token's WETH pair on the chain's V2 factory.
+///
+/// Spot price only: good enough to rank opportunities against each other,
+/// not to size trades.
+#[derive(Debug)]
+pub struct PriceOracle {
+    provider: Arc<SignerClient>,
+    pool_cache: Arc<PoolCache>,
+    weth: Address,
+    factory: Address,
+}
+
+impl PriceOracle {
+    pub fn new(
+        provider: Arc<SignerClient>,
+        pool_cache: Arc<PoolCache>,
+        chain: &ChainConfig,
+    ) -> Self {
+        Self { provider, pool_cache, weth: chain.weth, factory: chain.v2_factory }
+    }
+
+    /// `amount` of `token` in wei, or `None` if the token has no WETH pair with liquidity.
+    pub async fn to_eth(&self, token: Address, amount: U256) -> Option<U256> {
+        if token == self.weth || amount.is_zero() {
+            return Some(amount);
+        }
+
+        let pair_address = UniV2Factory::new(self.factory, self.provider.clone())
+            .get_pair(token, self.weth)
+            .call()
+            .await
+            .ok()?;
+        if pair_address.is_zero() {
+            return None;
+        }
+
+        let reserves = self.pool_cache.reserves(pair_address).await?;
+        let (token_reserve, weth_reserve) = reserves.oriented(token);
+
+        Self::quote(amount, token_reserve, weth_reserve)
+    }
+
+    /// Spot value of `amount` against `reserve_in`/`reserve_out`, without fees or impact.
+    pub fn quote(amount: U256, reserve_in: U256, reserve_out: U256) -> Option<U256> {
+        if reserve_in.is_zero() {
+            return None;
+        }
+        amount.full_mul(reserve_out).checked_div(reserve_in.into())?.try_into().ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quote_is_the_spot_value_without_fees() {
+        // 2000 USDC (6 decimals) per ETH
+        let (usdc_reserve, weth_reserve) = (U256::from(2_000_000_000_000u64), U256::exp10(21));
+        assert_eq!(PriceOracle::quote(U256::from(3_000_000_000u64), usdc_reserve, weth_reserve), Some(U256::exp10(18) * 3 / 2));
+        assert_eq!(PriceOracle::quote(U256::one(), U256::zero(), weth_reserve), None);
+        // Values past U256 don't wrap
+        assert_eq!(PriceOracle::quote(U256::MAX, U256::one(), U256::from(2)), None);
+    }
+}
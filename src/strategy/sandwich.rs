@@ -1,211 +1,786 @@
 use ethers::prelude::*;
 use ethers::types::transaction::eip2718::TypedTransaction;
-use ethers::abi::AbiDecode;
-use std::sync::Arc;
-use crate::{Config, address_book::UniV2RouterCalls, uni};
+use ethers::abi::AbiEncode;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+use crate::{Config, uni, uni_v3};
+use crate::classifier::{classify, TxClass};
+use crate::universal_router::Command;
+use crate::address_book::{
+    ExactInputSingleCall, ExactInputSingleParams, SwapExactETHForTokensCall, SwapExactTokensForTokensCall,
+    UniV2RouterCalls, UniV3Factory, UniV3Pool, ERC20,
+};
+use crate::gas_model::RouteShape;
+use crate::gas_oracle::GasOracle;
+use crate::helpers::eth_to_wei;
 use super::types::*;
+use super::own_capital_limit;
+use super::dex_registry::DexRegistry;
+use super::pairs::PairAddressCache;
+use super::competition::{bid_ceiling, CompetitionTracker, GasWarResponse};
+use super::flashloan_balancer::FlashloanProvider;
+use super::price_oracle::PriceOracle;
+use super::token_safety::TokenSafety;
+use super::trace::{DecisionTrace, TraceStep};
+
+/// Uniswap V2 swap fee, in hundredths of a bip.
+const V2_FEE_PPM: u32 = 3000;
+
+/// Victims seen in a block, keyed by (pool, token in), waiting to share a sandwich.
+type PendingVictims = HashMap<(Address, Address), (U64, Vec<VictimSwap>)>;
 
 #[derive(Debug)]
 pub struct SandwichStrategy {
     config: Arc<Config>,
     min_profit_wei: U256,
+    victim_safety_margin_bps: u64,
+    /// Frontruns are capped at this many bps of the pool's input reserve.
+    max_frontrun_reserve_bps: u64,
+    pair_cache: PairAddressCache,
+    /// V2-style DEXes whose pairs victims can trade against, by router.
+    dexes: DexRegistry,
+    multi_victim: MultiVictimConfig,
+    /// Sandwich V3 swaps on this fee tier instead of the victim's.
+    v3_fee_tier_override: Option<u32>,
+    /// Sandwich token-to-token victims, taking profit in their input token.
+    token_to_token: bool,
+    /// Skip victims whose deadline passes before our target block.
+    check_victim_deadline: bool,
+    block_time_secs: u64,
+    price_oracle: PriceOracle,
+    /// Own ETH available for a frontrun; `None` is whatever the wallet holds.
+    capital_wei: Option<U256>,
+    /// Kept back from the wallet's balance for gas, never spent on a frontrun.
+    gas_reserve_wei: U256,
+    /// Also size each sandwich on a loan from here and keep the better of the two.
+    flashloan: Option<FlashloanProvider>,
+    /// Pending bids on each pool, to outbid other searchers' frontruns.
+    competition: Arc<CompetitionTracker>,
+    /// Honeypot and transfer-fee screening of the tokens we'd end up holding.
+    token_safety: TokenSafety,
+    /// Victims seen this block, waiting to share a sandwich.
+    pending_victims: Mutex<PendingVictims>,
 }
 
 impl SandwichStrategy {
-    pub fn new(config: Arc<Config>) -> Self {
+    pub fn new(config: Arc<Config>, competition: Arc<CompetitionTracker>) -> Self {
         Self {
+            competition,
+            victim_safety_margin_bps: config.settings.victim_safety_margin_bps,
+            max_frontrun_reserve_bps: config.settings.max_frontrun_reserve_bps,
+            pair_cache: PairAddressCache::new(config.settings.cache_pair_addresses),
+            dexes: config.chain.dex_registry(config.settings.dex_preset.as_deref()),
+            multi_victim: MultiVictimConfig {
+                enabled: config.settings.multi_victim_sandwich,
+                max_victims: config.settings.max_victims_per_sandwich,
+            },
+            pending_victims: Mutex::new(HashMap::new()),
+            v3_fee_tier_override: config.settings.v3_sandwich_fee_tier,
+            token_to_token: config.settings.token_to_token_sandwich,
+            check_victim_deadline: config.settings.check_victim_deadline,
+            block_time_secs: config.settings.block_time_secs,
+            price_oracle: PriceOracle::new(config.http.clone(), config.pool_cache.clone(), &config.chain),
+            token_safety: TokenSafety::from_settings(&config.settings, &config.chain),
+            capital_wei: config.settings.risk.sandwich_capital_eth.map(eth_to_wei),
+            gas_reserve_wei: eth_to_wei(config.settings.gas.reserve_eth),
+            flashloan: config.settings.sandwich_flashloan,
+            min_profit_wei: eth_to_wei(config.settings.sandwich_min_profit_eth),
             config,
-            min_profit_wei: U256::from(10).pow(U256::from(17)), // 0.1 ETH minimum profit
         }
     }
 
+    /// Oracle the legs are bid off; the one in the shared `Config`.
+    pub fn gas_oracle(&self) -> &Arc<GasOracle> {
+        &self.config.gas_oracle
+    }
 
     fn calculate_frontrun_gas_price(&self, victim_tx: &Transaction) -> U256 {
-        let base_price = victim_tx.gas_price.unwrap_or(U256::from(20_000_000_000u64)); // 20 gwei default
+        let gas_oracle = &self.config.gas_oracle;
+        let base_price = gas_oracle.effective_gas_price(victim_tx).unwrap_or_else(|| gas_oracle.gas_price(50.0));
         // Safely add premium without overflow
-        base_price.saturating_add(U256::from(2_000_000_000u64)) // 2 gwei premium
+        base_price.saturating_add(self.config.gas_oracle.bid_step())
     }
 
     fn calculate_backrun_gas_price(&self, victim_tx: &Transaction) -> U256 {
-        let base_price = victim_tx.gas_price.unwrap_or(U256::from(20_000_000_000u64)); // 20 gwei default
+        let gas_oracle = &self.config.gas_oracle;
+        let base_price = gas_oracle.effective_gas_price(victim_tx).unwrap_or_else(|| gas_oracle.gas_price(50.0));
         // Safely subtract premium without underflow
-        if base_price > U256::from(2_000_000_000u64) {
-            base_price - U256::from(2_000_000_000u64)
+        let premium = self.config.gas_oracle.bid_step();
+        if base_price > premium {
+            base_price - premium
         } else {
             base_price / 2 // If too low, use half the price
         }
     }
 
-    fn validate_profitable_victim(&self, tx: &Transaction, min_value: U256) -> bool {
-        // Skip transactions with very low value
-        if tx.value < min_value {
-            return false;
+    pub async fn analyze(&self, tx: &Transaction, class: &TxClass, trace: &DecisionTrace) -> Vec<MEVOpportunity> {
+        let mut opportunities = Vec::new();
+
+        let decoded = matches!(class, TxClass::SwapV2(_) | TxClass::SwapV3(_) | TxClass::UniversalRouter(_));
+        if !trace.gate("decode_router_call", decoded) {
+            return opportunities;
         }
-        
-        // Skip transactions with unreasonable gas prices
-        if let Some(gas_price) = tx.gas_price {
-            if gas_price > U256::from(500_000_000_000u64) || gas_price == U256::zero() {
-                return false;
+        if trace.is_enabled() {
+            trace.record(TraceStep::Decoded(format!("{:?}", class)));
+        }
+
+        match class {
+            TxClass::SwapV3(v3_swaps) => {
+                // Further swaps in the same multicall would move the pool under our sizing
+                let [swap] = &v3_swaps[..] else {
+                    trace.gate("v3_single_swap", false);
+                    return opportunities;
+                };
+                if !self.deadline_satisfied(swap.deadline, trace).await {
+                    return opportunities;
+                }
+                self.analyze_v3_swap(tx, swap, trace).await
+            }
+            TxClass::UniversalRouter(execute) => {
+                // Wraps and permits around the swap are fine; a second swap isn't
+                let swaps: Vec<&Command> = execute.swaps().collect();
+                let [swap] = &swaps[..] else {
+                    trace.gate("universal_single_swap", false);
+                    return opportunities;
+                };
+                if !self.deadline_satisfied(execute.deadline, trace).await {
+                    return opportunities;
+                }
+                if !trace.gate("universal_permit_covers_input", execute.permits_cover_swaps()) {
+                    return opportunities;
+                }
+                match swap {
+                    Command::V2SwapExactIn { path, amount_in, amount_out_min } => {
+                        self.analyze_token_to_token_swap(tx, path.clone(), *amount_in, *amount_out_min, trace).await
+                    }
+                    Command::V2SwapExactOut { path, amount_out, amount_in_max } => {
+                        self.analyze_exact_output_swap(tx, path.clone(), *amount_out, *amount_in_max, trace).await
+                    }
+                    Command::V3Swap(swap) => self.analyze_v3_swap(tx, swap, trace).await,
+                    _ => opportunities,
+                }
             }
+            TxClass::SwapV2(call) => {
+                if !self.deadline_satisfied(Self::v2_deadline(call), trace).await {
+                    return opportunities;
+                }
+                match call.clone() {
+                    UniV2RouterCalls::SwapExactETHForTokens(call) => {
+                        opportunities.extend(self.analyze_eth_to_token_swap(tx, call.path, call.amount_out_min, trace).await);
+                    },
+                    UniV2RouterCalls::SwapExactETHForTokensSupportingFeeOnTransferTokens(call) => {
+                        opportunities.extend(self.analyze_eth_to_token_swap(tx, call.path, call.amount_out_min, trace).await);
+                    },
+                    UniV2RouterCalls::SwapExactTokensForETH(call) => {
+                        opportunities.extend(self.analyze_token_to_eth_swap(tx, call.path, call.amount_in, call.amount_out_min, trace).await);
+                    },
+                    UniV2RouterCalls::SwapExactTokensForETHSupportingFeeOnTransferTokens(call) => {
+                        opportunities.extend(self.analyze_token_to_eth_swap(tx, call.path, call.amount_in, call.amount_out_min, trace).await);
+                    },
+                    UniV2RouterCalls::SwapExactTokensForTokens(call) => {
+                        opportunities.extend(self.analyze_token_to_token_swap(tx, call.path, call.amount_in, call.amount_out_min, trace).await);
+                    },
+                    UniV2RouterCalls::SwapExactTokensForTokensSupportingFeeOnTransferTokens(call) => {
+                        opportunities.extend(self.analyze_token_to_token_swap(tx, call.path, call.amount_in, call.amount_out_min, trace).await);
+                    },
+                    exact_output => {
+                        if let Some((path, amount_out, amount_in_max)) = Self::v2_exact_output(tx, &exact_output) {
+                            opportunities.extend(self.analyze_exact_output_swap(tx, path, amount_out, amount_in_max, trace).await);
+                        }
+                    }
+                }
+                opportunities
+            }
+            _ => opportunities,
+        }
+    }
+
+    /// False if the victim's `deadline` passes before the block we'd sandwich it in,
+    /// i.e. the victim (and with it the whole bundle) would revert.
+    async fn deadline_satisfied(&self, deadline: Option<U256>, trace: &DecisionTrace) -> bool {
+        let Some(deadline) = deadline.filter(|_| self.check_victim_deadline) else {
+            return true;
+        };
+        // The block scanner's head, rather than another `get_block` per victim
+        let latest = self.config.block_height.timestamp();
+        if latest == 0 {
+            return true;
         }
+        let target_timestamp = U256::from(latest + self.block_time_secs);
+        trace.gate("victim_deadline", deadline >= target_timestamp)
+    }
+
+    fn v2_deadline(call: &UniV2RouterCalls) -> Option<U256> {
+        match call {
+            UniV2RouterCalls::SwapExactETHForTokens(c) => Some(c.deadline),
+            UniV2RouterCalls::SwapExactETHForTokensSupportingFeeOnTransferTokens(c) => Some(c.deadline),
+            UniV2RouterCalls::SwapExactTokensForETH(c) => Some(c.deadline),
+            UniV2RouterCalls::SwapExactTokensForETHSupportingFeeOnTransferTokens(c) => Some(c.deadline),
+            UniV2RouterCalls::SwapExactTokensForTokens(c) => Some(c.deadline),
+            UniV2RouterCalls::SwapExactTokensForTokensSupportingFeeOnTransferTokens(c) => Some(c.deadline),
+            UniV2RouterCalls::SwapETHForExactTokens(c) => Some(c.deadline),
+            UniV2RouterCalls::SwapTokensForExactETH(c) => Some(c.deadline),
+            UniV2RouterCalls::SwapTokensForExactTokens(c) => Some(c.deadline),
+            _ => None,
+        }
+    }
+
+    fn v2_path(call: &UniV2RouterCalls) -> Option<&[Address]> {
+        match call {
+            UniV2RouterCalls::SwapExactETHForTokens(c) => Some(&c.path),
+            UniV2RouterCalls::SwapExactETHForTokensSupportingFeeOnTransferTokens(c) => Some(&c.path),
+            UniV2RouterCalls::SwapExactTokensForETH(c) => Some(&c.path),
+            UniV2RouterCalls::SwapExactTokensForETHSupportingFeeOnTransferTokens(c) => Some(&c.path),
+            UniV2RouterCalls::SwapExactTokensForTokens(c) => Some(&c.path),
+            UniV2RouterCalls::SwapExactTokensForTokensSupportingFeeOnTransferTokens(c) => Some(&c.path),
+            UniV2RouterCalls::SwapETHForExactTokens(c) => Some(&c.path),
+            UniV2RouterCalls::SwapTokensForExactETH(c) => Some(&c.path),
+            UniV2RouterCalls::SwapTokensForExactTokens(c) => Some(&c.path),
+            _ => None,
+        }
+    }
+
+    /// Path, amount bought and most paid of an exact-output V2 swap. For
+    /// `swapETHForExactTokens` that's the ETH sent; the router refunds the rest.
+    fn v2_exact_output(tx: &Transaction, call: &UniV2RouterCalls) -> Option<(Vec<Address>, U256, U256)> {
+        match call {
+            UniV2RouterCalls::SwapETHForExactTokens(c) => Some((c.path.clone(), c.amount_out, tx.value)),
+            UniV2RouterCalls::SwapTokensForExactETH(c) => Some((c.path.clone(), c.amount_out, c.amount_in_max)),
+            UniV2RouterCalls::SwapTokensForExactTokens(c) => Some((c.path.clone(), c.amount_out, c.amount_in_max)),
+            _ => None,
+        }
+    }
+
+    /// The V2 pool a router swap trades against first, without reading any state.
+    pub fn pool_of(&self, tx: &Transaction) -> Option<Address> {
+        let TxClass::SwapV2(call) = classify(tx) else {
+            return None;
+        };
+        match Self::v2_path(&call)? {
+            [token_in, token_out, ..] => self.get_pair_address(self.victim_dex(tx), *token_in, *token_out),
+            _ => None,
+        }
+    }
+
+    async fn analyze_token_to_eth_swap(
+        &self,
+        victim_tx: &Transaction,
+        path: Vec<Address>,
+        amount_in: U256,
+        amount_out_min: U256,
+        trace: &DecisionTrace,
+    ) -> Vec<MEVOpportunity> {
+        if !trace.gate("path_length", path.len() >= 2) {
+            return Vec::new();
+        }
+
+        let token_in = path[0];
+        let weth = path[path.len() - 1];
         
-        true
+        // Get pool info
+        let Some((pool_address, router)) = self.v2_venue(victim_tx, token_in, weth) else {
+            return Vec::new();
+        };
+        let Some((reserve0, reserve1)) = self.get_reserves(pool_address, token_in).await else {
+            return Vec::new();
+        };
+        trace.record(TraceStep::Reserves { pool: pool_address, reserve_in: reserve0, reserve_out: reserve1 });
+
+        let victim = VictimSwap {
+            tx: victim_tx.clone(),
+            amount_in,
+            amount_out_min,
+            exact_output: false,
+        };
+        let pool = SandwichPool {
+            address: pool_address,
+            token_in,
+            token_out: weth,
+            reserve_in: reserve0,
+            reserve_out: reserve1,
+            fee_ppm: V2_FEE_PPM,
+            router: LegRouter::V2(router),
+        };
+
+        self.sandwich_victim(victim, &pool, trace).await
+    }
+
+    /// Sandwiches a single-hop V3 swap on the pool of the fee tier it trades on,
+    /// unless a tier is forced via `V3_SANDWICH_FEE_TIER`.
+    async fn analyze_v3_swap(
+        &self,
+        victim_tx: &Transaction,
+        swap: &uni_v3::RouterSwap,
+        trace: &DecisionTrace,
+    ) -> Vec<MEVOpportunity> {
+        if !trace.gate("v3_single_hop", swap.is_single_hop()) {
+            return Vec::new();
+        }
+
+        let fee_tier = self.v3_fee_tier(swap);
+        if !trace.gate("v3_fee_tier_known", uni_v3::FEE_TIERS.contains(&fee_tier)) {
+            return Vec::new();
+        }
+        // Our legs need a router that takes a deadline per swap
+        let Some(router) = self.config.chain.v3_router else {
+            trace.gate("v3_router_known", false);
+            return Vec::new();
+        };
+
+        let Some(pool) = self.get_v3_pool(swap.token_in(), swap.token_out(), fee_tier, router).await else {
+            trace.gate("v3_pool_exists", false);
+            return Vec::new();
+        };
+        trace.record(TraceStep::Reserves { pool: pool.address, reserve_in: pool.reserve_in, reserve_out: pool.reserve_out });
+
+        // Exact output: `amount_in` is the victim's max and `amount_out` what they buy
+        let victim = VictimSwap {
+            tx: victim_tx.clone(),
+            amount_in: swap.amount_in,
+            amount_out_min: swap.amount_out,
+            exact_output: swap.exact_output,
+        };
+        self.sandwich_victim(victim, &pool, trace).await
+    }
+
+    /// Fee tier to sandwich a V3 swap on: the override if set, else the tier of
+    /// the pool the victim trades.
+    fn v3_fee_tier(&self, swap: &uni_v3::RouterSwap) -> u32 {
+        self.v3_fee_tier_override.unwrap_or(swap.fees[0])
+    }
+
+    /// In-range state of the V3 pool for `fee_tier`, as virtual reserves oriented to `token_in`.
+    async fn get_v3_pool(&self, token_in: Address, token_out: Address, fee_tier: u32, router: Address) -> Option<SandwichPool> {
+        let factory = UniV3Factory::new(self.config.chain.v3_factory?, self.config.http.clone());
+        let pool_address = factory.get_pool(token_in, token_out, fee_tier).call().await.ok()?;
+        if pool_address.is_zero() {
+            return None;
+        }
+
+        let pool = UniV3Pool::new(pool_address, self.config.http.clone());
+        let slot0 = pool.slot_0().call().await.ok()?;
+        let liquidity = pool.liquidity().call().await.ok()?;
+        let token0 = pool.token_0().call().await.ok()?;
+
+        let (reserve0, reserve1) = uni_v3::virtual_reserves(slot0.0, liquidity);
+        let (reserve_in, reserve_out) = if token0 == token_in { (reserve0, reserve1) } else { (reserve1, reserve0) };
+
+        Some(SandwichPool {
+            address: pool_address,
+            token_in,
+            token_out,
+            reserve_in,
+            reserve_out,
+            fee_ppm: fee_tier,
+            router: LegRouter::V3(router),
+        })
     }
-    pub async fn analyze(&self, tx: &Transaction) -> Vec<MEVOpportunity> {
+
+    /// Sandwiches `victim` alone and, if enabled, together with the other victims seen on its pool.
+    async fn sandwich_victim(&self, victim: VictimSwap, pool: &SandwichPool, trace: &DecisionTrace) -> Vec<MEVOpportunity> {
         let mut opportunities = Vec::new();
+        // A token we can't sell back (or that taxes transfers) turns the frontrun into a loss
+        let weth = self.get_weth_address();
+        for token in [pool.token_in, pool.token_out] {
+            if token != weth && !trace.gate("token_safety", self.token_safety.is_safe(token).await) {
+                return opportunities;
+            }
+        }
+        let current_block = self.get_current_block().await;
+        self.competition.observe(pool.address, &victim.tx, current_block);
 
-        // Decode router calls
-        if let Ok(decoded) = UniV2RouterCalls::decode(&tx.input) {
-            match decoded {
-                UniV2RouterCalls::SwapExactETHForTokens(call) => {
-                    if let Some(opp) = self.analyze_eth_to_token_swap(tx, call.path, call.amount_out_min).await {
-                        opportunities.push(opp);
-                    }
-                },
-                UniV2RouterCalls::SwapExactETHForTokensSupportingFeeOnTransferTokens(call) => {
-                    if let Some(opp) = self.analyze_eth_to_token_swap(tx, call.path, call.amount_out_min).await {
-                        opportunities.push(opp);
-                    }
-                },
-                UniV2RouterCalls::SwapExactTokensForETH(call) => {
-                    if let Some(opp) = self.analyze_token_to_eth_swap(tx, call.path, call.amount_in, call.amount_out_min).await {
-                        opportunities.push(opp);
-                    }
-                },
-                UniV2RouterCalls::SwapExactTokensForETHSupportingFeeOnTransferTokens(call) => {
-                    if let Some(opp) = self.analyze_token_to_eth_swap(tx, call.path, call.amount_in, call.amount_out_min).await {
-                        opportunities.push(opp);
-                    }
-                },
-                UniV2RouterCalls::SwapExactTokensForTokens(call) => {
-                    if let Some(opp) = self.analyze_token_to_token_swap(tx, call.path, call.amount_in, call.amount_out_min).await {
-                        opportunities.push(opp);
-                    }
-                },
-                UniV2RouterCalls::SwapExactTokensForTokensSupportingFeeOnTransferTokens(call) => {
-                    if let Some(opp) = self.analyze_token_to_token_swap(tx, call.path, call.amount_in, call.amount_out_min).await {
-                        opportunities.push(opp);
-                    }
-                },
-                _ => {}
+        if let Some(opp) = self.build_sandwich(std::slice::from_ref(&victim), pool, current_block, "min_profit", trace).await {
+            opportunities.push(opp);
+        }
+
+        // Bracket every victim seen on this pool this block with one frontrun/backrun
+        if self.multi_victim.enabled {
+            let group = self.group_victim(pool, current_block, victim);
+            if group.len() > 1 {
+                if let Some(opp) = self.build_sandwich(&group, pool, current_block, "multi_victim_min_profit", trace).await {
+                    opportunities.push(opp);
+                }
             }
         }
 
         opportunities
     }
 
-    async fn analyze_token_to_eth_swap(
+    /// Sizes and builds one sandwich around `victims`, landing in the given order.
+    async fn build_sandwich(
         &self,
-        _victim_tx: &Transaction,
-        _path: Vec<Address>,
-        _amount_in: U256,
-        _amount_out_min: U256,
+        victims: &[VictimSwap],
+        pool: &SandwichPool,
+        current_block: U64,
+        gate: &'static str,
+        trace: &DecisionTrace,
     ) -> Option<MEVOpportunity> {
-        if _path.len() < 2 {
+        let (lead, co_victims) = victims.split_first()?;
+
+        let Some((optimal_sandwich, funding, profit_eth)) = self.choose_funding(victims, pool).await else {
+            trace.gate("profit_priced", false);
             return None;
+        };
+
+        if trace.is_enabled() {
+            let token = self.config.token_info.resolve(pool.token_in).await;
+            trace.record(TraceStep::OptimalAmount { amount: optimal_sandwich.frontrun_amount, token });
         }
 
-        let token_in = _path[0];
-        let weth = _path[_path.len() - 1];
-        
-        // Get pool info
-        let pool_address = self.get_pair_address(token_in, weth);
-        let (reserve0, reserve1) = self.get_reserves(pool_address).await?;
-        
-        // Calculate optimal sandwich amounts
-        let optimal_sandwich = self.calculate_optimal_sandwich(
-            _amount_in,
-            reserve0,
-            reserve1,
-            true, // token to ETH
-        );
+        // No frontrun both pays and leaves the victims' slippage checks passing
+        if !trace.gate("victim_slippage_room", !optimal_sandwich.frontrun_amount.is_zero()) {
+            return None;
+        }
 
-        if optimal_sandwich.profit < self.min_profit_wei {
+        if !trace.gate(gate, profit_eth >= self.min_profit_wei) {
             return None;
         }
 
+        // Outbid the highest-paying victim going in, sit under the lowest coming out
+        let by_gas_price = |v: &&VictimSwap| self.config.gas_oracle.effective_gas_price(&v.tx).unwrap_or_default();
+        let frontrun_anchor = &victims.iter().max_by_key(by_gas_price)?.tx;
+        let backrun_anchor = &victims.iter().min_by_key(by_gas_price)?.tx;
+
         // Build frontrun and backrun transactions
-        let frontrun_tx = self.build_frontrun_tx(
-            token_in,
-            weth,
+        let deadline = self.config.swap_deadline();
+        let mut frontrun_tx = self.build_frontrun_tx(
+            pool,
             optimal_sandwich.frontrun_amount,
-            _victim_tx,
+            funding,
+            frontrun_anchor,
+            deadline,
         );
         
         let backrun_tx = self.build_backrun_tx(
-            token_in,
-            weth,
+            pool,
             optimal_sandwich.backrun_amount,
-            _victim_tx,
+            optimal_sandwich.frontrun_amount,
+            backrun_anchor,
+            deadline,
         );
 
+        // Searchers escalating against each other on this pool: bow out or bid a capped amount
+        if let Some(war) = self.competition.gas_war(pool.address, current_block) {
+            let current = frontrun_tx.gas_price().unwrap_or_default();
+            let backrun_cost = backrun_tx.gas().copied().unwrap_or_default() * backrun_tx.gas_price().unwrap_or_default();
+            let ceiling = bid_ceiling(profit_eth, frontrun_tx.gas().copied().unwrap_or_default(), backrun_cost);
+            match self.competition.respond_to_war(&war, current, ceiling) {
+                GasWarResponse::BowOut => {
+                    trace.gate("gas_war", false);
+                    return None;
+                }
+                GasWarResponse::Bid(bid) => {
+                    frontrun_tx.set_gas_price(bid);
+                }
+                GasWarResponse::Proceed => {}
+            }
+        }
+
+        // Someone else is already frontrunning these victims: outbid them, up to break-even
+        if self.competition.is_enabled() {
+            let victim_hashes: Vec<H256> = victims.iter().map(|v| v.tx.hash).collect();
+            let anchor_price = self.config.gas_oracle.effective_gas_price(frontrun_anchor).unwrap_or_default();
+            if let Some(competitor) = self.competition.top_bid(pool.address, current_block, &victim_hashes, anchor_price) {
+                let current = frontrun_tx.gas_price().unwrap_or_default();
+                let backrun_cost = backrun_tx.gas().copied().unwrap_or_default() * backrun_tx.gas_price().unwrap_or_default();
+                let ceiling = bid_ceiling(profit_eth, frontrun_tx.gas().copied().unwrap_or_default(), backrun_cost);
+                if let Some(bid) = self.competition.rebid(current, competitor, ceiling) {
+                    frontrun_tx.set_gas_price(bid);
+                }
+            }
+        }
+
+        let id = if co_victims.is_empty() {
+            format!("sandwich_{}", lead.tx.hash)
+        } else {
+            format!("multi_sandwich_{}_{}", lead.tx.hash, victims.len())
+        };
+
         Some(MEVOpportunity {
-            id: format!("sandwich_{}", _victim_tx.hash),
-            target_tx: _victim_tx.clone(),
+            id,
+            target_tx: lead.tx.clone(),
             strategy_type: StrategyType::Sandwich(SandwichDetails {
-                victim_tx: _victim_tx.clone(),
+                victim_tx: lead.tx.clone(),
+                co_victims: co_victims.iter().map(|v| v.tx.clone()).collect(),
                 frontrun_tx,
                 backrun_tx,
-                target_pool: pool_address,
-                token_in,
-                token_out: weth,
+                target_pool: pool.address,
+                token_in: pool.token_in,
+                token_out: pool.token_out,
                 optimal_amount: optimal_sandwich.frontrun_amount,
-                victim_amount_in: _amount_in,
-                victim_amount_out_min: _amount_out_min,
+                victim_amount_in: victims.iter().fold(U256::zero(), |sum, v| sum + v.amount_in),
+                victim_amount_out_min: lead.amount_out_min,
                 price_impact: optimal_sandwich.price_impact,
+                funding,
             }),
-            estimated_profit: optimal_sandwich.profit,
+            estimated_profit: profit_eth,
             gas_cost: optimal_sandwich.gas_cost,
-            priority: self.calculate_priority(&optimal_sandwich),
-            expiry_block: self.get_current_block().await + 1,
+            priority: self.calculate_priority(profit_eth),
+            reserve_block: current_block,
+            expiry_block: current_block + 1,
+            observed_at: Instant::now(),
+            landing_probability: 1.0,
         })
     }
 
+    /// Sizes the sandwich on own capital and, if configured, on a flash loan, and
+    /// returns whichever nets more ETH after the loan's fee and extra gas.
+    ///
+    /// Own capital is held in ETH, so it only funds sandwiches that spend WETH, and
+    /// no more of it than the wallet holds; with a capital limit set, anything else
+    /// needs the loan.
+    async fn choose_funding(&self, victims: &[VictimSwap], pool: &SandwichPool) -> Option<(OptimalSandwich, Funding, U256)> {
+        let max_frontrun = uni::max_frontrun(pool.reserve_in, self.max_frontrun_reserve_bps);
+        let own_limit = if pool.token_in == self.get_weth_address() {
+            let balance = self.config.http.get_balance(self.config.http.address(), None).await.unwrap_or_default();
+            own_capital_limit(self.capital_wei, balance, self.gas_reserve_wei).min(max_frontrun)
+        } else if self.capital_wei.is_none() {
+            // Spends our balance of the token itself, which pays no gas
+            let us = self.config.http.address();
+            let balance = ERC20::new(pool.token_in, self.config.http.clone()).balance_of(us).call().await.unwrap_or_default();
+            own_capital_limit(None, balance, U256::zero()).min(max_frontrun)
+        } else {
+            U256::zero()
+        };
+
+        // Gas measured by simulating sandwiches on this pool, once there are any
+        let route_gas = |flashloan| self.config.gas_model.sandwich_route_gas(&RouteShape::new(vec![pool.address], flashloan));
+
+        let own = self.calculate_optimal_sandwich(victims, pool, own_limit, None, route_gas(false));
+        let own_eth = if own_limit.is_zero() {
+            None
+        } else {
+            self.profit_in_eth(pool, own.profit).await
+        };
+
+        let Some(provider) = self.flashloan else {
+            return own_eth.map(|profit_eth| (own, Funding::OwnCapital, profit_eth));
+        };
+        let borrowed = self.calculate_optimal_sandwich(victims, pool, max_frontrun, Some(provider), route_gas(true));
+        let borrowed_eth = if borrowed.frontrun_amount.is_zero() || borrowed.profit <= own.profit {
+            None
+        } else {
+            self.profit_in_eth(pool, borrowed.profit).await
+        };
+
+        let net = |profit_eth: U256, sandwich: &OptimalSandwich| profit_eth.saturating_sub(sandwich.gas_cost);
+        match (own_eth, borrowed_eth) {
+            (Some(own_eth), Some(borrowed_eth)) if net(own_eth, &own) >= net(borrowed_eth, &borrowed) => {
+                Some((own, Funding::OwnCapital, own_eth))
+            }
+            (_, Some(borrowed_eth)) => {
+                let fee = provider.fee(borrowed.frontrun_amount);
+                Some((borrowed, Funding::Flashloan { provider, fee }, borrowed_eth))
+            }
+            (Some(own_eth), None) => Some((own, Funding::OwnCapital, own_eth)),
+            (None, None) => None,
+        }
+    }
+
+    /// Converts sandwich profit to wei. The backrun sells back into the token the
+    /// frontrun spent, so profit is denominated in the pool's input token.
+    async fn profit_in_eth(&self, pool: &SandwichPool, profit: U256) -> Option<U256> {
+        let weth = self.get_weth_address();
+        if pool.token_in == weth {
+            Some(profit)
+        } else if pool.token_out == weth {
+            // Already holding the token/WETH reserves: price off the pre-sandwich spot
+            PriceOracle::quote(profit, pool.reserve_in, pool.reserve_out)
+        } else {
+            self.price_oracle.to_eth(pool.token_in, profit).await
+        }
+    }
+
+    /// Adds `victim` to the pending group for its pool and direction, dropping
+    /// groups from earlier blocks, and returns the current group.
+    fn group_victim(&self, pool: &SandwichPool, current_block: U64, victim: VictimSwap) -> Vec<VictimSwap> {
+        let mut pending = self.pending_victims.lock().unwrap();
+        pending.retain(|_, (block, _)| *block == current_block);
+
+        let (_, group) = pending
+            .entry((pool.address, pool.token_in))
+            .or_insert_with(|| (current_block, Vec::new()));
+        if group.iter().any(|v| v.tx.hash == victim.tx.hash) {
+            return Vec::new();
+        }
+        if !group.is_empty() && group.len() >= self.multi_victim.max_victims {
+            group.remove(0);
+        }
+        group.push(victim);
+        group.clone()
+    }
+
+    /// Single-hop ETH-to-token victims. The calldata carries no input amount: the
+    /// victim swaps the ETH the tx sends.
     async fn analyze_eth_to_token_swap(
         &self,
-        _victim_tx: &Transaction,
-        _path: Vec<Address>,
-        _amount_out_min: U256,
-    ) -> Option<MEVOpportunity> {
-        // Similar implementation for ETH to token swaps
-        // Frontrun by buying tokens with ETH, backrun by selling tokens for ETH
-        None // Simplified for brevity
+        victim_tx: &Transaction,
+        path: Vec<Address>,
+        amount_out_min: U256,
+        trace: &DecisionTrace,
+    ) -> Vec<MEVOpportunity> {
+        // Multi-hop routes would need the frontrun to follow every hop
+        if !trace.gate("single_hop", path.len() == 2) {
+            return Vec::new();
+        }
+
+        let (weth, token_out) = (path[0], path[1]);
+        let Some((pool_address, router)) = self.v2_venue(victim_tx, weth, token_out) else {
+            return Vec::new();
+        };
+        let Some((reserve_in, reserve_out)) = self.get_reserves(pool_address, weth).await else {
+            return Vec::new();
+        };
+        trace.record(TraceStep::Reserves { pool: pool_address, reserve_in, reserve_out });
+
+        let victim = VictimSwap {
+            tx: victim_tx.clone(),
+            amount_in: victim_tx.value,
+            amount_out_min,
+            exact_output: false,
+        };
+        let pool = SandwichPool {
+            address: pool_address,
+            token_in: weth,
+            token_out,
+            reserve_in,
+            reserve_out,
+            fee_ppm: V2_FEE_PPM,
+            router: LegRouter::V2(router),
+        };
+
+        self.sandwich_victim(victim, &pool, trace).await
     }
 
+    /// Single-hop token-to-token victims, on the pair they trade against.
     async fn analyze_token_to_token_swap(
         &self,
-        _victim_tx: &Transaction,
-        _path: Vec<Address>,
-        _amount_in: U256,
-        _amount_out_min: U256,
-    ) -> Option<MEVOpportunity> {
-        // Multi-hop sandwich attacks
-        None // Simplified for brevity
+        victim_tx: &Transaction,
+        path: Vec<Address>,
+        amount_in: U256,
+        amount_out_min: U256,
+        trace: &DecisionTrace,
+    ) -> Vec<MEVOpportunity> {
+        if !trace.gate("token_to_token_enabled", self.token_to_token) {
+            return Vec::new();
+        }
+        // Multi-hop routes would need the frontrun to follow every hop
+        if !trace.gate("single_hop", path.len() == 2) {
+            return Vec::new();
+        }
+
+        let (token_in, token_out) = (path[0], path[1]);
+        let Some((pool_address, router)) = self.v2_venue(victim_tx, token_in, token_out) else {
+            return Vec::new();
+        };
+        let Some((reserve_in, reserve_out)) = self.get_reserves(pool_address, token_in).await else {
+            return Vec::new();
+        };
+        trace.record(TraceStep::Reserves { pool: pool_address, reserve_in, reserve_out });
+
+        let victim = VictimSwap {
+            tx: victim_tx.clone(),
+            amount_in,
+            amount_out_min,
+            exact_output: false,
+        };
+        let pool = SandwichPool {
+            address: pool_address,
+            token_in,
+            token_out,
+            reserve_in,
+            reserve_out,
+            fee_ppm: V2_FEE_PPM,
+            router: LegRouter::V2(router),
+        };
+
+        self.sandwich_victim(victim, &pool, trace).await
+    }
+
+    /// Single-hop exact-output victims. The victim's input grows with our frontrun,
+    /// so it is bounded by `amount_in_max` rather than by a minimum output.
+    async fn analyze_exact_output_swap(
+        &self,
+        victim_tx: &Transaction,
+        path: Vec<Address>,
+        amount_out: U256,
+        amount_in_max: U256,
+        trace: &DecisionTrace,
+    ) -> Vec<MEVOpportunity> {
+        // Multi-hop routes would need the frontrun to follow every hop
+        if !trace.gate("single_hop", path.len() == 2) {
+            return Vec::new();
+        }
+        let (token_in, token_out) = (path[0], path[1]);
+        let weth = self.get_weth_address();
+        if token_in != weth && token_out != weth && !trace.gate("token_to_token_enabled", self.token_to_token) {
+            return Vec::new();
+        }
+
+        let Some((pool_address, router)) = self.v2_venue(victim_tx, token_in, token_out) else {
+            return Vec::new();
+        };
+        let Some((reserve_in, reserve_out)) = self.get_reserves(pool_address, token_in).await else {
+            return Vec::new();
+        };
+        trace.record(TraceStep::Reserves { pool: pool_address, reserve_in, reserve_out });
+
+        let victim = VictimSwap {
+            tx: victim_tx.clone(),
+            amount_in: amount_in_max,
+            amount_out_min: amount_out,
+            exact_output: true,
+        };
+
+        // No room under `amountInMax` even before our frontrun: nothing to take
+        let headroom = self.replay(U256::zero(), std::slice::from_ref(&victim), reserve_in, reserve_out, V2_FEE_PPM);
+        if !trace.gate("exact_output_headroom", headroom.victims_fill) {
+            return Vec::new();
+        }
+
+        let pool = SandwichPool {
+            address: pool_address,
+            token_in,
+            token_out,
+            reserve_in,
+            reserve_out,
+            fee_ppm: V2_FEE_PPM,
+            router: LegRouter::V2(router),
+        };
+
+        self.sandwich_victim(victim, &pool, trace).await
     }
 
     fn calculate_optimal_sandwich(
         &self,
-        victim_amount: U256,
-        reserve_in: U256,
-        reserve_out: U256,
-        _is_token_to_eth: bool,
+        victims: &[VictimSwap],
+        pool: &SandwichPool,
+        max_frontrun: U256,
+        loan: Option<FlashloanProvider>,
+        gas: u64,
     ) -> OptimalSandwich {
+        let (reserve_in, reserve_out, fee_ppm) = (pool.reserve_in, pool.reserve_out, pool.fee_ppm);
         // Advanced sandwich calculation using binary search
         let mut low = U256::from(0);
-        let mut high = reserve_in / 10; // Max 10% of pool
+        let mut high = self.victim_slippage_cap(victims, reserve_in, reserve_out, fee_ppm, max_frontrun).unwrap_or_default();
         let mut best_profit = U256::from(0);
         let mut best_amount = U256::from(0);
+        let gas_cost = self.config.gas_model.cost(gas, 50.0);
         
         while low <= high {
             let mid = (low + high) / 2;
-            
+
             // Simulate sandwich attack
-            let (profit, gas_cost) = self.simulate_sandwich_profit(
+            let profit = self.simulate_sandwich_profit(
                 mid,
-                victim_amount,
+                victims,
                 reserve_in,
                 reserve_out,
+                fee_ppm,
             );
+            // A borrowed frontrun owes the lender's fee out of the same profit
+            let profit = profit.saturating_sub(loan.map_or(U256::zero(), |provider| provider.fee(mid)));
             
             if profit > best_profit {
                 best_profit = profit;
@@ -215,114 +790,253 @@ impl SandwichStrategy {
             // Binary search logic
             if profit > gas_cost {
                 low = mid + 1;
+            } else if mid.is_zero() {
+                break;
             } else {
                 high = mid - 1;
             }
         }
         
+        // Sell back only what it takes to recover the stake, loan fee and profit; any excess stays as inventory
+        let loan_fee = loan.map_or(U256::zero(), |provider| provider.fee(best_amount));
+        let backrun_amount = self.backrun_amount_for(
+            best_amount + loan_fee + best_profit,
+            best_amount,
+            victims,
+            reserve_in,
+            reserve_out,
+            fee_ppm,
+        );
+
         OptimalSandwich {
             frontrun_amount: best_amount,
-            backrun_amount: best_amount * 95 / 100, // Account for slippage
+            backrun_amount,
             profit: best_profit,
-            gas_cost: U256::from(500000) * U256::from(50) * U256::from(10).pow(U256::from(9)), // Estimate
-            price_impact: (best_amount.as_u64() as f64) / (reserve_in.as_u64() as f64),
+            gas_cost,
+            price_impact: uni::price_impact(best_amount, reserve_in),
+        }
+    }
+
+    /// Largest frontrun (up to `max_frontrun`) after which every victim's swap still
+    /// clears its `amountOutMin` (or `amountInMax`) by the safety margin. A bigger
+    /// frontrun reverts the victims and leaves ours stranded. `None` if the victims
+    /// don't fill even unsandwiched.
+    fn victim_slippage_cap(
+        &self,
+        victims: &[VictimSwap],
+        reserve_in: U256,
+        reserve_out: U256,
+        fee_ppm: u32,
+        max_frontrun: U256,
+    ) -> Option<U256> {
+        let fills = |frontrun: U256| self.replay(frontrun, victims, reserve_in, reserve_out, fee_ppm).victims_fill;
+        if !fills(U256::zero()) {
+            return None;
+        }
+        if fills(max_frontrun) {
+            return Some(max_frontrun);
+        }
+
+        // Victims get worse prices the more we buy first, so the cap is a single crossover
+        let (mut low, mut high) = (U256::zero(), max_frontrun);
+        while high - low > U256::one() {
+            let mid = (low + high) / 2;
+            if fills(mid) {
+                low = mid;
+            } else {
+                high = mid;
+            }
         }
+        Some(low)
+    }
+
+    /// Replays our frontrun of `frontrun_amount` and then each victim, in bundle order.
+    fn replay(
+        &self,
+        frontrun_amount: U256,
+        victims: &[VictimSwap],
+        reserve_in: U256,
+        reserve_out: U256,
+        fee_ppm: u32,
+    ) -> SandwichReplay {
+        let (frontrun_out, mut reserve_in, mut reserve_out) =
+            uni::get_amount_out_with_fee(frontrun_amount, reserve_in, reserve_out, fee_ppm);
+
+        // Don't squeeze the victims to their exact limit: tiny reserve drift would flip them into a revert
+        let margin = |amount: U256| amount * (10_000 + self.victim_safety_margin_bps) / 10_000;
+        let mut victims_fill = true;
+        for victim in victims {
+            let (fills, new_reserve_in, new_reserve_out) = if victim.exact_output {
+                // Pays whatever the fixed output now costs, up to its max
+                let (paid, new_reserve_in, new_reserve_out) =
+                    uni::get_amount_in_with_fee(victim.amount_out_min, reserve_in, reserve_out, fee_ppm);
+                (paid != U256::MAX && margin(paid) <= victim.amount_in, new_reserve_in, new_reserve_out)
+            } else {
+                let (received, new_reserve_in, new_reserve_out) =
+                    uni::get_amount_out_with_fee(victim.amount_in, reserve_in, reserve_out, fee_ppm);
+                (received >= margin(victim.amount_out_min), new_reserve_in, new_reserve_out)
+            };
+            victims_fill &= fills;
+            reserve_in = new_reserve_in;
+            reserve_out = new_reserve_out;
+        }
+
+        SandwichReplay { frontrun_out, victims_fill, reserve_in, reserve_out }
+    }
+
+    /// Tokens the backrun must sell to receive exactly `target_out` of the input token,
+    /// against the reserves left by our frontrun and the victims.
+    fn backrun_amount_for(
+        &self,
+        target_out: U256,
+        frontrun_amount: U256,
+        victims: &[VictimSwap],
+        reserve_in: U256,
+        reserve_out: U256,
+        fee_ppm: u32,
+    ) -> U256 {
+        let after = self.replay(frontrun_amount, victims, reserve_in, reserve_out, fee_ppm);
+
+        // The backrun swaps the other way: the token out is what it pays in
+        let (amount, _, _) = uni::get_amount_in_with_fee(target_out, after.reserve_out, after.reserve_in, fee_ppm);
+        amount
     }
 
     fn simulate_sandwich_profit(
         &self,
         frontrun_amount: U256,
-        victim_amount: U256,
+        victims: &[VictimSwap],
         reserve_in: U256,
         reserve_out: U256,
-    ) -> (U256, U256) {
-        // Step 1: Frontrun transaction
-        let (frontrun_out, new_reserve_in, new_reserve_out) = uni::get_amount_out(
-            frontrun_amount,
-            reserve_in,
-            reserve_out,
-        );
+        fee_ppm: u32,
+    ) -> U256 {
+        // Frontrun, then the victims in bundle order
+        let after = self.replay(frontrun_amount, victims, reserve_in, reserve_out, fee_ppm);
         
-        // Step 2: Victim transaction
-        let (_, new_reserve_in_2, new_reserve_out_2) = uni::get_amount_out(
-            victim_amount,
-            new_reserve_in,
-            new_reserve_out,
-        );
-        
-        // Step 3: Backrun transaction (sell back)
-        let (backrun_out, _, _) = uni::get_amount_out(
-            frontrun_out,
-            new_reserve_out_2,
-            new_reserve_in_2,
+        // Backrun transaction (sell back)
+        let (backrun_out, _, _) = uni::get_amount_out_with_fee(
+            after.frontrun_out,
+            after.reserve_out,
+            after.reserve_in,
+            fee_ppm,
         );
         
         // Calculate profit
-        let profit = if backrun_out > frontrun_amount {
+        if backrun_out > frontrun_amount {
             backrun_out - frontrun_amount
         } else {
             U256::from(0)
-        };
-        
-        let gas_cost = U256::from(300000) * U256::from(50) * U256::from(10).pow(U256::from(9));
-        
-        (profit, gas_cost)
+        }
     }
 
     fn build_frontrun_tx(
         &self,
-        _token_in: Address,
-        _token_out: Address,
-        _amount: U256,
-        _victim_tx: &Transaction,
+        pool: &SandwichPool,
+        amount: U256,
+        funding: Funding,
+        victim_tx: &Transaction,
+        deadline: U256,
     ) -> TypedTransaction {
-        // Build the frontrun transaction
+        // Build the frontrun transaction; a borrowed frontrun spends the loan, not our ETH
+        let value = match funding {
+            Funding::OwnCapital if pool.token_in == self.get_weth_address() => amount,
+            _ => U256::zero(),
+        };
+        // Ours is the first swap in the bundle, so the current reserves price it
+        // exactly (on V3, as long as it stays within the in-range liquidity)
+        let (amount_out, _, _) = uni::get_amount_out_with_fee(amount, pool.reserve_in, pool.reserve_out, pool.fee_ppm);
+        let recipient = self.config.http.address();
+        let data = match pool.router {
+            LegRouter::V2(_) => {
+                let path = vec![pool.token_in, pool.token_out];
+                v2_leg_calldata(amount, amount_out, path, !value.is_zero(), recipient, deadline)
+            }
+            // The V3 router wraps ETH sent with a WETH input itself
+            LegRouter::V3(_) => v3_leg_calldata(pool.token_in, pool.token_out, pool.fee_ppm, amount, amount_out, recipient, deadline),
+        };
+
         let mut tx = TypedTransaction::default();
-        tx.set_to(_victim_tx.to.unwrap())
-            .set_value(_amount)
+        tx.set_to(pool.router.address())
+            .set_value(value)
+            .set_data(data)
             .set_gas(U256::from(300000))
-            .set_gas_price(self.calculate_frontrun_gas_price(_victim_tx));
-        
+            .set_gas_price(self.calculate_frontrun_gas_price(victim_tx));
         tx
     }
 
+    /// Sells `amount` of the token the frontrun bought, for at least `min_out` of the
+    /// input token back.
     fn build_backrun_tx(
         &self,
-        _token_in: Address,
-        _token_out: Address,
-        _amount: U256,
-        _victim_tx: &Transaction,
+        pool: &SandwichPool,
+        amount: U256,
+        min_out: U256,
+        victim_tx: &Transaction,
+        deadline: U256,
     ) -> TypedTransaction {
-        // Build the backrun transaction
+        let recipient = self.config.http.address();
+        let data = match pool.router {
+            LegRouter::V2(_) => v2_leg_calldata(amount, min_out, vec![pool.token_out, pool.token_in], false, recipient, deadline),
+            LegRouter::V3(_) => v3_leg_calldata(pool.token_out, pool.token_in, pool.fee_ppm, amount, min_out, recipient, deadline),
+        };
+
         let mut tx = TypedTransaction::default();
-        tx.set_to(_victim_tx.to.unwrap())
+        tx.set_to(pool.router.address())
+            .set_data(data)
             .set_gas(U256::from(300000))
-            .set_gas_price(self.calculate_backrun_gas_price(_victim_tx));
-        
+            .set_gas_price(self.calculate_backrun_gas_price(victim_tx));
         tx
     }
 
-    fn get_pair_address(&self, _token0: Address, _token1: Address) -> Address {
-        // Calculate Uniswap V2 pair address
-        // In production, this should use CREATE2 calculation
-        Address::zero() // Placeholder
+    /// Pair address of `token0` and `token1` on `dex`, derived via CREATE2 with
+    /// that DEX's own factory and init code hash.
+    fn get_pair_address(&self, dex: DexType, token0: Address, token1: Address) -> Option<Address> {
+        let (factory, init_code_hash) = self.dexes.pair_derivation(dex)?;
+        Some(self.pair_cache.pair_address(factory, init_code_hash, token0, token1))
+    }
+
+    /// DEX a V2 swap trades on: its router's, or the chain's default V2 DEX when
+    /// it goes through an aggregator or the Universal Router.
+    fn victim_dex(&self, tx: &Transaction) -> DexType {
+        tx.to.and_then(|router| self.dexes.dex_of_router(router)).unwrap_or_else(|| self.default_dex())
+    }
+
+    fn default_dex(&self) -> DexType {
+        self.dexes.dex_of_factory(self.config.chain.v2_factory).unwrap_or(DexType::UniswapV2)
+    }
+
+    /// The pair `victim_tx` swaps `token_in` for `token_out` through, and the
+    /// router of the same DEX for our legs to trade it.
+    fn v2_venue(&self, victim_tx: &Transaction, token_in: Address, token_out: Address) -> Option<(Address, Address)> {
+        let dex = self.victim_dex(victim_tx);
+        let router = self.dexes.router(dex).unwrap_or(self.config.chain.v2_router);
+        Some((self.get_pair_address(dex, token_in, token_out)?, router))
+    }
+
+    /// Pre-positions for an imminent swap of `token` against WETH (e.g. after seeing its approval).
+    pub fn prewarm(&self, token: Address) {
+        self.get_pair_address(self.default_dex(), token, self.get_weth_address());
+    }
+
+    fn get_weth_address(&self) -> Address {
+        self.config.chain.weth
     }
 
-    async fn get_reserves(&self, _pool: Address) -> Option<(U256, U256)> {
-        // Get pool reserves from chain
-        // In production, this should call the pool contract
-        Some((U256::from(1000000), U256::from(2000000))) // Placeholder
+    /// `(reserve_in, reserve_out)` of `pool` for a swap selling `token_in`, at the current block.
+    async fn get_reserves(&self, pool: Address, token_in: Address) -> Option<(U256, U256)> {
+        let reserves = self.config.pool_cache.reserves(pool).await?;
+        Some(reserves.oriented(token_in))
     }
 
     async fn get_current_block(&self) -> U64 {
-        self.config.http.get_block_number().await.unwrap_or_default()
+        self.config.block_height.get()
     }
 
-    fn calculate_priority(&self, sandwich: &OptimalSandwich) -> u8 {
+    fn calculate_priority(&self, profit_eth: U256) -> u8 {
         // Higher profit = higher priority
-        if sandwich.profit > U256::from(10).pow(U256::from(18)) {
+        if profit_eth > U256::from(10).pow(U256::from(18)) {
             10
-        } else if sandwich.profit > U256::from(5) * U256::from(10).pow(U256::from(17)) {
+        } else if profit_eth > U256::from(5) * U256::from(10).pow(U256::from(17)) {
             8
         } else {
             5
@@ -330,11 +1044,431 @@ impl SandwichStrategy {
     }
 }
 
+/// V2 router calldata for one sandwich leg swapping `amount` along `path` to
+/// `recipient`, paid in ETH when `eth_in`.
+fn v2_leg_calldata(amount: U256, amount_out_min: U256, path: Vec<Address>, eth_in: bool, recipient: Address, deadline: U256) -> Bytes {
+    if eth_in {
+        SwapExactETHForTokensCall { amount_out_min, path, to: recipient, deadline }.encode().into()
+    } else {
+        SwapExactTokensForTokensCall { amount_in: amount, amount_out_min, path, to: recipient, deadline }.encode().into()
+    }
+}
+
+/// V3 `SwapRouter` calldata for one sandwich leg selling `amount` of `token_in`
+/// on the `fee` tier pool, to `recipient`.
+fn v3_leg_calldata(
+    token_in: Address,
+    token_out: Address,
+    fee: u32,
+    amount: U256,
+    amount_out_min: U256,
+    recipient: Address,
+    deadline: U256,
+) -> Bytes {
+    let params = ExactInputSingleParams {
+        token_in,
+        token_out,
+        fee,
+        recipient,
+        deadline,
+        amount_in: amount,
+        amount_out_minimum: amount_out_min,
+        sqrt_price_limit_x96: U256::zero(),
+    };
+    ExactInputSingleCall { params }.encode().into()
+}
+
 #[derive(Debug)]
 struct OptimalSandwich {
     frontrun_amount: U256,
     backrun_amount: U256,
+    /// In the pool's input token.
     profit: U256,
     gas_cost: U256,
     price_impact: f64,
-} 
\ No newline at end of file
+}
+
+#[derive(Debug)]
+struct MultiVictimConfig {
+    enabled: bool,
+    max_victims: usize,
+}
+
+/// A decoded victim swap, as sized against the pool.
+#[derive(Debug, Clone)]
+struct VictimSwap {
+    tx: Transaction,
+    /// Exact input, or for exact-output swaps the most the victim will pay.
+    amount_in: U256,
+    /// Least the victim accepts, or for exact-output swaps the exact amount bought.
+    amount_out_min: U256,
+    /// Buys exactly `amount_out_min`, paying whatever that costs up to `amount_in`.
+    exact_output: bool,
+}
+
+/// Pool state after our frontrun and the victims.
+#[derive(Debug)]
+struct SandwichReplay {
+    frontrun_out: U256,
+    /// Every victim still fills outside the safety margin.
+    victims_fill: bool,
+    reserve_in: U256,
+    reserve_out: U256,
+}
+
+#[derive(Debug)]
+struct SandwichPool {
+    address: Address,
+    token_in: Address,
+    token_out: Address,
+    reserve_in: U256,
+    reserve_out: U256,
+    /// Swap fee in hundredths of a bip.
+    fee_ppm: u32,
+    /// Router our legs swap through.
+    router: LegRouter,
+}
+
+/// Router of a sandwiched pool, by the calldata our legs send it.
+#[derive(Debug, Clone, Copy)]
+enum LegRouter {
+    /// A V2 router, swapping along `[token_in, token_out]`.
+    V2(Address),
+    /// The V3 `SwapRouter`, swapping on the pool's fee tier.
+    V3(Address),
+}
+
+impl LegRouter {
+    fn address(self) -> Address {
+        match self {
+            Self::V2(router) | Self::V3(router) => router,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ethers::abi::AbiDecode;
+
+    use super::*;
+    use crate::address_book::{
+        AddLiquidityETHCall, SwapETHForExactTokensCall, SwapTokensForExactETHCall, SwapTokensForExactTokensCall,
+    };
+    use crate::pool_cache::PoolReserves;
+    use crate::test_support::{self, MockNode};
+    use serde_json::json;
+
+    fn path() -> Vec<Address> {
+        vec![Address::from_low_u64_be(1), Address::from_low_u64_be(2)]
+    }
+
+    fn eth(n: u64) -> U256 {
+        U256::from(n) * U256::exp10(18)
+    }
+
+    async fn strategy(node: &MockNode, settings: crate::settings::Settings) -> SandwichStrategy {
+        let config = test_support::config(&node.url(), settings).await;
+        SandwichStrategy::new(Arc::new(config), Arc::new(CompetitionTracker::new(false, U256::zero())))
+    }
+
+    /// A WETH -> token pool on the chain's V2 router.
+    fn pool(strategy: &SandwichStrategy, reserve_in: U256, reserve_out: U256) -> SandwichPool {
+        SandwichPool {
+            address: Address::from_low_u64_be(0x9a1),
+            token_in: strategy.get_weth_address(),
+            token_out: Address::from_low_u64_be(2),
+            reserve_in,
+            reserve_out,
+            fee_ppm: V2_FEE_PPM,
+            router: LegRouter::V2(strategy.config.chain.v2_router),
+        }
+    }
+
+    /// A victim selling `amount_in` into `pool` that accepts `slippage_bps` below
+    /// what the untouched pool pays.
+    fn victim(hash: u64, pool: &SandwichPool, amount_in: U256, slippage_bps: u64) -> VictimSwap {
+        let (expected, _, _) = uni::get_amount_out_with_fee(amount_in, pool.reserve_in, pool.reserve_out, pool.fee_ppm);
+        VictimSwap {
+            tx: Transaction { hash: H256::from_low_u64_be(hash), ..Default::default() },
+            amount_in,
+            amount_out_min: expected * (10_000 - slippage_bps) / 10_000,
+            exact_output: false,
+        }
+    }
+
+    /// The best own-capital sandwich on `pool` around `victims`, frontrun capped as configured.
+    fn size(strategy: &SandwichStrategy, victims: &[VictimSwap], pool: &SandwichPool) -> OptimalSandwich {
+        let max_frontrun = uni::max_frontrun(pool.reserve_in, strategy.max_frontrun_reserve_bps);
+        strategy.calculate_optimal_sandwich(victims, pool, max_frontrun, None, strategy.config.gas_model.sandwich_gas(false))
+    }
+
+    /// Caches `token`'s WETH pair reserves on the default V2 DEX at the current block.
+    fn cache_reserves(strategy: &SandwichStrategy, token: Address, reserve_weth: U256, reserve_token: U256) {
+        let weth = strategy.get_weth_address();
+        let pair = strategy.get_pair_address(strategy.default_dex(), weth, token).unwrap();
+        let reserves = if token < weth {
+            PoolReserves { token0: token, reserve0: reserve_token, reserve1: reserve_weth }
+        } else {
+            PoolReserves { token0: weth, reserve0: reserve_weth, reserve1: reserve_token }
+        };
+        strategy.config.pool_cache.insert(pair, strategy.config.block_height.get(), reserves);
+    }
+
+    /// A `swapExactETHForTokens` of `value` into `token` through the V2 router.
+    fn eth_in_swap(strategy: &SandwichStrategy, value: U256, amount_out_min: U256, token: Address, deadline: U256) -> Transaction {
+        let call = SwapExactETHForTokensCall {
+            amount_out_min,
+            path: vec![strategy.get_weth_address(), token],
+            to: Address::from_low_u64_be(0xb0b),
+            deadline,
+        };
+        Transaction {
+            hash: H256::from_low_u64_be(0x5a5),
+            to: Some(strategy.config.chain.v2_router),
+            value,
+            input: call.encode().into(),
+            gas_price: Some(U256::exp10(10)),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn v2_deadline_reads_every_swap_but_not_liquidity_calls() {
+        let deadline = U256::from(1_700_000_000u64);
+        let exact_in = UniV2RouterCalls::SwapExactETHForTokens(SwapExactETHForTokensCall {
+            amount_out_min: U256::one(),
+            path: path(),
+            to: Address::zero(),
+            deadline,
+        });
+        let exact_out = UniV2RouterCalls::SwapTokensForExactTokens(SwapTokensForExactTokensCall {
+            amount_out: U256::one(),
+            amount_in_max: U256::MAX,
+            path: path(),
+            to: Address::zero(),
+            deadline,
+        });
+        let add_liquidity = UniV2RouterCalls::AddLiquidityETH(AddLiquidityETHCall {
+            token: Address::from_low_u64_be(2),
+            amount_token_desired: U256::one(),
+            amount_token_min: U256::one(),
+            amount_eth_min: U256::one(),
+            to: Address::zero(),
+            deadline,
+        });
+
+        assert_eq!(SandwichStrategy::v2_deadline(&exact_in), Some(deadline));
+        assert_eq!(SandwichStrategy::v2_deadline(&exact_out), Some(deadline));
+        assert_eq!(SandwichStrategy::v2_deadline(&add_liquidity), None);
+    }
+
+    #[test]
+    fn exact_output_swaps_decode_to_what_they_buy_and_the_most_they_pay() {
+        let deadline = U256::from(1_700_000_000u64);
+        let (amount_out, amount_in_max) = (U256::from(5_000), eth(2));
+        let decode = |calldata: Vec<u8>, value: U256| {
+            let call = UniV2RouterCalls::decode(calldata).unwrap();
+            SandwichStrategy::v2_exact_output(&Transaction { value, ..Default::default() }, &call)
+        };
+
+        let eth_in = SwapETHForExactTokensCall { amount_out, path: path(), to: Address::zero(), deadline };
+        assert_eq!(decode(eth_in.encode(), amount_in_max), Some((path(), amount_out, amount_in_max)));
+
+        let eth_out = SwapTokensForExactETHCall { amount_out, amount_in_max, path: path(), to: Address::zero(), deadline };
+        assert_eq!(decode(eth_out.encode(), U256::zero()), Some((path(), amount_out, amount_in_max)));
+
+        let tokens = SwapTokensForExactTokensCall { amount_out, amount_in_max, path: path(), to: Address::zero(), deadline };
+        assert_eq!(decode(tokens.encode(), U256::zero()), Some((path(), amount_out, amount_in_max)));
+
+        let exact_in = SwapExactETHForTokensCall { amount_out_min: amount_out, path: path(), to: Address::zero(), deadline };
+        assert_eq!(decode(exact_in.encode(), amount_in_max), None);
+    }
+
+    #[test]
+    fn v2_leg_calldata_carries_the_deadline() {
+        let deadline = U256::from(1_700_000_012u64);
+        let recipient = Address::from_low_u64_be(7);
+
+        let eth_in = v2_leg_calldata(U256::exp10(18), U256::one(), path(), true, recipient, deadline);
+        let call = SwapExactETHForTokensCall::decode(&eth_in).unwrap();
+        assert_eq!((call.to, call.deadline), (recipient, deadline));
+
+        let token_in = v2_leg_calldata(U256::exp10(18), U256::one(), path(), false, recipient, deadline);
+        let call = SwapExactTokensForTokensCall::decode(&token_in).unwrap();
+        assert_eq!((call.amount_in, call.deadline), (U256::exp10(18), deadline));
+    }
+
+    #[tokio::test]
+    async fn v3_swaps_are_sandwiched_on_the_victims_fee_tier() {
+        let node = MockNode::new().await;
+        let swap = uni_v3::RouterSwap {
+            tokens: path(),
+            fees: vec![500],
+            amount_in: eth(1),
+            amount_out: U256::one(),
+            exact_output: false,
+            deadline: None,
+        };
+        assert_eq!(strategy(&node, test_support::settings()).await.v3_fee_tier(&swap), 500);
+
+        let forced = crate::settings::Settings { v3_sandwich_fee_tier: Some(3000), ..test_support::settings() };
+        assert_eq!(strategy(&node, forced).await.v3_fee_tier(&swap), 3000);
+    }
+
+    #[tokio::test]
+    async fn v3_legs_swap_through_the_v3_router_on_the_pools_fee_tier() {
+        let node = MockNode::new().await;
+        let strategy = strategy(&node, test_support::settings()).await;
+        let router = strategy.config.chain.v3_router.unwrap();
+        let pool = SandwichPool { fee_ppm: 500, router: LegRouter::V3(router), ..pool(&strategy, eth(1_000), eth(2_000_000)) };
+        let deadline = U256::from(1_700_000_012u64);
+        let victim = Transaction::default();
+
+        let frontrun = strategy.build_frontrun_tx(&pool, eth(2), Funding::OwnCapital, &victim, deadline);
+        assert_eq!(frontrun.to_addr(), Some(&router));
+        assert_eq!(frontrun.value(), Some(&eth(2)));
+        let call = ExactInputSingleCall::decode(frontrun.data().unwrap()).unwrap().params;
+        let (expected_out, _, _) = uni::get_amount_out_with_fee(eth(2), pool.reserve_in, pool.reserve_out, 500);
+        assert_eq!((call.token_in, call.token_out, call.fee), (pool.token_in, pool.token_out, 500));
+        assert_eq!((call.amount_in, call.amount_out_minimum, call.deadline), (eth(2), expected_out, deadline));
+
+        let backrun = strategy.build_backrun_tx(&pool, expected_out, eth(2), &victim, deadline);
+        assert_eq!(backrun.to_addr(), Some(&router));
+        let call = ExactInputSingleCall::decode(backrun.data().unwrap()).unwrap().params;
+        assert_eq!((call.token_in, call.token_out, call.fee), (pool.token_out, pool.token_in, 500));
+        assert_eq!((call.amount_in, call.amount_out_minimum), (expected_out, eth(2)));
+        assert_eq!(call.recipient, strategy.config.http.address());
+    }
+
+    #[tokio::test]
+    async fn sized_frontrun_leaves_the_victim_its_safety_margin_above_amount_out_min() {
+        let node = MockNode::new().await;
+        let margined = strategy(&node, crate::settings::Settings { victim_safety_margin_bps: 100, ..test_support::settings() }).await;
+        let pool = pool(&margined, eth(1_000), eth(2_000_000));
+        let victim = victim(1, &pool, eth(20), 300);
+
+        let frontrun = size(&margined, std::slice::from_ref(&victim), &pool).frontrun_amount;
+        assert!(!frontrun.is_zero());
+        let victim_receives = |frontrun: U256| {
+            let (_, reserve_in, reserve_out) = uni::get_amount_out_with_fee(frontrun, pool.reserve_in, pool.reserve_out, pool.fee_ppm);
+            uni::get_amount_out_with_fee(victim.amount_in, reserve_in, reserve_out, pool.fee_ppm).0
+        };
+        assert!(victim_receives(frontrun) >= victim.amount_out_min * 10_100 / 10_000);
+
+        // Without the margin the same victim is squeezed harder
+        let unmargined = strategy(&node, crate::settings::Settings { victim_safety_margin_bps: 0, ..test_support::settings() }).await;
+        assert!(size(&unmargined, std::slice::from_ref(&victim), &pool).frontrun_amount > frontrun);
+    }
+
+    #[tokio::test]
+    async fn tight_victim_slippage_forces_a_smaller_frontrun() {
+        let node = MockNode::new().await;
+        let strategy = strategy(&node, test_support::settings()).await;
+        let pool = pool(&strategy, eth(1_000), eth(2_000_000));
+
+        let loose = size(&strategy, &[victim(1, &pool, eth(20), 500)], &pool).frontrun_amount;
+        let tight = size(&strategy, &[victim(1, &pool, eth(20), 150)], &pool).frontrun_amount;
+        assert!(!tight.is_zero());
+        assert!(tight < loose);
+
+        // Under the safety margin there's no room left at all
+        assert!(size(&strategy, &[victim(1, &pool, eth(20), 40)], &pool).frontrun_amount.is_zero());
+    }
+
+    #[tokio::test]
+    async fn an_eth_in_victims_tx_value_sizes_the_frontrun() {
+        let mut node = MockNode::new().await;
+        node.rpc("eth_getBalance", json!(eth(1_000))).await;
+        let token = Address::from_low_u64_be(2);
+
+        let mut frontruns = Vec::new();
+        for value in [eth(5), eth(20)] {
+            let strategy = strategy(&node, test_support::settings()).await;
+            cache_reserves(&strategy, token, eth(100), eth(200_000));
+            // The calldata holds only the minimum out; what's swapped is the ETH sent
+            let amount_out_min = victim(0, &pool(&strategy, eth(100), eth(200_000)), value, 300).amount_out_min;
+            let tx = eth_in_swap(&strategy, value, amount_out_min, token, U256::MAX);
+
+            let opportunities = strategy.analyze(&tx, &classify(&tx), &DecisionTrace::disabled()).await;
+            let Some(StrategyType::Sandwich(details)) = opportunities.first().map(|op| &op.strategy_type) else {
+                panic!("no sandwich around a {} ETH victim", ethers::utils::format_ether(value));
+            };
+            assert_eq!(details.victim_amount_in, value);
+            frontruns.push(details.optimal_amount);
+        }
+        assert!(!frontruns[0].is_zero());
+        assert!(frontruns[0] < frontruns[1]);
+    }
+
+    #[tokio::test]
+    async fn a_victim_whose_deadline_passes_before_our_block_is_skipped() {
+        let mut node = MockNode::new().await;
+        node.rpc("eth_getBalance", json!(eth(1_000))).await;
+        let token = Address::from_low_u64_be(2);
+        let strategy = strategy(&node, test_support::settings()).await;
+        cache_reserves(&strategy, token, eth(100), eth(200_000));
+        let head = 1_700_000_000u64;
+        strategy.config.block_height.advance_timestamp(head);
+        let amount_out_min = victim(0, &pool(&strategy, eth(100), eth(200_000)), eth(20), 300).amount_out_min;
+
+        // Expires before the next block, 12s after the head
+        let expiring = eth_in_swap(&strategy, eth(20), amount_out_min, token, U256::from(head + 5));
+        let trace = DecisionTrace::enabled(expiring.hash);
+        assert!(strategy.analyze(&expiring, &classify(&expiring), &trace).await.is_empty());
+        assert!(trace.steps().iter().any(|step| matches!(step, TraceStep::Gate { name: "victim_deadline", passed: false })));
+
+        let live = eth_in_swap(&strategy, eth(20), amount_out_min, token, U256::from(head + 60));
+        assert!(!strategy.analyze(&live, &classify(&live), &DecisionTrace::disabled()).await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn a_short_own_balance_funds_the_sandwich_with_a_flash_loan() {
+        let settings = || crate::settings::Settings {
+            sandwich_flashloan: Some(FlashloanProvider::Balancer),
+            ..test_support::settings()
+        };
+        let funding = |node: MockNode| async move {
+            let strategy = strategy(&node, settings()).await;
+            let pool = pool(&strategy, eth(1_000), eth(2_000_000));
+            let victims = [victim(1, &pool, eth(20), 300)];
+            strategy.choose_funding(&victims, &pool).await.map(|(_, funding, _)| funding)
+        };
+
+        let mut short = MockNode::new().await;
+        short.rpc("eth_getBalance", json!(U256::exp10(16))).await;
+        assert!(matches!(funding(short).await, Some(Funding::Flashloan { provider: FlashloanProvider::Balancer, .. })));
+
+        // Holding enough, our own ETH skips the loan's fee and extra gas
+        let mut flush = MockNode::new().await;
+        flush.rpc("eth_getBalance", json!(eth(1_000))).await;
+        assert!(matches!(funding(flush).await, Some(Funding::OwnCapital)));
+    }
+
+    #[tokio::test]
+    async fn three_victims_in_one_sandwich_beat_three_separate_sandwiches() {
+        let node = MockNode::new().await;
+        let strategy = strategy(&node, test_support::settings()).await;
+        let pool = pool(&strategy, eth(1_000), eth(2_000_000));
+        // Each too small on its own to pay back the fees of a round trip through the pool
+        let victims: Vec<VictimSwap> = (1..=3).map(|n| victim(n, &pool, eth(3), 500)).collect();
+
+        // One frontrun and one backrun around all three, paying gas once
+        let together = size(&strategy, &victims, &pool);
+        let net = |sandwich: &OptimalSandwich| sandwich.profit.saturating_sub(sandwich.gas_cost);
+
+        // Three sandwiches in a row, each on the pool the one before it left behind
+        let mut apart = U256::zero();
+        let mut current = SandwichPool { ..pool };
+        for victim in &victims {
+            let sandwich = size(&strategy, std::slice::from_ref(victim), &current);
+            apart += net(&sandwich);
+            let after = strategy.replay(sandwich.frontrun_amount, std::slice::from_ref(victim), current.reserve_in, current.reserve_out, current.fee_ppm);
+            let (_, reserve_out, reserve_in) =
+                uni::get_amount_out_with_fee(sandwich.backrun_amount, after.reserve_out, after.reserve_in, current.fee_ppm);
+            current = SandwichPool { reserve_in, reserve_out, ..current };
+        }
+
+        assert!(!net(&together).is_zero());
+        assert!(net(&together) > apart, "together {} vs apart {}", net(&together), apart);
+    }
+}
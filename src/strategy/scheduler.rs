@@ -0,0 +1,267 @@
+use std::cmp::{Ordering, Reverse};
+use std::collections::BinaryHeap;
+use std::sync::Mutex;
+
+use ethers::prelude::*;
+
+use super::types::{weight_by_probability, MEVOpportunity, StrategyType};
+use crate::helpers::eth_to_wei;
+use crate::settings::Settings;
+
+/// Most one block's dispatches may spend; `None` leaves that side unlimited.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SchedulerBudget {
+    /// Own funds tied up by the block's opportunities, see `MEVOpportunity::capital`.
+    pub capital: Option<U256>,
+    /// Gas cost of the block's opportunities, in wei.
+    pub gas: Option<U256>,
+}
+
+#[derive(Debug)]
+struct Queued {
+    /// Profit weighted by the opportunity's landing probability.
+    expected_value: U256,
+    seq: u64,
+    opportunity: MEVOpportunity,
+}
+
+impl Queued {
+    /// Highest expected value first, then the soonest to expire, then the oldest.
+    fn rank(&self) -> (U256, Reverse<U64>, Reverse<u64>) {
+        (self.expected_value, Reverse(self.opportunity.expiry_block), Reverse(self.seq))
+    }
+}
+
+impl PartialEq for Queued {
+    fn eq(&self, other: &Self) -> bool {
+        self.rank() == other.rank()
+    }
+}
+
+impl Eq for Queued {}
+
+impl PartialOrd for Queued {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Queued {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.rank().cmp(&other.rank())
+    }
+}
+
+/// What has gone out for the current block.
+#[derive(Debug, Default)]
+struct Dispatched {
+    block: U64,
+    opportunities: Vec<MEVOpportunity>,
+    capital: U256,
+    gas: U256,
+}
+
+#[derive(Debug, Default)]
+struct Queue {
+    heap: BinaryHeap<Queued>,
+    dispatched: Dispatched,
+    next_seq: u64,
+}
+
+/// Queues opportunities across pending txs and dispatches every one per block
+/// that fits the budget and doesn't conflict with one already dispatched,
+/// rather than only the best of each tx.
+#[derive(Debug)]
+pub struct OpportunityScheduler {
+    budget: SchedulerBudget,
+    queue: Mutex<Queue>,
+}
+
+impl OpportunityScheduler {
+    pub fn new(budget: SchedulerBudget) -> Self {
+        Self {
+            budget,
+            queue: Mutex::new(Queue::default()),
+        }
+    }
+
+    pub fn from_settings(settings: &Settings) -> Self {
+        Self::new(SchedulerBudget {
+            capital: settings.risk.scheduler_capital_budget_eth.map(eth_to_wei),
+            gas: settings.risk.scheduler_gas_budget_eth.map(eth_to_wei),
+        })
+    }
+
+    /// Queues `opportunity`, ranked by `profit` (its net or settled profit)
+    /// weighted by its landing probability.
+    pub fn schedule(&self, opportunity: MEVOpportunity, profit: U256) {
+        let expected_value = weight_by_probability(profit, opportunity.landing_probability);
+        let mut queue = self.queue.lock().unwrap();
+        let seq = queue.next_seq;
+        queue.next_seq += 1;
+        queue.heap.push(Queued { expected_value, seq, opportunity });
+    }
+
+    pub fn len(&self) -> usize {
+        self.queue.lock().unwrap().heap.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Drops queued opportunities built around `victim`, e.g. once it's been
+    /// replaced. Returns how many were queued or already dispatched this block.
+    pub fn cancel_victim(&self, victim: H256) -> usize {
+        let mut queue = self.queue.lock().unwrap();
+        let queued = queue.heap.len();
+        queue.heap.retain(|queued| !involves(&queued.opportunity, victim));
+        let dispatched = queue.dispatched.opportunities.iter().filter(|opportunity| involves(opportunity, victim)).count();
+        queued - queue.heap.len() + dispatched
+    }
+
+    /// Takes the opportunities to submit now that the chain is at `block`, best
+    /// first. Expired ones are dropped; ones that conflict with an opportunity
+    /// already dispatched for this block, or that would overrun its budget, stay
+    /// queued for a later block.
+    pub fn dispatch(&self, block: U64) -> Vec<MEVOpportunity> {
+        let mut queue = self.queue.lock().unwrap();
+        if block > queue.dispatched.block {
+            queue.dispatched = Dispatched { block, ..Default::default() };
+        }
+        // The target block is the one after `block`; anything expiring by then is stale
+        queue.heap.retain(|queued| queued.opportunity.expiry_block > block);
+
+        let mut ready = Vec::new();
+        let mut deferred = Vec::new();
+        while let Some(queued) = queue.heap.pop() {
+            let dispatched = &mut queue.dispatched;
+            let clashes = dispatched.opportunities.iter().any(|other| conflicts(other, &queued.opportunity));
+            let capital = dispatched.capital.saturating_add(queued.opportunity.capital());
+            let gas = dispatched.gas.saturating_add(queued.opportunity.gas_cost);
+            let fits = self.budget.capital.is_none_or(|budget| capital <= budget)
+                && self.budget.gas.is_none_or(|budget| gas <= budget);
+
+            if clashes || !fits {
+                deferred.push(queued);
+                continue;
+            }
+            dispatched.capital = capital;
+            dispatched.gas = gas;
+            dispatched.opportunities.push(queued.opportunity.clone());
+            ready.push(queued.opportunity);
+        }
+        queue.heap.extend(deferred);
+        ready
+    }
+}
+
+fn involves(opportunity: &MEVOpportunity, tx: H256) -> bool {
+    match &opportunity.strategy_type {
+        StrategyType::Sandwich(details) => {
+            details.victim_tx.hash == tx || details.co_victims.iter().any(|victim| victim.hash == tx)
+        }
+        _ => opportunity.victim().is_some_and(|(victim, _)| victim == tx),
+    }
+}
+
+/// Same victim trade, or victims in the same sender's nonce lane.
+fn conflicts(a: &MEVOpportunity, b: &MEVOpportunity) -> bool {
+    a.conflicts_with(b) || (a.nonce_lane().is_some() && a.nonce_lane() == b.nonce_lane())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Instant;
+
+    use ethers::types::transaction::eip2718::TypedTransaction;
+
+    use super::*;
+    use crate::strategy::types::{ArbitrageDetails, BackrunDetails};
+
+    fn opportunity(strategy_type: StrategyType, gas_cost: u64, expiry_block: u64) -> MEVOpportunity {
+        MEVOpportunity {
+            id: format!("{}-{}", strategy_type.name(), expiry_block),
+            target_tx: Transaction::default(),
+            strategy_type,
+            estimated_profit: U256::from(1_000),
+            gas_cost: U256::from(gas_cost),
+            priority: 0,
+            reserve_block: U64::zero(),
+            expiry_block: U64::from(expiry_block),
+            observed_at: Instant::now(),
+            landing_probability: 1.0,
+        }
+    }
+
+    /// A backrun of a victim tx `hash` sent by `from`.
+    fn backrun(hash: u64, from: u64) -> StrategyType {
+        let victim_tx = Transaction { hash: H256::from_low_u64_be(hash), from: Address::from_low_u64_be(from), ..Default::default() };
+        StrategyType::Backrun(BackrunDetails { victim_tx, backrun_tx: TypedTransaction::default() })
+    }
+
+    fn arbitrage(amount_in: u64) -> StrategyType {
+        StrategyType::Arbitrage(ArbitrageDetails {
+            path: Vec::new(),
+            pools: Vec::new(),
+            amount_in: U256::from(amount_in),
+            expected_profit: U256::zero(),
+            gas_estimate: U256::zero(),
+            router: None,
+        })
+    }
+
+    #[test]
+    fn dispatches_best_first_and_drops_expired() {
+        let scheduler = OpportunityScheduler::new(SchedulerBudget::default());
+        scheduler.schedule(opportunity(backrun(1, 1), 0, 12), U256::from(10));
+        scheduler.schedule(opportunity(backrun(2, 2), 0, 11), U256::from(30));
+        scheduler.schedule(opportunity(backrun(3, 3), 0, 10), U256::from(50));
+
+        let ready = scheduler.dispatch(U64::from(10));
+        let victims: Vec<_> = ready.iter().map(|op| op.victim().unwrap().0).collect();
+        assert_eq!(victims, vec![H256::from_low_u64_be(2), H256::from_low_u64_be(1)]);
+        assert!(scheduler.is_empty());
+    }
+
+    #[test]
+    fn defers_what_conflicts_with_this_block_until_the_next() {
+        let scheduler = OpportunityScheduler::new(SchedulerBudget::default());
+        scheduler.schedule(opportunity(backrun(1, 7), 0, 20), U256::from(50));
+        // Another victim in the same sender's nonce lane
+        scheduler.schedule(opportunity(backrun(2, 7), 0, 20), U256::from(10));
+
+        assert_eq!(scheduler.dispatch(U64::from(10)).len(), 1);
+        assert_eq!(scheduler.len(), 1);
+        assert!(scheduler.dispatch(U64::from(10)).is_empty());
+        assert_eq!(scheduler.dispatch(U64::from(11)).len(), 1);
+    }
+
+    #[test]
+    fn keeps_each_block_within_its_budget() {
+        let budget = SchedulerBudget { capital: Some(U256::from(100)), gas: Some(U256::from(10)) };
+        let scheduler = OpportunityScheduler::new(budget);
+        scheduler.schedule(opportunity(arbitrage(80), 4, 20), U256::from(50));
+        scheduler.schedule(opportunity(arbitrage(30), 4, 20), U256::from(40));
+        scheduler.schedule(opportunity(arbitrage(20), 4, 20), U256::from(30));
+        scheduler.schedule(opportunity(arbitrage(0), 4, 20), U256::from(20));
+
+        // 80 + 20 capital fits, 30 more doesn't; the third 4-gas one overruns the gas budget
+        let ready = scheduler.dispatch(U64::from(10));
+        let capital: Vec<_> = ready.iter().map(|op| op.capital().as_u64()).collect();
+        assert_eq!(capital, vec![80, 20]);
+        assert_eq!(scheduler.len(), 2);
+    }
+
+    #[test]
+    fn cancel_victim_counts_queued_and_dispatched() {
+        let scheduler = OpportunityScheduler::new(SchedulerBudget::default());
+        scheduler.schedule(opportunity(backrun(1, 1), 0, 20), U256::from(50));
+        scheduler.dispatch(U64::from(10));
+        scheduler.schedule(opportunity(backrun(1, 1), 0, 20), U256::from(40));
+        scheduler.schedule(opportunity(backrun(2, 2), 0, 20), U256::from(30));
+
+        assert_eq!(scheduler.cancel_victim(H256::from_low_u64_be(1)), 2);
+        assert_eq!(scheduler.len(), 1);
+    }
+}
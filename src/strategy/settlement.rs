@@ -0,0 +1,218 @@
+use ethers::abi::AbiEncode;
+use ethers::prelude::*;
+use ethers::types::transaction::eip2718::TypedTransaction;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use crate::address_book::{SwapExactTokensForTokensCall, UniV2Factory, ERC20};
+use crate::chain::ChainConfig;
+use crate::uni;
+use crate::Config;
+
+/// Gas a settlement swap through the V2 router is given.
+const SETTLEMENT_SWAP_GAS: u64 = 200_000;
+
+/// What to do with profit that lands in a token, once the opportunity lands.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SettlementPolicy {
+    /// Keep profit in whatever token it lands in.
+    HoldWeth,
+    /// Swap profit that lands in any other token into WETH.
+    SwapToWeth,
+    /// Swap profit into the chain's USDC, WETH included.
+    SwapToStable,
+}
+
+impl SettlementPolicy {
+    /// Token `profit_token` should be swapped into, if any.
+    pub fn target(&self, profit_token: Address, chain: &ChainConfig) -> Option<Address> {
+        let target = match self {
+            SettlementPolicy::HoldWeth => return None,
+            SettlementPolicy::SwapToWeth => chain.weth,
+            SettlementPolicy::SwapToStable => chain.usdc,
+        };
+        (profit_token != target).then_some(target)
+    }
+}
+
+impl FromStr for SettlementPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "hold_weth" => Ok(SettlementPolicy::HoldWeth),
+            "swap_to_weth" => Ok(SettlementPolicy::SwapToWeth),
+            "swap_to_stable" => Ok(SettlementPolicy::SwapToStable),
+            _ => Err(format!("unknown settlement policy {}, expected hold_weth, swap_to_weth or swap_to_stable", s)),
+        }
+    }
+}
+
+/// A V2 router swap of `amount_in` of `token_in` into `token_out` for `recipient`,
+/// reverting if it returns more than `max_slippage_bps` under `expected_out` or
+/// lands after `deadline`.
+#[derive(Debug, Clone)]
+pub struct SettlementSwap {
+    pub router: Address,
+    pub token_in: Address,
+    pub token_out: Address,
+    pub amount_in: U256,
+    pub expected_out: U256,
+    pub max_slippage_bps: u64,
+    pub recipient: Address,
+    pub deadline: U256,
+}
+
+impl SettlementSwap {
+    pub fn amount_out_min(&self) -> U256 {
+        self.expected_out * (10_000 - self.max_slippage_bps.min(10_000)) / 10_000
+    }
+
+    pub fn tx(&self) -> TypedTransaction {
+        let mut tx = TypedTransaction::default();
+        tx.set_to(self.router)
+            .set_from(self.recipient)
+            .set_data(
+                SwapExactTokensForTokensCall {
+                    amount_in: self.amount_in,
+                    amount_out_min: self.amount_out_min(),
+                    path: vec![self.token_in, self.token_out],
+                    to: self.recipient,
+                    deadline: self.deadline,
+                }
+                .encode()
+                .into(),
+            )
+            .set_gas(SETTLEMENT_SWAP_GAS);
+        tx
+    }
+}
+
+/// Converts profit that landed in a token according to `SETTLEMENT_POLICY`.
+///
+/// The amount converted is how much our balance of the token grew between
+/// submission and inclusion, so profit from other opportunities landing in the
+/// same token meanwhile is swapped along with it.
+#[derive(Debug)]
+pub struct Settlement {
+    config: Arc<Config>,
+    policy: SettlementPolicy,
+    max_slippage_bps: u64,
+}
+
+impl Settlement {
+    pub fn from_config(config: Arc<Config>) -> Self {
+        Self {
+            policy: config.settings.settlement_policy,
+            max_slippage_bps: config.settings.settlement_max_slippage_bps,
+            config,
+        }
+    }
+
+    /// Token profit landing in `profit_token` gets swapped into, if any.
+    pub fn target(&self, profit_token: Address) -> Option<Address> {
+        self.policy.target(profit_token, &self.config.chain)
+    }
+
+    /// Our balance of `token`, to measure realized profit against.
+    pub async fn balance(&self, token: Address) -> Result<U256, Box<dyn std::error::Error + Send + Sync>> {
+        Ok(ERC20::new(token, self.config.http.clone()).balance_of(self.config.http.address()).call().await?)
+    }
+
+    /// Swaps what our `token` balance gained over `before` into the policy's
+    /// target, approving the router first if needed.
+    pub async fn settle(&self, token: Address, before: U256) -> Result<Option<TxHash>, Box<dyn std::error::Error + Send + Sync>> {
+        let Some(target) = self.target(token) else {
+            return Ok(None);
+        };
+        let realized = self.balance(token).await?.saturating_sub(before);
+        if realized.is_zero() {
+            return Ok(None);
+        }
+
+        let chain = &self.config.chain;
+        let pair = UniV2Factory::new(chain.v2_factory, self.config.http.clone()).get_pair(token, target).call().await?;
+        if pair.is_zero() {
+            return Err(format!("no V2 pool to settle {:?} into {:?}", token, target).into());
+        }
+        let (reserve_in, reserve_out) = self
+            .config
+            .pool_cache
+            .reserves(pair)
+            .await
+            .ok_or("settlement pool reserves unavailable")?
+            .oriented(token);
+        let (expected_out, _, _) = uni::get_amount_out(realized, reserve_in, reserve_out);
+
+        let us = self.config.http.address();
+        let erc20 = ERC20::new(token, self.config.http.clone());
+        if erc20.allowance(us, chain.v2_router).call().await? < realized {
+            erc20.approve(chain.v2_router, U256::MAX).send().await?;
+        }
+
+        let mut tx = SettlementSwap {
+            router: chain.v2_router,
+            token_in: token,
+            token_out: target,
+            amount_in: realized,
+            expected_out,
+            max_slippage_bps: self.max_slippage_bps,
+            recipient: us,
+            deadline: self.config.swap_deadline(),
+        }
+        .tx();
+        tx.set_gas_price(self.config.gas_oracle.gas_price(50.0));
+        let pending = self.config.http.send_transaction(tx, None).await?;
+        Ok(Some(pending.tx_hash()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::address_book::UniV2RouterCalls;
+    use ethers::abi::AbiDecode;
+
+    #[test]
+    fn policy_targets_skip_profit_already_in_the_target() {
+        let chain = ChainConfig::mainnet();
+        let token = Address::from_low_u64_be(0x70c);
+        assert_eq!(SettlementPolicy::HoldWeth.target(token, &chain), None);
+        assert_eq!(SettlementPolicy::SwapToWeth.target(token, &chain), Some(chain.weth));
+        assert_eq!(SettlementPolicy::SwapToWeth.target(chain.weth, &chain), None);
+        // WETH profit is swapped too under the stable policy
+        assert_eq!(SettlementPolicy::SwapToStable.target(chain.weth, &chain), Some(chain.usdc));
+        assert_eq!(SettlementPolicy::SwapToStable.target(chain.usdc, &chain), None);
+    }
+
+    #[test]
+    fn policy_parses_from_its_env_names() {
+        assert_eq!("swap_to_stable".parse(), Ok(SettlementPolicy::SwapToStable));
+        assert!("swap_to_usdc".parse::<SettlementPolicy>().is_err());
+    }
+
+    #[test]
+    fn swap_tx_bounds_the_output_by_the_slippage() {
+        let swap = SettlementSwap {
+            router: Address::from_low_u64_be(0x7a11),
+            token_in: Address::from_low_u64_be(1),
+            token_out: Address::from_low_u64_be(2),
+            amount_in: U256::from(1_000),
+            expected_out: U256::from(2_000),
+            max_slippage_bps: 50,
+            recipient: Address::from_low_u64_be(0xa11ce),
+            deadline: U256::from(1_700_000_060u64),
+        };
+        assert_eq!(swap.amount_out_min(), U256::from(1_990));
+        assert_eq!(SettlementSwap { max_slippage_bps: 20_000, ..swap.clone() }.amount_out_min(), U256::zero());
+
+        let tx = swap.tx();
+        assert_eq!(tx.to_addr(), Some(&swap.router));
+        let Ok(UniV2RouterCalls::SwapExactTokensForTokens(call)) = UniV2RouterCalls::decode(tx.data().unwrap()) else {
+            panic!("not a swapExactTokensForTokens");
+        };
+        assert_eq!((call.amount_in, call.amount_out_min, call.deadline), (swap.amount_in, U256::from(1_990), swap.deadline));
+        assert_eq!(call.path, vec![swap.token_in, swap.token_out]);
+        assert_eq!(call.to, swap.recipient);
+    }
+}
@@ -1,28 +1,89 @@
+use ethers::abi::{self, ParamType};
 use ethers::prelude::*;
 use ethers::types::transaction::eip2718::TypedTransaction;
 use std::sync::Arc;
+use std::time::Instant;
+use tracing::info;
+use crate::executor;
+use crate::gas_model::GasModel;
+use crate::gas_oracle::GasOracle;
+use crate::signer::SignerClient;
+use super::bundle::{encode_arbitrage_route, BundleBuilder, CallBundleTxResult};
 use super::types::*;
 
+/// Gas reported for simulations that failed before gas could be estimated.
+const FALLBACK_GAS: u64 = 300_000;
+
 #[derive(Debug)]
 pub struct TxSimulator {
-    provider: Arc<SignerMiddleware<Provider<Http>, Wallet<k256::ecdsa::SigningKey>>>,
+    provider: Arc<SignerClient>,
+    /// Gas bump (bps) of the victim replacement sandwiches are stress-tested against, if any.
+    replacement_bump_bps: Option<u64>,
+    gas_oracle: Arc<GasOracle>,
+    /// Learns each sandwich route's measured gas for sizing the next one.
+    gas_model: Arc<GasModel>,
+    /// Runs sandwiches as a bundle through the relay, so victims are checked
+    /// against the price our frontrun leaves; `None` checks them on current state.
+    /// Arbitrage goes through the relay's `eth_callBundle` too when set.
+    bundle_builder: Option<Arc<BundleBuilder>>,
+    /// Contract arbitrage routes run through, as `BundleBuilder` submits them.
+    arb_executor: Option<Address>,
+    weth: Address,
 }
 
 impl TxSimulator {
-    pub fn new(provider: Arc<SignerMiddleware<Provider<Http>, Wallet<k256::ecdsa::SigningKey>>>) -> Self {
+    pub fn new(
+        provider: Arc<SignerClient>,
+        replacement_bump_bps: Option<u64>,
+        gas_oracle: Arc<GasOracle>,
+        gas_model: Arc<GasModel>,
+        bundle_builder: Option<Arc<BundleBuilder>>,
+        arb_executor: Option<Address>,
+        weth: Address,
+    ) -> Self {
         Self {
             provider,
+            replacement_bump_bps,
+            gas_oracle,
+            gas_model,
+            bundle_builder,
+            arb_executor,
+            weth,
         }
     }
 
+    /// Oracle simulated gas is priced at.
+    pub fn gas_oracle(&self) -> &Arc<GasOracle> {
+        &self.gas_oracle
+    }
+
     pub async fn simulate(&self, opportunity: &MEVOpportunity) -> Result<SimulationResult, Box<dyn std::error::Error>> {
-        match &opportunity.strategy_type {
-            StrategyType::Sandwich(details) => self.simulate_sandwich(details).await,
-            StrategyType::Arbitrage(details) => self.simulate_arbitrage(details).await,
+        let result = match &opportunity.strategy_type {
+            StrategyType::Sandwich(details) => self.simulate_sandwich(details, opportunity.estimated_profit).await?,
+            StrategyType::Arbitrage(details) => self.simulate_arbitrage(details).await?,
+            StrategyType::Flashloan(details) => {
+                self.simulate_single_tx(&details.flashloan_tx, opportunity.estimated_profit).await?
+            },
+            StrategyType::Backrun(details) => {
+                self.simulate_single_tx(&details.backrun_tx, opportunity.estimated_profit).await?
+            },
+        };
+
+        // A failed simulation must never look viable, whatever gas figure it carries
+        if !result.success {
+            return Ok(SimulationResult::failed(result.gas_used, result.revert_reason));
         }
+
+        Ok(result)
     }
 
-    async fn simulate_sandwich(&self, details: &SandwichDetails) -> Result<SimulationResult, Box<dyn std::error::Error>> {
+    /// Simulates the sandwich and prices it by what the bundle returns when run
+    /// through the relay; without a relay, by `expected_profit`.
+    async fn simulate_sandwich(
+        &self,
+        details: &SandwichDetails,
+        expected_profit: U256,
+    ) -> Result<SimulationResult, Box<dyn std::error::Error>> {
         // Fork the current state
         let _current_block = self.provider.get_block_number().await?;
         
@@ -32,77 +93,216 @@ impl TxSimulator {
         // Simulate frontrun transaction
         let frontrun_result = self.simulate_transaction(&details.frontrun_tx).await?;
         if !frontrun_result.success {
-            return Ok(SimulationResult {
-                success: false,
-                profit: U256::from(0),
-                gas_used: frontrun_result.gas_used,
-                revert_reason: frontrun_result.revert_reason,
-            });
+            return Ok(SimulationResult::failed(frontrun_result.gas_used, frontrun_result.revert_reason));
+        }
+
+        // A victim that reverts behind our frontrun drops out of the block, orphaning it
+        let mut bundle_profit = None;
+        if let Some(builder) = &self.bundle_builder {
+            let results = self.call_sandwich_bundle(builder, details).await.map_err(|e| -> Box<dyn std::error::Error> { e })?;
+            let victims: Vec<H256> = std::iter::once(&details.victim_tx).chain(&details.co_victims).map(|victim| victim.hash).collect();
+            if let Some((hash, reason)) = reverted_victim(&results, &victims) {
+                let reason = format!("victim {:?} reverts after our frontrun: {}", hash, reason);
+                return Ok(SimulationResult::failed(frontrun_result.gas_used, Some(reason)));
+            }
+            match sandwich_profit(&results, details.optimal_amount) {
+                Ok(profit) => bundle_profit = Some(profit),
+                Err(reason) => return Ok(SimulationResult::failed(frontrun_result.gas_used, Some(reason))),
+            }
         }
 
         // Simulate victim transaction (convert to TypedTransaction)
         let victim_tx = self.convert_to_typed_transaction(&details.victim_tx);
         let victim_result = self.simulate_transaction(&victim_tx).await?;
         if !victim_result.success {
-            return Ok(SimulationResult {
-                success: false,
-                profit: U256::from(0),
-                gas_used: frontrun_result.gas_used,
-                revert_reason: Some("Victim transaction would fail".to_string()),
-            });
+            return Ok(SimulationResult::failed(
+                frontrun_result.gas_used,
+                Some("Victim transaction would fail".to_string()),
+            ));
+        }
+
+        let mut victims_gas = victim_result.gas_used;
+        for co_victim in &details.co_victims {
+            let co_victim_result = self.simulate_transaction(&self.convert_to_typed_transaction(co_victim)).await?;
+            if !co_victim_result.success {
+                return Ok(SimulationResult::failed(
+                    frontrun_result.gas_used + victims_gas,
+                    Some("Victim transaction would fail".to_string()),
+                ));
+            }
+            victims_gas += co_victim_result.gas_used;
         }
 
         // Simulate backrun transaction
         let backrun_result = self.simulate_transaction(&details.backrun_tx).await?;
         if !backrun_result.success {
-            return Ok(SimulationResult {
-                success: false,
-                profit: U256::from(0),
-                gas_used: frontrun_result.gas_used + victim_result.gas_used,
-                revert_reason: backrun_result.revert_reason,
-            });
+            return Ok(SimulationResult::failed(
+                frontrun_result.gas_used + victims_gas,
+                backrun_result.revert_reason,
+            ));
         }
 
-        // Calculate total profit
+        // Calculate total profit; the victims pay their own gas
         let total_gas = frontrun_result.gas_used + backrun_result.gas_used;
-        let gas_cost = total_gas * U256::from(50) * U256::from(10).pow(U256::from(9)); // 50 gwei
+        self.gas_model.record_route(details.route_shape(), total_gas.low_u64());
+        let gas_cost = total_gas * self.gas_oracle.gas_price(50.0);
         
-        // Get balance changes
-        let profit = self.calculate_balance_change(
-            &details.frontrun_tx,
-            &details.backrun_tx,
-            details.token_out,
-        ).await?;
+        // What the backrun returned over what the frontrun spent, in the input token.
+        // Only a WETH profit is priced here; for other tokens the bundle just has
+        // to confirm the round trip gains.
+        let profit = match bundle_profit {
+            Some(profit) if details.token_in == self.weth => profit,
+            Some(profit) if profit.is_zero() => profit,
+            _ => expected_profit,
+        };
+
+        let profit = if profit > gas_cost { profit - gas_cost } else { U256::from(0) };
+        let fragile = match self.replacement_bump_bps {
+            Some(bump_bps) => !self.survives_replacement(details, frontrun_result.gas_used, profit, bump_bps).await?,
+            None => false,
+        };
 
         Ok(SimulationResult {
             success: true,
-            profit: if profit > gas_cost { profit - gas_cost } else { U256::from(0) },
+            profit,
             gas_used: total_gas,
             revert_reason: None,
+            fragile,
         })
     }
 
+    /// Simulates frontrun, victims and backrun in bundle order on the relay.
+    async fn call_sandwich_bundle(
+        &self,
+        builder: &BundleBuilder,
+        details: &SandwichDetails,
+    ) -> Result<Vec<CallBundleTxResult>, Box<dyn std::error::Error + Send + Sync>> {
+        let nonce = self
+            .provider
+            .get_transaction_count(self.provider.address(), Some(BlockNumber::Pending.into()))
+            .await?;
+        let victims: Vec<&Transaction> = std::iter::once(&details.victim_tx).chain(&details.co_victims).collect();
+
+        let mut raw_txs = vec![builder.sign_raw(&details.frontrun_tx, nonce).await?];
+        raw_txs.extend(victims.iter().map(|victim| victim.rlp()));
+        raw_txs.push(builder.sign_raw(&details.backrun_tx, nonce + 1).await?);
+
+        let target_block = self.provider.get_block_number().await? + 1;
+        builder.call_bundle(&raw_txs, target_block).await
+    }
+
+    /// Whether the sandwich still pays if the victim re-broadcasts the same swap
+    /// with gas bumped by `bump_bps`: the replacement must still execute, and
+    /// outbidding it raises our frontrun's gas price by the same bump.
+    async fn survives_replacement(
+        &self,
+        details: &SandwichDetails,
+        frontrun_gas: U256,
+        profit: U256,
+        bump_bps: u64,
+    ) -> Result<bool, Box<dyn std::error::Error>> {
+        let (replacement, bump) = self.bumped_replacement(&details.victim_tx, bump_bps);
+        if !self.simulate_transaction(&replacement).await?.success {
+            return Ok(false);
+        }
+
+        Ok(profit > frontrun_gas * bump)
+    }
+
+    /// The victim tx with its gas price raised by `bump_bps`, and the per-gas increase.
+    fn bumped_replacement(&self, victim_tx: &Transaction, bump_bps: u64) -> (TypedTransaction, U256) {
+        let mut replacement = self.convert_to_typed_transaction(victim_tx);
+        let gas_price = victim_tx.gas_price.unwrap_or_default();
+        let bump = gas_price * bump_bps / 10_000;
+        replacement.set_gas_price(gas_price + bump);
+        (replacement, bump)
+    }
+
     async fn simulate_arbitrage(&self, details: &ArbitrageDetails) -> Result<SimulationResult, Box<dyn std::error::Error>> {
         // Build the arbitrage transaction
         let arb_tx = self.build_arbitrage_tx(details)?;
-        
-        // Simulate the transaction
-        let result = self.simulate_transaction(&arb_tx).await?;
-        
-        if result.success {
-            // Calculate profit from balance changes
-            let profit = self.calculate_arbitrage_profit(details, &result).await?;
-            let gas_cost = result.gas_used * U256::from(50) * U256::from(10).pow(U256::from(9));
-            
-            Ok(SimulationResult {
-                success: true,
-                profit: if profit > gas_cost { profit - gas_cost } else { U256::from(0) },
-                gas_used: result.gas_used,
-                revert_reason: None,
-            })
-        } else {
-            Ok(result)
+
+        // The tx we'd submit, run on current state: a route that doesn't close
+        // above its input plus the expected profit reverts on its own minimum
+        let (output, gas_used) = match self.call_arbitrage(&arb_tx).await? {
+            Ok(call) => call,
+            Err(reason) => return Ok(SimulationResult::failed(FALLBACK_GAS.into(), Some(reason))),
+        };
+        let Some(amount_out) = self.arbitrage_amount_out(&output) else {
+            return Ok(SimulationResult::failed(gas_used, Some("undecodable arbitrage output".to_string())));
+        };
+
+        // What the route actually returned, not what it was expected to
+        let profit = amount_out.saturating_sub(details.amount_in);
+        let gas_cost = gas_used * self.gas_oracle.gas_price(50.0);
+        Ok(SimulationResult {
+            success: true,
+            profit: profit.saturating_sub(gas_cost),
+            gas_used,
+            revert_reason: None,
+            fragile: false,
+        })
+    }
+
+    /// Runs `arb_tx` through the relay's `eth_callBundle` if we have one, otherwise
+    /// `eth_call`, returning its output and gas, or why it failed.
+    async fn call_arbitrage(&self, arb_tx: &TypedTransaction) -> Result<Result<(Bytes, U256), String>, Box<dyn std::error::Error>> {
+        let Some(builder) = &self.bundle_builder else {
+            return match self.provider.call(arb_tx, None).await {
+                Ok(output) => {
+                    let gas = self.provider.estimate_gas(arb_tx, None).await.unwrap_or_else(|_| FALLBACK_GAS.into());
+                    Ok(Ok((output, gas)))
+                }
+                Err(e) => Ok(Err(e.to_string())),
+            };
+        };
+
+        let nonce = self
+            .provider
+            .get_transaction_count(self.provider.address(), Some(BlockNumber::Pending.into()))
+            .await?;
+        let raw_tx = builder.sign_raw(arb_tx, nonce).await.map_err(|e| -> Box<dyn std::error::Error> { e })?;
+        let target_block = self.provider.get_block_number().await? + 1;
+        let results = builder.call_bundle(&[raw_tx], target_block).await.map_err(|e| -> Box<dyn std::error::Error> { e })?;
+        let Some(result) = results.into_iter().next() else {
+            return Ok(Err("missing from simulation".to_string()));
+        };
+        if let Some(reason) = result.failure() {
+            return Ok(Err(reason.to_string()));
+        }
+        Ok(Ok((result.value.unwrap_or_default(), result.gas_used.into())))
+    }
+
+    /// What the route returned: the executor's `amountOut`, or the last of a
+    /// router swap's `amounts`.
+    fn arbitrage_amount_out(&self, output: &[u8]) -> Option<U256> {
+        if self.arb_executor.is_some() {
+            return abi::decode(&[ParamType::Uint(256)], output).ok()?.pop()?.into_uint();
         }
+        let amounts = abi::decode(&[ParamType::Array(Box::new(ParamType::Uint(256)))], output).ok()?.pop()?.into_array()?;
+        amounts.last()?.clone().into_uint()
+    }
+
+    /// Simulates an opportunity that executes as one tx, e.g. a flash loan whose
+    /// borrow, swaps and repay all run inside the same call.
+    async fn simulate_single_tx(
+        &self,
+        tx: &TypedTransaction,
+        expected_profit: U256,
+    ) -> Result<SimulationResult, Box<dyn std::error::Error>> {
+        let result = self.simulate_transaction(tx).await?;
+        if !result.success {
+            return Ok(result);
+        }
+
+        let gas_cost = result.gas_used * self.gas_oracle.gas_price(50.0);
+        Ok(SimulationResult {
+            success: true,
+            profit: if expected_profit > gas_cost { expected_profit - gas_cost } else { U256::from(0) },
+            gas_used: result.gas_used,
+            revert_reason: None,
+            fragile: false,
+        })
     }
 
     async fn simulate_transaction(&self, tx: &TypedTransaction) -> Result<SimulationResult, Box<dyn std::error::Error>> {
@@ -111,76 +311,64 @@ impl TxSimulator {
         
         match result {
             Ok(_bytes) => {
-                // Estimate gas for successful call
-                let gas = self.provider.estimate_gas(tx, None).await?;
+                // Estimate gas for successful call. It can still revert on state the
+                // call didn't hit; that means non-viable, not an infra failure.
+                let gas = match self.provider.estimate_gas(tx, None).await {
+                    Ok(gas) => gas,
+                    Err(e) if e.as_error_response().is_some_and(|err| err.is_revert()) => {
+                        return Ok(SimulationResult::failed(
+                            FALLBACK_GAS.into(),
+                            Some(format!("estimate_gas reverted: {}", e)),
+                        ));
+                    }
+                    Err(e) => return Err(e.into()),
+                };
                 
                 Ok(SimulationResult {
                     success: true,
                     profit: U256::from(0), // Will be calculated separately
                     gas_used: gas,
                     revert_reason: None,
+                    fragile: false,
                 })
             },
             Err(e) => {
-                // Extract revert reason if available
-                let revert_reason = Some(e.to_string());
-                
-                Ok(SimulationResult {
-                    success: false,
-                    profit: U256::from(0),
-                    gas_used: U256::from(300000), // Default gas estimate
-                    revert_reason,
-                })
+                // Extract revert reason if available; the gas figure is a placeholder, not a measurement
+                Ok(SimulationResult::failed(FALLBACK_GAS.into(), Some(e.to_string())))
             }
         }
     }
 
+    /// The arbitrage tx as `BundleBuilder` would submit it: through the executor if
+    /// one is deployed, otherwise the route's single router. It never expires here.
     fn build_arbitrage_tx(&self, details: &ArbitrageDetails) -> Result<TypedTransaction, Box<dyn std::error::Error>> {
-        // Build a multicall transaction for the arbitrage
-        // This is simplified - in production, use proper routing
-        
+        let us = self.provider.address();
         let mut tx = TypedTransaction::default();
-        tx.set_to(details.pools[0].address)
-            .set_value(details.amount_in)
-            .set_gas(U256::from(500000));
-        
+        if let Some(arb_executor) = self.arb_executor {
+            tx.set_to(arb_executor).set_data(executor::encode_execute(details, U256::MAX)?);
+        } else {
+            let router = details.router.ok_or("arbitrage route spans DEXes and no executor is configured")?;
+            let value = if details.path.first() == Some(&self.weth) { details.amount_in } else { U256::zero() };
+            tx.set_to(router)
+                .set_value(value)
+                .set_data(encode_arbitrage_route(details, self.weth, us, U256::MAX)?);
+        }
+        tx.set_from(us).set_gas(details.gas_estimate.max(FALLBACK_GAS.into()));
         Ok(tx)
     }
 
-    async fn calculate_balance_change(
-        &self,
-        _frontrun_tx: &TypedTransaction,
-        _backrun_tx: &TypedTransaction,
-        _token: Address,
-    ) -> Result<U256, Box<dyn std::error::Error>> {
-        // Calculate the net balance change after sandwich
-        // In production, track state changes properly
-        
-        // Placeholder calculation
-        Ok(U256::from(10).pow(U256::from(17))) // 0.1 ETH profit
-    }
-
-    async fn calculate_arbitrage_profit(
-        &self,
-        details: &ArbitrageDetails,
-        _sim_result: &SimulationResult,
-    ) -> Result<U256, Box<dyn std::error::Error>> {
-        // Calculate profit from arbitrage path
-        Ok(details.expected_profit)
-    }
-
     pub async fn test_strategy_profitability(&self) -> Result<(), Box<dyn std::error::Error>> {
-        println!("Testing MEV strategies...");
+        info!("Testing MEV strategies...");
         
         // Test sandwich attack on a known transaction
         let test_sandwich = self.create_test_sandwich();
         let sandwich_result = self.simulate(&test_sandwich).await?;
-        println!("Sandwich simulation: {:?}", sandwich_result);
+        info!("Sandwich simulation: {:?}", sandwich_result);
         
         // Test arbitrage opportunity
         let test_arb = self.create_test_arbitrage();
         let arb_result = self.simulate(&test_arb).await?;
-        println!("Arbitrage simulation: {:?}", arb_result);
+        info!("Arbitrage simulation: {:?}", arb_result);
         
         Ok(())
     }
@@ -196,6 +384,7 @@ impl TxSimulator {
             target_tx: victim_tx.clone(),
             strategy_type: StrategyType::Sandwich(SandwichDetails {
                 victim_tx,
+                co_victims: Vec::new(),
                 frontrun_tx,
                 backrun_tx,
                 target_pool: Address::zero(),
@@ -205,11 +394,15 @@ impl TxSimulator {
                 victim_amount_in: U256::from(10).pow(U256::from(18)),
                 victim_amount_out_min: U256::from(0),
                 price_impact: 0.01,
+                funding: Funding::OwnCapital,
             }),
             estimated_profit: U256::from(10).pow(U256::from(17)),
             gas_cost: U256::from(10).pow(U256::from(16)),
             priority: 5,
+            reserve_block: U64::from(999999),
             expiry_block: U64::from(1000000),
+            observed_at: Instant::now(),
+            landing_probability: 1.0,
         }
     }
 
@@ -224,11 +417,15 @@ impl TxSimulator {
                 amount_in: U256::from(10).pow(U256::from(18)),
                 expected_profit: U256::from(5) * U256::from(10).pow(U256::from(16)),
                 gas_estimate: U256::from(400000),
+                router: None,
             }),
             estimated_profit: U256::from(5) * U256::from(10).pow(U256::from(16)),
             gas_cost: U256::from(2) * U256::from(10).pow(U256::from(16)),
             priority: 7,
+            reserve_block: U64::from(999999),
             expiry_block: U64::from(1000000),
+            observed_at: Instant::now(),
+            landing_probability: 1.0,
         }
     }
 
@@ -247,4 +444,299 @@ impl TxSimulator {
         
         typed_tx
     }
-} 
\ No newline at end of file
+}
+
+/// The first of `victims` that failed in a bundle simulation, with why. A
+/// victim missing from the results counts as failed.
+pub fn reverted_victim(results: &[CallBundleTxResult], victims: &[H256]) -> Option<(H256, String)> {
+    victims.iter().find_map(|victim| match results.iter().find(|result| result.tx_hash == *victim) {
+        Some(result) => result.failure().map(|reason| (*victim, reason.to_string())),
+        None => Some((*victim, "missing from simulation".to_string())),
+    })
+}
+
+/// What a simulated sandwich bundle gained in the input token: the backrun's
+/// output over the `frontrun_amount` spent, or why the backrun didn't pay out.
+fn sandwich_profit(results: &[CallBundleTxResult], frontrun_amount: U256) -> Result<U256, String> {
+    let backrun = results.last().ok_or("backrun missing from simulation")?;
+    if let Some(reason) = backrun.failure() {
+        return Err(format!("backrun reverts: {}", reason));
+    }
+    let output = backrun.value.clone().unwrap_or_default();
+    // A V3 `exactInputSingle` returns its output; a V2 swap every hop's `amounts`
+    let amount_out = if output.len() == 32 {
+        U256::from_big_endian(&output)
+    } else {
+        let amounts = abi::decode(&[ParamType::Array(Box::new(ParamType::Uint(256)))], &output)
+            .ok()
+            .and_then(|mut tokens| tokens.pop()?.into_array())
+            .and_then(|amounts| amounts.last()?.clone().into_uint());
+        amounts.ok_or("undecodable backrun output")?
+    };
+    Ok(amount_out.saturating_sub(frontrun_amount))
+}
+
+#[cfg(test)]
+mod tests {
+    use ethers::abi::Token;
+    use serde_json::json;
+
+    use super::*;
+    use crate::test_support::{self, MockNode};
+
+    const GWEI: u64 = 1_000_000_000;
+
+    fn eth_fraction(n: u64, per: u64) -> U256 {
+        U256::exp10(18) * n / per
+    }
+
+    /// A node on which every call succeeds and every tx takes 100k gas.
+    async fn node() -> MockNode {
+        let mut node = MockNode::new().await;
+        node.rpc("eth_blockNumber", json!("0x64")).await;
+        node.rpc("eth_getTransactionCount", json!("0x0")).await;
+        node.rpc("eth_call", json!("0x")).await;
+        node.rpc("eth_estimateGas", json!("0x186a0")).await;
+        node
+    }
+
+    /// A relay on which the sandwich's victim fills and its V2 backrun returns `backrun_out`.
+    async fn relay(backrun_out: U256) -> MockNode {
+        let amounts = abi::encode(&[Token::Array(vec![Token::Uint(U256::one()), Token::Uint(backrun_out)])]);
+        let mut relay = MockNode::new().await;
+        relay
+            .rpc(
+                "eth_callBundle",
+                json!({ "results": [
+                    { "txHash": H256::from_low_u64_be(1), "gasUsed": 100_000 },
+                    { "txHash": H256::from_low_u64_be(0x71c), "gasUsed": 100_000 },
+                    { "txHash": H256::from_low_u64_be(2), "gasUsed": 100_000, "value": Bytes::from(amounts) },
+                ] }),
+            )
+            .await;
+        relay
+    }
+
+    async fn simulator(node: &MockNode, relay: &MockNode, replacement_bump_bps: Option<u64>) -> TxSimulator {
+        let config = test_support::config(&node.url(), test_support::settings()).await;
+        let builder = Arc::new(test_support::bundle_builder(&config, &relay.url()));
+        TxSimulator::new(
+            config.http.clone(),
+            replacement_bump_bps,
+            config.gas_oracle.clone(),
+            config.gas_model.clone(),
+            Some(builder),
+            None,
+            config.chain.weth,
+        )
+    }
+
+    /// A backrun the strategy expects `estimated_profit` from.
+    fn backrun(estimated_profit: U256) -> MEVOpportunity {
+        let mut backrun_tx = TypedTransaction::default();
+        backrun_tx.set_to(Address::from_low_u64_be(0x7a)).set_gas(100_000);
+        MEVOpportunity {
+            id: "backrun".to_string(),
+            target_tx: Transaction::default(),
+            strategy_type: StrategyType::Backrun(BackrunDetails { victim_tx: Transaction::default(), backrun_tx }),
+            estimated_profit,
+            gas_cost: U256::zero(),
+            priority: 5,
+            reserve_block: U64::from(100),
+            expiry_block: U64::from(101),
+            observed_at: Instant::now(),
+            landing_probability: 1.0,
+        }
+    }
+
+    /// A 1 ETH sandwich around a victim paying 100 gwei.
+    fn sandwich(weth: Address) -> SandwichDetails {
+        let leg = || {
+            let mut tx = TypedTransaction::default();
+            tx.set_to(Address::from_low_u64_be(0x7a)).set_gas(100_000).set_gas_price(50 * GWEI);
+            tx
+        };
+        SandwichDetails {
+            victim_tx: Transaction {
+                hash: H256::from_low_u64_be(0x71c),
+                to: Some(Address::from_low_u64_be(0x7a)),
+                gas_price: Some((100 * GWEI).into()),
+                ..Default::default()
+            },
+            co_victims: Vec::new(),
+            frontrun_tx: leg(),
+            backrun_tx: leg(),
+            target_pool: Address::from_low_u64_be(0x9001),
+            token_in: weth,
+            token_out: Address::from_low_u64_be(0x70c),
+            optimal_amount: U256::exp10(18),
+            victim_amount_in: U256::exp10(19),
+            victim_amount_out_min: U256::one(),
+            price_impact: 0.0,
+            funding: Funding::OwnCapital,
+        }
+    }
+
+    fn result(hash: u64, revert: Option<&str>) -> CallBundleTxResult {
+        CallBundleTxResult {
+            tx_hash: H256::from_low_u64_be(hash),
+            revert: revert.map(str::to_string),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn reverted_victim_finds_the_first_failed_victim() {
+        let results = vec![result(1, None), result(2, None), result(3, Some("UniswapV2: K")), result(4, None)];
+        let victims = [H256::from_low_u64_be(2), H256::from_low_u64_be(3)];
+        assert_eq!(reverted_victim(&results, &victims), Some((victims[1], "UniswapV2: K".to_string())));
+        assert_eq!(reverted_victim(&results, &victims[..1]), None);
+    }
+
+    #[test]
+    fn reverted_victim_counts_a_missing_victim_as_failed() {
+        let victim = H256::from_low_u64_be(9);
+        let (hash, reason) = reverted_victim(&[result(1, None)], &[victim]).unwrap();
+        assert_eq!(hash, victim);
+        assert_eq!(reason, "missing from simulation");
+    }
+
+    #[tokio::test]
+    async fn sandwich_profit_is_what_the_bundle_returns() {
+        let node = node().await;
+        let relay = relay(eth_fraction(11, 10)).await;
+        let simulator = simulator(&node, &relay, None).await;
+
+        // Not the 5 ETH the strategy expected, but the 0.1 ETH the backrun returns
+        let result = simulator.simulate_sandwich(&sandwich(simulator.weth), eth_fraction(5, 1)).await.unwrap();
+        let gas_cost = U256::from(200_000) * simulator.gas_oracle.gas_price(50.0);
+        assert!(result.success);
+        assert_eq!(result.profit, eth_fraction(1, 10) - gas_cost);
+    }
+
+    #[tokio::test]
+    async fn a_sandwich_a_bumped_victim_makes_unprofitable_is_fragile() {
+        let node = node().await;
+        let gas_cost = U256::from(200_000) * test_support::config(&node.url(), test_support::settings()).await.gas_oracle.gas_price(50.0);
+        // Outbidding a 10% bump on 100 gwei costs our 100k-gas frontrun 0.001 ETH more
+        let bump_cost = U256::from(100_000) * 10 * GWEI;
+
+        let thin = relay(U256::exp10(18) + gas_cost + bump_cost / 2).await;
+        let simulator = simulator(&node, &thin, Some(1_000)).await;
+        let result = simulator.simulate_sandwich(&sandwich(simulator.weth), U256::exp10(18)).await.unwrap();
+        assert!(result.success && !result.profit.is_zero());
+        assert!(result.fragile);
+
+        let fat = relay(U256::exp10(18) + gas_cost + bump_cost * 2).await;
+        let simulator = self::simulator(&node, &fat, Some(1_000)).await;
+        assert!(!simulator.simulate_sandwich(&sandwich(simulator.weth), U256::exp10(18)).await.unwrap().fragile);
+    }
+
+    #[tokio::test]
+    async fn a_reverted_simulation_is_never_viable_whatever_it_expected() {
+        let mut node = MockNode::new().await;
+        node.rpc_error("eth_call", 3, "execution reverted: INSUFFICIENT_OUTPUT_AMOUNT").await;
+        let simulator = simulator(&node, &node, None).await;
+
+        // Priced on the 300k placeholder gas, 5 ETH would still look like a profit
+        let result = simulator.simulate(&backrun(eth_fraction(5, 1))).await.unwrap();
+        assert!(!result.success);
+        assert_eq!(result.gas_used, FALLBACK_GAS.into());
+        assert!(result.profit.is_zero());
+        assert!(!result.is_viable());
+        assert!(result.revert_reason.unwrap().contains("INSUFFICIENT_OUTPUT_AMOUNT"));
+    }
+
+    #[tokio::test]
+    async fn an_estimate_gas_revert_is_a_failed_simulation() {
+        let mut node = MockNode::new().await;
+        node.rpc("eth_call", json!("0x")).await;
+        node.rpc_error("eth_estimateGas", 3, "execution reverted").await;
+        let simulator = simulator(&node, &node, None).await;
+
+        let result = simulator.simulate(&backrun(eth_fraction(5, 1))).await.unwrap();
+        assert!(!result.is_viable() && result.profit.is_zero());
+        assert!(result.revert_reason.unwrap().starts_with("estimate_gas reverted: "));
+    }
+
+    #[tokio::test]
+    async fn an_estimate_gas_transport_error_is_an_error() {
+        let mut node = MockNode::new().await;
+        node.rpc("eth_call", json!("0x")).await;
+        let _unavailable = node
+            .server
+            .mock("POST", "/")
+            .match_body(mockito::Matcher::PartialJson(json!({ "method": "eth_estimateGas" })))
+            .with_status(502)
+            .with_body("bad gateway")
+            .create_async()
+            .await;
+        let simulator = simulator(&node, &node, None).await;
+
+        // Not the tx's fault, so not written off as a failed simulation
+        assert!(simulator.simulate(&backrun(eth_fraction(5, 1))).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn an_arbitrage_route_returning_less_than_its_gas_simulates_to_zero() {
+        let mut node = MockNode::new().await;
+        // The router's amounts: 1 ETH in, 1.001 ETH back, short of the 0.005 ETH the 100k gas costs
+        let amounts = abi::encode(&[Token::Array(vec![
+            Token::Uint(U256::exp10(18)),
+            Token::Uint(U256::exp10(21)),
+            Token::Uint(eth_fraction(1001, 1000)),
+        ])]);
+        node.rpc("eth_call", json!(Bytes::from(amounts))).await;
+        node.rpc("eth_estimateGas", json!("0x186a0")).await;
+        let config = test_support::config(&node.url(), test_support::settings()).await;
+        let weth = config.chain.weth;
+        let simulator = TxSimulator::new(
+            config.http.clone(),
+            None,
+            config.gas_oracle.clone(),
+            config.gas_model.clone(),
+            None,
+            None,
+            weth,
+        );
+
+        let details = ArbitrageDetails {
+            path: vec![weth, Address::from_low_u64_be(0x70c), weth],
+            pools: Vec::new(),
+            amount_in: U256::exp10(18),
+            expected_profit: eth_fraction(1, 2),
+            gas_estimate: U256::from(100_000),
+            router: Some(Address::from_low_u64_be(0x7a)),
+        };
+        let result = simulator.simulate_arbitrage(&details).await.unwrap();
+        assert!(result.success);
+        assert!(result.profit.is_zero(), "echoed {} instead of what the route returned", result.profit);
+        assert!(!result.is_viable());
+    }
+
+    #[tokio::test]
+    async fn a_frontrun_big_enough_to_revert_the_victim_is_rejected() {
+        let node = node().await;
+        let mut relay = MockNode::new().await;
+        relay
+            .rpc(
+                "eth_callBundle",
+                json!({ "results": [
+                    { "txHash": H256::from_low_u64_be(1), "gasUsed": 100_000 },
+                    { "txHash": H256::from_low_u64_be(0x71c), "gasUsed": 30_000, "revert": "UniswapV2Router: INSUFFICIENT_OUTPUT_AMOUNT" },
+                    { "txHash": H256::from_low_u64_be(2), "gasUsed": 100_000 },
+                ] }),
+            )
+            .await;
+        let simulator = simulator(&node, &relay, None).await;
+
+        // 100 ETH ahead of a 10 ETH victim moves the price past its amountOutMin
+        let mut details = sandwich(simulator.weth);
+        details.optimal_amount = U256::exp10(20);
+        let result = simulator.simulate_sandwich(&details, eth_fraction(5, 1)).await.unwrap();
+        assert!(!result.is_viable() && result.profit.is_zero());
+        let reason = result.revert_reason.unwrap();
+        assert!(reason.contains(&format!("{:?}", details.victim_tx.hash)), "{}", reason);
+        assert!(reason.ends_with("INSUFFICIENT_OUTPUT_AMOUNT"), "{}", reason);
+    }
+}
@@ -0,0 +1,254 @@
+use ethers::prelude::*;
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::sync::Mutex;
+use tracing::{info, warn};
+
+use crate::address_book::{UniV2Router, ERC20};
+use crate::chain::ChainConfig;
+use crate::helpers::{address, eth_to_wei};
+use crate::settings::Settings;
+
+/// Account the probe trades from on the fork.
+const PROBER: &str = "0x0000000000000000000000000000000000005aFE";
+
+/// Why a token failed its round trip.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TokenRisk {
+    /// The buy reverted or delivered nothing.
+    BuyFailed(String),
+    /// The tokens we bought can't be sold back.
+    SellFailed(String),
+    /// Buying and selling back lost more than the threshold, e.g. to transfer fees.
+    RoundTripLoss { loss_bps: u64 },
+}
+
+impl std::fmt::Display for TokenRisk {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TokenRisk::BuyFailed(reason) => write!(f, "buy failed: {}", reason),
+            TokenRisk::SellFailed(reason) => write!(f, "sell failed: {}", reason),
+            TokenRisk::RoundTripLoss { loss_bps } => write!(f, "round trip lost {} bps", loss_bps),
+        }
+    }
+}
+
+/// Screens tokens for honeypots and transfer fees before we hold them.
+///
+/// Each new token is bought with a small amount of ETH and sold straight back on
+/// a forked node (anvil/hardhat at `token_safety_rpc`), inside a snapshot that is
+/// reverted afterwards. Verdicts are cached per token. Without a fork every
+/// token is let through.
+#[derive(Debug)]
+pub struct TokenSafety {
+    fork: Option<Arc<Provider<Http>>>,
+    /// Wrapped native token the probe buys with, and the V2 router it trades on.
+    weth: Address,
+    router: Address,
+    probe_wei: U256,
+    max_loss_bps: u64,
+    verdicts: RwLock<HashMap<Address, Option<TokenRisk>>>,
+    /// Probes share the fork's state, so they take turns.
+    probing: Mutex<()>,
+}
+
+impl TokenSafety {
+    pub fn new(fork: Option<Provider<Http>>, chain: &ChainConfig, probe_wei: U256, max_loss_bps: u64) -> Self {
+        Self {
+            fork: fork.map(Arc::new),
+            weth: chain.weth,
+            router: chain.v2_router,
+            probe_wei,
+            max_loss_bps,
+            verdicts: RwLock::new(HashMap::new()),
+            probing: Mutex::new(()),
+        }
+    }
+
+    pub fn from_settings(settings: &Settings, chain: &ChainConfig) -> Self {
+        let fork = settings.token_safety_rpc.as_deref().and_then(|url| match Provider::<Http>::try_from(url) {
+            Ok(provider) => Some(provider),
+            Err(e) => {
+                warn!("⚠️ Invalid TOKEN_SAFETY_RPC {}: {}", url, e);
+                None
+            }
+        });
+        Self::new(fork, chain, eth_to_wei(settings.token_safety_probe_eth), settings.token_safety_max_loss_bps)
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.fork.is_some()
+    }
+
+    /// Cached verdict for `token`, if it has been probed.
+    pub fn verdict(&self, token: Address) -> Option<Option<TokenRisk>> {
+        self.verdicts.read().unwrap().get(&token).cloned()
+    }
+
+    /// Whether `token` can be bought and sold back without an outsized loss,
+    /// probing it on first sight. A probe that couldn't run isn't cached.
+    pub async fn is_safe(&self, token: Address) -> bool {
+        let Some(fork) = &self.fork else {
+            return true;
+        };
+        if let Some(verdict) = self.verdict(token) {
+            return verdict.is_none();
+        }
+
+        let _turn = self.probing.lock().await;
+        // Another task may have probed it while we waited
+        if let Some(verdict) = self.verdict(token) {
+            return verdict.is_none();
+        }
+        match self.probe(fork, token).await {
+            Ok(risk) => {
+                if let Some(risk) = &risk {
+                    info!("☠️ Flagged token {:?}: {}", token, risk);
+                }
+                let safe = risk.is_none();
+                self.verdicts.write().unwrap().insert(token, risk);
+                safe
+            }
+            Err(e) => {
+                warn!("⚠️ Token probe of {:?} failed: {}", token, e);
+                false
+            }
+        }
+    }
+
+    /// Round-trips `probe_wei` through `token`'s V2 WETH pair inside a fork snapshot.
+    async fn probe(
+        &self,
+        fork: &Arc<Provider<Http>>,
+        token: Address,
+    ) -> Result<Option<TokenRisk>, Box<dyn std::error::Error + Send + Sync>> {
+        let snapshot: U256 = fork.request("evm_snapshot", ()).await?;
+        let result = self.round_trip(fork, token).await;
+        let _: bool = fork.request("evm_revert", [snapshot]).await?;
+        Ok(match result? {
+            Err(risk) => Some(risk),
+            Ok(eth_out) => {
+                let loss_bps = round_trip_loss_bps(self.probe_wei, eth_out);
+                (loss_bps > self.max_loss_bps).then_some(TokenRisk::RoundTripLoss { loss_bps })
+            }
+        })
+    }
+
+    /// Buys `token` with `probe_wei` then sells it all back, returning either the ETH
+    /// that came back or why the round trip failed.
+    async fn round_trip(
+        &self,
+        fork: &Arc<Provider<Http>>,
+        token: Address,
+    ) -> Result<Result<U256, TokenRisk>, Box<dyn std::error::Error + Send + Sync>> {
+        let prober = address(PROBER);
+        let weth = self.weth;
+        let router = UniV2Router::new(self.router, fork.clone());
+        let erc20 = ERC20::new(token, fork.clone());
+        let deadline = U256::from(SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs() + 3600);
+
+        let _: () = fork.request("anvil_setBalance", (prober, self.probe_wei * 10)).await?;
+        let _: () = fork.request("anvil_impersonateAccount", [prober]).await?;
+
+        let held = erc20.balance_of(prober).call().await?;
+        let buy = router
+            .swap_exact_eth_for_tokens_supporting_fee_on_transfer_tokens(U256::zero(), vec![weth, token], prober, deadline)
+            .from(prober)
+            .value(self.probe_wei);
+        if let Err(reason) = send(buy).await {
+            return Ok(Err(TokenRisk::BuyFailed(reason)));
+        }
+        let bought = erc20.balance_of(prober).call().await?.saturating_sub(held);
+        if bought.is_zero() {
+            return Ok(Err(TokenRisk::BuyFailed("no tokens received".to_string())));
+        }
+
+        if let Err(reason) = send(erc20.approve(router.address(), U256::MAX).from(prober)).await {
+            return Ok(Err(TokenRisk::SellFailed(format!("approve: {}", reason))));
+        }
+        let before = fork.get_balance(prober, None).await?;
+        let sell = router
+            .swap_exact_tokens_for_eth_supporting_fee_on_transfer_tokens(bought, U256::zero(), vec![token, weth], prober, deadline)
+            .from(prober);
+        let receipt = match send(sell).await {
+            Ok(receipt) => receipt,
+            Err(reason) => return Ok(Err(TokenRisk::SellFailed(reason))),
+        };
+        let after = fork.get_balance(prober, None).await?;
+        let gas_paid = receipt.gas_used.unwrap_or_default() * receipt.effective_gas_price.unwrap_or_default();
+
+        Ok(Ok((after + gas_paid).saturating_sub(before)))
+    }
+}
+
+/// Sends `call` on the fork and waits for it to mine, returning why if it didn't succeed.
+async fn send<D: ethers::abi::Detokenize>(
+    call: ContractCall<Provider<Http>, D>,
+) -> Result<TransactionReceipt, String> {
+    let pending = call.send().await.map_err(|e| e.to_string())?;
+    match pending.await {
+        Ok(Some(receipt)) if receipt.status == Some(1.into()) => Ok(receipt),
+        Ok(Some(_)) => Err("reverted".to_string()),
+        Ok(None) => Err("dropped".to_string()),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+/// Share of `eth_in` (bps) lost buying and selling straight back.
+pub fn round_trip_loss_bps(eth_in: U256, eth_out: U256) -> u64 {
+    if eth_in.is_zero() {
+        return 0;
+    }
+    let lost = eth_in.saturating_sub(eth_out);
+    (lost * U256::from(10_000) / eth_in).as_u64()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::uni::get_amount_out;
+    use ethers::utils::parse_ether;
+
+    /// ETH back from buying a token taxing `fee_bps` of every transfer with `eth_in`
+    /// on a deep pool and selling it straight back.
+    fn taxed_round_trip(eth_in: U256, fee_bps: u64) -> U256 {
+        let (reserve_eth, reserve_token) = (parse_ether(1_000).unwrap(), parse_ether(1_000_000).unwrap());
+        let tax = |amount: U256| amount - amount * fee_bps / 10_000;
+        let (bought, reserve_eth, reserve_token) = get_amount_out(eth_in, reserve_eth, reserve_token);
+        // Taxed on the way out of the pair and again on the way back in
+        let (eth_out, _, _) = get_amount_out(tax(tax(bought)), reserve_token, reserve_eth);
+        eth_out
+    }
+
+    #[test]
+    fn a_plain_token_loses_only_the_pool_fees() {
+        let eth_in = parse_ether(0.01).unwrap();
+        let loss_bps = round_trip_loss_bps(eth_in, taxed_round_trip(eth_in, 0));
+        assert!((59..=61).contains(&loss_bps), "lost {} bps", loss_bps);
+        assert!(loss_bps <= Settings::default().token_safety_max_loss_bps);
+    }
+
+    #[test]
+    fn a_fee_on_transfer_token_is_past_the_default_threshold() {
+        let eth_in = parse_ether(0.01).unwrap();
+        // A 5% tax each way
+        let loss_bps = round_trip_loss_bps(eth_in, taxed_round_trip(eth_in, 500));
+        assert!(loss_bps > Settings::default().token_safety_max_loss_bps, "lost {} bps", loss_bps);
+        assert!((1_000..=1_100).contains(&loss_bps), "lost {} bps", loss_bps);
+    }
+
+    #[test]
+    fn round_trip_loss_bps_handles_gains_and_empty_probes() {
+        assert_eq!(round_trip_loss_bps(U256::zero(), U256::one()), 0);
+        assert_eq!(round_trip_loss_bps(U256::from(100), U256::from(150)), 0);
+        assert_eq!(round_trip_loss_bps(U256::from(100), U256::zero()), 10_000);
+    }
+
+    #[tokio::test]
+    async fn every_token_is_safe_without_a_fork() {
+        let safety = TokenSafety::new(None, &ChainConfig::mainnet(), U256::exp10(16), 500);
+        assert!(!safety.is_enabled());
+        assert!(safety.is_safe(Address::random()).await);
+    }
+}
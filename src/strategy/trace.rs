@@ -0,0 +1,142 @@
+use ethers::prelude::*;
+use std::fmt;
+use std::sync::Mutex;
+use tracing::info;
+
+use crate::settings::Settings;
+use crate::token_info::TokenInfo;
+
+/// A single step in the decision made about a victim tx.
+#[derive(Debug, Clone)]
+pub enum TraceStep {
+    Decoded(String),
+    Reserves { pool: Address, reserve_in: U256, reserve_out: U256 },
+    OptimalAmount { amount: U256, token: TokenInfo },
+    SimulatedProfit { opportunity: String, profit: U256 },
+    Gate { name: &'static str, passed: bool },
+    Action(String),
+}
+
+impl fmt::Display for TraceStep {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TraceStep::Decoded(call) => write!(f, "decoded: {}", call),
+            TraceStep::Reserves { pool, reserve_in, reserve_out } => {
+                write!(f, "reserves: pool {:?} in {} out {}", pool, reserve_in, reserve_out)
+            }
+            TraceStep::OptimalAmount { amount, token } => write!(f, "optimal amount: {}", token.format(*amount)),
+            TraceStep::SimulatedProfit { opportunity, profit } => {
+                write!(f, "simulated profit: {} -> {} ETH", opportunity, ethers::utils::format_ether(*profit))
+            }
+            TraceStep::Gate { name, passed } => {
+                write!(f, "gate {}: {}", name, if *passed { "pass" } else { "FAIL" })
+            }
+            TraceStep::Action(action) => write!(f, "action: {}", action),
+        }
+    }
+}
+
+/// Verbose per-tx record of every decision taken, for debugging missed opportunities.
+/// A disabled trace ignores all records, so it can be threaded through unconditionally.
+#[derive(Debug)]
+pub struct DecisionTrace {
+    tx_hash: H256,
+    enabled: bool,
+    steps: Mutex<Vec<TraceStep>>,
+}
+
+impl DecisionTrace {
+    pub fn new(settings: &Settings, tx_hash: H256) -> Self {
+        Self {
+            tx_hash,
+            enabled: settings.trace_enabled_for(&tx_hash),
+            steps: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Records regardless of settings, e.g. when a tx is inspected on demand.
+    pub fn enabled(tx_hash: H256) -> Self {
+        Self {
+            tx_hash,
+            enabled: true,
+            steps: Mutex::new(Vec::new()),
+        }
+    }
+
+    pub fn disabled() -> Self {
+        Self {
+            tx_hash: H256::zero(),
+            enabled: false,
+            steps: Mutex::new(Vec::new()),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub fn record(&self, step: TraceStep) {
+        if self.enabled {
+            self.steps.lock().unwrap().push(step);
+        }
+    }
+
+    /// Records a pass/fail gate and hands back the result, so it can wrap a condition.
+    pub fn gate(&self, name: &'static str, passed: bool) -> bool {
+        self.record(TraceStep::Gate { name, passed });
+        passed
+    }
+
+    pub fn steps(&self) -> Vec<TraceStep> {
+        self.steps.lock().unwrap().clone()
+    }
+
+    /// Logs the recorded trace, if any, one event per step.
+    pub fn emit(&self) {
+        if !self.enabled {
+            return;
+        }
+
+        let tx = self.tx_hash;
+        for (i, step) in self.steps.lock().unwrap().iter().enumerate() {
+            let n = i + 1;
+            match step {
+                TraceStep::Reserves { pool, reserve_in, reserve_out } => {
+                    info!(tx = ?tx, step = n, pool = ?pool, reserve_in = %reserve_in, reserve_out = %reserve_out, "🧭 {}", step)
+                }
+                TraceStep::SimulatedProfit { opportunity, profit } => {
+                    info!(tx = ?tx, step = n, opportunity = %opportunity, profit = %profit, "🧭 {}", step)
+                }
+                TraceStep::Gate { name, passed } => info!(tx = ?tx, step = n, gate = *name, passed = *passed, "🧭 {}", step),
+                _ => info!(tx = ?tx, step = n, "🧭 {}", step),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_the_configured_tx_is_traced() {
+        let (victim, other) = (H256::from_low_u64_be(1), H256::from_low_u64_be(2));
+        let settings = Settings { trace_tx: Some(victim), ..Settings::default() };
+        assert!(DecisionTrace::new(&settings, victim).is_enabled());
+        assert!(!DecisionTrace::new(&settings, other).is_enabled());
+        assert!(DecisionTrace::new(&Settings { trace_all: true, ..Settings::default() }, other).is_enabled());
+    }
+
+    #[test]
+    fn gates_pass_their_result_through_and_are_recorded_when_enabled() {
+        let trace = DecisionTrace::enabled(H256::zero());
+        assert!(!trace.gate("min_profit", false));
+        trace.record(TraceStep::Action("skip".to_string()));
+        let steps: Vec<String> = trace.steps().iter().map(ToString::to_string).collect();
+        assert_eq!(steps, vec!["gate min_profit: FAIL", "action: skip"]);
+
+        let disabled = DecisionTrace::disabled();
+        assert!(disabled.gate("min_profit", true));
+        assert!(disabled.steps().is_empty());
+    }
+}
@@ -1,6 +1,10 @@
 use ethers::prelude::*;
 use ethers::types::transaction::eip2718::TypedTransaction;
 use serde::{Deserialize, Serialize};
+use std::time::Instant;
+
+use crate::gas_model::RouteShape;
+use super::flashloan_balancer::FlashloanProvider;
 
 #[derive(Debug, Clone)]
 pub struct MEVOpportunity {
@@ -10,18 +14,179 @@ pub struct MEVOpportunity {
     pub estimated_profit: U256,
     pub gas_cost: U256,
     pub priority: u8,
+    /// Block whose state the opportunity's reserves were read from.
+    pub reserve_block: U64,
     pub expiry_block: U64,
+    /// When the tx it acts on was first seen, or when it was found if it acts on none.
+    pub observed_at: Instant,
+    /// Chance the submission lands, from how our past bids at its gas price fared.
+    pub landing_probability: f64,
+}
+
+impl MEVOpportunity {
+    /// Identity of the trade itself, stable across blocks: an arbitrage that stays
+    /// open is the same route through the same pools, a victim trade is its tx.
+    pub fn fingerprint(&self) -> String {
+        match &self.strategy_type {
+            StrategyType::Arbitrage(details) => {
+                let pools: Vec<String> = details.pools.iter().map(|pool| format!("{:?}", pool.address)).collect();
+                let path: Vec<String> = details.path.iter().map(|token| format!("{:?}", token)).collect();
+                format!("arbitrage:{}:{}", path.join(">"), pools.join(","))
+            }
+            StrategyType::Sandwich(details) => format!("sandwich:{:?}", details.victim_tx.hash),
+            StrategyType::Flashloan(details) => format!("flashloan:{:?}", details.victim_tx.hash),
+            StrategyType::Backrun(details) => format!("backrun:{:?}", details.victim_tx.hash),
+        }
+    }
+
+    /// Estimated profit left after gas.
+    pub fn net_profit(&self) -> U256 {
+        self.estimated_profit.saturating_sub(self.gas_cost)
+    }
+
+    /// Net profit weighted by the chance of landing, what opportunities are ranked on.
+    pub fn expected_value(&self) -> U256 {
+        weight_by_probability(self.net_profit(), self.landing_probability)
+    }
+
+    /// Whether every leg we'd send is encoded; a leg without calldata would only
+    /// burn gas.
+    pub fn is_executable(&self) -> bool {
+        match &self.strategy_type {
+            StrategyType::Sandwich(details) => details.frontrun_tx.data().is_some() && details.backrun_tx.data().is_some(),
+            _ => true,
+        }
+    }
+
+    /// Per-gas price the opportunity's first tx bids, if it is priced yet.
+    /// Arbitrage is priced only when its bundle is built.
+    pub fn bid_gas_price(&self) -> Option<U256> {
+        match &self.strategy_type {
+            StrategyType::Sandwich(details) => details.frontrun_tx.gas_price(),
+            StrategyType::Flashloan(details) => details.flashloan_tx.gas_price(),
+            StrategyType::Backrun(details) => details.backrun_tx.gas_price(),
+            StrategyType::Arbitrage(_) => None,
+        }
+    }
+
+    /// Whether the profit lands as `weth`, which has to be unwrapped before it can pay
+    /// for gas: arbitrage routes ending in WETH, anything repaying a WETH loan, and
+    /// WETH-side sandwiches, whose backrun sells back to WETH through the router.
+    pub fn settles_in_weth(&self, weth: Address) -> bool {
+        match &self.strategy_type {
+            StrategyType::Sandwich(details) => details.token_in == weth,
+            StrategyType::Arbitrage(details) => details.path.last() == Some(&weth),
+            StrategyType::Flashloan(details) => details.loan_token == weth,
+            StrategyType::Backrun(_) => false,
+        }
+    }
+
+    /// ERC20 the profit lands in, or `None` for native ETH.
+    pub fn profit_token(&self) -> Option<Address> {
+        match &self.strategy_type {
+            StrategyType::Sandwich(details) => Some(details.token_in),
+            StrategyType::Arbitrage(details) => details.path.last().copied(),
+            StrategyType::Flashloan(details) => Some(details.loan_token),
+            StrategyType::Backrun(_) => None,
+        }
+    }
+
+    /// Net profit in native ETH, after paying `unwrap_cost` to unwrap `weth` proceeds.
+    pub fn settled_profit(&self, weth: Address, unwrap_cost: U256) -> U256 {
+        if self.settles_in_weth(weth) {
+            self.net_profit().saturating_sub(unwrap_cost)
+        } else {
+            self.net_profit()
+        }
+    }
+
+    /// The pending tx this opportunity lands around, and the pool it trades if known.
+    /// `None` for opportunities not tied to a victim, like standing arbitrages.
+    pub fn victim(&self) -> Option<(TxHash, Option<Address>)> {
+        match &self.strategy_type {
+            StrategyType::Sandwich(details) => Some((details.victim_tx.hash, Some(details.target_pool))),
+            StrategyType::Flashloan(details) => Some((details.victim_tx.hash, None)),
+            StrategyType::Backrun(details) => Some((details.victim_tx.hash, None)),
+            StrategyType::Arbitrage(_) => None,
+        }
+    }
+
+    /// Whether both bundles would position around the same victim trade, so at
+    /// most one of them can be submitted. An unknown pool conflicts with any.
+    pub fn conflicts_with(&self, other: &MEVOpportunity) -> bool {
+        match (self.victim(), other.victim()) {
+            (Some((tx, pool)), Some((other_tx, other_pool))) => {
+                tx == other_tx && (pool.is_none() || other_pool.is_none() || pool == other_pool)
+            }
+            _ => false,
+        }
+    }
+
+    /// Sender of the victim tx the bundle includes. Two bundles in one sender's
+    /// lane can't both land: each needs that sender's next nonce.
+    pub fn nonce_lane(&self) -> Option<Address> {
+        match &self.strategy_type {
+            StrategyType::Sandwich(details) => Some(details.victim_tx.from),
+            StrategyType::Flashloan(details) => Some(details.victim_tx.from),
+            StrategyType::Backrun(details) => Some(details.victim_tx.from),
+            StrategyType::Arbitrage(_) => None,
+        }
+    }
+
+    /// Own ETH the opportunity ties up until it settles; borrowed funds don't count.
+    /// A sandwich spending a token we hold ties up that token, capped by its balance
+    /// when sized, rather than ETH.
+    pub fn capital(&self) -> U256 {
+        match &self.strategy_type {
+            StrategyType::Sandwich(details) => match details.funding {
+                Funding::OwnCapital => details.frontrun_tx.value().copied().unwrap_or_default(),
+                Funding::Flashloan { .. } => U256::zero(),
+            },
+            StrategyType::Arbitrage(details) => details.amount_in,
+            StrategyType::Flashloan(_) | StrategyType::Backrun(_) => U256::zero(),
+        }
+    }
+}
+
+/// `amount` scaled by `probability`, clamped to 0-1, at basis-point precision.
+pub fn weight_by_probability(amount: U256, probability: f64) -> U256 {
+    let bps = (probability.clamp(0.0, 1.0) * 10_000.0).round() as u64;
+    amount.saturating_mul(U256::from(bps)) / 10_000
 }
 
 #[derive(Debug, Clone)]
+#[allow(clippy::large_enum_variant)]
 pub enum StrategyType {
     Sandwich(SandwichDetails),
     Arbitrage(ArbitrageDetails),
+    Flashloan(FlashloanDetails),
+    Backrun(BackrunDetails),
+}
+
+impl StrategyType {
+    pub fn name(&self) -> &'static str {
+        match self {
+            StrategyType::Sandwich(_) => "sandwich",
+            StrategyType::Arbitrage(_) => "arbitrage",
+            StrategyType::Flashloan(_) => "flashloan",
+            StrategyType::Backrun(_) => "backrun",
+        }
+    }
+}
+
+/// How a sandwich's frontrun is paid for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Funding {
+    OwnCapital,
+    /// Borrowed for the frontrun; `fee` is owed on top of the principal.
+    Flashloan { provider: FlashloanProvider, fee: U256 },
 }
 
 #[derive(Debug, Clone)]
 pub struct SandwichDetails {
     pub victim_tx: Transaction,
+    /// Further victims on the same pool, bundled after `victim_tx` and before the backrun.
+    pub co_victims: Vec<Transaction>,
     pub frontrun_tx: TypedTransaction,
     pub backrun_tx: TypedTransaction,
     pub target_pool: Address,
@@ -31,6 +196,14 @@ pub struct SandwichDetails {
     pub victim_amount_in: U256,
     pub victim_amount_out_min: U256,
     pub price_impact: f64,
+    pub funding: Funding,
+}
+
+impl SandwichDetails {
+    /// Both legs swap through the one target pool.
+    pub fn route_shape(&self) -> RouteShape {
+        RouteShape::new(vec![self.target_pool], matches!(self.funding, Funding::Flashloan { .. }))
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -40,6 +213,32 @@ pub struct ArbitrageDetails {
     pub amount_in: U256,
     pub expected_profit: U256,
     pub gas_estimate: U256,
+    /// V2 router able to swap the whole path in one call; `None` if it spans DEXes.
+    pub router: Option<Address>,
+}
+
+/// A backrun funded by a Balancer flash loan, executed atomically in one tx.
+#[derive(Debug, Clone)]
+pub struct FlashloanDetails {
+    pub victim_tx: Transaction,
+    pub flashloan_tx: TypedTransaction,
+    pub loan_token: Address,
+    pub loan_amount: U256,
+    pub steps: Vec<FlashloanStep>,
+}
+
+/// A single tx landing right behind the victim.
+#[derive(Debug, Clone)]
+pub struct BackrunDetails {
+    pub victim_tx: Transaction,
+    pub backrun_tx: TypedTransaction,
+}
+
+/// One call the executor makes inside the flash loan callback.
+#[derive(Debug, Clone)]
+pub struct FlashloanStep {
+    pub target: Address,
+    pub data: Bytes,
 }
 
 #[derive(Debug, Clone)]
@@ -51,6 +250,16 @@ pub struct PoolInfo {
     pub reserve1: U256,
     pub fee: u16,
     pub dex_type: DexType,
+    pub v3: Option<V3PoolState>,
+}
+
+/// Concentrated-liquidity state of a Uniswap V3 pool. For these pools the
+/// `PoolInfo` reserves are the virtual reserves of the current tick range.
+#[derive(Debug, Clone)]
+pub struct V3PoolState {
+    pub sqrt_price_x96: U256,
+    pub liquidity: u128,
+    pub fee_tier: u32,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
@@ -68,17 +277,169 @@ pub struct SimulationResult {
     pub profit: U256,
     pub gas_used: U256,
     pub revert_reason: Option<String>,
+    /// Profitable only against the exact victim tx, not a plausible gas-bumped replacement.
+    pub fragile: bool,
+}
+
+impl SimulationResult {
+    /// A failed simulation. `gas_used` is informational only and never feeds profit.
+    pub fn failed(gas_used: U256, revert_reason: Option<String>) -> Self {
+        Self {
+            success: false,
+            profit: U256::zero(),
+            gas_used,
+            revert_reason,
+            fragile: false,
+        }
+    }
+
+    /// Only successful, robust simulations with positive profit are worth executing.
+    pub fn is_viable(&self) -> bool {
+        self.success && !self.fragile && !self.profit.is_zero()
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct BundleTransaction {
     pub signer: Address,
+    /// The tx as filled in, for the nonce it uses.
     pub tx: TypedTransaction,
+    /// The signed tx, RLP-encoded, as the relay receives it.
+    pub raw: Bytes,
     pub can_revert: bool,
 }
 
+impl BundleTransaction {
+    /// The tx's hash once mined.
+    pub fn hash(&self) -> H256 {
+        H256::from(ethers::utils::keccak256(&self.raw))
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Bundle {
     pub txs: Vec<BundleTransaction>,
     pub block_number: U64,
-} 
\ No newline at end of file
+} 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::strategy::bundle::unwrap_cost;
+
+    fn opportunity(strategy_type: StrategyType) -> MEVOpportunity {
+        MEVOpportunity {
+            id: "test".to_string(),
+            target_tx: Transaction::default(),
+            strategy_type,
+            estimated_profit: U256::from(1_000),
+            gas_cost: U256::from(200),
+            priority: 0,
+            reserve_block: U64::zero(),
+            expiry_block: U64::one(),
+            observed_at: Instant::now(),
+            landing_probability: 1.0,
+        }
+    }
+
+    fn backrun() -> StrategyType {
+        StrategyType::Backrun(BackrunDetails { victim_tx: Transaction::default(), backrun_tx: TypedTransaction::default() })
+    }
+
+    fn sandwich(token_in: Address, funding: Funding) -> StrategyType {
+        let leg = |value: u64| {
+            let mut tx = TypedTransaction::default();
+            tx.set_value(value).set_data(vec![0x38, 0xed, 0x17, 0x39].into());
+            tx
+        };
+        StrategyType::Sandwich(SandwichDetails {
+            victim_tx: Transaction::default(),
+            co_victims: Vec::new(),
+            frontrun_tx: leg(if token_in == weth() { 5 } else { 0 }),
+            backrun_tx: leg(0),
+            target_pool: Address::from_low_u64_be(0x9001),
+            token_in,
+            token_out: Address::from_low_u64_be(0x70c),
+            optimal_amount: U256::from(5),
+            victim_amount_in: U256::from(50),
+            victim_amount_out_min: U256::one(),
+            price_impact: 0.0,
+            funding,
+        })
+    }
+
+    fn weth() -> Address {
+        Address::from_low_u64_be(0xe7)
+    }
+
+    #[test]
+    fn own_capital_sandwiches_settle_in_their_input_token() {
+        let weth_side = opportunity(sandwich(weth(), Funding::OwnCapital));
+        assert!(weth_side.settles_in_weth(weth()));
+        assert_eq!(weth_side.profit_token(), Some(weth()));
+        // The WETH the backrun bought back has to be unwrapped before it pays for gas
+        assert_eq!(weth_side.settled_profit(weth(), U256::from(100)), U256::from(700));
+
+        let usdc = Address::from_low_u64_be(0x05dc);
+        let token_side = opportunity(sandwich(usdc, Funding::OwnCapital));
+        assert!(!token_side.settles_in_weth(weth()));
+        assert_eq!(token_side.profit_token(), Some(usdc));
+        assert_eq!(token_side.settled_profit(weth(), U256::from(100)), U256::from(800));
+    }
+
+    #[test]
+    fn weth_profit_smaller_than_its_unwrap_gas_is_no_profit() {
+        // 45k gas at 50 gwei costs 0.00225 ETH; the sandwich nets 0.002 ETH in WETH
+        let unwrap_cost = unwrap_cost(U256::from(50_000_000_000u64));
+        let mut weth_side = opportunity(sandwich(weth(), Funding::OwnCapital));
+        weth_side.estimated_profit = U256::from(2_000_000_000_000_000u64);
+        weth_side.gas_cost = U256::zero();
+        assert!(weth_side.settled_profit(weth(), unwrap_cost).is_zero());
+
+        // The same profit paid in ETH needs no unwrapping
+        let mut eth_side = opportunity(backrun());
+        eth_side.estimated_profit = weth_side.estimated_profit;
+        eth_side.gas_cost = U256::zero();
+        assert_eq!(eth_side.settled_profit(weth(), unwrap_cost), eth_side.estimated_profit);
+    }
+
+    #[test]
+    fn sandwiches_missing_leg_calldata_are_not_executable() {
+        assert!(opportunity(sandwich(weth(), Funding::OwnCapital)).is_executable());
+        assert!(opportunity(backrun()).is_executable());
+
+        let StrategyType::Sandwich(mut details) = sandwich(weth(), Funding::OwnCapital) else { unreachable!() };
+        // Priced, but without calldata
+        details.backrun_tx = TypedTransaction::default();
+        assert!(!opportunity(StrategyType::Sandwich(details)).is_executable());
+    }
+
+    #[test]
+    fn capital_counts_only_the_eth_a_sandwich_sends() {
+        assert_eq!(opportunity(sandwich(weth(), Funding::OwnCapital)).capital(), U256::from(5));
+        // Spends a token we hold, not ETH
+        assert!(opportunity(sandwich(Address::from_low_u64_be(0x05dc), Funding::OwnCapital)).capital().is_zero());
+        let borrowed = Funding::Flashloan { provider: FlashloanProvider::Balancer, fee: U256::zero() };
+        assert!(opportunity(sandwich(weth(), borrowed)).capital().is_zero());
+    }
+
+    #[test]
+    fn weight_by_probability_clamps_to_the_unit_range() {
+        assert_eq!(weight_by_probability(U256::from(10_000), 0.25), U256::from(2_500));
+        assert_eq!(weight_by_probability(U256::from(10_000), 1.5), U256::from(10_000));
+        assert_eq!(weight_by_probability(U256::from(10_000), -1.0), U256::zero());
+        assert_eq!(weight_by_probability(U256::MAX, 1.0), U256::MAX / 10_000);
+    }
+
+    #[test]
+    fn a_likely_small_win_outranks_an_unlikely_large_one() {
+        let mut likely = opportunity(backrun());
+        likely.landing_probability = 0.9;
+        let mut unlikely = opportunity(backrun());
+        unlikely.estimated_profit = U256::from(2_000);
+        unlikely.landing_probability = 0.3;
+
+        assert_eq!(likely.expected_value(), U256::from(720));
+        assert_eq!(unlikely.expected_value(), U256::from(540));
+        assert!(likely.expected_value() > unlikely.expected_value());
+    }
+}
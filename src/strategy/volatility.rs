@@ -0,0 +1,144 @@
+use ethers::prelude::*;
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use crate::settings::Settings;
+
+/// Weight of the newest block's squared return in the variance average.
+const EWMA_ALPHA: f64 = 0.1;
+
+#[derive(Debug, Clone, Copy)]
+struct TokenVolatility {
+    block: U64,
+    price: f64,
+    /// Moving average of squared per-block log returns; none until a second block is seen.
+    variance: Option<f64>,
+}
+
+/// Realized per-token volatility, from how a token's price moves between the
+/// blocks it is seen in, turned into the slippage its sandwich legs allow.
+///
+/// A return spanning several blocks counts as that many blocks' worth of
+/// variance, so sparsely traded tokens aren't overstated.
+#[derive(Debug)]
+pub struct VolatilityTracker {
+    tokens: Mutex<HashMap<Address, TokenVolatility>>,
+    multiplier: f64,
+    min_bps: u64,
+    max_bps: u64,
+    default_bps: u64,
+}
+
+impl VolatilityTracker {
+    pub fn new(multiplier: f64, min_bps: u64, max_bps: u64, default_bps: u64) -> Self {
+        Self { tokens: Mutex::new(HashMap::new()), multiplier, min_bps, max_bps, default_bps }
+    }
+
+    pub fn from_settings(settings: &Settings) -> Self {
+        Self::new(
+            settings.slippage_volatility_multiplier,
+            settings.slippage_min_bps,
+            settings.slippage_max_bps,
+            settings.slippage_default_bps,
+        )
+    }
+
+    /// Records `token` trading at `price` (in any fixed unit) at `block`. Later
+    /// reads in the same block replace the price; reads from older blocks are ignored.
+    pub fn observe(&self, token: Address, block: U64, price: f64) {
+        if !price.is_finite() || price <= 0.0 {
+            return;
+        }
+        let mut tokens = self.tokens.lock().unwrap();
+        let Some(entry) = tokens.get_mut(&token) else {
+            tokens.insert(token, TokenVolatility { block, price, variance: None });
+            return;
+        };
+        if block < entry.block {
+            return;
+        }
+        if block > entry.block {
+            let blocks = (block - entry.block).as_u64() as f64;
+            let per_block = (price / entry.price).ln().powi(2) / blocks;
+            entry.variance = Some(match entry.variance {
+                Some(variance) => variance + EWMA_ALPHA * (per_block - variance),
+                None => per_block,
+            });
+            entry.block = block;
+        }
+        entry.price = price;
+    }
+
+    /// Standard deviation of `token`'s per-block log return, once two blocks have been seen.
+    pub fn volatility(&self, token: Address) -> Option<f64> {
+        self.tokens.lock().unwrap().get(&token)?.variance.map(f64::sqrt)
+    }
+
+    /// Slippage, in bps, a leg trading `token` within one block should allow.
+    pub fn slippage_bps(&self, token: Address) -> u64 {
+        let bps = match self.volatility(token) {
+            Some(sigma) => ((sigma * self.multiplier * 10_000.0).round() as u64).max(self.min_bps).min(self.max_bps),
+            None => self.default_bps,
+        };
+        bps.min(10_000)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn token() -> Address {
+        Address::from_low_u64_be(1)
+    }
+
+    #[test]
+    fn unseen_tokens_get_the_default_slippage() {
+        let tracker = VolatilityTracker::new(3.0, 10, 1_000, 300);
+        assert_eq!(tracker.slippage_bps(token()), 300);
+        tracker.observe(token(), U64::from(1), 100.0);
+        // One block isn't a return yet
+        assert_eq!(tracker.volatility(token()), None);
+        assert_eq!(tracker.slippage_bps(token()), 300);
+    }
+
+    #[test]
+    fn slippage_scales_with_realized_volatility_within_bounds() {
+        let tracker = VolatilityTracker::new(5.0, 10, 1_000, 300);
+        tracker.observe(token(), U64::from(1), 100.0);
+        tracker.observe(token(), U64::from(2), 100.0 * 0.01f64.exp());
+        let sigma = tracker.volatility(token()).unwrap();
+        assert!((sigma - 0.01).abs() < 1e-12);
+        assert_eq!(tracker.slippage_bps(token()), 500);
+
+        let calm = Address::from_low_u64_be(2);
+        tracker.observe(calm, U64::from(1), 100.0);
+        tracker.observe(calm, U64::from(2), 100.0);
+        assert_eq!(tracker.slippage_bps(calm), 10);
+
+        let wild = Address::from_low_u64_be(3);
+        tracker.observe(wild, U64::from(1), 100.0);
+        tracker.observe(wild, U64::from(2), 200.0);
+        assert_eq!(tracker.slippage_bps(wild), 1_000);
+    }
+
+    #[test]
+    fn a_return_over_several_blocks_counts_per_block() {
+        let tracker = VolatilityTracker::new(1.0, 0, 10_000, 0);
+        tracker.observe(token(), U64::from(1), 100.0);
+        tracker.observe(token(), U64::from(5), 100.0 * 0.02f64.exp());
+        // 0.02 over four blocks is 0.0001 of variance per block
+        assert!((tracker.volatility(token()).unwrap() - 0.01).abs() < 1e-12);
+    }
+
+    #[test]
+    fn stale_and_invalid_prices_are_ignored() {
+        let tracker = VolatilityTracker::new(1.0, 0, 10_000, 0);
+        tracker.observe(token(), U64::from(5), 100.0);
+        tracker.observe(token(), U64::from(4), 1.0);
+        tracker.observe(token(), U64::from(6), f64::NAN);
+        tracker.observe(token(), U64::from(6), 0.0);
+        tracker.observe(token(), U64::from(6), 100.0);
+        assert_eq!(tracker.volatility(token()), Some(0.0));
+    }
+}
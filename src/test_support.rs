@@ -0,0 +1,169 @@
+//! Offline stand-ins for the node, relay and WebSocket feed, so tests can build
+//! a real `Config` and drive strategies without a network.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use ethers::prelude::*;
+use futures_util::{SinkExt, StreamExt};
+use mockito::{Matcher, Mock, ServerGuard};
+use serde_json::{json, Value};
+use tokio::net::TcpListener;
+use tokio::sync::broadcast;
+use tokio_tungstenite::tungstenite::Message;
+
+use crate::block_height::BlockHeight;
+use crate::chain::ChainConfig;
+use crate::gas_model::GasModel;
+use crate::gas_oracle::GasOracle;
+use crate::pool_cache::PoolCache;
+use crate::rpc::RetryHttp;
+use crate::settings::Settings;
+use crate::signer::{BotSigner, SignerClient};
+use crate::strategy::{BidPolicy, BlockSource, BundleBuilder, BundleBuilderParams, NonceManager};
+use crate::token_info::TokenInfoResolver;
+use crate::Config;
+
+/// Private key of the test wallet, which signs as the bot.
+pub const KEY: &str = "4c0883a69102937d6231471b5dbb6204fe5129617082792ae468d01a3f362318";
+
+pub fn wallet() -> LocalWallet {
+    KEY.parse::<LocalWallet>().unwrap().with_chain_id(1u64)
+}
+
+/// Settings that fail fast instead of retrying against a mock, and keep no ledger.
+pub fn settings() -> Settings {
+    Settings { rpc_max_retries: 0, ledger_path: None, ..Settings::default() }
+}
+
+/// A JSON-RPC node (or relay) answering each method with a canned result.
+pub struct MockNode {
+    pub server: ServerGuard,
+    /// Kept alive, as dropping a mock unregisters it.
+    mocks: Vec<Mock>,
+}
+
+impl MockNode {
+    pub async fn new() -> Self {
+        Self { server: mockito::Server::new_async().await, mocks: Vec::new() }
+    }
+
+    pub fn url(&self) -> String {
+        self.server.url()
+    }
+
+    /// Answers every `method` request with `result`.
+    pub async fn rpc(&mut self, method: &str, result: Value) {
+        self.rpc_matching(json!({ "method": method }), result).await;
+    }
+
+    /// Answers every request `request` partially matches (e.g. a method and
+    /// its exact params) with `result`.
+    pub async fn rpc_matching(&mut self, request: Value, result: Value) {
+        let body = json!({ "jsonrpc": "2.0", "id": 1, "result": result });
+        let mock = self
+            .server
+            .mock("POST", "/")
+            .match_body(Matcher::PartialJson(request))
+            .with_header("content-type", "application/json")
+            .with_body(body.to_string())
+            .create_async()
+            .await;
+        self.mocks.push(mock);
+    }
+
+    /// Answers every `method` request with a JSON-RPC error.
+    pub async fn rpc_error(&mut self, method: &str, code: i64, message: &str) {
+        let body = json!({ "jsonrpc": "2.0", "id": 1, "error": { "code": code, "message": message } });
+        let mock = self
+            .server
+            .mock("POST", "/")
+            .match_body(Matcher::PartialJson(json!({ "method": method })))
+            .with_header("content-type", "application/json")
+            .with_body(body.to_string())
+            .create_async()
+            .await;
+        self.mocks.push(mock);
+    }
+}
+
+/// Our signing client against the node at `url`, as the test wallet on mainnet.
+pub fn signer_client(url: &str) -> Arc<SignerClient> {
+    let provider = Provider::new(RetryHttp::from_settings(url.parse::<Http>().unwrap(), &settings()));
+    Arc::new(SignerMiddleware::new(provider, BotSigner::new(wallet())))
+}
+
+/// A WebSocket endpoint answering requests by method from `results`, as a
+/// node's would; anything else gets an error.
+pub async fn ws_node(results: HashMap<String, Value>) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("ws://{}", listener.local_addr().unwrap());
+    let results = Arc::new(results);
+    tokio::spawn(async move {
+        while let Ok((stream, _)) = listener.accept().await {
+            let results = results.clone();
+            tokio::spawn(async move {
+                let Ok(mut ws) = tokio_tungstenite::accept_async(stream).await else {
+                    return;
+                };
+                while let Some(Ok(Message::Text(text))) = ws.next().await {
+                    let request: Value = serde_json::from_str(&text).unwrap_or_default();
+                    let mut response = match results.get(request["method"].as_str().unwrap_or_default()) {
+                        Some(result) => json!({ "result": result }),
+                        None => json!({ "error": { "code": -32601, "message": "method not found" } }),
+                    };
+                    response["jsonrpc"] = json!("2.0");
+                    response["id"] = request["id"].clone();
+                    if ws.send(Message::Text(response.to_string())).await.is_err() {
+                        return;
+                    }
+                }
+            });
+        }
+    });
+    url
+}
+
+/// A mainnet `Config` whose HTTP provider is the node at `http_url` and whose
+/// WebSocket feed is `ws_node(ws_results)`, at block `block`.
+pub async fn config_with(http_url: &str, ws_results: HashMap<String, Value>, settings: Settings, block: u64) -> Config {
+    let http = signer_client(http_url);
+    let wss = Provider::<Ws>::connect(ws_node(ws_results).await).await.unwrap();
+    let block_height = Arc::new(BlockHeight::new(U64::from(block)));
+    let gas_oracle = Arc::new(GasOracle::new(settings.gas.oracle_window_blocks));
+    Config {
+        dexes: Vec::new(),
+        pool_cache: Arc::new(PoolCache::new(http.clone(), block_height.clone())),
+        token_info: Arc::new(TokenInfoResolver::new(http.clone(), settings.token_info_cache_size)),
+        http,
+        wss: Arc::new(wss),
+        block_height,
+        gas_model: Arc::new(GasModel::from_settings(&settings, gas_oracle.clone())),
+        gas_oracle,
+        reorgs: broadcast::channel(16).0,
+        heads: broadcast::channel(16).0,
+        settings,
+        chain: ChainConfig::for_chain_id(1).unwrap(),
+    }
+}
+
+/// `config_with` and a WebSocket feed that answers nothing.
+pub async fn config(http_url: &str, settings: Settings) -> Config {
+    config_with(http_url, HashMap::new(), settings, 100).await
+}
+
+/// A bundle builder for `config`'s wallet, submitting to the relay at `relay`.
+pub fn bundle_builder(config: &Config, relay: &str) -> BundleBuilder {
+    BundleBuilder::new(BundleBuilderParams {
+        provider: config.http.clone(),
+        ws_provider: config.wss.clone(),
+        block_source: BlockSource::Http,
+        gas_oracle: config.gas_oracle.clone(),
+        relay: relay.to_string(),
+        relay_auth: config.settings.relay.auth(relay),
+        nonces: Arc::new(NonceManager::new(config.http.clone(), false)),
+        arb_executor: None,
+        weth: config.chain.weth,
+        bid_policy: BidPolicy::from_settings(&config.settings),
+    })
+}
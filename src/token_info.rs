@@ -0,0 +1,159 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use ethers::prelude::{*};
+
+use crate::address_book::ERC20;
+use crate::signer::SignerClient;
+
+/// Decimals assumed for a token whose `decimals()` can't be read.
+pub const DEFAULT_DECIMALS: u8 = 18;
+
+/// Metadata needed to size and display amounts of an ERC20.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TokenInfo {
+    pub decimals: u8,
+    pub symbol: String,
+}
+
+impl TokenInfo {
+    /// `amount` in whole tokens, e.g. `1500.25 USDC`.
+    pub fn format(&self, amount: U256) -> String {
+        let units = ethers::utils::format_units(amount, self.decimals as u32).unwrap_or_else(|_| amount.to_string());
+        format!("{} {}", units, self.symbol)
+    }
+
+    /// Rescales an amount given with 18 decimals (as ETH-denominated limits are)
+    /// to this token's decimals.
+    pub fn from_18_decimals(&self, amount: U256) -> U256 {
+        let decimals = self.decimals as usize;
+        if decimals >= 18 {
+            amount.saturating_mul(U256::exp10(decimals - 18))
+        } else {
+            amount / U256::exp10(18 - decimals)
+        }
+    }
+}
+
+/// Reads `decimals()`/`symbol()` on demand, keeping the most recently used
+/// `capacity` tokens. Tokens whose `decimals()` fails aren't cached, so a
+/// flaky RPC doesn't pin them to `DEFAULT_DECIMALS`.
+#[derive(Debug)]
+pub struct TokenInfoResolver {
+    provider: Arc<SignerClient>,
+    cache: Mutex<TokenInfoLru>,
+}
+
+impl TokenInfoResolver {
+    pub fn new(provider: Arc<SignerClient>, capacity: usize) -> Self {
+        Self {
+            provider,
+            cache: Mutex::new(TokenInfoLru::new(capacity)),
+        }
+    }
+
+    pub async fn resolve(&self, token: Address) -> TokenInfo {
+        if let Some(info) = self.cache.lock().unwrap().get(token) {
+            return info;
+        }
+
+        let contract = ERC20::new(token, self.provider.clone());
+        let Ok(decimals) = contract.decimals().call().await else {
+            return TokenInfo { decimals: DEFAULT_DECIMALS, symbol: format!("{:?}", token) };
+        };
+        // Some old tokens (MKR, SAI) return a bytes32 symbol, which doesn't decode as a string
+        let symbol = contract.symbol().call().await.unwrap_or_else(|_| format!("{:?}", token));
+
+        let info = TokenInfo { decimals, symbol };
+        self.cache.lock().unwrap().insert(token, info.clone());
+        info
+    }
+
+    pub async fn decimals(&self, token: Address) -> u8 {
+        self.resolve(token).await.decimals
+    }
+}
+
+/// Least-recently-used map of token metadata. Eviction scans for the oldest
+/// entry, which is cheap at the few thousand tokens this holds.
+#[derive(Debug)]
+pub struct TokenInfoLru {
+    capacity: usize,
+    tick: u64,
+    entries: HashMap<Address, (u64, TokenInfo)>,
+}
+
+impl TokenInfoLru {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            tick: 0,
+            entries: HashMap::new(),
+        }
+    }
+
+    pub fn get(&mut self, token: Address) -> Option<TokenInfo> {
+        self.tick += 1;
+        let (last_used, info) = self.entries.get_mut(&token)?;
+        *last_used = self.tick;
+        Some(info.clone())
+    }
+
+    pub fn insert(&mut self, token: Address, info: TokenInfo) {
+        if self.capacity == 0 {
+            return;
+        }
+        if !self.entries.contains_key(&token) && self.entries.len() >= self.capacity {
+            let oldest = self.entries.iter().min_by_key(|(_, (last_used, _))| *last_used).map(|(token, _)| *token);
+            if let Some(oldest) = oldest {
+                self.entries.remove(&oldest);
+            }
+        }
+        self.tick += 1;
+        self.entries.insert(token, (self.tick, info));
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn usdc() -> TokenInfo {
+        TokenInfo { decimals: 6, symbol: "USDC".to_string() }
+    }
+
+    #[test]
+    fn amounts_format_and_rescale_by_decimals() {
+        assert_eq!(usdc().format(U256::from(1_500_250_000u64)), "1500.250000 USDC");
+        assert_eq!(usdc().from_18_decimals(U256::exp10(18)), U256::exp10(6));
+        let wide = TokenInfo { decimals: 24, symbol: "WIDE".to_string() };
+        assert_eq!(wide.from_18_decimals(U256::exp10(18)), U256::exp10(24));
+    }
+
+    #[test]
+    fn lru_evicts_the_least_recently_used_token() {
+        let token = Address::from_low_u64_be;
+        let mut lru = TokenInfoLru::new(2);
+        lru.insert(token(1), usdc());
+        lru.insert(token(2), usdc());
+        // Reading token 1 makes token 2 the oldest
+        assert!(lru.get(token(1)).is_some());
+        lru.insert(token(3), usdc());
+
+        assert_eq!(lru.len(), 2);
+        assert!(lru.get(token(2)).is_none());
+        assert!(lru.get(token(1)).is_some() && lru.get(token(3)).is_some());
+
+        let mut disabled = TokenInfoLru::new(0);
+        disabled.insert(token(1), usdc());
+        assert!(disabled.is_empty());
+    }
+}
@@ -30,25 +30,285 @@ pub fn get_amount_out(a_in: U256, reserve_in: U256, reserve_out: U256) -> (U256,
     (a_out, new_reserve_in, new_reserve_out)
 }
 
+// Same as `get_amount_out`, for pools charging `fee_ppm` (hundredths of a bip, 3000 = 0.3%).
+// Also prices a V3 pool within its current tick range when given its virtual reserves.
+pub fn get_amount_out_with_fee(a_in: U256, reserve_in: U256, reserve_out: U256, fee_ppm: u32) -> (U256, U256, U256) {
+    let a_in_with_fee = a_in * (1_000_000 - fee_ppm);
+    let numerator = a_in_with_fee * reserve_out;
+    let denominator = a_in_with_fee + reserve_in * 1_000_000;
+    if denominator.is_zero() {
+        return (U256::zero(), reserve_in, reserve_out);
+    }
+    let a_out = numerator / denominator;
+
+    let new_reserve_out = reserve_out.saturating_sub(a_out).max(U256::one());
+    let new_reserve_in = reserve_in.saturating_add(a_in);
+
+    (a_out, new_reserve_in, new_reserve_out)
+}
+
 // Returns the minimum input asset amount required to buy the given output asset amount (accounting for fees) given reserves.
 // Uniswap v2; x * y = k formula
-// How much out do we get if we supply out?
+// How much in do we need to get out?
+// Rounds up, so `get_amount_out(get_amount_in(a_out))` never falls short of `a_out`.
+// Asking for the whole reserve (or more) can't be filled: returns U256::MAX.
 pub fn get_amount_in(a_out: U256, reserve_in: U256, reserve_out: U256) -> (U256, U256, U256) {
-    // Underflow
-    let mut new_reserve_out = reserve_out - a_out;
-    if new_reserve_out < U256::zero() || reserve_out > reserve_out {
-        new_reserve_out = U256::one();
+    get_amount_in_with_fee(a_out, reserve_in, reserve_out, 3000)
+}
+
+// Same as `get_amount_in`, for pools charging `fee_ppm` (hundredths of a bip, 3000 = 0.3%).
+pub fn get_amount_in_with_fee(a_out: U256, reserve_in: U256, reserve_out: U256, fee_ppm: u32) -> (U256, U256, U256) {
+    if a_out >= reserve_out || fee_ppm >= 1_000_000 {
+        return (U256::MAX, U256::MAX, U256::one());
     }
+    let new_reserve_out = reserve_out - a_out;
 
-    let numerator = reserve_in * a_out * 1000;
-    let denominator = new_reserve_out * 997;
-    let a_amount_in = numerator / denominator + U256::one();
+    let numerator = U512::from(reserve_in) * U512::from(a_out) * U512::from(1_000_000u64);
+    let denominator = U512::from(new_reserve_out) * U512::from(1_000_000 - fee_ppm);
+    let a_amount_in = U256::try_from(numerator / denominator + U512::one()).unwrap_or(U256::MAX);
 
-    // Overflow
-    let mut new_reserve_in = reserve_in + a_amount_in;
-    if new_reserve_in < reserve_in {
-        new_reserve_in = U256::MAX;
-    }
+    let new_reserve_in = reserve_in.saturating_add(a_amount_in);
 
     (a_amount_in, new_reserve_in, new_reserve_out)
-}
\ No newline at end of file
+}
+
+// Closed-form optimal input for a two-pool cycle: buy on pool A, sell the proceeds back on pool B.
+// Collapses both hops into one virtual constant-product pool (E0, E1) and maximizes
+// out(x) - x, giving x* = (sqrt(g_a * E0 * E1) - E0) / g_a. Fees are in basis points.
+// Returns zero when the cycle isn't profitable.
+pub fn optimal_arb_input(
+    buy_reserve_in: U256,
+    buy_reserve_out: U256,
+    buy_fee_bps: u16,
+    sell_reserve_in: U256,
+    sell_reserve_out: U256,
+    sell_fee_bps: u16,
+) -> U256 {
+    let bps = U512::from(10_000u64);
+    let g_a = U512::from(10_000 - buy_fee_bps as u64);
+    let g_b = U512::from(10_000 - sell_fee_bps as u64);
+
+    let (a_in, a_out) = (U512::from(buy_reserve_in), U512::from(buy_reserve_out));
+    let (b_in, b_out) = (U512::from(sell_reserve_in), U512::from(sell_reserve_out));
+
+    let denominator = b_in * bps + g_b * a_out;
+    if denominator.is_zero() {
+        return U256::zero();
+    }
+
+    // Virtual reserves of the combined route
+    let e0 = a_in * b_in * bps / denominator;
+    let e1 = g_b * a_out * b_out / denominator;
+
+    let root = (g_a * e0 * e1 / bps).integer_sqrt();
+    if root <= e0 {
+        return U256::zero();
+    }
+
+    U256::try_from((root - e0) * bps / g_a).unwrap_or(U256::MAX)
+}
+
+/// `amount_in` as a fraction of `reserve_in`, the price impact a sandwich reports.
+/// Worked out on U256 so reserves past `u64` (about 18 ETH) and beyond don't panic.
+pub fn price_impact(amount_in: U256, reserve_in: U256) -> f64 {
+    if reserve_in.is_zero() {
+        return 0.0;
+    }
+    const SCALE: u64 = 1_000_000_000_000;
+    let scaled = amount_in.full_mul(U256::from(SCALE)) / U512::from(reserve_in);
+    if scaled > U512::from(u128::MAX) {
+        return f64::MAX;
+    }
+    scaled.low_u128() as f64 / SCALE as f64
+}
+
+/// Largest frontrun a sandwich puts into a pool holding `reserve_in`: `max_reserve_bps`
+/// of it. Past that the price impact leaves the backrun little room to land.
+pub fn max_frontrun(reserve_in: U256, max_reserve_bps: u64) -> U256 {
+    reserve_in * U256::from(max_reserve_bps.min(10_000)) / 10_000
+}
+
+// Closed-form optimal frontrun for a sandwich on a constant-product pool charging `fee_ppm`
+// on every leg. With g = 1 - fee, a = reserve_in and v the victim's input, the profit
+// derivative changes sign at the positive root of
+//   A x^2 + B x + C, where A = a(1 - g^2) - g^2 v(1 - g), B = 2a(a(1 - g^2) - g^2 v),
+//   C = a^3(1 - g^2) - a^2 g^2 v(1 + g) - a g^3 v^2.
+// The output-side reserve cancels out. Returns zero when no frontrun is profitable,
+// U256::MAX when profit keeps growing with size (a victim around twice the pool), and
+// None if the terms overflow.
+pub fn optimal_sandwich_input(victim_amount_in: U256, reserve_in: U256, fee_ppm: u32) -> Option<U256> {
+    if fee_ppm >= 1_000_000 {
+        return None;
+    }
+    let d = U512::from(1_000_000u64);
+    let n = U512::from(1_000_000 - fee_ppm as u64);
+    let (a, v) = (U512::from(reserve_in), U512::from(victim_amount_in));
+
+    // 1 - g^2 and friends, scaled by d^3
+    let fee_term = d.checked_mul(d * d - n * n)?;
+    let a_fee = a.checked_mul(fee_term)?;
+
+    // Each coefficient as (positive part, negative part)
+    let a_pos = a_fee;
+    let a_neg = (n * n).checked_mul(v)?.checked_mul(d - n)?;
+    let b_pos = a.checked_mul(a_fee)?.checked_mul(U512::from(2u8))?;
+    let b_neg = a.checked_mul(d)?.checked_mul(n * n)?.checked_mul(v)?.checked_mul(U512::from(2u8))?;
+    let c_pos = a.checked_mul(a)?.checked_mul(a_fee)?;
+    let c_neg = a
+        .checked_mul(a)?
+        .checked_mul(v)?
+        .checked_mul(n * n)?
+        .checked_mul(d + n)?
+        .checked_add(a.checked_mul(v)?.checked_mul(v)?.checked_mul(n * n * n)?)?;
+
+    // The first unit of frontrun already loses money
+    if c_pos >= c_neg {
+        return Some(U256::zero());
+    }
+    if a_pos <= a_neg {
+        return Some(U256::MAX);
+    }
+
+    let a_coef = a_pos - a_neg;
+    let c_abs = c_neg - c_pos;
+    let (b_abs, b_negative) = if b_pos >= b_neg { (b_pos - b_neg, false) } else { (b_neg - b_pos, true) };
+    let discriminant = b_abs.checked_mul(b_abs)?.checked_add(a_coef.checked_mul(c_abs)?.checked_mul(U512::from(4u8))?)?;
+    let root = discriminant.integer_sqrt();
+    let numerator = if b_negative { root.checked_add(b_abs)? } else { root - b_abs };
+
+    Some(U256::try_from(numerator / (a_coef * 2)).unwrap_or(U256::MAX))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethers::utils::parse_ether;
+
+    #[test]
+    fn get_amount_in_round_trips_through_get_amount_out() {
+        let (reserve_in, reserve_out) = (parse_ether(1_000).unwrap(), U256::from(2_000_000_000_000u64));
+        for a_out in [U256::one(), U256::from(1_000_000u64), U256::from(999_999_999_999u64)] {
+            for fee_ppm in [500, 3000, 10_000] {
+                let (a_in, _, _) = get_amount_in_with_fee(a_out, reserve_in, reserve_out, fee_ppm);
+                // Never short of the output asked for, and rounded up by no more than a wei
+                assert!(get_amount_out_with_fee(a_in, reserve_in, reserve_out, fee_ppm).0 >= a_out);
+                assert!(get_amount_out_with_fee(a_in - 1, reserve_in, reserve_out, fee_ppm).0 <= a_out);
+            }
+        }
+        assert_eq!(get_amount_in(reserve_out, reserve_in, reserve_out).0, U256::MAX);
+    }
+
+    #[test]
+    fn price_impact_handles_reserves_past_u64() {
+        let reserve_in = parse_ether(1_000).unwrap();
+        assert!(reserve_in > U256::from(u64::MAX));
+
+        let impact = price_impact(parse_ether(10).unwrap(), reserve_in);
+        assert!((impact - 0.01).abs() < 1e-9);
+        assert_eq!(price_impact(U256::zero(), reserve_in), 0.0);
+        assert_eq!(price_impact(U256::one(), U256::zero()), 0.0);
+    }
+
+    /// Our profit in the input token from frontrunning a `victim` swap with `x`
+    /// and selling everything the frontrun bought straight after it.
+    fn sandwich_profit(x: U256, victim: U256, reserve_in: U256, reserve_out: U256, fee_ppm: u32) -> I256 {
+        let (bought, reserve_in, reserve_out) = get_amount_out_with_fee(x, reserve_in, reserve_out, fee_ppm);
+        let (_, reserve_in, reserve_out) = get_amount_out_with_fee(victim, reserve_in, reserve_out, fee_ppm);
+        let (sold, _, _) = get_amount_out_with_fee(bought, reserve_out, reserve_in, fee_ppm);
+        I256::from_raw(sold) - I256::from_raw(x)
+    }
+
+    #[test]
+    fn optimal_sandwich_input_matches_a_brute_force_scan() {
+        let eth = |n: u64| parse_ether(n).unwrap();
+        // (reserve_in, reserve_out, victim, fee_ppm)
+        let cases = [
+            (eth(1_000), U256::from(2_000_000_000_000u64), eth(30), 3000),
+            (eth(50), eth(50), eth(2), 3000),
+            (eth(500), eth(1_000_000), eth(20), 500),
+            (eth(10_000), eth(10_000), eth(400), 10_000),
+        ];
+        const STEPS: u64 = 4_000;
+
+        for (reserve_in, reserve_out, victim, fee_ppm) in cases {
+            let optimal = optimal_sandwich_input(victim, reserve_in, fee_ppm).unwrap();
+            assert!(!optimal.is_zero() && optimal != U256::MAX, "no finite optimum for {:?}", (reserve_in, victim, fee_ppm));
+
+            // Scan up to twice the closed form, so the optimum sits mid-range
+            let step = optimal * 2 / STEPS;
+            let (best_x, best_profit) = (1..=STEPS)
+                .map(|i| step * i)
+                .map(|x| (x, sandwich_profit(x, victim, reserve_in, reserve_out, fee_ppm)))
+                .max_by_key(|(_, profit)| *profit)
+                .unwrap();
+
+            let closed_form_profit = sandwich_profit(optimal, victim, reserve_in, reserve_out, fee_ppm);
+            // Within rounding of the best scanned input, and no worse than it
+            let rounding = I256::from(1_000_000u64);
+            assert!(closed_form_profit + rounding >= best_profit, "{} < {} for {:?}", closed_form_profit, best_profit, (reserve_in, victim, fee_ppm));
+            let distance = if best_x > optimal { best_x - optimal } else { optimal - best_x };
+            assert!(distance <= step, "scan peaked at {}, closed form at {}", best_x, optimal);
+        }
+    }
+
+    #[test]
+    fn optimal_sandwich_input_is_zero_when_no_frontrun_pays() {
+        let reserve_in = parse_ether(1_000).unwrap();
+        let victim = parse_ether(1).unwrap();
+        assert_eq!(optimal_sandwich_input(victim, reserve_in, 3000), Some(U256::zero()));
+        // Any frontrun at all loses to the fees
+        let profit = sandwich_profit(victim / 10, victim, reserve_in, reserve_in, 3000);
+        assert!(profit.is_negative());
+    }
+
+    /// What buying on `(a_in, a_out)` with `x` and selling the proceeds on `(b_in, b_out)` nets.
+    fn arb_profit(x: U256, (a_in, a_out, a_fee): (U256, U256, u32), (b_in, b_out, b_fee): (U256, U256, u32)) -> I256 {
+        let (bought, _, _) = get_amount_out_with_fee(x, a_in, a_out, a_fee);
+        let (sold, _, _) = get_amount_out_with_fee(bought, b_in, b_out, b_fee);
+        I256::from_raw(sold) - I256::from_raw(x)
+    }
+
+    #[test]
+    fn optimal_arb_input_matches_a_brute_force_scan() {
+        let eth = |n: u64| parse_ether(n).unwrap();
+        // Pool A prices the token ~5% cheaper than pool B; (reserve_in, reserve_out, fee_ppm)
+        let cases = [
+            ((eth(1_000), eth(2_100_000), 3000), (eth(2_000_000), eth(1_000), 3000)),
+            ((eth(40), eth(42), 3000), (eth(400), eth(400), 500)),
+        ];
+        const STEPS: u64 = 4_000;
+
+        for (buy, sell) in cases {
+            let optimal = optimal_arb_input(buy.0, buy.1, (buy.2 / 100) as u16, sell.0, sell.1, (sell.2 / 100) as u16);
+            assert!(!optimal.is_zero());
+
+            let step = optimal * 2 / STEPS;
+            let (best_x, best_profit) = (1..=STEPS)
+                .map(|i| step * i)
+                .map(|x| (x, arb_profit(x, buy, sell)))
+                .max_by_key(|(_, profit)| *profit)
+                .unwrap();
+
+            let closed_form_profit = arb_profit(optimal, buy, sell);
+            assert!(closed_form_profit.is_positive());
+            assert!(closed_form_profit + I256::from(1_000_000u64) >= best_profit, "{} < {}", closed_form_profit, best_profit);
+            let distance = if best_x > optimal { best_x - optimal } else { optimal - best_x };
+            assert!(distance <= step, "scan peaked at {}, closed form at {}", best_x, optimal);
+        }
+    }
+
+    #[test]
+    fn optimal_arb_input_is_zero_without_a_profitable_gap() {
+        let eth = |n: u64| parse_ether(n).unwrap();
+        // 0.1% apart, less than the two 0.3% fees
+        assert!(optimal_arb_input(eth(1_000), eth(1_001_000), 30, eth(1_000_000), eth(1_000), 30).is_zero());
+    }
+
+    #[test]
+    fn max_frontrun_is_a_capped_share_of_the_reserve() {
+        let reserve = parse_ether(1_000).unwrap();
+        assert_eq!(max_frontrun(reserve, 500), parse_ether(50).unwrap());
+        assert_eq!(max_frontrun(reserve, 50_000), reserve);
+        assert!(max_frontrun(reserve, 0).is_zero());
+    }
+}
@@ -0,0 +1,361 @@
+#![allow(dead_code)]
+use ethers::abi::AbiDecode;
+use ethers::prelude::*;
+
+use crate::address_book::{QuoteExactInputSingleParams, QuoterV2, UniV3RouterCalls};
+
+// ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+//  Uniswap V3
+//  - Reference: https://docs.uniswap.org/contracts/v3/reference/periphery/libraries/Path
+// ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+
+const ADDR_SIZE: usize = 20;
+const FEE_SIZE: usize = 3;
+
+// Decodes a V3 encoded path (token ++ fee ++ token ++ fee ++ token ...) into its tokens and fee tiers.
+// Returns None if the path is malformed.
+pub fn decode_path(path: &[u8]) -> Option<(Vec<Address>, Vec<u32>)> {
+    if path.len() < ADDR_SIZE || !(path.len() - ADDR_SIZE).is_multiple_of(ADDR_SIZE + FEE_SIZE) {
+        return None;
+    }
+
+    let mut tokens = vec![Address::from_slice(&path[..ADDR_SIZE])];
+    let mut fees = Vec::new();
+    let mut offset = ADDR_SIZE;
+    while offset < path.len() {
+        let fee = &path[offset..offset + FEE_SIZE];
+        fees.push(u32::from_be_bytes([0, fee[0], fee[1], fee[2]]));
+        offset += FEE_SIZE;
+        tokens.push(Address::from_slice(&path[offset..offset + ADDR_SIZE]));
+        offset += ADDR_SIZE;
+    }
+
+    Some((tokens, fees))
+}
+
+// ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+//  Quoting
+//  - Reference: https://docs.uniswap.org/contracts/v3/reference/core/libraries/SqrtPriceMath
+// ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+
+/// Fee tiers (hundredths of a bip) deployed by the canonical V3 factory.
+pub const FEE_TIERS: [u32; 3] = [500, 3000, 10000];
+
+fn q96() -> U512 {
+    U512::one() << 96
+}
+
+fn to_u256(value: U512) -> U256 {
+    U256::try_from(value).unwrap_or(U256::MAX)
+}
+
+// Given an input amount, returns the output of a swap that stays inside the current tick range.
+// Ignores tick crossings, so it overestimates output for swaps large relative to in-range liquidity.
+// `fee` is in hundredths of a bip (3000 = 0.3%).
+pub fn get_amount_out(a_in: U256, sqrt_price_x96: U256, liquidity: u128, fee: u32, zero_for_one: bool) -> U256 {
+    if liquidity == 0 || sqrt_price_x96.is_zero() {
+        return U256::zero();
+    }
+
+    let a_in = U512::from(a_in) * U512::from(1_000_000 - fee) / U512::from(1_000_000u64);
+    let l = U512::from(liquidity);
+    let sqrt_p = U512::from(sqrt_price_x96);
+
+    if zero_for_one {
+        // token0 in: sqrtP' = L * sqrtP / (L + amountIn * sqrtP / Q96)
+        let denominator = l * q96() + a_in * sqrt_p;
+        let new_sqrt_p = l * q96() * sqrt_p / denominator;
+        to_u256(l * (sqrt_p - new_sqrt_p) / q96())
+    } else {
+        // token1 in: sqrtP' = sqrtP + amountIn * Q96 / L
+        let new_sqrt_p = sqrt_p + a_in * q96() / l;
+        to_u256(l * q96() * (new_sqrt_p - sqrt_p) / (new_sqrt_p * sqrt_p))
+    }
+}
+
+// Virtual (V2-equivalent) reserves of the current tick range: x = L / sqrtP, y = L * sqrtP.
+// Lets constant-product price comparisons treat V2 and V3 pools alike.
+pub fn virtual_reserves(sqrt_price_x96: U256, liquidity: u128) -> (U256, U256) {
+    if sqrt_price_x96.is_zero() {
+        return (U256::zero(), U256::zero());
+    }
+
+    let l = U512::from(liquidity);
+    let sqrt_p = U512::from(sqrt_price_x96);
+    (to_u256(l * q96() / sqrt_p), to_u256(l * sqrt_p / q96()))
+}
+
+/// Quotes an exact-input single-pool swap against the QuoterV2 at `quoter`.
+pub async fn quote_exact_input_single<M: Middleware + 'static>(
+    client: std::sync::Arc<M>,
+    quoter: Address,
+    token_in: Address,
+    token_out: Address,
+    fee: u32,
+    amount_in: U256,
+) -> Option<U256> {
+    let quoter = QuoterV2::new(quoter, client);
+    let params = QuoteExactInputSingleParams {
+        token_in,
+        token_out,
+        amount_in,
+        fee,
+        sqrt_price_limit_x96: U256::zero(),
+    };
+
+    quoter
+        .quote_exact_input_single(params)
+        .call()
+        .await
+        .ok()
+        .map(|(amount_out, _, _, _)| amount_out)
+}
+
+// ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+//  Router calls
+//  - Reference: https://docs.uniswap.org/contracts/v3/reference/periphery/SwapRouter
+// ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+
+// SwapRouter02 reuses SwapRouter's struct names without the deadline, so it is bound here
+abigen!(SwapRouter02, "src/abi/SwapRouter02.json");
+
+/// One swap made through a V3 router, whichever router and call it came in.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RouterSwap {
+    /// Tokens in trade order, input first (exact-output paths are un-reversed).
+    pub tokens: Vec<Address>,
+    /// Fee tier of each hop.
+    pub fees: Vec<u32>,
+    /// Exact input: the amount sold. Exact output: the most the swapper pays.
+    pub amount_in: U256,
+    /// Exact input: the least the swapper accepts. Exact output: the amount bought.
+    pub amount_out: U256,
+    pub exact_output: bool,
+    /// SwapRouter02 calls only carry one on their enclosing multicall.
+    pub deadline: Option<U256>,
+}
+
+impl RouterSwap {
+    pub fn token_in(&self) -> Address {
+        self.tokens[0]
+    }
+
+    pub fn token_out(&self) -> Address {
+        self.tokens[self.tokens.len() - 1]
+    }
+
+    pub fn is_single_hop(&self) -> bool {
+        self.fees.len() == 1
+    }
+}
+
+// Decodes the swaps of a SwapRouter or SwapRouter02 call, looking inside `multicall`.
+// Anything else in a multicall (unwrapping, refunds, ...) is skipped.
+pub fn decode_router_swaps(input: &[u8]) -> Vec<RouterSwap> {
+    decode_router_call(input, None, true)
+}
+
+fn decode_router_call(input: &[u8], deadline: Option<U256>, nested: bool) -> Vec<RouterSwap> {
+    if let Ok(call) = UniV3RouterCalls::decode(input) {
+        let swap = match call {
+            UniV3RouterCalls::ExactInputSingle(c) => single_hop(
+                c.params.token_in,
+                c.params.token_out,
+                c.params.fee,
+                c.params.amount_in,
+                c.params.amount_out_minimum,
+                false,
+                Some(c.params.deadline),
+            ),
+            UniV3RouterCalls::ExactOutputSingle(c) => single_hop(
+                c.params.token_in,
+                c.params.token_out,
+                c.params.fee,
+                c.params.amount_in_maximum,
+                c.params.amount_out,
+                true,
+                Some(c.params.deadline),
+            ),
+            UniV3RouterCalls::ExactInput(c) => {
+                multi_hop(&c.params.path, c.params.amount_in, c.params.amount_out_minimum, false, Some(c.params.deadline))
+            }
+            UniV3RouterCalls::ExactOutput(c) => {
+                multi_hop(&c.params.path, c.params.amount_in_maximum, c.params.amount_out, true, Some(c.params.deadline))
+            }
+            UniV3RouterCalls::Multicall(c) if nested => return decode_multicall(&c.data, deadline),
+            UniV3RouterCalls::Multicall(_) => None,
+        };
+        return swap.into_iter().collect();
+    }
+
+    let swap = match SwapRouter02Calls::decode(input) {
+        Ok(SwapRouter02Calls::ExactInputSingle(c)) => single_hop(
+            c.params.token_in,
+            c.params.token_out,
+            c.params.fee,
+            c.params.amount_in,
+            c.params.amount_out_minimum,
+            false,
+            deadline,
+        ),
+        Ok(SwapRouter02Calls::ExactOutputSingle(c)) => single_hop(
+            c.params.token_in,
+            c.params.token_out,
+            c.params.fee,
+            c.params.amount_in_maximum,
+            c.params.amount_out,
+            true,
+            deadline,
+        ),
+        Ok(SwapRouter02Calls::ExactInput(c)) => {
+            multi_hop(&c.params.path, c.params.amount_in, c.params.amount_out_minimum, false, deadline)
+        }
+        Ok(SwapRouter02Calls::ExactOutput(c)) => {
+            multi_hop(&c.params.path, c.params.amount_in_maximum, c.params.amount_out, true, deadline)
+        }
+        Ok(SwapRouter02Calls::MulticallWithDeadline(c)) if nested => return decode_multicall(&c.data, Some(c.deadline)),
+        Ok(SwapRouter02Calls::Multicall(c)) if nested => return decode_multicall(&c.data, deadline),
+        Ok(SwapRouter02Calls::MulticallWithPreviousBlockhash(c)) if nested => return decode_multicall(&c.data, deadline),
+        _ => None,
+    };
+    swap.into_iter().collect()
+}
+
+fn decode_multicall(calls: &[Bytes], deadline: Option<U256>) -> Vec<RouterSwap> {
+    calls.iter().flat_map(|call| decode_router_call(call, deadline, false)).collect()
+}
+
+fn single_hop(
+    token_in: Address,
+    token_out: Address,
+    fee: u32,
+    amount_in: U256,
+    amount_out: U256,
+    exact_output: bool,
+    deadline: Option<U256>,
+) -> Option<RouterSwap> {
+    Some(RouterSwap { tokens: vec![token_in, token_out], fees: vec![fee], amount_in, amount_out, exact_output, deadline })
+}
+
+// Exact-output paths are encoded output first.
+fn multi_hop(path: &[u8], amount_in: U256, amount_out: U256, exact_output: bool, deadline: Option<U256>) -> Option<RouterSwap> {
+    let (mut tokens, mut fees) = decode_path(path)?;
+    if exact_output {
+        tokens.reverse();
+        fees.reverse();
+    }
+    Some(RouterSwap { tokens, fees, amount_in, amount_out, exact_output, deadline })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::address_book::{ExactInputSingleCall as V1ExactInputSingleCall, ExactInputSingleParams as V1ExactInputSingleParams};
+    use ethers::abi::AbiEncode;
+
+    fn token(n: u64) -> Address {
+        Address::from_low_u64_be(n)
+    }
+
+    fn path(tokens: &[Address], fees: &[u32]) -> Bytes {
+        let mut path = tokens[0].as_bytes().to_vec();
+        for (fee, token) in fees.iter().zip(&tokens[1..]) {
+            path.extend_from_slice(&fee.to_be_bytes()[1..]);
+            path.extend_from_slice(token.as_bytes());
+        }
+        path.into()
+    }
+
+    #[test]
+    fn decode_path_splits_tokens_and_fees() {
+        let tokens = [token(1), token(2), token(3)];
+        assert_eq!(decode_path(&path(&tokens, &[500, 3000])), Some((tokens.to_vec(), vec![500, 3000])));
+        assert_eq!(decode_path(&path(&tokens[..1], &[])), Some((vec![token(1)], vec![])));
+    }
+
+    #[test]
+    fn decode_path_rejects_truncated_paths() {
+        let encoded = path(&[token(1), token(2)], &[3000]);
+        assert_eq!(decode_path(&encoded[..encoded.len() - 1]), None);
+        assert_eq!(decode_path(&encoded[..ADDR_SIZE + FEE_SIZE]), None);
+        assert_eq!(decode_path(&[]), None);
+    }
+
+    #[test]
+    fn in_range_quotes_match_the_virtual_reserves() {
+        // Price 1 at 1e18 liquidity: both virtual reserves are 1e18
+        let (sqrt_price, liquidity) = (U256::one() << 96, 10u128.pow(18));
+        let (reserve0, reserve1) = virtual_reserves(sqrt_price, liquidity);
+        assert_eq!((reserve0, reserve1), (U256::exp10(18), U256::exp10(18)));
+
+        let a_in = U256::exp10(15);
+        let a_in_after_fee = a_in * 997 / 1000;
+        let constant_product = a_in_after_fee * reserve1 / (reserve0 + a_in_after_fee);
+        for zero_for_one in [true, false] {
+            let out = get_amount_out(a_in, sqrt_price, liquidity, 3000, zero_for_one);
+            assert!(out.abs_diff(constant_product) <= U256::one(), "{} vs {}", out, constant_product);
+        }
+        assert_eq!(get_amount_out(a_in, sqrt_price, 0, 3000, true), U256::zero());
+    }
+
+    fn v1_exact_input_single() -> V1ExactInputSingleCall {
+        V1ExactInputSingleCall {
+            params: V1ExactInputSingleParams {
+                token_in: token(1),
+                token_out: token(2),
+                fee: 500,
+                recipient: token(9),
+                deadline: 77.into(),
+                amount_in: 1_000.into(),
+                amount_out_minimum: 990.into(),
+                sqrt_price_limit_x96: U256::zero(),
+            },
+        }
+    }
+
+    #[test]
+    fn swap_router_calls_carry_their_own_deadline() {
+        let swaps = decode_router_swaps(&v1_exact_input_single().encode());
+        assert_eq!(swaps.len(), 1);
+        assert_eq!((swaps[0].token_in(), swaps[0].token_out(), swaps[0].deadline), (token(1), token(2), Some(77.into())));
+        assert!(swaps[0].is_single_hop() && !swaps[0].exact_output);
+    }
+
+    #[test]
+    fn router02_multicalls_unreverse_exact_output_paths() {
+        // Exact-output paths are encoded output first: this buys token(3) with token(1)
+        let exact_output = ExactOutputCall {
+            params: ExactOutputParams {
+                path: path(&[token(3), token(2), token(1)], &[3000, 500]),
+                recipient: token(9),
+                amount_out: 5_000.into(),
+                amount_in_maximum: 6_000.into(),
+            },
+        };
+        let multicall = MulticallWithDeadlineCall {
+            deadline: 88.into(),
+            // A non-swap call alongside, like an unwrap, is skipped
+            data: vec![exact_output.encode().into(), Bytes::from(vec![0xde, 0xad, 0xbe, 0xef])],
+        };
+
+        let swaps = decode_router_swaps(&multicall.encode());
+        assert_eq!(
+            swaps,
+            vec![RouterSwap {
+                tokens: vec![token(1), token(2), token(3)],
+                fees: vec![500, 3000],
+                amount_in: 6_000.into(),
+                amount_out: 5_000.into(),
+                exact_output: true,
+                deadline: Some(88.into()),
+            }]
+        );
+    }
+
+    #[test]
+    fn nested_multicalls_are_not_followed() {
+        let inner = MulticallWithDeadlineCall { deadline: 1.into(), data: vec![v1_exact_input_single().encode().into()] };
+        let outer = MulticallWithDeadlineCall { deadline: 2.into(), data: vec![inner.encode().into()] };
+        assert!(decode_router_swaps(&outer.encode()).is_empty());
+    }
+}
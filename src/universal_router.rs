@@ -0,0 +1,346 @@
+use std::collections::HashMap;
+
+use ethers::abi::{self, AbiDecode, ParamType, Token};
+use ethers::prelude::*;
+
+use crate::address_book::UniversalRouterCalls;
+use crate::uni_v3::{self, RouterSwap};
+
+// ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+//  Universal Router
+//  - Reference: https://docs.uniswap.org/contracts/universal-router/technical-reference
+// ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+
+pub const V3_SWAP_EXACT_IN: u8 = 0x00;
+pub const V3_SWAP_EXACT_OUT: u8 = 0x01;
+pub const PERMIT2_TRANSFER_FROM: u8 = 0x02;
+pub const PERMIT2_PERMIT_BATCH: u8 = 0x03;
+pub const SWEEP: u8 = 0x04;
+pub const TRANSFER: u8 = 0x05;
+pub const PAY_PORTION: u8 = 0x06;
+pub const V2_SWAP_EXACT_IN: u8 = 0x08;
+pub const V2_SWAP_EXACT_OUT: u8 = 0x09;
+pub const PERMIT2_PERMIT: u8 = 0x0a;
+pub const WRAP_ETH: u8 = 0x0b;
+pub const UNWRAP_WETH: u8 = 0x0c;
+pub const PERMIT2_TRANSFER_FROM_BATCH: u8 = 0x0d;
+
+/// Command byte bits naming the command; the top bit only lets it revert without failing the tx.
+const COMMAND_TYPE_MASK: u8 = 0x3f;
+
+/// `amountIn` meaning "whatever the router holds", e.g. right after `WRAP_ETH`.
+fn contract_balance() -> U256 {
+    U256::one() << 255
+}
+
+/// One command of an `execute` call.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Command {
+    V2SwapExactIn { path: Vec<Address>, amount_in: U256, amount_out_min: U256 },
+    V2SwapExactOut { path: Vec<Address>, amount_out: U256, amount_in_max: U256 },
+    V3Swap(RouterSwap),
+    WrapEth { amount: U256 },
+    UnwrapWeth { amount_min: U256 },
+    /// Permit2 allowances signed into the tx, as `(token, amount)`; they move no pool.
+    Permit2Permit { permits: Vec<(Address, U256)> },
+    /// Tokens pulled from the sender into the router through Permit2, as `(token, amount)`.
+    Permit2TransferFrom { transfers: Vec<(Address, U256)> },
+    /// Sweeps, transfers, and commands we don't decode.
+    Other(u8),
+}
+
+impl Command {
+    pub fn is_swap(&self) -> bool {
+        matches!(self, Command::V2SwapExactIn { .. } | Command::V2SwapExactOut { .. } | Command::V3Swap(_))
+    }
+
+    /// Token a swap spends and the most it can spend.
+    pub fn swap_input(&self) -> Option<(Address, U256)> {
+        match self {
+            Command::V2SwapExactIn { path, amount_in, .. } => Some((*path.first()?, *amount_in)),
+            Command::V2SwapExactOut { path, amount_in_max, .. } => Some((*path.first()?, *amount_in_max)),
+            Command::V3Swap(swap) => Some((*swap.tokens.first()?, swap.amount_in)),
+            _ => None,
+        }
+    }
+}
+
+/// A decoded Universal Router `execute` call.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Execute {
+    pub commands: Vec<Command>,
+    pub deadline: Option<U256>,
+}
+
+impl Execute {
+    pub fn swaps(&self) -> impl Iterator<Item = &Command> {
+        self.commands.iter().filter(|command| command.is_swap())
+    }
+
+    /// Whether every Permit2 permit or transfer of a swap's input token covers
+    /// what the swap spends. One that covers less makes the swap revert, or
+    /// means it spends something other than what we decoded, so it's no victim.
+    pub fn permits_cover_swaps(&self) -> bool {
+        let permitted = self.commands.iter().flat_map(|command| match command {
+            Command::Permit2Permit { permits } => permits.as_slice(),
+            Command::Permit2TransferFrom { transfers } => transfers.as_slice(),
+            _ => &[],
+        });
+        let mut least: HashMap<Address, U256> = HashMap::new();
+        for (token, amount) in permitted {
+            least.entry(*token).and_modify(|least| *least = (*least).min(*amount)).or_insert(*amount);
+        }
+        self.swaps().filter_map(Command::swap_input).all(|(token, spent)| match least.get(&token) {
+            Some(covered) => *covered >= spent,
+            None => true,
+        })
+    }
+}
+
+/// Decodes a Universal Router `execute` call into its commands. `None` if `input`
+/// isn't one, or its inputs don't match its command bytes.
+pub fn decode_execute(input: &[u8]) -> Option<Execute> {
+    let (commands, inputs, deadline) = match UniversalRouterCalls::decode(input).ok()? {
+        UniversalRouterCalls::Execute(c) => (c.commands, c.inputs, None),
+        UniversalRouterCalls::ExecuteWithCommandsAndInputs(c) => (c.commands, c.inputs, Some(c.deadline)),
+    };
+    if commands.len() != inputs.len() {
+        return None;
+    }
+
+    let mut wrapped = None;
+    let mut pulled = HashMap::new();
+    let mut decoded = Vec::with_capacity(commands.len());
+    for (command, input) in commands.iter().zip(inputs.iter()) {
+        let mut command = decode_command(command & COMMAND_TYPE_MASK, input)?;
+        // Swaps spending the ETH just wrapped, or the tokens just pulled in through
+        // Permit2, pass "the router's balance" as their input
+        match &mut command {
+            Command::WrapEth { amount } => wrapped = Some(*amount),
+            Command::Permit2TransferFrom { transfers } => {
+                for (token, amount) in transfers.iter() {
+                    *pulled.entry(*token).or_insert_with(U256::zero) += *amount;
+                }
+            }
+            Command::V2SwapExactIn { path, amount_in, .. } if *amount_in == contract_balance() => {
+                *amount_in = pulled.get(path.first()?).copied().or(wrapped)?;
+            }
+            Command::V3Swap(swap) if !swap.exact_output && swap.amount_in == contract_balance() => {
+                swap.amount_in = pulled.get(swap.tokens.first()?).copied().or(wrapped)?;
+            }
+            _ => {}
+        }
+        decoded.push(command);
+    }
+
+    Some(Execute { commands: decoded, deadline })
+}
+
+fn decode_command(command: u8, input: &[u8]) -> Option<Command> {
+    let swap_params = |path: ParamType| {
+        vec![ParamType::Address, ParamType::Uint(256), ParamType::Uint(256), path, ParamType::Bool]
+    };
+
+    Some(match command {
+        V2_SWAP_EXACT_IN | V2_SWAP_EXACT_OUT => {
+            let tokens = abi::decode(&swap_params(ParamType::Array(Box::new(ParamType::Address))), input).ok()?;
+            let (first, second) = (uint(&tokens[1])?, uint(&tokens[2])?);
+            let path = tokens[3].clone().into_array()?.into_iter().map(|token| token.into_address()).collect::<Option<Vec<_>>>()?;
+            if command == V2_SWAP_EXACT_IN {
+                Command::V2SwapExactIn { path, amount_in: first, amount_out_min: second }
+            } else {
+                Command::V2SwapExactOut { path, amount_out: first, amount_in_max: second }
+            }
+        }
+        V3_SWAP_EXACT_IN | V3_SWAP_EXACT_OUT => {
+            let tokens = abi::decode(&swap_params(ParamType::Bytes), input).ok()?;
+            let (first, second) = (uint(&tokens[1])?, uint(&tokens[2])?);
+            let (mut path, mut fees) = uni_v3::decode_path(&tokens[3].clone().into_bytes()?)?;
+            let exact_output = command == V3_SWAP_EXACT_OUT;
+            let (amount_in, amount_out) = if exact_output {
+                // Exact-output paths are encoded output first
+                path.reverse();
+                fees.reverse();
+                (second, first)
+            } else {
+                (first, second)
+            };
+            Command::V3Swap(RouterSwap { tokens: path, fees, amount_in, amount_out, exact_output, deadline: None })
+        }
+        WRAP_ETH => Command::WrapEth { amount: recipient_and_amount(input)? },
+        UNWRAP_WETH => Command::UnwrapWeth { amount_min: recipient_and_amount(input)? },
+        PERMIT2_PERMIT | PERMIT2_PERMIT_BATCH => Command::Permit2Permit { permits: decode_permit(command, input)? },
+        PERMIT2_TRANSFER_FROM | PERMIT2_TRANSFER_FROM_BATCH => {
+            Command::Permit2TransferFrom { transfers: decode_transfer_from(command, input)? }
+        }
+        other => Command::Other(other),
+    })
+}
+
+/// `(token, amount)` of each token a `PERMIT2_PERMIT` or `PERMIT2_PERMIT_BATCH`
+/// input allows, from its `PermitSingle`/`PermitBatch` and signature.
+fn decode_permit(command: u8, input: &[u8]) -> Option<Vec<(Address, U256)>> {
+    // (address token, uint160 amount, uint48 expiration, uint48 nonce)
+    let details = ParamType::Tuple(vec![ParamType::Address, ParamType::Uint(160), ParamType::Uint(48), ParamType::Uint(48)]);
+    let details = if command == PERMIT2_PERMIT { details } else { ParamType::Array(Box::new(details)) };
+    // (details, address spender, uint256 sigDeadline), bytes signature
+    let permit = ParamType::Tuple(vec![details, ParamType::Address, ParamType::Uint(256)]);
+    let tokens = abi::decode(&[permit, ParamType::Bytes], input).ok()?;
+    let details = tokens[0].clone().into_tuple()?.into_iter().next()?;
+    let details = if command == PERMIT2_PERMIT { vec![details] } else { details.into_array()? };
+    details.into_iter().map(|details| token_and_amount(details.into_tuple()?, 0, 1)).collect()
+}
+
+/// `(token, amount)` of each transfer a `PERMIT2_TRANSFER_FROM` or
+/// `PERMIT2_TRANSFER_FROM_BATCH` input pulls in.
+fn decode_transfer_from(command: u8, input: &[u8]) -> Option<Vec<(Address, U256)>> {
+    if command == PERMIT2_TRANSFER_FROM {
+        // (address token, address recipient, uint160 amount)
+        let tokens = abi::decode(&[ParamType::Address, ParamType::Address, ParamType::Uint(160)], input).ok()?;
+        return Some(vec![token_and_amount(tokens, 0, 2)?]);
+    }
+    // (address from, address to, uint160 amount, address token)[]
+    let transfer = ParamType::Tuple(vec![ParamType::Address, ParamType::Address, ParamType::Uint(160), ParamType::Address]);
+    let tokens = abi::decode(&[ParamType::Array(Box::new(transfer))], input).ok()?;
+    tokens[0].clone().into_array()?.into_iter().map(|transfer| token_and_amount(transfer.into_tuple()?, 3, 2)).collect()
+}
+
+fn token_and_amount(tokens: Vec<Token>, token: usize, amount: usize) -> Option<(Address, U256)> {
+    Some((tokens.get(token)?.clone().into_address()?, uint(tokens.get(amount)?)?))
+}
+
+/// The amount of a `(address recipient, uint256 amount)` input.
+fn recipient_and_amount(input: &[u8]) -> Option<U256> {
+    let tokens = abi::decode(&[ParamType::Address, ParamType::Uint(256)], input).ok()?;
+    uint(&tokens[1])
+}
+
+fn uint(token: &Token) -> Option<U256> {
+    token.clone().into_uint()
+}
+
+#[cfg(test)]
+mod tests {
+    use ethers::abi::AbiEncode;
+
+    use super::*;
+    use crate::address_book::{ExecuteCall, ExecuteWithCommandsAndInputsCall, WETH};
+    use crate::helpers::address;
+
+    fn token(n: u64) -> Address {
+        Address::from_low_u64_be(n)
+    }
+
+    fn execute(commands: &[u8], inputs: Vec<Vec<u8>>) -> Vec<u8> {
+        ExecuteWithCommandsAndInputsCall {
+            commands: commands.to_vec().into(),
+            inputs: inputs.into_iter().map(Bytes::from).collect(),
+            deadline: U256::from(1_700_000_000u64),
+        }
+        .encode()
+    }
+
+    fn v2_swap_exact_in(amount_in: U256, path: &[Address]) -> Vec<u8> {
+        abi::encode(&[
+            Token::Address(token(0xbeef)),
+            Token::Uint(amount_in),
+            Token::Uint(U256::one()),
+            Token::Array(path.iter().map(|token| Token::Address(*token)).collect()),
+            Token::Bool(true),
+        ])
+    }
+
+    fn permit_single(token: Address, amount: U256) -> Vec<u8> {
+        let details = Token::Tuple(vec![Token::Address(token), Token::Uint(amount), Token::Uint(0.into()), Token::Uint(0.into())]);
+        abi::encode(&[Token::Tuple(vec![details, Token::Address(self::token(0xcafe)), Token::Uint(0.into())]), Token::Bytes(vec![0; 65])])
+    }
+
+    #[test]
+    fn decodes_a_permit2_permit_ahead_of_the_swap_it_covers() {
+        let path = [token(1), token(2)];
+        let input = execute(
+            &[PERMIT2_PERMIT, V2_SWAP_EXACT_IN],
+            vec![permit_single(token(1), 1_000.into()), v2_swap_exact_in(1_000.into(), &path)],
+        );
+
+        let decoded = decode_execute(&input).unwrap();
+        assert_eq!(decoded.deadline, Some(U256::from(1_700_000_000u64)));
+        assert_eq!(decoded.commands[0], Command::Permit2Permit { permits: vec![(token(1), 1_000.into())] });
+        assert_eq!(decoded.swaps().count(), 1);
+        assert!(decoded.permits_cover_swaps());
+    }
+
+    #[test]
+    fn a_permit_smaller_than_the_swap_does_not_cover_it() {
+        let input = execute(
+            &[PERMIT2_PERMIT, V2_SWAP_EXACT_IN],
+            vec![permit_single(token(1), 999.into()), v2_swap_exact_in(1_000.into(), &[token(1), token(2)])],
+        );
+        assert!(!decode_execute(&input).unwrap().permits_cover_swaps());
+    }
+
+    #[test]
+    fn swaps_of_the_router_balance_spend_what_permit2_pulled_in() {
+        let transfer = abi::encode(&[Token::Address(token(1)), Token::Address(token(0xbeef)), Token::Uint(500.into())]);
+        let input = execute(
+            // The allow-revert bit doesn't change which command it is
+            &[PERMIT2_TRANSFER_FROM | 0x80, V2_SWAP_EXACT_IN],
+            vec![transfer, v2_swap_exact_in(contract_balance(), &[token(1), token(2)])],
+        );
+
+        let decoded = decode_execute(&input).unwrap();
+        assert_eq!(decoded.commands[0], Command::Permit2TransferFrom { transfers: vec![(token(1), 500.into())] });
+        assert_eq!(decoded.commands[1].swap_input(), Some((token(1), 500.into())));
+    }
+
+    #[test]
+    fn rejects_calls_whose_inputs_do_not_match_their_commands() {
+        let input = ExecuteCall { commands: vec![WRAP_ETH, V2_SWAP_EXACT_IN].into(), inputs: vec![Bytes::default()] }.encode();
+        assert!(decode_execute(&input).is_none());
+    }
+
+    #[test]
+    fn decodes_a_wrap_and_v3_swap_as_the_router_receives_it() {
+        // 1 ETH for at least 3000 USDC through the 0.05% pool: execute(bytes,bytes[],uint256)
+        // with WRAP_ETH to the router, then V3_SWAP_EXACT_IN of the router's balance
+        let input: Bytes = concat!(
+        "3593564c",
+        "0000000000000000000000000000000000000000000000000000000000000060",
+        "00000000000000000000000000000000000000000000000000000000000000a0",
+        "000000000000000000000000000000000000000000000000000000006553f100",
+        "0000000000000000000000000000000000000000000000000000000000000002",
+        "0b00000000000000000000000000000000000000000000000000000000000000",
+        "0000000000000000000000000000000000000000000000000000000000000002",
+        "0000000000000000000000000000000000000000000000000000000000000040",
+        "00000000000000000000000000000000000000000000000000000000000000a0",
+        "0000000000000000000000000000000000000000000000000000000000000040",
+        "0000000000000000000000000000000000000000000000000000000000000002",
+        "0000000000000000000000000000000000000000000000000de0b6b3a7640000",
+        "0000000000000000000000000000000000000000000000000000000000000100",
+        "0000000000000000000000000000000000000000000000000000000000000001",
+        "8000000000000000000000000000000000000000000000000000000000000000",
+        "00000000000000000000000000000000000000000000000000000000b2d05e00",
+        "00000000000000000000000000000000000000000000000000000000000000a0",
+        "0000000000000000000000000000000000000000000000000000000000000000",
+        "000000000000000000000000000000000000000000000000000000000000002b",
+        "c02aaa39b223fe8d0a0e5c4f27ead9083c756cc20001f4a0b86991c6218b36c1",
+        "d19d4a2e9eb0ce3606eb48000000000000000000000000000000000000000000",
+        )
+        .parse()
+        .unwrap();
+        let (weth, usdc) = (address(WETH), address("0xA0b86991c6218b36c1d19D4a2e9Eb0cE3606eB48"));
+
+        let decoded = decode_execute(&input).unwrap();
+        assert_eq!(decoded.deadline, Some(U256::from(1_700_000_000u64)));
+        assert_eq!(decoded.commands[0], Command::WrapEth { amount: U256::exp10(18) });
+        let Command::V3Swap(swap) = &decoded.commands[1] else {
+            panic!("{:?}", decoded.commands[1]);
+        };
+        assert_eq!(swap.tokens, [weth, usdc]);
+        assert_eq!(swap.fees, [500]);
+        // The wrapped ETH, not the "router balance" sentinel
+        assert_eq!((swap.amount_in, swap.amount_out), (U256::exp10(18), U256::from(3_000_000_000u64)));
+        assert!(!swap.exact_output);
+        assert_eq!(decoded.swaps().filter_map(Command::swap_input).collect::<Vec<_>>(), [(weth, U256::exp10(18))]);
+    }
+}